@@ -0,0 +1,2 @@
+DROP TABLE scheduled_events;
+DROP TYPE SCHEDULED_EVENT_TYPE;
\ No newline at end of file
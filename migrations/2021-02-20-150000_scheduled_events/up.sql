@@ -0,0 +1,13 @@
+CREATE TYPE SCHEDULED_EVENT_TYPE AS ENUM ('worker_task', 'check_resting_visitors', 'check_visitor_hp', 'pay_taxes');
+
+-- Persisted copy of the game-master event queue, to survive restarts.
+-- Depending on the event type, exactly one of the reference columns is set (or none for pay_taxes).
+CREATE TABLE scheduled_events (
+    id BIGSERIAL PRIMARY KEY,
+    event_type SCHEDULED_EVENT_TYPE NOT NULL,
+    due TIMESTAMP NOT NULL,
+    task_id BIGINT REFERENCES tasks(id) ON DELETE CASCADE,
+    village_id BIGINT REFERENCES villages(id) ON DELETE CASCADE,
+    hobo_id BIGINT REFERENCES hobos(id) ON DELETE CASCADE
+);
+CREATE INDEX scheduled_events_due_idx ON scheduled_events (due);
\ No newline at end of file
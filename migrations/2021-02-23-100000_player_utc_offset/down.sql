@@ -0,0 +1 @@
+ALTER TABLE players DROP COLUMN utc_offset_minutes;
\ No newline at end of file
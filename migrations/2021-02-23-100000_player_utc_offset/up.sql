@@ -0,0 +1,2 @@
+-- Offset of the player's local time, used to respect quiet hours when sending visitors
+ALTER TABLE players ADD COLUMN utc_offset_minutes INTEGER NOT NULL DEFAULT 0;
\ No newline at end of file
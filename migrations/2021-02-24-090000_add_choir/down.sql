@@ -0,0 +1,14 @@
+DELETE FROM buildings
+    WHERE building_type = 'choir';
+
+DELETE FROM quest_building_conditions
+    WHERE building_type = 'choir';
+
+-- Values cannot be removed from an enum, the type has to be recreated without it
+ALTER TYPE building_type RENAME TO building_type_old;
+CREATE TYPE BUILDING_TYPE AS ENUM ('blue_flowers', 'red_flowers', 'tree', 'bundling_station', 'saw_mill', 'present_a', 'present_b', 'temple', 'single_nest', 'triple_nest', 'watergate');
+ALTER TABLE buildings
+    ALTER COLUMN building_type TYPE BUILDING_TYPE USING building_type::text::BUILDING_TYPE;
+ALTER TABLE quest_building_conditions
+    ALTER COLUMN building_type TYPE BUILDING_TYPE USING building_type::text::BUILDING_TYPE;
+DROP TYPE building_type_old;
\ No newline at end of file
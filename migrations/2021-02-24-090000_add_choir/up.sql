@@ -0,0 +1 @@
+ALTER TYPE building_type ADD VALUE 'choir';
\ No newline at end of file
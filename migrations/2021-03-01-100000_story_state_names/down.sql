@@ -0,0 +1,25 @@
+CREATE TYPE STORY_STATE_TYPE AS ENUM (
+  'initialized',
+  'servant_accepted',
+  'temple_built',
+  'building_watergate',
+  'watergate_built',
+  'visitor_queued',
+  'visitor_arrived',
+  'welcome_visitor_quest_started',
+  'first_visitor_welcomed',
+  'picking_primary_civ_bonus',
+  'solving_primary_civ_quest_part_a',
+  'solving_primary_civ_quest_part_b',
+  'unlocking_invitation_path_a',
+  'unlocking_invitation_path_b',
+  'dialogue_balance_a',
+  'dialogue_balance_b',
+  'solving_secondary_quest_a',
+  'solving_secondary_quest_b',
+  'all_done'
+);
+
+ALTER TABLE players ALTER COLUMN story_state DROP DEFAULT;
+ALTER TABLE players ALTER COLUMN story_state TYPE STORY_STATE_TYPE USING story_state::STORY_STATE_TYPE;
+ALTER TABLE players ALTER COLUMN story_state SET DEFAULT 'initialized';
@@ -0,0 +1,6 @@
+-- Story states are defined in specification/story.ron, the database only stores their names
+ALTER TABLE players ALTER COLUMN story_state DROP DEFAULT;
+ALTER TABLE players ALTER COLUMN story_state TYPE VARCHAR(64) USING story_state::text;
+ALTER TABLE players ALTER COLUMN story_state SET DEFAULT 'initialized';
+
+DROP TYPE STORY_STATE_TYPE;
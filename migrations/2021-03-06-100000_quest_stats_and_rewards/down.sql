@@ -0,0 +1,8 @@
+DROP TABLE unlocked_buildings;
+DROP TABLE quest_building_rewards;
+DROP TABLE quest_perk_rewards;
+DROP TABLE quest_worker_rewards;
+DROP TABLE quest_progress;
+DROP TABLE quest_stat_conditions;
+DROP TYPE CIV_PERK_TYPE;
+DROP TYPE QUEST_STAT_TYPE;
@@ -0,0 +1,47 @@
+CREATE TYPE QUEST_STAT_TYPE AS ENUM ('welcomed_visitors', 'worker_level', 'settled_hobos', 'sent_invitations');
+CREATE TYPE CIV_PERK_TYPE AS ENUM ('nest_building', 'triple_nest_building', 'invitation', 'conversion');
+
+CREATE TABLE quest_stat_conditions (
+    id BIGSERIAL PRIMARY KEY,
+    quest_id BIGINT NOT NULL REFERENCES quests(id) ON DELETE CASCADE,
+    stat_type QUEST_STAT_TYPE NOT NULL,
+    amount BIGINT NOT NULL
+);
+
+-- Counts game events since the quest has been assigned to the player.
+-- A row exists for each tracked statistic the quest has a condition on.
+CREATE TABLE quest_progress (
+    quest_id BIGINT NOT NULL,
+    player_id BIGINT NOT NULL,
+    stat_type QUEST_STAT_TYPE NOT NULL,
+    amount BIGINT NOT NULL DEFAULT 0,
+    CONSTRAINT quest_progress_pk PRIMARY KEY (quest_id, player_id, stat_type),
+    FOREIGN KEY (quest_id, player_id) REFERENCES quest_to_player (quest_id, player_id) ON DELETE CASCADE
+);
+CREATE INDEX quest_progress_player_idx ON quest_progress (player_id, stat_type);
+
+-- Rewards that go to the hero of the village
+CREATE TABLE quest_worker_rewards (
+    quest_id BIGINT PRIMARY KEY REFERENCES quests(id) ON DELETE CASCADE,
+    mana BIGINT NOT NULL DEFAULT 0,
+    experience BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE TABLE quest_perk_rewards (
+    quest_id BIGINT NOT NULL REFERENCES quests(id) ON DELETE CASCADE,
+    perk CIV_PERK_TYPE NOT NULL,
+    CONSTRAINT quest_perk_rewards_pk PRIMARY KEY (quest_id, perk)
+);
+
+CREATE TABLE quest_building_rewards (
+    quest_id BIGINT NOT NULL REFERENCES quests(id) ON DELETE CASCADE,
+    building_type BUILDING_TYPE NOT NULL,
+    CONSTRAINT quest_building_rewards_pk PRIMARY KEY (quest_id, building_type)
+);
+
+-- Buildings a player may build regardless of the usual restrictions (karma, story state, perks)
+CREATE TABLE unlocked_buildings (
+    player_id BIGINT NOT NULL REFERENCES players(id) ON DELETE CASCADE,
+    building_type BUILDING_TYPE NOT NULL,
+    CONSTRAINT unlocked_buildings_pk PRIMARY KEY (player_id, building_type)
+);
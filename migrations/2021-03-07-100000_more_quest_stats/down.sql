@@ -0,0 +1,9 @@
+DELETE FROM quest_progress
+    WHERE stat_type::text IN ('placed_buildings', 'gathered_sticks', 'gathered_logs', 'gathered_feathers', 'completed_tasks');
+
+DELETE FROM quest_stat_conditions
+    WHERE stat_type::text IN ('placed_buildings', 'gathered_sticks', 'gathered_logs', 'gathered_feathers', 'completed_tasks');
+
+DELETE FROM pg_enum
+    WHERE pg_enum.enumtypid = 'quest_stat_type'::regtype::oid
+    AND pg_enum.enumlabel IN ('placed_buildings', 'gathered_sticks', 'gathered_logs', 'gathered_feathers', 'completed_tasks');
@@ -0,0 +1,5 @@
+ALTER TYPE quest_stat_type ADD VALUE 'placed_buildings';
+ALTER TYPE quest_stat_type ADD VALUE 'gathered_sticks';
+ALTER TYPE quest_stat_type ADD VALUE 'gathered_logs';
+ALTER TYPE quest_stat_type ADD VALUE 'gathered_feathers';
+ALTER TYPE quest_stat_type ADD VALUE 'completed_tasks';
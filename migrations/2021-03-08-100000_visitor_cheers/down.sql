@@ -0,0 +1,3 @@
+ALTER TABLE attacks_to_hobos
+    DROP COLUMN cheers,
+    DROP COLUMN cheered_until;
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE attacks_to_hobos
+    ADD COLUMN cheers INT NOT NULL DEFAULT 0,
+    ADD COLUMN cheered_until TIMESTAMP;
\ No newline at end of file
@@ -0,0 +1,12 @@
+CREATE TABLE idempotency_keys (
+    player_uuid UUID NOT NULL,
+    idempotency_key VARCHAR(255) NOT NULL,
+    method VARCHAR(16) NOT NULL,
+    path TEXT NOT NULL,
+    body_hash BYTEA NOT NULL,
+    status SMALLINT,
+    content_type TEXT,
+    response_body BYTEA,
+    expires TIMESTAMP NOT NULL,
+    PRIMARY KEY (player_uuid, idempotency_key)
+);
\ No newline at end of file
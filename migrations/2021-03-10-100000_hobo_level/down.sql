@@ -0,0 +1 @@
+ALTER TABLE hobos DROP COLUMN level;
\ No newline at end of file
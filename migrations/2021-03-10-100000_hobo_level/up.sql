@@ -0,0 +1,9 @@
+ALTER TABLE hobos ADD COLUMN level INT NOT NULL DEFAULT 0;
+
+-- Existing hobos get the lowest level at which an anarchist can have their HP, see HoboLevel::of_anarchist_hp
+UPDATE hobos SET level = (
+    SELECT COUNT(*)
+    FROM unnest(ARRAY[2, 3, 4, 5, 8, 9, 13, 20, 40, 60]) AS threshold
+    WHERE (hobos.hurried AND hobos.hp >= threshold)
+       OR (NOT hobos.hurried AND hobos.hp > threshold)
+);
\ No newline at end of file
@@ -0,0 +1,139 @@
+//! Client for the game-master, which validates and executes all actions that change the game state.
+//!
+//! The db-interface itself only reads from the database.
+//! GraphQL mutations are forwarded to the REST API of the game-master on behalf of the player.
+//!
+//! Rocket 0.4 handles each request on a worker thread, which blocks until the game-master responds.
+//! Only a part of the workers may wait for the game-master at once, the others stay free to serve queries.
+
+use paddlers_shared_lib::api::error::{GameMasterError as ActionError, GameMasterErrorBody};
+use paddlers_shared_lib::config::Config;
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+pub struct GameMaster {
+    agent: ureq::Agent,
+    base_url: String,
+    token: Option<String>,
+    /// Requests waiting for a response, shared by all copies of the client
+    pending: Arc<AtomicUsize>,
+    max_pending: usize,
+}
+
+/// Why the game-master did not execute an action
+#[derive(Debug)]
+pub enum GameMasterError {
+    /// The request was valid but the game rules do not allow the action
+    Rejected(ActionError),
+    /// The player does not own an object referenced by the request
+    Forbidden(ActionError),
+    /// The game-master did not accept the authentication token
+    Unauthorized,
+    /// The game-master could not be reached or failed internally
+    Unavailable(String),
+}
+
+impl GameMaster {
+    /// At most `max_pending` requests wait for the game-master at once, further requests fail immediately
+    pub fn new(config: &Config, max_pending: usize) -> Self {
+        GameMaster {
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(CONNECT_TIMEOUT)
+                .timeout(TIMEOUT)
+                .build(),
+            base_url: format!("http://{}", config.game_master_service_name),
+            token: None,
+            pending: Arc::new(AtomicUsize::new(0)),
+            max_pending: max_pending.max(1),
+        }
+    }
+    /// Copy of the client that acts on behalf of the player identified by the token
+    pub fn with_token(&self, token: &str) -> Self {
+        GameMaster {
+            token: Some(token.to_owned()),
+            ..self.clone()
+        }
+    }
+    /// Sends a request to a REST endpoint of the game-master and returns the body of a successful response
+    pub fn post<B: Serialize>(&self, path: &str, body: &B) -> Result<String, GameMasterError> {
+        let token = self.token.as_ref().ok_or(GameMasterError::Unauthorized)?;
+        let _pending = self.reserve()?;
+        let body = serde_json::to_value(body)
+            .map_err(|e| GameMasterError::Unavailable(format!("Invalid request body: {}", e)))?;
+        let response = self
+            .agent
+            .post(&format!("{}{}", self.base_url, path))
+            .set("Authorization", token)
+            .send_json(body);
+        match response {
+            Ok(response) => read_body(response),
+            Err(ureq::Error::Status(status, response)) => {
+                let body = read_body(response)?;
+                Err(match status {
+                    400 | 404 => GameMasterError::Rejected(parse_error(body)),
+                    401 => GameMasterError::Unauthorized,
+                    403 => GameMasterError::Forbidden(parse_error(body)),
+                    _ => GameMasterError::Unavailable(format!("Status {}: {}", status, body)),
+                })
+            }
+            Err(e) => Err(GameMasterError::Unavailable(e.to_string())),
+        }
+    }
+    fn reserve(&self) -> Result<Pending, GameMasterError> {
+        // Released on drop, also if the limit has been reached
+        let pending = Pending(self.pending.clone());
+        if pending.0.fetch_add(1, Ordering::SeqCst) >= self.max_pending {
+            return Err(GameMasterError::Unavailable(
+                "Too many actions waiting for the game-master".to_owned(),
+            ));
+        }
+        Ok(pending)
+    }
+}
+
+/// Counts as pending request until dropped
+struct Pending(Arc<AtomicUsize>);
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn read_body(response: ureq::Response) -> Result<String, GameMasterError> {
+    response
+        .into_string()
+        .map_err(|e| GameMasterError::Unavailable(e.to_string()))
+}
+
+/// Bodies that are not structured errors, e.g. from the request parsing of the web framework, are treated as invalid requests
+fn parse_error(body: String) -> ActionError {
+    serde_json::from_str::<GameMasterErrorBody>(&body)
+        .map(|b| b.error)
+        .unwrap_or(ActionError::InvalidRequest(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_requests_are_limited() {
+        let game_master = GameMaster::new(&Config::default(), 2);
+        let copy = game_master.with_token("token");
+        let first = game_master.reserve().unwrap();
+        let _second = copy.reserve().unwrap();
+        assert!(matches!(
+            game_master.reserve(),
+            Err(GameMasterError::Unavailable(_))
+        ));
+        drop(first);
+        assert!(copy.reserve().is_ok());
+    }
+}
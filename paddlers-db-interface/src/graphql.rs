@@ -1,33 +1,52 @@
 //! Module for the GraphQL root query definition.
 
-use super::DbConn;
+use crate::game_master::GameMaster;
+use crate::sql::RequestDb;
 use chrono::prelude::NaiveDateTime;
 use juniper;
-use juniper::FieldResult;
+use juniper::{FieldResult, IntoFieldError};
 use paddlers_shared_lib::graphql_types::*;
 use paddlers_shared_lib::prelude::*;
 use paddlers_shared_lib::user_authentication::PadlUser;
-use std::sync::Arc;
+use std::rc::Rc;
 
+mod gql_connections;
 mod gql_err;
 mod gql_helper_types;
+mod gql_limits;
+mod gql_loaders;
+mod gql_mutations;
 pub mod gql_private;
 mod gql_public;
+#[cfg(test)]
+mod query_count_test;
+#[cfg(test)]
+mod schema_test;
 
+use gql_connections::*;
 use gql_err::ReadableInterfaceError;
 pub use gql_helper_types::*;
+pub use gql_limits::QueryLimits;
+use gql_limits::{QueryBudget, SCOREBOARD_DEFAULT_LIMIT};
+use gql_loaders::Loaders;
+use gql_mutations::Mutation;
 use gql_public::*;
 
-pub struct Mutation;
 pub struct Query;
 
 pub struct AuthenticatedContext {
-    db: Arc<DbConn>,
+    db: Rc<RequestDb>,
+    loaders: Loaders,
+    budget: QueryBudget,
     user: Player,
     villages: Vec<VillageKey>,
+    /// Only available if mutations are enabled
+    game_master: Option<GameMaster>,
 }
 pub struct UnauthenticatedContext {
-    db: Arc<DbConn>,
+    db: Rc<RequestDb>,
+    loaders: Loaders,
+    budget: QueryBudget,
 }
 pub enum Context {
     Public(UnauthenticatedContext),
@@ -37,34 +56,31 @@ pub enum Context {
 impl juniper::Context for Context {}
 
 impl Context {
-    pub fn new(db: DbConn, user: Option<PadlUser>) -> Option<Self> {
-        let conn = Arc::new(db);
+    pub fn new(db: RequestDb, user: Option<PadlUser>, limits: QueryLimits) -> Option<Self> {
+        let conn = Rc::new(db);
         if let Some(user) = user {
-            let player = conn.player_by_uuid(user.uuid)?;
-            let vids = conn
-                .player_villages(player.key())
-                .into_iter()
-                .map(|v| v.key())
-                .collect();
-            Some(Context::Authenticated(AuthenticatedContext {
-                db: conn,
-                user: player,
-                villages: vids,
-            }))
+            Self::player_context(conn, user.uuid, limits)
         } else {
             #[cfg(feature = "local")]
             {
-                let tester_context = Self::tester_context(conn.clone());
+                let tester_context = Self::tester_context(conn.clone(), limits);
                 if tester_context.is_some() {
                     return tester_context;
                 }
             }
-            Some(Context::Public(UnauthenticatedContext { db: conn }))
+            Some(Context::Public(UnauthenticatedContext {
+                loaders: Loaders::new(conn.clone()),
+                budget: QueryBudget::new(limits),
+                db: conn,
+            }))
         }
     }
     #[cfg(feature = "local")]
-    fn tester_context(conn: Arc<DbConn>) -> Option<Self> {
+    fn tester_context(conn: Rc<RequestDb>, limits: QueryLimits) -> Option<Self> {
         let uuid = uuid::Uuid::parse_str(paddlers_shared_lib::test_data::TEST_PLAYER_UUID).unwrap();
+        Self::player_context(conn, uuid, limits)
+    }
+    fn player_context(conn: Rc<RequestDb>, uuid: uuid::Uuid, limits: QueryLimits) -> Option<Self> {
         let player = conn.player_by_uuid(uuid)?;
         let vids = conn
             .player_villages(player.key())
@@ -72,23 +88,57 @@ impl Context {
             .map(|v| v.key())
             .collect();
         Some(Context::Authenticated(AuthenticatedContext {
+            loaders: Loaders::new(conn.clone()),
+            budget: QueryBudget::new(limits),
             db: conn,
             user: player,
             villages: vids,
+            game_master: None,
         }))
     }
-    pub fn db(&self) -> &Arc<DbConn> {
+    /// Allows the authenticated player to execute mutations through the game-master
+    pub fn with_game_master(mut self, game_master: GameMaster) -> Self {
+        if let Context::Authenticated(ctx) = &mut self {
+            ctx.game_master = Some(game_master);
+        }
+        self
+    }
+    pub fn db(&self) -> &Rc<RequestDb> {
         match self {
             Context::Authenticated(ctx) => &ctx.db,
             Context::Public(ctx) => &ctx.db,
         }
     }
+    /// Batched lookups, preferred over direct DB access for fields of objects that appear in lists
+    pub fn loaders(&self) -> &Loaders {
+        match self {
+            Context::Authenticated(ctx) => &ctx.loaders,
+            Context::Public(ctx) => &ctx.loaders,
+        }
+    }
+    /// Rejects a root field when its selection exceeds what is left of the request's complexity budget
+    /// The error keeps its extensions, which a conversion with `?` would drop
+    fn charge(&self, executor: &juniper::Executor<Context>) -> FieldResult<()> {
+        let budget = match self {
+            Context::Authenticated(ctx) => &ctx.budget,
+            Context::Public(ctx) => &ctx.budget,
+        };
+        budget
+            .charge(&executor.look_ahead())
+            .map_err(IntoFieldError::into_field_error)
+    }
     pub fn authenticated(&self) -> Result<&AuthenticatedContext, ReadableInterfaceError> {
         match self {
             Context::Authenticated(ctx) => Ok(ctx),
             Context::Public(_) => Err(ReadableInterfaceError::RequiresAuthentication),
         }
     }
+    fn game_master(&self) -> Result<&GameMaster, ReadableInterfaceError> {
+        self.authenticated()?
+            .game_master
+            .as_ref()
+            .ok_or(ReadableInterfaceError::MutationsDisabled)
+    }
     fn check_user_key(&self, key: PlayerKey) -> Result<(), ReadableInterfaceError> {
         if key.0 == self.authenticated()?.user.id {
             Ok(())
@@ -113,7 +163,12 @@ pub fn new_schema() -> Schema {
 #[juniper::object(Context = Context)]
 impl Query {
     // Object Visibility: public
-    fn player(ctx: &Context, player_id: Option<i32>) -> FieldResult<GqlPlayer> {
+    fn player(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        player_id: Option<i32>,
+    ) -> FieldResult<GqlPlayer> {
+        ctx.charge(executor)?;
         if let Some(i) = player_id {
             let key = PlayerKey(i as i64);
             let player = ctx.db().player(key).ok_or("No such player")?;
@@ -124,7 +179,12 @@ impl Query {
         }
     }
     // Object Visibility: public
-    fn village(ctx: &Context, village_id: i32) -> FieldResult<GqlVillage> {
+    fn village(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        village_id: i32,
+    ) -> FieldResult<GqlVillage> {
+        ctx.charge(executor)?;
         let village = ctx
             .db()
             .village(VillageKey(village_id as i64))
@@ -132,7 +192,12 @@ impl Query {
         Ok(GqlVillage(village))
     }
     // Object Visibility: user
-    fn worker(ctx: &Context, worker_id: i32) -> FieldResult<GqlWorker> {
+    fn worker(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        worker_id: i32,
+    ) -> FieldResult<GqlWorker> {
+        ctx.charge(executor)?;
         Ok(GqlWorker::authorized(
             ctx.db()
                 .worker_auth_by_player(WorkerKey(worker_id as i64), ctx.authenticated()?.user.key())
@@ -140,7 +205,8 @@ impl Query {
         ))
     }
     // Object Visibility: user
-    fn hobo(ctx: &Context, hobo_id: i32) -> FieldResult<GqlHobo> {
+    fn hobo(ctx: &Context, executor: &juniper::Executor, hobo_id: i32) -> FieldResult<GqlHobo> {
+        ctx.charge(executor)?;
         Ok(GqlHobo(
             ctx.db()
                 .hobo(HoboKey(hobo_id as i64))
@@ -148,27 +214,27 @@ impl Query {
         ))
     }
     // Object Visibility: public
-    fn map(low_x: i32, high_x: i32) -> GqlMapSlice {
-        GqlMapSlice { low_x, high_x }
+    fn map(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        low_x: i32,
+        high_x: i32,
+    ) -> FieldResult<GqlMapSlice> {
+        ctx.charge(executor)?;
+        Ok(GqlMapSlice { low_x, high_x })
     }
     // Object Visibility: public
-    fn scoreboard(ctx: &Context, offset: i32, limit: Option<i32>) -> GqlScoreboard {
-        GqlScoreboard {
-            offset,
-            limit: limit.unwrap_or(100),
-        }
-    }
-}
-
-#[juniper::object(
-    Context = Context,
-)]
-impl Mutation {
-    /// You might think this is silly and has no reason to be here.
-    /// You would be right for the former but wring for the latter.
-    /// Why? GraphiQL is not able to display a schema with an empty Mutation.
-    fn say_hi() -> &str {
-        "Hi!"
+    fn scoreboard(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        offset: Option<i32>,
+        limit: Option<i32>,
+    ) -> FieldResult<GqlScoreboard> {
+        ctx.charge(executor)?;
+        Ok(GqlScoreboard {
+            offset: offset.unwrap_or(0),
+            limit: limit.unwrap_or(SCOREBOARD_DEFAULT_LIMIT),
+        })
     }
 }
 
@@ -0,0 +1,235 @@
+//! Relay-style connections to page through long lists.
+//!
+//! Pages are selected by keyset, a cursor encodes the sort key of a row.
+//! Unlike offsets, cursors keep pointing to the same row when other rows are inserted, removed or reordered.
+//! Pages are requested with either `first` and `after` (forward) or `last` and `before` (backward).
+
+use super::*;
+use paddlers_shared_lib::sql::Page;
+
+/// Number of nodes on a page if neither first nor last is given
+pub const DEFAULT_PAGE_SIZE: i32 = 20;
+pub const MAX_PAGE_SIZE: i32 = 100;
+
+pub struct GqlConnection<N> {
+    edges: Vec<GqlEdge<N>>,
+    page_info: GqlPageInfo,
+    total_count: i64,
+}
+pub struct GqlEdge<N> {
+    cursor: String,
+    node: N,
+}
+#[derive(juniper::GraphQLObject)]
+pub struct GqlPageInfo {
+    has_next_page: bool,
+    has_previous_page: bool,
+    start_cursor: Option<String>,
+    end_cursor: Option<String>,
+}
+
+/// Sort key of a row that can be used as cursor
+pub trait CursorKey: Sized {
+    fn encode(&self) -> String;
+    fn decode(cursor: &str) -> Option<Self>;
+}
+
+/// Translates the pagination arguments of a connection field to a page
+pub fn page<K: CursorKey>(
+    first: Option<i32>,
+    after: Option<String>,
+    last: Option<i32>,
+    before: Option<String>,
+) -> FieldResult<Page<K>> {
+    let backward = last.is_some() || before.is_some();
+    if backward && (first.is_some() || after.is_some()) {
+        return Err("Use either first and after or last and before".into());
+    }
+    let size = first.or(last).unwrap_or(DEFAULT_PAGE_SIZE);
+    if size < 0 || size > MAX_PAGE_SIZE {
+        return Err(format!("Page size must be between 0 and {}", MAX_PAGE_SIZE).into());
+    }
+    let cursor = match after.or(before) {
+        Some(c) => Some(K::decode(&c).ok_or("Invalid cursor")?),
+        None => None,
+    };
+    Ok(Page {
+        cursor,
+        backward,
+        size: size as i64,
+    })
+}
+
+impl<N> GqlConnection<N> {
+    /// Builds the connection from the rows returned for a page.
+    /// Nodes are created in a batch, which allows to prime loaders for their fields.
+    pub fn new<R, K: CursorKey>(
+        page: &Page<K>,
+        mut rows: Vec<R>,
+        total_count: i64,
+        key: impl Fn(&R) -> K,
+        nodes: impl FnOnce(Vec<R>) -> Vec<N>,
+    ) -> Self {
+        let more = rows.len() as i64 > page.size;
+        rows.truncate(page.size as usize);
+        if page.backward {
+            rows.reverse();
+        }
+        let cursors: Vec<String> = rows.iter().map(|r| key(r).encode()).collect();
+        let page_info = GqlPageInfo {
+            has_next_page: if page.backward {
+                page.cursor.is_some()
+            } else {
+                more
+            },
+            has_previous_page: if page.backward {
+                more
+            } else {
+                page.cursor.is_some()
+            },
+            start_cursor: cursors.first().cloned(),
+            end_cursor: cursors.last().cloned(),
+        };
+        let edges = cursors
+            .into_iter()
+            .zip(nodes(rows))
+            .map(|(cursor, node)| GqlEdge { cursor, node })
+            .collect();
+        GqlConnection {
+            edges,
+            page_info,
+            total_count,
+        }
+    }
+}
+
+/// Defines the GraphQL objects for connections and edges of a node type
+macro_rules! connection {
+    ($node:ident, $connection:tt, $edge:tt) => {
+        #[juniper::object(Context = Context, name = $connection)]
+        impl GqlConnection<$node> {
+            fn edges(&self) -> &[GqlEdge<$node>] {
+                &self.edges
+            }
+            /// The nodes of all edges, for clients that do not need a cursor per node
+            fn nodes(&self) -> Vec<&$node> {
+                self.edges.iter().map(|e| &e.node).collect()
+            }
+            fn page_info(&self) -> &GqlPageInfo {
+                &self.page_info
+            }
+            /// Number of nodes in the entire list, not only on this page
+            fn total_count(&self) -> i32 {
+                self.total_count as i32
+            }
+        }
+        #[juniper::object(Context = Context, name = $edge)]
+        impl GqlEdge<$node> {
+            fn cursor(&self) -> &str {
+                &self.cursor
+            }
+            fn node(&self) -> &$node {
+                &self.node
+            }
+        }
+    };
+}
+
+connection!(GqlAttack, "GqlAttackConnection", "GqlAttackEdge");
+connection!(
+    GqlAttackReport,
+    "GqlAttackReportConnection",
+    "GqlAttackReportEdge"
+);
+connection!(GqlPlayer, "GqlPlayerConnection", "GqlPlayerEdge");
+
+impl CursorKey for VisitReportKey {
+    fn encode(&self) -> String {
+        encode_cursor(&[self.num()])
+    }
+    fn decode(cursor: &str) -> Option<Self> {
+        match decode_cursor(cursor)?.as_slice() {
+            [id] => Some(VisitReportKey(*id)),
+            _ => None,
+        }
+    }
+}
+/// Arrival in microseconds, the precision stored in the DB, and attack id
+impl CursorKey for (NaiveDateTime, AttackKey) {
+    fn encode(&self) -> String {
+        encode_cursor(&[self.0.timestamp_micros(), self.1.num()])
+    }
+    fn decode(cursor: &str) -> Option<Self> {
+        match decode_cursor(cursor)?.as_slice() {
+            [micros, id] => Some((
+                NaiveDateTime::from_timestamp_micros(*micros)?,
+                AttackKey(*id),
+            )),
+            _ => None,
+        }
+    }
+}
+/// Karma and player id
+impl CursorKey for (i64, PlayerKey) {
+    fn encode(&self) -> String {
+        encode_cursor(&[self.0, self.1.num()])
+    }
+    fn decode(cursor: &str) -> Option<Self> {
+        match decode_cursor(cursor)?.as_slice() {
+            [karma, id] => Some((*karma, PlayerKey(*id))),
+            _ => None,
+        }
+    }
+}
+
+fn encode_cursor(values: &[i64]) -> String {
+    values
+        .iter()
+        .map(i64::to_string)
+        .collect::<Vec<_>>()
+        .join(":")
+}
+fn decode_cursor(cursor: &str) -> Option<Vec<i64>> {
+    cursor.split(':').map(|v| v.parse().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trip() {
+        let key = VisitReportKey(42);
+        assert_eq!(VisitReportKey::decode(&key.encode()), Some(key));
+        let key = (-7, PlayerKey(3));
+        assert_eq!(<(i64, PlayerKey)>::decode(&key.encode()), Some(key));
+        let arrival = NaiveDateTime::from_timestamp_micros(1_600_000_000_123_456).unwrap();
+        let key = (arrival, AttackKey(9));
+        assert_eq!(
+            <(NaiveDateTime, AttackKey)>::decode(&key.encode()),
+            Some(key)
+        );
+        assert!(VisitReportKey::decode("1:2").is_none());
+        assert!(<(i64, PlayerKey)>::decode("x:2").is_none());
+    }
+
+    #[test]
+    fn backward_page_info() {
+        let page = page::<VisitReportKey>(None, None, Some(2), Some("10".to_owned())).unwrap();
+        // Rows closest to the cursor come first, one more than requested
+        let rows = vec![11, 12, 13];
+        let connection = GqlConnection::new(&page, rows, 20, |r| VisitReportKey(*r), |r| r);
+        let nodes: Vec<i64> = connection.edges.iter().map(|e| e.node).collect();
+        assert_eq!(nodes, vec![12, 11]);
+        assert!(connection.page_info.has_previous_page);
+        assert!(connection.page_info.has_next_page);
+        assert_eq!(connection.page_info.start_cursor.as_deref(), Some("12"));
+    }
+
+    #[test]
+    fn invalid_page_arguments() {
+        assert!(page::<VisitReportKey>(Some(1), None, Some(1), None).is_err());
+        assert!(page::<VisitReportKey>(Some(MAX_PAGE_SIZE + 1), None, None, None).is_err());
+        assert!(page::<VisitReportKey>(None, Some("a".to_owned()), None, None).is_err());
+    }
+}
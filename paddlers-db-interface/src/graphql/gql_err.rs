@@ -5,6 +5,10 @@ use juniper::*;
 pub enum ReadableInterfaceError {
     NotAllowed,
     RequiresAuthentication,
+    QueryTooDeep { depth: u32, max_depth: u32 },
+    QueryTooExpensive { cost: u32, max_cost: u32 },
+    MutationsDisabled,
+    GameMasterUnavailable(String),
 }
 
 impl IntoFieldError for ReadableInterfaceError {
@@ -18,10 +22,48 @@ impl IntoFieldError for ReadableInterfaceError {
                 "Please authenticate before reading this field.",
                 graphql_value!({ "internal_error": "Authentication required" }),
             ),
+            ReadableInterfaceError::QueryTooDeep { depth, max_depth } => {
+                let (depth, max_depth) = (gql_int(depth), gql_int(max_depth));
+                FieldError::new(
+                    format!(
+                        "Query nesting of {} exceeds the limit of {}.",
+                        depth, max_depth
+                    ),
+                    graphql_value!({
+                        "internal_error": "Query too deep",
+                        "depth": depth,
+                        "max_depth": max_depth
+                    }),
+                )
+            }
+            ReadableInterfaceError::QueryTooExpensive { cost, max_cost } => {
+                let (cost, max_cost) = (gql_int(cost), gql_int(max_cost));
+                FieldError::new(
+                    format!("Query cost of {} exceeds the limit of {}.", cost, max_cost),
+                    graphql_value!({
+                        "internal_error": "Query too expensive",
+                        "cost": cost,
+                        "max_cost": max_cost
+                    }),
+                )
+            }
+            ReadableInterfaceError::MutationsDisabled => FieldError::new(
+                "Mutations are not enabled on this server, use the game-master API instead.",
+                graphql_value!({ "internal_error": "Mutations disabled" }),
+            ),
+            ReadableInterfaceError::GameMasterUnavailable(reason) => FieldError::new(
+                format!("The game-master could not execute the action. {}", reason),
+                graphql_value!({ "internal_error": "Game-master unavailable" }),
+            ),
         }
     }
 }
 
+/// GraphQL integers are 32 bit signed
+fn gql_int(n: u32) -> i32 {
+    n.min(i32::MAX as u32) as i32
+}
+
 impl std::fmt::Display for ReadableInterfaceError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{:?}", self)
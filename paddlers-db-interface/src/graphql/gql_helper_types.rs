@@ -3,7 +3,8 @@
 
 use super::*;
 use juniper;
-use paddlers_shared_lib::prelude::ResourceType;
+use paddlers_shared_lib::civilization::{CivilizationPerks, SerializedCivPerks};
+use paddlers_shared_lib::prelude::{QuestStatType, ResourceType};
 
 pub struct Resources {
     res: Vec<(ResourceType, i64)>,
@@ -17,6 +18,11 @@ pub struct WorkerCondition {
     tt: TaskType,
     amount: i32,
 }
+pub struct StatCondition {
+    st: QuestStatType,
+    amount: i32,
+    current: i32,
+}
 
 pub struct QuestConditions {
     res: Resources,
@@ -24,6 +30,15 @@ pub struct QuestConditions {
     pop: Option<i32>,
     buildings: Vec<BuildingCondition>,
     worker: Vec<WorkerCondition>,
+    stats: Vec<StatCondition>,
+}
+
+pub struct QuestRewards {
+    pub res: Resources,
+    pub mana: i32,
+    pub experience: i32,
+    pub perks: CivilizationPerks,
+    pub buildings: Vec<BuildingType>,
 }
 
 #[juniper::object (Context = Context)]
@@ -56,6 +71,32 @@ impl QuestConditions {
     pub fn workers(&self) -> &[WorkerCondition] {
         &self.worker
     }
+    pub fn stats(&self) -> &[StatCondition] {
+        &self.stats
+    }
+}
+
+#[juniper::object (Context = Context)]
+impl QuestRewards {
+    pub fn resources(&self) -> &Resources {
+        &self.res
+    }
+    /// Mana for the hero
+    pub fn mana(&self) -> i32 {
+        self.mana
+    }
+    /// Experience for the hero
+    pub fn experience(&self) -> i32 {
+        self.experience
+    }
+    /// Civilization perks unlocked by the quest, encoded in a single number
+    pub fn perks(&self) -> SerializedCivPerks {
+        self.perks.encode()
+    }
+    /// Buildings unlocked by the quest
+    pub fn buildings(&self) -> &[BuildingType] {
+        &self.buildings
+    }
 }
 
 #[juniper::object (Context = Context)]
@@ -76,6 +117,20 @@ impl WorkerCondition {
         self.amount
     }
 }
+#[juniper::object (Context = Context)]
+impl StatCondition {
+    pub fn stat_type(&self) -> QuestStatType {
+        self.st
+    }
+    pub fn amount(&self) -> i32 {
+        self.amount
+    }
+    /// Value of the statistic for the player right now.
+    /// For statistics counting events, only those since the quest has been assigned are included.
+    pub fn current(&self) -> i32 {
+        self.current
+    }
+}
 
 impl Resources {
     fn resource(&self, res: ResourceType) -> i32 {
@@ -100,6 +155,7 @@ impl QuestConditions {
         pop: Option<i64>,
         buildings: Vec<BuildingCondition>,
         worker: Vec<WorkerCondition>,
+        stats: Vec<StatCondition>,
     ) -> Self {
         Self {
             res,
@@ -107,6 +163,17 @@ impl QuestConditions {
             pop: pop.map(|i| i as i32),
             buildings,
             worker,
+            stats,
+        }
+    }
+}
+
+impl StatCondition {
+    pub fn new(qsc: QuestStatCondition, current: i64) -> Self {
+        Self {
+            st: qsc.stat_type,
+            amount: qsc.amount as i32,
+            current: current as i32,
         }
     }
 }
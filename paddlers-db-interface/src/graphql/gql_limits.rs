@@ -0,0 +1,328 @@
+//! Limits on the complexity of GraphQL queries.
+//!
+//! Every root field charges its selection against the budget of the request before it is resolved.
+//! The cost of a field is one plus the cost of its children, multiplied by the number of objects
+//! the field can be expected to return.
+//! For fields with range or page arguments, that number is derived from the arguments.
+//! Other lists are expected to be as long as the loaders or the game allow them to be, e.g. one building per tile of a town.
+//! Nesting lists therefore multiplies their sizes, which quickly exhausts the budget.
+
+use super::gql_connections::DEFAULT_PAGE_SIZE;
+use super::gql_err::ReadableInterfaceError;
+use juniper::{
+    DefaultScalarValue, LookAheadMethods, LookAheadSelection, LookAheadValue, ScalarValue,
+};
+use paddlers_shared_lib::config::Config;
+use paddlers_shared_lib::game_mechanics::map::MAP_H;
+use paddlers_shared_lib::game_mechanics::town::{TOWN_X, TOWN_Y};
+use paddlers_shared_lib::sql::*;
+use std::cell::Cell;
+
+type Selection<'a> = LookAheadSelection<'a, DefaultScalarValue>;
+
+/// Number of players on the scoreboard when no limit is given
+pub const SCOREBOARD_DEFAULT_LIMIT: i32 = 100;
+
+#[derive(Clone, Copy, Debug)]
+pub struct QueryLimits {
+    pub max_depth: u32,
+    pub max_cost: u32,
+}
+
+/// Keeps track of the cost spent by all root fields of a request
+pub struct QueryBudget {
+    limits: QueryLimits,
+    spent: Cell<u32>,
+}
+
+impl From<&Config> for QueryLimits {
+    fn from(config: &Config) -> Self {
+        QueryLimits {
+            max_depth: config.graphql_max_depth,
+            max_cost: config.graphql_max_cost,
+        }
+    }
+}
+
+impl QueryBudget {
+    pub fn new(limits: QueryLimits) -> Self {
+        QueryBudget {
+            limits,
+            spent: Cell::new(0),
+        }
+    }
+    pub fn charge(&self, selection: &Selection) -> Result<(), ReadableInterfaceError> {
+        let depth = depth(selection);
+        if depth > self.limits.max_depth {
+            return Err(ReadableInterfaceError::QueryTooDeep {
+                depth,
+                max_depth: self.limits.max_depth,
+            });
+        }
+        let cost = self.spent.get().saturating_add(cost(selection));
+        if cost > self.limits.max_cost {
+            return Err(ReadableInterfaceError::QueryTooExpensive {
+                cost,
+                max_cost: self.limits.max_cost,
+            });
+        }
+        self.spent.set(cost);
+        Ok(())
+    }
+}
+
+/// The parts of a selection that its depth and cost are computed from
+trait QueryNode: Sized {
+    fn field_name(&self) -> &str;
+    fn children(&self) -> Vec<&Self>;
+    fn int_argument(&self, name: &str) -> Option<i32>;
+}
+
+impl<'a> QueryNode for Selection<'a> {
+    fn field_name(&self) -> &str {
+        LookAheadMethods::field_name(self)
+    }
+    fn children(&self) -> Vec<&Self> {
+        self.child_names()
+            .into_iter()
+            .filter_map(|name| self.select_child(name))
+            .collect()
+    }
+    fn int_argument(&self, name: &str) -> Option<i32> {
+        match self.argument(name)?.value() {
+            LookAheadValue::Scalar(s) => s.as_int(),
+            _ => None,
+        }
+    }
+}
+
+fn depth(selection: &impl QueryNode) -> u32 {
+    1 + selection
+        .children()
+        .into_iter()
+        .map(depth)
+        .max()
+        .unwrap_or(0)
+}
+
+fn cost(selection: &impl QueryNode) -> u32 {
+    let children = selection
+        .children()
+        .into_iter()
+        .fold(0u32, |sum, child| sum.saturating_add(cost(child)));
+    children
+        .saturating_mul(expected_objects(selection))
+        .saturating_add(1)
+}
+
+/// Upper bound for the number of objects returned by a field, derived from its range or page arguments
+/// or from the size limits of the list it returns
+fn expected_objects(selection: &impl QueryNode) -> u32 {
+    let n = match selection.field_name() {
+        "map" => {
+            selection.int_argument("highX").unwrap_or(0) as i64
+                - selection.int_argument("lowX").unwrap_or(0) as i64
+                + 1
+        }
+        "scoreboard"
+            if selection
+                .children()
+                .iter()
+                .any(|c| c.field_name() == "playersByKarma") =>
+        {
+            selection
+                .int_argument("limit")
+                .unwrap_or(SCOREBOARD_DEFAULT_LIMIT) as i64
+        }
+        name if name.ends_with("Connection") => selection
+            .int_argument("first")
+            .or_else(|| selection.int_argument("last"))
+            .unwrap_or(DEFAULT_PAGE_SIZE) as i64,
+        name => list_size(name) as i64,
+    };
+    n.max(1).min(u32::MAX as i64) as u32
+}
+
+/// Maximum length of lists without range or page arguments, 1 for fields that are not lists
+fn list_size(field_name: &str) -> usize {
+    match field_name {
+        // At most one per tile of a town
+        "buildings" | "hobos" => TOWN_X * TOWN_Y,
+        "units" => MAX_UNITS_PER_ATTACK,
+        "attacks" => MAX_ATTACKS_PER_VILLAGE,
+        "reports" => MAX_REPORTS_PER_VILLAGE,
+        "quests" => MAX_QUESTS_PER_PLAYER,
+        "workers" => MAX_WORKERS_PER_VILLAGE,
+        "tasks" => MAX_TASKS_PER_WORKER,
+        "abilities" => MAX_ABILITIES_PER_WORKER,
+        "effects" => MAX_EFFECTS_PER_HOBO,
+        // Of a player, or of a column of the map, which has at most one per row
+        "villages" | "streams" => MAX_VILLAGES_PER_PLAYER.max(MAP_H as usize),
+        // Conditions of a quest, one per type of statistic
+        "stats" => 9,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphql::{new_schema, Context};
+    use crate::sql::RequestDb;
+    use diesel::prelude::*;
+    use juniper::{Value, Variables};
+
+    /// Selection built by hand, for tests that do not execute a query
+    struct Node {
+        name: &'static str,
+        arguments: Vec<(&'static str, i32)>,
+        children: Vec<Node>,
+    }
+
+    fn node(name: &'static str, children: Vec<Node>) -> Node {
+        Node {
+            name,
+            arguments: vec![],
+            children,
+        }
+    }
+
+    impl QueryNode for Node {
+        fn field_name(&self) -> &str {
+            self.name
+        }
+        fn children(&self) -> Vec<&Self> {
+            self.children.iter().collect()
+        }
+        fn int_argument(&self, name: &str) -> Option<i32> {
+            self.arguments
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| *v)
+        }
+    }
+
+    #[test]
+    fn depth_counts_the_longest_path() {
+        assert_eq!(depth(&node("player", vec![])), 1);
+        let query = node(
+            "village",
+            vec![
+                node("id", vec![]),
+                node("hobos", vec![node("nest", vec![node("id", vec![])])]),
+            ],
+        );
+        assert_eq!(depth(&query), 4);
+    }
+
+    #[test]
+    fn cost_multiplies_by_list_size() {
+        assert_eq!(cost(&node("player", vec![])), 1);
+        // 1 for the village, 1 for the list, 2 fields per worker
+        let workers = node(
+            "village",
+            vec![node("workers", vec![node("id", vec![]), node("x", vec![])])],
+        );
+        assert_eq!(cost(&workers), 1 + 1 + 2 * MAX_WORKERS_PER_VILLAGE as u32);
+        // Nested lists multiply
+        let tasks = node("workers", vec![node("tasks", vec![node("id", vec![])])]);
+        assert_eq!(
+            cost(&tasks),
+            1 + (1 + MAX_TASKS_PER_WORKER as u32) * MAX_WORKERS_PER_VILLAGE as u32
+        );
+    }
+
+    #[test]
+    fn cost_follows_range_and_page_arguments() {
+        let map = Node {
+            name: "map",
+            arguments: vec![("lowX", 3), ("highX", 7)],
+            children: vec![node("streams", vec![])],
+        };
+        assert_eq!(cost(&map), 1 + 5);
+        let page = Node {
+            name: "playersConnection",
+            arguments: vec![("first", 4)],
+            children: vec![node("nodes", vec![node("karma", vec![])])],
+        };
+        assert_eq!(cost(&page), 1 + 4 * 2);
+        let default_page = node("playersConnection", vec![node("totalCount", vec![])]);
+        assert_eq!(cost(&default_page), 1 + DEFAULT_PAGE_SIZE as u32);
+    }
+
+    /// Executes the query without authentication and returns the internal error codes of all errors
+    fn rejections(query: &str) -> Vec<String> {
+        rejections_with(
+            query,
+            QueryLimits {
+                max_depth: 4,
+                max_cost: 50,
+            },
+        )
+    }
+
+    fn rejections_with(query: &str, limits: QueryLimits) -> Vec<String> {
+        let url = paddlers_shared_lib::get_db_url();
+        let conn = PgConnection::establish(&url).expect("DB connection");
+        let ctx = Context::new(RequestDb::new(Box::new(conn)), None, limits).unwrap();
+        let (_, errors) =
+            juniper::execute(query, None, &new_schema(), &Variables::new(), &ctx).unwrap();
+        errors
+            .iter()
+            .filter_map(|e| {
+                match e
+                    .error()
+                    .extensions()
+                    .as_object_value()?
+                    .get_field_value("internal_error")?
+                {
+                    Value::Scalar(DefaultScalarValue::String(s)) => Some(s.clone()),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn over_limit_queries_are_rejected() {
+        assert!(rejections("{ map(lowX: 0, highX: 1) { villages { id } } }").is_empty());
+        assert_eq!(
+            rejections("{ map(lowX: 0, highX: 1) { villages { owner { villages { id } } } } }"),
+            vec!["Query too deep"]
+        );
+        assert_eq!(
+            rejections("{ scoreboard(offset: 0, limit: 100) { playersByKarma { karma } } }"),
+            vec!["Query too expensive"]
+        );
+        // The budget is shared by all root fields
+        assert_eq!(
+            rejections(
+                "{ a: map(lowX: 0, highX: 19) { villages { id } } b: map(lowX: 0, highX: 19) { villages { id } } }"
+            ),
+            vec!["Query too expensive"]
+        );
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn nested_lists_are_charged() {
+        let limits = QueryLimits::from(&Config::default());
+        let too_expensive = "Query too expensive".to_owned();
+        assert!(!rejections_with(
+            "{ village(villageId: 1) { hobos { home { id } } } }",
+            limits
+        )
+        .contains(&too_expensive));
+        // 63 hobos with 63 hobos each at home, with 63 hobos each at home
+        assert_eq!(
+            rejections_with(
+                "{ village(villageId: 1) { hobos { home { hobos { home { hobos { id } } } } } } }",
+                limits
+            ),
+            vec![too_expensive]
+        );
+    }
+}
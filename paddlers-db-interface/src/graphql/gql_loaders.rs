@@ -0,0 +1,379 @@
+//! Request-scoped batching and caching of DB lookups.
+//!
+//! Resolvers of lists announce (prime) the keys of objects that are likely to be looked up next.
+//! The first lookup that misses the cache then loads all announced keys with a single query.
+//! This way, the number of queries for a GraphQL request does not grow with the number of objects in the response.
+//!
+//! Loaders do not perform any authorization, that remains the responsibility of gql_public.
+
+use crate::sql::RequestDb;
+use paddlers_shared_lib::prelude::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::rc::Rc;
+
+/// Batches and caches the lookup of a one-to-many relation
+pub struct Loader<K, V> {
+    fetch: fn(&RequestDb, &[K]) -> Vec<(K, V)>,
+    pending: RefCell<HashSet<K>>,
+    loaded: RefCell<HashMap<K, Vec<V>>>,
+}
+
+pub struct Loaders {
+    db: Rc<RequestDb>,
+    pub players: Loader<PlayerKey, Player>,
+    pub villages: Loader<VillageKey, Village>,
+    pub player_villages: Loader<PlayerKey, Village>,
+    pub streams: Loader<StreamKey, Stream>,
+    pub buildings: Loader<BuildingKey, Building>,
+    pub hobos: Loader<HoboKey, Hobo>,
+    pub village_workers: Loader<VillageKey, Worker>,
+    pub worker_tasks: Loader<WorkerKey, Task>,
+    pub worker_abilities: Loader<WorkerKey, Ability>,
+    /// Keyed by village and the minimal attack id
+    pub village_attacks: Loader<(VillageKey, i64), Attack>,
+    pub attack_units: Loader<AttackKey, (Hobo, AttackToHobo)>,
+    pub hobo_attacks: Loader<HoboKey, AttackToHobo>,
+    pub hobo_effects: Loader<HoboKey, Effect>,
+    pub report_rewards: Loader<VisitReportKey, Reward>,
+    pub quest_res_rewards: Loader<QuestKey, QuestResReward>,
+    pub quest_res_conditions: Loader<QuestKey, QuestResCondition>,
+    pub quest_building_conditions: Loader<QuestKey, QuestBuildingCondition>,
+    pub quest_worker_conditions: Loader<QuestKey, QuestWorkerCondition>,
+    pub quest_stat_conditions: Loader<QuestKey, QuestStatCondition>,
+    pub quest_worker_rewards: Loader<QuestKey, QuestWorkerReward>,
+    pub quest_perk_rewards: Loader<QuestKey, QuestPerkReward>,
+    pub quest_building_rewards: Loader<QuestKey, QuestBuildingReward>,
+    /// Caches statistics, which are shared among all quests of a player
+    pub village_stats: Loader<(VillageKey, QuestStatType), i64>,
+    pub player_quest_progress: Loader<PlayerKey, QuestProgress>,
+}
+
+impl<K: Copy + Eq + Hash, V: Clone> Loader<K, V> {
+    fn new(fetch: fn(&RequestDb, &[K]) -> Vec<(K, V)>) -> Self {
+        Loader {
+            fetch,
+            pending: Default::default(),
+            loaded: Default::default(),
+        }
+    }
+    /// Announces keys that will probably be loaded later
+    pub fn prime(&self, keys: impl IntoIterator<Item = K>) {
+        let loaded = self.loaded.borrow();
+        self.pending
+            .borrow_mut()
+            .extend(keys.into_iter().filter(|key| !loaded.contains_key(key)));
+    }
+    fn load(&self, db: &RequestDb, key: K) -> Vec<V> {
+        self.load_then(db, key, |_| {})
+    }
+    /// On a cache miss, fetches all pending keys and hands the fetched values to `on_fetch`, which can prime other loaders.
+    fn load_then(&self, db: &RequestDb, key: K, on_fetch: impl FnOnce(&[(K, V)])) -> Vec<V> {
+        if let Some(values) = self.loaded.borrow().get(&key) {
+            return values.clone();
+        }
+        let mut keys: Vec<K> = self.pending.borrow_mut().drain().collect();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+        let fetched = (self.fetch)(db, &keys);
+        on_fetch(&fetched);
+        let mut loaded = self.loaded.borrow_mut();
+        for k in keys {
+            loaded.entry(k).or_default();
+        }
+        for (k, v) in fetched {
+            loaded.entry(k).or_default().push(v);
+        }
+        loaded[&key].clone()
+    }
+}
+
+impl Loaders {
+    pub fn new(db: Rc<RequestDb>) -> Self {
+        Loaders {
+            db,
+            players: Loader::new(|db, keys| {
+                db.players_by_keys(keys)
+                    .into_iter()
+                    .map(|p| (p.key(), p))
+                    .collect()
+            }),
+            villages: Loader::new(|db, keys| {
+                db.villages_by_keys(keys)
+                    .into_iter()
+                    .map(|v| (v.key(), v))
+                    .collect()
+            }),
+            player_villages: Loader::new(|db, keys| {
+                db.villages_of_players(keys)
+                    .into_iter()
+                    .filter_map(|v| Some((v.owner()?, v)))
+                    .collect()
+            }),
+            streams: Loader::new(|db, keys| {
+                db.streams_by_keys(keys)
+                    .into_iter()
+                    .map(|s| (s.key(), s))
+                    .collect()
+            }),
+            buildings: Loader::new(|db, keys| {
+                db.buildings_by_keys(keys)
+                    .into_iter()
+                    .map(|b| (b.key(), b))
+                    .collect()
+            }),
+            hobos: Loader::new(|db, keys| {
+                db.hobos_by_keys(keys)
+                    .into_iter()
+                    .map(|h| (h.key(), h))
+                    .collect()
+            }),
+            village_workers: Loader::new(|db, keys| {
+                db.workers_of_villages(keys)
+                    .into_iter()
+                    .map(|w| (w.home(), w))
+                    .collect()
+            }),
+            worker_tasks: Loader::new(|db, keys| {
+                db.tasks_of_workers(keys)
+                    .into_iter()
+                    .map(|t| (t.worker(), t))
+                    .collect()
+            }),
+            worker_abilities: Loader::new(|db, keys| {
+                db.abilities_of_workers(keys)
+                    .into_iter()
+                    .map(|a| (WorkerKey(a.worker_id), a))
+                    .collect()
+            }),
+            village_attacks: Loader::new(|db, keys| {
+                let mut out = vec![];
+                for atk in db.attacks_on_villages(keys) {
+                    for key in keys {
+                        if key.0 == atk.destination() && atk.id >= key.1 {
+                            out.push((*key, atk.clone()));
+                        }
+                    }
+                }
+                out
+            }),
+            attack_units: Loader::new(|db, keys| {
+                db.hobos_of_attacks_with_attack_info(keys)
+                    .into_iter()
+                    .map(|unit| (AttackKey(unit.1.attack_id), unit))
+                    .collect()
+            }),
+            hobo_attacks: Loader::new(|db, keys| {
+                db.attack_info_of_hobos(keys)
+                    .into_iter()
+                    .map(|info| (HoboKey(info.hobo_id), info))
+                    .collect()
+            }),
+            hobo_effects: Loader::new(|db, keys| {
+                db.effects_on_hobos(keys)
+                    .into_iter()
+                    .map(|e| (HoboKey(e.hobo_id), e))
+                    .collect()
+            }),
+            report_rewards: Loader::new(|db, keys| {
+                db.rewards_of_reports(keys)
+                    .into_iter()
+                    .map(|r| (VisitReportKey(r.visit_report_id), r))
+                    .collect()
+            }),
+            quest_res_rewards: Loader::new(|db, keys| {
+                db.quests_res_rewards(keys)
+                    .into_iter()
+                    .map(|r| (QuestKey(r.quest_id), r))
+                    .collect()
+            }),
+            quest_res_conditions: Loader::new(|db, keys| {
+                db.quests_res_conditions(keys)
+                    .into_iter()
+                    .map(|c| (QuestKey(c.quest_id), c))
+                    .collect()
+            }),
+            quest_building_conditions: Loader::new(|db, keys| {
+                db.quests_building_conditions(keys)
+                    .into_iter()
+                    .map(|c| (QuestKey(c.quest_id), c))
+                    .collect()
+            }),
+            quest_worker_conditions: Loader::new(|db, keys| {
+                db.quests_worker_conditions(keys)
+                    .into_iter()
+                    .map(|c| (QuestKey(c.quest_id), c))
+                    .collect()
+            }),
+            quest_stat_conditions: Loader::new(|db, keys| {
+                db.quests_stat_conditions(keys)
+                    .into_iter()
+                    .map(|c| (QuestKey(c.quest_id), c))
+                    .collect()
+            }),
+            quest_worker_rewards: Loader::new(|db, keys| {
+                db.quests_worker_rewards(keys)
+                    .into_iter()
+                    .map(|r| (QuestKey(r.quest_id), r))
+                    .collect()
+            }),
+            quest_perk_rewards: Loader::new(|db, keys| {
+                db.quests_perk_rewards(keys)
+                    .into_iter()
+                    .map(|r| (QuestKey(r.quest_id), r))
+                    .collect()
+            }),
+            quest_building_rewards: Loader::new(|db, keys| {
+                db.quests_building_rewards(keys)
+                    .into_iter()
+                    .map(|r| (QuestKey(r.quest_id), r))
+                    .collect()
+            }),
+            // One query per statistic, the number of quests does not matter
+            village_stats: Loader::new(|db, keys| {
+                keys.iter()
+                    .filter_map(|&(village, stat)| {
+                        Some(((village, stat), db.village_stat(stat, village)?))
+                    })
+                    .collect()
+            }),
+            player_quest_progress: Loader::new(|db, keys| {
+                db.players_quest_progress(keys)
+                    .into_iter()
+                    .map(|p| (PlayerKey(p.player_id), p))
+                    .collect()
+            }),
+        }
+    }
+
+    pub fn player(&self, key: PlayerKey) -> Option<Player> {
+        self.players.load(&self.db, key).pop()
+    }
+    pub fn village(&self, key: VillageKey) -> Option<Village> {
+        self.villages
+            .load_then(&self.db, key, |villages| {
+                self.prime_village_fields(villages.iter().map(|(_, v)| v))
+            })
+            .pop()
+    }
+    pub fn player_villages(&self, key: PlayerKey) -> Vec<Village> {
+        self.player_villages.load_then(&self.db, key, |villages| {
+            self.prime_village_fields(villages.iter().map(|(_, v)| v))
+        })
+    }
+    /// Primes what is needed to display villages on the map
+    pub fn prime_village_fields<'a>(&self, villages: impl Iterator<Item = &'a Village> + Clone) {
+        self.streams
+            .prime(villages.clone().map(|v| StreamKey(v.stream_id)));
+        self.players.prime(villages.filter_map(Village::owner));
+    }
+    pub fn stream(&self, key: StreamKey) -> Option<Stream> {
+        self.streams.load(&self.db, key).pop()
+    }
+    pub fn building(&self, key: BuildingKey) -> Option<Building> {
+        self.buildings.load(&self.db, key).pop()
+    }
+    pub fn hobo(&self, key: HoboKey) -> Option<Hobo> {
+        self.hobos
+            .load_then(&self.db, key, |hobos| {
+                self.villages.prime(hobos.iter().map(|(_, h)| h.home()))
+            })
+            .pop()
+    }
+    pub fn workers(&self, village: VillageKey) -> Vec<Worker> {
+        self.village_workers
+            .load_then(&self.db, village, |workers| {
+                self.worker_tasks
+                    .prime(workers.iter().map(|(_, w)| w.key()));
+                self.worker_abilities
+                    .prime(workers.iter().map(|(_, w)| w.key()));
+            })
+    }
+    pub fn tasks(&self, worker: WorkerKey) -> Vec<Task> {
+        self.worker_tasks.load(&self.db, worker)
+    }
+    pub fn abilities(&self, worker: WorkerKey) -> Vec<Ability> {
+        self.worker_abilities.load(&self.db, worker)
+    }
+    /// Attacks on the village with id >= min_id, sorted by arrival
+    pub fn attacks(&self, village: VillageKey, min_id: i64) -> Vec<Attack> {
+        self.village_attacks
+            .load_then(&self.db, (village, min_id), |attacks| {
+                self.attack_units
+                    .prime(attacks.iter().map(|(_, atk)| atk.key()));
+                self.villages
+                    .prime(attacks.iter().filter_map(|(_, atk)| atk.origin()));
+            })
+    }
+    pub fn attack_units(&self, attack: AttackKey) -> Vec<(Hobo, AttackToHobo)> {
+        self.attack_units.load_then(&self.db, attack, |units| {
+            self.hobo_effects
+                .prime(units.iter().map(|(_, (hobo, _))| hobo.key()));
+        })
+    }
+    pub fn hobo_is_attacking(&self, hobo: HoboKey) -> bool {
+        !self.hobo_attacks.load(&self.db, hobo).is_empty()
+    }
+    pub fn effects(&self, hobo: HoboKey) -> Vec<Effect> {
+        self.hobo_effects.load(&self.db, hobo)
+    }
+    /// Sum of rewards per resource type
+    pub fn report_rewards(&self, report: VisitReportKey) -> Vec<(ResourceType, i64)> {
+        let mut out: Vec<(ResourceType, i64)> = vec![];
+        for reward in self.report_rewards.load(&self.db, report) {
+            match out.iter_mut().find(|(rt, _)| *rt == reward.resource_type) {
+                Some((_, n)) => *n += reward.amount,
+                None => out.push((reward.resource_type, reward.amount)),
+            }
+        }
+        out
+    }
+    pub fn prime_quests(&self, quests: &[QuestKey]) {
+        self.quest_res_rewards.prime(quests.iter().copied());
+        self.quest_res_conditions.prime(quests.iter().copied());
+        self.quest_building_conditions.prime(quests.iter().copied());
+        self.quest_worker_conditions.prime(quests.iter().copied());
+        self.quest_stat_conditions.prime(quests.iter().copied());
+        self.quest_worker_rewards.prime(quests.iter().copied());
+        self.quest_perk_rewards.prime(quests.iter().copied());
+        self.quest_building_rewards.prime(quests.iter().copied());
+    }
+    pub fn quest_res_rewards(&self, quest: QuestKey) -> Vec<QuestResReward> {
+        self.quest_res_rewards.load(&self.db, quest)
+    }
+    pub fn quest_res_conditions(&self, quest: QuestKey) -> Vec<QuestResCondition> {
+        self.quest_res_conditions.load(&self.db, quest)
+    }
+    pub fn quest_building_conditions(&self, quest: QuestKey) -> Vec<QuestBuildingCondition> {
+        self.quest_building_conditions.load(&self.db, quest)
+    }
+    pub fn quest_worker_conditions(&self, quest: QuestKey) -> Vec<QuestWorkerCondition> {
+        self.quest_worker_conditions.load(&self.db, quest)
+    }
+    pub fn quest_stat_conditions(&self, quest: QuestKey) -> Vec<QuestStatCondition> {
+        self.quest_stat_conditions.load(&self.db, quest)
+    }
+    pub fn quest_worker_reward(&self, quest: QuestKey) -> Option<QuestWorkerReward> {
+        self.quest_worker_rewards.load(&self.db, quest).pop()
+    }
+    pub fn quest_perk_rewards(&self, quest: QuestKey) -> Vec<QuestPerkReward> {
+        self.quest_perk_rewards.load(&self.db, quest)
+    }
+    pub fn quest_building_rewards(&self, quest: QuestKey) -> Vec<QuestBuildingReward> {
+        self.quest_building_rewards.load(&self.db, quest)
+    }
+    /// None for tracked statistics, see `quest_progress`
+    pub fn village_stat(&self, village: VillageKey, stat: QuestStatType) -> Option<i64> {
+        self.village_stats.load(&self.db, (village, stat)).pop()
+    }
+    pub fn quest_progress(&self, player: PlayerKey, quest: QuestKey, stat: QuestStatType) -> i64 {
+        self.player_quest_progress
+            .load(&self.db, player)
+            .into_iter()
+            .find(|p| p.quest_id == quest.num() && p.stat_type == stat)
+            .map(|p| p.amount)
+            .unwrap_or(0)
+    }
+}
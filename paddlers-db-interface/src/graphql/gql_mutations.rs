@@ -0,0 +1,591 @@
+//! Mutations that change the game state.
+//!
+//! The db-interface does not write to the database, every mutation is forwarded to the game-master,
+//! which applies the same validation as for requests to its REST API.
+//! Rejections by the game rules are part of the result type, so that clients can handle them like any other result.
+//! Errors that are not the player's fault, like an unreachable game-master, are returned as GraphQL errors.
+//!
+//! Mutations are only available if enabled in the configuration and the request carries an authentication token.
+
+use super::*;
+use crate::game_master::GameMasterError;
+use juniper::FieldError;
+use paddlers_shared_lib::api::{
+    error::GameMasterError as ActionError,
+    hobo::SettleHobo,
+    quests::QuestCollect,
+    reports::ReportCollect,
+    shop::{BuildingDeletion, BuildingPurchase, BuildingUpgrade, ProphetPurchase},
+    tasks::{RawTask, TaskError, TaskList},
+};
+use serde::Serialize;
+
+pub struct Mutation;
+
+/// The action has been executed
+pub struct GqlActionSuccess;
+/// The game rules do not allow the action at this time, for example because resources are missing
+pub struct GqlActionRejected {
+    reason: String,
+}
+/// The action refers to objects the player does not own
+pub struct GqlActionNotAllowed {
+    reason: String,
+}
+pub struct GqlBuildingPlaced(GqlBuilding);
+/// The task list was rejected because of an ability target
+pub struct GqlTaskRejected(TaskError);
+
+pub enum GqlActionResult {
+    Success(GqlActionSuccess),
+    Rejected(GqlActionRejected),
+    NotAllowed(GqlActionNotAllowed),
+}
+pub enum GqlBuildingPurchaseResult {
+    Placed(GqlBuildingPlaced),
+    Rejected(GqlActionRejected),
+    NotAllowed(GqlActionNotAllowed),
+}
+pub enum GqlTaskListResult {
+    Success(GqlActionSuccess),
+    TaskRejected(GqlTaskRejected),
+    Rejected(GqlActionRejected),
+    NotAllowed(GqlActionNotAllowed),
+}
+
+#[derive(juniper::GraphQLInputObject)]
+pub struct GqlTaskInput {
+    task_type: TaskType,
+    x: i32,
+    y: i32,
+    /// Hobo targeted by an ability
+    target_hobo_id: Option<i32>,
+}
+
+#[juniper::object(Context = Context)]
+impl Mutation {
+    // Object Visibility: user
+    fn purchase_building(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        village_id: i32,
+        building_type: BuildingType,
+        x: i32,
+        y: i32,
+    ) -> FieldResult<GqlBuildingPurchaseResult> {
+        ctx.charge(executor)?;
+        let body = BuildingPurchase {
+            village: VillageKey(village_id as i64),
+            building_type,
+            x: coordinate(x)?,
+            y: coordinate(y)?,
+        };
+        let response = match forward(ctx, "/shop/building", &body)? {
+            Ok(response) => response,
+            Err(GameMasterError::Rejected(e)) => {
+                return Ok(GqlBuildingPurchaseResult::Rejected(GqlActionRejected {
+                    reason: e.to_string(),
+                }))
+            }
+            Err(GameMasterError::Forbidden(e)) => {
+                return Ok(GqlBuildingPurchaseResult::NotAllowed(GqlActionNotAllowed {
+                    reason: e.to_string(),
+                }))
+            }
+            Err(e) => return Err(unexpected(e)),
+        };
+        let id: i64 = serde_json::from_str(&response)
+            .map_err(|_| unexpected(GameMasterError::Unavailable(response)))?;
+        let building = ctx
+            .db()
+            .building(BuildingKey(id))
+            .ok_or("Placed building not found")?;
+        Ok(GqlBuildingPurchaseResult::Placed(GqlBuildingPlaced(
+            GqlBuilding(building),
+        )))
+    }
+    // Object Visibility: user
+    fn delete_building(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        village_id: i32,
+        x: i32,
+        y: i32,
+    ) -> FieldResult<GqlActionResult> {
+        ctx.charge(executor)?;
+        let body = BuildingDeletion {
+            village: VillageKey(village_id as i64),
+            x: coordinate(x)?,
+            y: coordinate(y)?,
+        };
+        GqlActionResult::from_response(forward(ctx, "/shop/building/delete", &body)?)
+    }
+    /// The current level prevents that a repeated request upgrades the building twice
+    // Object Visibility: user
+    fn upgrade_building(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        building_id: i32,
+        current_level: i32,
+    ) -> FieldResult<GqlActionResult> {
+        ctx.charge(executor)?;
+        let body = BuildingUpgrade {
+            building: BuildingKey(building_id as i64),
+            current_level: current_level.max(0) as usize,
+        };
+        GqlActionResult::from_response(forward(ctx, "/shop/building/upgrade", &body)?)
+    }
+    // Object Visibility: user
+    fn purchase_prophet(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        village_id: i32,
+    ) -> FieldResult<GqlActionResult> {
+        ctx.charge(executor)?;
+        let body = ProphetPurchase {
+            village: VillageKey(village_id as i64),
+        };
+        GqlActionResult::from_response(forward(ctx, "/shop/unit/prophet", &body)?)
+    }
+    /// Replaces all tasks of the worker
+    // Object Visibility: user
+    fn overwrite_tasks(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        worker_id: i32,
+        tasks: Vec<GqlTaskInput>,
+    ) -> FieldResult<GqlTaskListResult> {
+        ctx.charge(executor)?;
+        let tasks = tasks
+            .into_iter()
+            .map(|t| {
+                Ok(RawTask {
+                    task_type: t.task_type,
+                    x: coordinate(t.x)?,
+                    y: coordinate(t.y)?,
+                    target: t.target_hobo_id.map(|id| id as i64),
+                })
+            })
+            .collect::<FieldResult<_>>()?;
+        let body = TaskList {
+            worker_id: WorkerKey(worker_id as i64),
+            tasks,
+        };
+        Ok(match forward(ctx, "/worker/overwriteTasks", &body)? {
+            Ok(_) => GqlTaskListResult::Success(GqlActionSuccess),
+            Err(GameMasterError::Rejected(ActionError::TaskRejected(e))) => {
+                GqlTaskListResult::TaskRejected(GqlTaskRejected(e))
+            }
+            Err(GameMasterError::Rejected(e)) => GqlTaskListResult::Rejected(GqlActionRejected {
+                reason: e.to_string(),
+            }),
+            Err(GameMasterError::Forbidden(e)) => {
+                GqlTaskListResult::NotAllowed(GqlActionNotAllowed {
+                    reason: e.to_string(),
+                })
+            }
+            Err(e) => return Err(unexpected(e)),
+        })
+    }
+    // Object Visibility: user
+    fn collect_quest(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        quest_id: i32,
+    ) -> FieldResult<GqlActionResult> {
+        ctx.charge(executor)?;
+        let body = QuestCollect {
+            quest: QuestKey(quest_id as i64),
+        };
+        GqlActionResult::from_response(forward(ctx, "/quest/collect", &body)?)
+    }
+    // Object Visibility: user
+    fn collect_reports(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        report_ids: Vec<i32>,
+    ) -> FieldResult<GqlActionResult> {
+        ctx.charge(executor)?;
+        let body = ReportCollect {
+            reports: report_ids
+                .into_iter()
+                .map(|id| VisitReportKey(id as i64))
+                .collect(),
+        };
+        GqlActionResult::from_response(forward(ctx, "/report/collect", &body)?)
+    }
+    /// Settles a new hobo in an empty nest
+    // Object Visibility: user
+    fn settle_hobo(
+        ctx: &Context,
+        executor: &juniper::Executor,
+        nest_id: i32,
+    ) -> FieldResult<GqlActionResult> {
+        ctx.charge(executor)?;
+        let body = SettleHobo {
+            nest: BuildingKey(nest_id as i64),
+        };
+        GqlActionResult::from_response(forward(ctx, "/hobo/settle", &body)?)
+    }
+}
+
+/// Sends the request to the game-master. The outer error is for requests that cannot be forwarded at all.
+fn forward<B: Serialize>(
+    ctx: &Context,
+    path: &str,
+    body: &B,
+) -> FieldResult<Result<String, GameMasterError>> {
+    let game_master = ctx
+        .game_master()
+        .map_err(IntoFieldError::into_field_error)?;
+    Ok(game_master.post(path, body))
+}
+
+/// Errors that are not part of the result types
+fn unexpected(e: GameMasterError) -> FieldError {
+    match e {
+        GameMasterError::Unauthorized => ReadableInterfaceError::RequiresAuthentication,
+        GameMasterError::Rejected(e) | GameMasterError::Forbidden(e) => {
+            ReadableInterfaceError::GameMasterUnavailable(e.to_string())
+        }
+        GameMasterError::Unavailable(msg) => ReadableInterfaceError::GameMasterUnavailable(msg),
+    }
+    .into_field_error()
+}
+
+fn coordinate(i: i32) -> FieldResult<usize> {
+    if i < 0 {
+        return Err("Coordinates cannot be negative".into());
+    }
+    Ok(i as usize)
+}
+
+impl GqlActionResult {
+    fn from_response(response: Result<String, GameMasterError>) -> FieldResult<Self> {
+        match response {
+            Ok(_) => Ok(GqlActionResult::Success(GqlActionSuccess)),
+            Err(GameMasterError::Rejected(e)) => Ok(GqlActionResult::Rejected(GqlActionRejected {
+                reason: e.to_string(),
+            })),
+            Err(GameMasterError::Forbidden(e)) => {
+                Ok(GqlActionResult::NotAllowed(GqlActionNotAllowed {
+                    reason: e.to_string(),
+                }))
+            }
+            Err(e) => Err(unexpected(e)),
+        }
+    }
+}
+
+#[juniper::union(Context = Context)]
+impl GqlActionResult {
+    fn resolve(&self) {
+        match self {
+            GqlActionSuccess => match *self {
+                GqlActionResult::Success(ref s) => Some(s),
+                _ => None,
+            },
+            GqlActionRejected => match *self {
+                GqlActionResult::Rejected(ref r) => Some(r),
+                _ => None,
+            },
+            GqlActionNotAllowed => match *self {
+                GqlActionResult::NotAllowed(ref n) => Some(n),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[juniper::union(Context = Context)]
+impl GqlBuildingPurchaseResult {
+    fn resolve(&self) {
+        match self {
+            GqlBuildingPlaced => match *self {
+                GqlBuildingPurchaseResult::Placed(ref p) => Some(p),
+                _ => None,
+            },
+            GqlActionRejected => match *self {
+                GqlBuildingPurchaseResult::Rejected(ref r) => Some(r),
+                _ => None,
+            },
+            GqlActionNotAllowed => match *self {
+                GqlBuildingPurchaseResult::NotAllowed(ref n) => Some(n),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[juniper::union(Context = Context)]
+impl GqlTaskListResult {
+    fn resolve(&self) {
+        match self {
+            GqlActionSuccess => match *self {
+                GqlTaskListResult::Success(ref s) => Some(s),
+                _ => None,
+            },
+            GqlTaskRejected => match *self {
+                GqlTaskListResult::TaskRejected(ref t) => Some(t),
+                _ => None,
+            },
+            GqlActionRejected => match *self {
+                GqlTaskListResult::Rejected(ref r) => Some(r),
+                _ => None,
+            },
+            GqlActionNotAllowed => match *self {
+                GqlTaskListResult::NotAllowed(ref n) => Some(n),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[juniper::object(Context = Context)]
+impl GqlActionSuccess {
+    /// Always true, a GraphQL object needs at least one field
+    fn done(&self) -> bool {
+        true
+    }
+}
+#[juniper::object(Context = Context)]
+impl GqlActionRejected {
+    fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+#[juniper::object(Context = Context)]
+impl GqlActionNotAllowed {
+    fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+#[juniper::object(Context = Context)]
+impl GqlBuildingPlaced {
+    fn building(&self) -> &GqlBuilding {
+        &self.0
+    }
+}
+#[juniper::object(Context = Context)]
+impl GqlTaskRejected {
+    fn reason(&self) -> String {
+        self.0.to_string()
+    }
+    /// Distance to the target, if it was out of range
+    fn distance(&self) -> Option<f64> {
+        match self.0 {
+            TaskError::TargetOutOfRange { distance, .. } => Some(distance as f64),
+            _ => None,
+        }
+    }
+    /// Range of the ability, if the target was out of range
+    fn range(&self) -> Option<f64> {
+        match self.0 {
+            TaskError::TargetOutOfRange { range, .. } => Some(range as f64),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_master::GameMaster;
+    use crate::graphql::new_schema;
+    use diesel::prelude::*;
+    use juniper::{DefaultScalarValue, Value, Variables};
+    use paddlers_shared_lib::api::error::GameObject;
+    use paddlers_shared_lib::story::story_state::StoryState;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    const PURCHASE_PROPHET: &str = "mutation { purchaseProphet(villageId: 1) { __typename ... on GqlActionRejected { reason } ... on GqlActionNotAllowed { reason } } }";
+
+    #[test]
+    fn game_master_errors_become_results() {
+        let rejected = ActionError::NotEnoughKarma;
+        match GqlActionResult::from_response(Err(GameMasterError::Rejected(rejected))) {
+            Ok(GqlActionResult::Rejected(r)) => assert_eq!(r.reason, "Not enough karma."),
+            _ => panic!("Expected a rejection"),
+        }
+        let forbidden = ActionError::NotOwned(GameObject::Village);
+        match GqlActionResult::from_response(Err(GameMasterError::Forbidden(forbidden))) {
+            Ok(GqlActionResult::NotAllowed(n)) => {
+                assert_eq!(n.reason, "Village not owned by player.")
+            }
+            _ => panic!("Expected a refusal"),
+        }
+        assert!(matches!(
+            GqlActionResult::from_response(Ok(String::new())),
+            Ok(GqlActionResult::Success(_))
+        ));
+        assert!(GqlActionResult::from_response(Err(GameMasterError::Unauthorized)).is_err());
+        assert!(
+            GqlActionResult::from_response(Err(GameMasterError::Unavailable("down".to_owned())))
+                .is_err()
+        );
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn mutations_require_authentication_and_game_master() {
+        let (_, errors) = execute(&public_context(limits()), PURCHASE_PROPHET);
+        assert_eq!(errors, vec!["Authentication required"]);
+        let (_, errors) = execute(&player_context(limits()), PURCHASE_PROPHET);
+        assert_eq!(errors, vec!["Mutations disabled"]);
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn mutations_are_charged() {
+        let tight = QueryLimits {
+            max_depth: 4,
+            max_cost: 0,
+        };
+        // The game-master would answer with success if it was contacted
+        let ctx = player_context(tight).with_game_master(fake_game_master(200, "".to_owned()));
+        let (_, errors) = execute(&ctx, PURCHASE_PROPHET);
+        assert_eq!(errors, vec!["Query too expensive"]);
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn game_master_responses_are_forwarded() {
+        let cases = vec![
+            (200, "".to_owned(), "GqlActionSuccess", None),
+            (
+                400,
+                error_body(ActionError::NotEnoughKarma),
+                "GqlActionRejected",
+                Some("Not enough karma."),
+            ),
+            (
+                403,
+                error_body(ActionError::NotOwned(GameObject::Village)),
+                "GqlActionNotAllowed",
+                Some("Village not owned by player."),
+            ),
+        ];
+        for (status, body, typename, reason) in cases {
+            let ctx = player_context(limits()).with_game_master(fake_game_master(status, body));
+            let (data, errors) = execute(&ctx, PURCHASE_PROPHET);
+            assert!(errors.is_empty(), "{:?}", errors);
+            let result = data
+                .as_object_value()
+                .and_then(|o| o.get_field_value("purchaseProphet"))
+                .and_then(Value::as_object_value)
+                .expect("Mutation result");
+            assert_eq!(
+                result.get_field_value("__typename"),
+                Some(&Value::scalar(typename))
+            );
+            assert_eq!(
+                result.get_field_value("reason").cloned(),
+                reason.map(Value::scalar)
+            );
+        }
+        let ctx = player_context(limits()).with_game_master(fake_game_master(500, "".to_owned()));
+        let (_, errors) = execute(&ctx, PURCHASE_PROPHET);
+        assert_eq!(errors, vec!["Game-master unavailable"]);
+    }
+
+    fn limits() -> QueryLimits {
+        (&Config::default()).into()
+    }
+
+    fn request_db() -> RequestDb {
+        let url = paddlers_shared_lib::get_db_url();
+        RequestDb::new(Box::new(
+            PgConnection::establish(&url).expect("DB connection"),
+        ))
+    }
+
+    fn public_context(limits: QueryLimits) -> Context {
+        Context::new(request_db(), None, limits).unwrap()
+    }
+
+    /// Authenticated context of a player that does not need to exist, all checks are left to the game-master
+    fn player_context(limits: QueryLimits) -> Context {
+        let db = Rc::new(request_db());
+        Context::Authenticated(AuthenticatedContext {
+            loaders: Loaders::new(db.clone()),
+            budget: QueryBudget::new(limits),
+            db,
+            user: Player {
+                id: 0,
+                uuid: uuid::Uuid::nil(),
+                karma: 0,
+                display_name: "Mutation Tester".to_owned(),
+                story_state: StoryState::INITIALIZED,
+                civ_perks: 0,
+                utc_offset_minutes: 0,
+            },
+            villages: vec![VillageKey(1)],
+            game_master: None,
+        })
+    }
+
+    /// Executes the query and returns the data and the internal error codes of all errors
+    fn execute(ctx: &Context, query: &str) -> (Value, Vec<String>) {
+        let (data, errors) =
+            juniper::execute(query, None, &new_schema(), &Variables::new(), ctx).unwrap();
+        let codes = errors
+            .iter()
+            .filter_map(|e| {
+                match e
+                    .error()
+                    .extensions()
+                    .as_object_value()?
+                    .get_field_value("internal_error")?
+                {
+                    Value::Scalar(DefaultScalarValue::String(s)) => Some(s.clone()),
+                    _ => None,
+                }
+            })
+            .collect();
+        (data, codes)
+    }
+
+    fn error_body(error: ActionError) -> String {
+        serde_json::to_string(&error.body()).unwrap()
+    }
+
+    /// Game-master that answers a single request with a fixed response
+    fn fake_game_master(status: u16, body: String) -> GameMaster {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = Config {
+            game_master_service_name: listener.local_addr().unwrap().to_string(),
+            ..Config::default()
+        };
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                let lower = line.to_ascii_lowercase();
+                if let Some(len) = lower.strip_prefix("content-length:") {
+                    content_length = len.trim().parse().unwrap();
+                }
+            }
+            let mut request_body = vec![0; content_length];
+            reader.read_exact(&mut request_body).unwrap();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {} Fake\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+        GameMaster::new(&config, 1).with_token("token")
+    }
+}
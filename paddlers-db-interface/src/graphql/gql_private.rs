@@ -5,6 +5,7 @@
 use super::*;
 use juniper;
 use juniper::FieldResult;
+use paddlers_shared_lib::civilization::CivilizationPerks;
 
 #[juniper::object (Context = Context)]
 impl GqlAttack {
@@ -13,8 +14,8 @@ impl GqlAttack {
     }
     fn units(&self, ctx: &Context) -> FieldResult<Vec<GqlAttackUnit>> {
         Ok(ctx
-            .db()
-            .attack_hobos_with_attack_info(&self.0)
+            .loaders()
+            .attack_units(self.0.key())
             .into_iter()
             .map(|(hobo, info)| GqlAttackUnit(GqlHobo(hobo), GqlHoboAttackInfo(info)))
             .collect())
@@ -32,14 +33,22 @@ impl GqlAttack {
             .map(datetime)
             .transpose()
     }
+    /// Village the visitors are coming from, if they have a specific origin
+    fn origin_village(&self, ctx: &Context) -> FieldResult<Option<GqlVillage>> {
+        Ok(self
+            .0
+            .origin_village_id
+            .and_then(|vid| ctx.loaders().village(VillageKey(vid)))
+            .map(GqlVillage))
+    }
     fn attacker(&self, ctx: &Context) -> FieldResult<Option<GqlPlayer>> {
-        let db = ctx.db();
+        let loaders = ctx.loaders();
         Ok(self
             .0
             .origin_village_id
-            .and_then(|vid| db.village(VillageKey(vid)))
+            .and_then(|vid| loaders.village(VillageKey(vid)))
             .and_then(|village| village.owner())
-            .and_then(|pid| db.player(pid))
+            .and_then(|pid| loaders.player(pid))
             .map(|player| GqlPlayer(player)))
     }
 }
@@ -60,6 +69,13 @@ impl GqlHoboAttackInfo {
     fn satisfied(&self) -> FieldResult<Option<bool>> {
         Ok(self.0.satisfied)
     }
+    /// Cheers received from buildings, counted until `cheeredUntil`
+    fn cheers(&self) -> i32 {
+        self.0.cheers
+    }
+    fn cheered_until(&self) -> FieldResult<Option<GqlTimestamp>> {
+        Ok(self.0.cheered_until.as_ref().map(GqlTimestamp::from_chrono))
+    }
 }
 
 #[juniper::object (Context = Context)]
@@ -83,15 +99,15 @@ impl GqlAttackReport {
 impl GqlAttackReport {
     pub fn load_rewards(&mut self, ctx: &Context) {
         if self.rewards.is_none() {
-            let db = ctx.db();
-            self.rewards = Some(db.rewards(self.inner.key()).into())
+            let rewards = ctx.loaders().report_rewards(self.inner.key());
+            self.rewards = Some(rewards.into())
         }
     }
     pub fn load_sender(&mut self, ctx: &Context) {
         if self.sender.is_none() {
-            let db = ctx.db();
             if let Some(sender_id) = self.inner.sender() {
-                self.sender = Some(Some(GqlHobo(db.hobo(sender_id).unwrap())));
+                let sender = ctx.loaders().hobo(sender_id).unwrap();
+                self.sender = Some(Some(GqlHobo(sender)));
             } else {
                 self.sender = Some(None)
             }
@@ -179,16 +195,16 @@ impl GqlWorker {
     }
 
     pub fn tasks(&self, ctx: &Context) -> Vec<GqlTask> {
-        ctx.db()
-            .worker_tasks(WorkerKey(self.0.id))
+        ctx.loaders()
+            .tasks(WorkerKey(self.0.id))
             .into_iter()
             .map(GqlTask::authorized) // Inherited authorization
             .collect()
     }
 
     fn abilities(&self, ctx: &Context) -> Vec<GqlAbility> {
-        ctx.db()
-            .worker_abilities(WorkerKey(self.0.id))
+        ctx.loaders()
+            .abilities(WorkerKey(self.0.id))
             .into_iter()
             .map(GqlAbility::authorized) // Inherited authorization
             .collect()
@@ -211,35 +227,70 @@ impl GqlQuest {
     pub fn key(&self) -> &str {
         &self.0.quest_key
     }
-    pub fn rewards(&self, ctx: &Context) -> Resources {
-        ctx.db()
-            .quest_res_rewards(QuestKey(self.0.id))
+    pub fn rewards(&self, ctx: &Context) -> QuestRewards {
+        let loaders = ctx.loaders();
+        let key = QuestKey(self.0.id);
+        let res = loaders
+            .quest_res_rewards(key)
             .into_iter()
             .map(|r| (r.resource_type, r.amount))
             .collect::<Vec<_>>()
-            .into()
+            .into();
+        let worker_reward = loaders.quest_worker_reward(key);
+        let mut perks = CivilizationPerks::new(0);
+        for reward in loaders.quest_perk_rewards(key) {
+            perks.set(reward.perk);
+        }
+        let buildings = loaders
+            .quest_building_rewards(key)
+            .into_iter()
+            .map(|r| r.building_type)
+            .collect();
+        QuestRewards {
+            res,
+            mana: worker_reward.map(|r| r.mana as i32).unwrap_or(0),
+            experience: worker_reward.map(|r| r.experience as i32).unwrap_or(0),
+            perks,
+            buildings,
+        }
     }
     pub fn conditions(&self, ctx: &Context) -> QuestConditions {
-        let db = ctx.db();
+        let loaders = ctx.loaders();
         let key = QuestKey(self.0.id);
-        let res = db
+        let res = loaders
             .quest_res_conditions(key)
             .into_iter()
             .map(|r| (r.resource_type, r.amount))
             .collect::<Vec<_>>()
             .into();
-        let buildings = db
+        let buildings = loaders
             .quest_building_conditions(key)
             .into_iter()
             .map(Into::into)
             .collect();
-        let worker = db
+        let worker = loaders
             .quest_worker_conditions(key)
             .into_iter()
             .map(Into::into)
             .collect();
+        // Assuming one village per player
+        let village = loaders.player_villages(self.1).first().map(|v| v.key());
+        let stats = loaders
+            .quest_stat_conditions(key)
+            .into_iter()
+            .map(|c| {
+                let current = if c.stat_type.is_tracked() {
+                    loaders.quest_progress(self.1, key, c.stat_type)
+                } else {
+                    village
+                        .and_then(|v| loaders.village_stat(v, c.stat_type))
+                        .unwrap_or(0)
+                };
+                StatCondition::new(c, current)
+            })
+            .collect();
         let karma = self.0.karma_condition;
         let pop = self.0.pop_condition;
-        QuestConditions::new(res, karma, pop, buildings, worker)
+        QuestConditions::new(res, karma, pop, buildings, worker, stats)
     }
 }
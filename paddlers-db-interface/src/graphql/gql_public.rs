@@ -20,7 +20,7 @@
 use super::*;
 use juniper;
 use juniper::FieldResult;
-use paddlers_shared_lib::{civilization::CivilizationPerks, story::story_state::StoryState};
+use paddlers_shared_lib::civilization::CivilizationPerks;
 use paddlers_shared_lib::{civilization::SerializedCivPerks, sql_db::keys::SqlKey};
 
 // Complete list of fully public objects without private sub fields.
@@ -57,7 +57,12 @@ pub struct GqlAttackReport {
     _priv: PrivacyGuard,
 }
 pub struct GqlEffect(pub paddlers_shared_lib::models::Effect, PrivacyGuard);
-pub struct GqlQuest(pub paddlers_shared_lib::models::Quest, PrivacyGuard);
+/// A quest together with the player it is assigned to
+pub struct GqlQuest(
+    pub paddlers_shared_lib::models::Quest,
+    pub PlayerKey,
+    PrivacyGuard,
+);
 pub struct GqlTask(pub paddlers_shared_lib::models::Task, PrivacyGuard);
 pub struct GqlWorker(pub paddlers_shared_lib::models::Worker, PrivacyGuard);
 
@@ -73,10 +78,10 @@ impl GqlPlayer {
     }
     /// Field Visibility: public
     fn villages(&self, ctx: &Context) -> Vec<GqlVillage> {
-        ctx.db()
-            .player_villages(PlayerKey(self.0.id))
+        ctx.loaders()
+            .player_villages(self.0.key())
             .into_iter()
-            .map(|t| GqlVillage(t))
+            .map(GqlVillage)
             .collect()
     }
     /// Field Visibility: public
@@ -89,11 +94,11 @@ impl GqlPlayer {
         ctx.check_user_key(self.0.key())?;
         Ok(ctx.db().player_prophets_count(self.0.uuid) as i32)
     }
-    /// Player progress in story
+    /// Player progress in story, the name of a state in the story graph
     /// Field Visibility: user
-    fn story_state(&self, ctx: &Context) -> FieldResult<StoryState> {
+    fn story_state(&self, ctx: &Context) -> FieldResult<String> {
         ctx.check_user_key(self.0.key())?;
-        Ok(self.0.story_state)
+        Ok(self.0.story_state.to_string())
     }
     /// Player civilization choices and progress, encoded in a single number
     /// Field Visibility: user
@@ -101,15 +106,22 @@ impl GqlPlayer {
         ctx.check_user_key(self.0.key())?;
         Ok(CivilizationPerks::new(self.0.civ_perks as u32).encode())
     }
+    /// Buildings unlocked by quests, in addition to those available through karma, story state and perks
+    /// Field Visibility: user
+    fn unlocked_buildings(&self, ctx: &Context) -> FieldResult<Vec<BuildingType>> {
+        ctx.check_user_key(self.0.key())?;
+        Ok(ctx.db().unlocked_buildings(self.0.key()))
+    }
     /// Active queries of a player
     /// Field Visibility: user
     fn quests(&self, ctx: &Context) -> FieldResult<Vec<GqlQuest>> {
         ctx.check_user_key(self.0.key())?;
-        Ok(ctx
-            .db()
-            .player_quests(PlayerKey(self.0.id))
+        let quests = ctx.db().player_quests(PlayerKey(self.0.id));
+        let keys: Vec<QuestKey> = quests.iter().map(|q| q.key()).collect();
+        ctx.loaders().prime_quests(&keys);
+        Ok(quests
             .into_iter()
-            .map(|t| GqlQuest(t, PrivacyGuard))
+            .map(|t| GqlQuest(t, self.0.key(), PrivacyGuard))
             .collect())
     }
 }
@@ -132,6 +144,15 @@ impl GqlVillage {
     fn faith(&self) -> i32 {
         self.0.faith as i32
     }
+    /// The stream the village is located at
+    /// Field Visibility: public
+    fn stream(&self, ctx: &Context) -> FieldResult<GqlStream> {
+        let stream = ctx
+            .loaders()
+            .stream(StreamKey(self.0.stream_id))
+            .ok_or("Invalid stream key on village")?;
+        Ok(GqlStream(stream))
+    }
     /// Field Visibility: user
     fn sticks(&self, ctx: &Context) -> FieldResult<i32> {
         ctx.check_village_key(self.0.key())?;
@@ -151,7 +172,7 @@ impl GqlVillage {
     fn workers(&self, ctx: &Context) -> FieldResult<Vec<GqlWorker>> {
         ctx.check_village_key(self.0.key())?;
         Ok(ctx
-            .db()
+            .loaders()
             .workers(self.0.key())
             .into_iter()
             .map(GqlWorker::authorized)
@@ -172,9 +193,10 @@ impl GqlVillage {
     /// Field Visibility: user
     fn attacks(&self, ctx: &Context, min_id: Option<i32>) -> FieldResult<Vec<GqlAttack>> {
         ctx.check_village_key(self.0.key())?;
+        let min_id = min_id.map(i64::from).unwrap_or(0);
         Ok(ctx
-            .db()
-            .attacks(self.0.key(), min_id.map(i64::from))
+            .loaders()
+            .attacks(self.0.key(), min_id)
             .into_iter()
             .map(GqlAttack::authorized)
             .collect())
@@ -183,7 +205,10 @@ impl GqlVillage {
     fn owner(&self, ctx: &Context) -> FieldResult<Option<GqlPlayer>> {
         Ok(if let Some(owner) = self.0.player_id {
             let key = PlayerKey(owner as i64);
-            let player = ctx.db().player(key).ok_or("Invalid owner key on village")?;
+            let player = ctx
+                .loaders()
+                .player(key)
+                .ok_or("Invalid owner key on village")?;
             Some(GqlPlayer(player))
         } else {
             None
@@ -191,35 +216,71 @@ impl GqlVillage {
     }
     /// Field Visibility: public
     fn hobos(&self, ctx: &Context) -> FieldResult<Vec<GqlHobo>> {
-        Ok(ctx
-            .db()
-            .village_hobos(self.0.key())
-            .into_iter()
-            .map(GqlHobo)
-            .collect())
+        let hobos = ctx.db().village_hobos(self.0.key());
+        let loaders = ctx.loaders();
+        loaders.hobo_attacks.prime(hobos.iter().map(|h| h.key()));
+        loaders.hobo_effects.prime(hobos.iter().map(|h| h.key()));
+        loaders
+            .buildings
+            .prime(hobos.iter().filter_map(|h| h.nest.map(BuildingKey)));
+        Ok(hobos.into_iter().map(GqlHobo).collect())
     }
     /// Field Visibility: user
     fn reports(&self, ctx: &Context, min_id: Option<i32>) -> FieldResult<Vec<GqlAttackReport>> {
         ctx.check_village_key(self.0.key())?;
-        Ok(ctx
-            .db()
-            .reports(self.0.key(), min_id.map(i64::from))
-            .into_iter()
-            .map(|report| GqlAttackReport {
-                inner: report,
-                rewards: None,
-                sender: None,
-                _priv: PrivacyGuard,
-            })
-            .map(|mut rep| {
-                rep.load_rewards(ctx);
-                rep
-            })
-            .map(|mut rep| {
-                rep.load_sender(ctx);
-                rep
-            })
-            .collect())
+        let reports = ctx.db().reports(self.0.key(), min_id.map(i64::from));
+        Ok(GqlAttackReport::authorized_list(ctx, reports))
+    }
+    /// Visit reports of the village, newest first
+    /// Field Visibility: user
+    fn reports_connection(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> FieldResult<GqlConnection<GqlAttackReport>> {
+        ctx.check_village_key(self.0.key())?;
+        let page = page(first, after, last, before)?;
+        let reports = ctx.db().reports_page(self.0.key(), &page);
+        let total_count = ctx.db().reports_count(self.0.key());
+        Ok(GqlConnection::new(
+            &page,
+            reports,
+            total_count,
+            |report| report.key(),
+            |reports| GqlAttackReport::authorized_list(ctx, reports),
+        ))
+    }
+    /// Attacks on the village, sorted by arrival
+    /// Field Visibility: user
+    fn attacks_connection(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> FieldResult<GqlConnection<GqlAttack>> {
+        ctx.check_village_key(self.0.key())?;
+        let page = page(first, after, last, before)?;
+        let attacks = ctx.db().attacks_page(self.0.key(), &page);
+        let total_count = ctx.db().attacks_count(self.0.key(), None) as i64;
+        let loaders = ctx.loaders();
+        loaders
+            .attack_units
+            .prime(attacks.iter().map(|atk| atk.key()));
+        loaders
+            .villages
+            .prime(attacks.iter().filter_map(|atk| atk.origin()));
+        Ok(GqlConnection::new(
+            &page,
+            attacks,
+            total_count,
+            |atk| (atk.arrival, atk.key()),
+            |attacks| attacks.into_iter().map(GqlAttack::authorized).collect(),
+        ))
     }
 }
 
@@ -277,14 +338,18 @@ impl GqlHobo {
         self.0.hp as i32
     }
     /// Field Visibility: public
+    pub fn level(&self) -> i32 {
+        self.0.level
+    }
+    /// Field Visibility: public
     // TODO: Proper type handling
     pub fn speed(&self) -> f64 {
         self.0.speed as f64
     }
     /// Field Visibility: public
     pub fn effects(&self, ctx: &Context) -> Vec<GqlEffect> {
-        ctx.db()
-            .effects_on_hobo(HoboKey(self.0.id))
+        ctx.loaders()
+            .effects(self.0.key())
             .into_iter()
             .map(GqlEffect::authorized)
             .collect()
@@ -295,17 +360,17 @@ impl GqlHobo {
     }
     /// Field Visibility: public
     pub fn home(&self, ctx: &Context) -> GqlVillage {
-        GqlVillage(ctx.db().village(self.0.home()).unwrap())
+        GqlVillage(ctx.loaders().village(self.0.home()).unwrap())
     }
     /// Field Visibility: public
     pub fn idle(&self, ctx: &Context) -> bool {
-        !ctx.db().hobo_is_attacking(self.0.key())
+        !ctx.loaders().hobo_is_attacking(self.0.key())
     }
     /// Field Visibility: public
     pub fn nest(&self, ctx: &Context) -> Option<GqlBuilding> {
         self.0
             .nest
-            .and_then(|b| ctx.db().building(BuildingKey(b)))
+            .and_then(|b| ctx.loaders().building(BuildingKey(b)))
             .map(GqlBuilding)
     }
 }
@@ -326,11 +391,9 @@ impl GqlMapSlice {
     }
     /// Field Visibility: public
     fn villages(&self, ctx: &Context) -> Vec<GqlVillage> {
-        ctx.db()
-            .villages(self.low_x as f32, self.high_x as f32)
-            .into_iter()
-            .map(|t| GqlVillage(t))
-            .collect()
+        let villages = ctx.db().villages(self.low_x as f32, self.high_x as f32);
+        ctx.loaders().prime_village_fields(villages.iter());
+        villages.into_iter().map(|t| GqlVillage(t)).collect()
     }
 }
 
@@ -353,13 +416,39 @@ impl GqlScoreboard {
         ctx.db().players_count() as i32
     }
     /// Field Visibility: public
-    /// Returns up to 100 players starting from the given rank upwards
+    /// Returns up to 100 players starting from the given rank upwards.
+    /// Prefer playersConnection for paging, offsets shift when the karma of players changes.
     fn players_by_karma(&self, ctx: &Context) -> Vec<GqlPlayer> {
-        ctx.db()
-            .players_sorted_by_karma(self.offset as i64, self.limit.min(100) as i64)
-            .into_iter()
-            .map(GqlPlayer)
-            .collect()
+        let players = ctx
+            .db()
+            .players_sorted_by_karma(self.offset as i64, self.limit.min(100) as i64);
+        ctx.loaders()
+            .player_villages
+            .prime(players.iter().map(|p| p.key()));
+        players.into_iter().map(GqlPlayer).collect()
+    }
+    /// Field Visibility: public
+    /// Players with the most karma first, players with equal karma are sorted by id
+    fn players_connection(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> FieldResult<GqlConnection<GqlPlayer>> {
+        let page = page(first, after, last, before)?;
+        let players = ctx.db().players_by_karma_page(&page);
+        ctx.loaders()
+            .player_villages
+            .prime(players.iter().map(|p| p.key()));
+        Ok(GqlConnection::new(
+            &page,
+            players,
+            ctx.db().players_count(),
+            |p| (p.karma, p.key()),
+            |players| players.into_iter().map(GqlPlayer).collect(),
+        ))
     }
 }
 
@@ -377,6 +466,38 @@ impl GqlAttack {
         GqlAttack(inner, PrivacyGuard)
     }
 }
+impl GqlAttackReport {
+    /// Creates the reports with batched loading of rewards and senders
+    fn authorized_list(
+        ctx: &Context,
+        reports: Vec<paddlers_shared_lib::models::VisitReport>,
+    ) -> Vec<Self> {
+        let loaders = ctx.loaders();
+        loaders
+            .report_rewards
+            .prime(reports.iter().map(|r| r.key()));
+        loaders
+            .hobos
+            .prime(reports.iter().filter_map(VisitReport::sender));
+        reports
+            .into_iter()
+            .map(|report| GqlAttackReport {
+                inner: report,
+                rewards: None,
+                sender: None,
+                _priv: PrivacyGuard,
+            })
+            .map(|mut rep| {
+                rep.load_rewards(ctx);
+                rep
+            })
+            .map(|mut rep| {
+                rep.load_sender(ctx);
+                rep
+            })
+            .collect()
+    }
+}
 impl GqlEffect {
     pub(super) fn authorized(inner: paddlers_shared_lib::models::Effect) -> Self {
         GqlEffect(inner, PrivacyGuard)
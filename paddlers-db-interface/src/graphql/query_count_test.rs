@@ -0,0 +1,362 @@
+//! Checks that the persisted frontend queries need the same number of DB queries, no matter how many objects they return.
+//! Requires a database, run with `cargo test -- --ignored`
+//!
+//! The statements are counted by Postgres itself, through the `pg_stat_statements` extension.
+//! It has to be loaded by the server, e.g. `postgres -c shared_preload_libraries=pg_stat_statements`.
+
+use super::*;
+use diesel::prelude::*;
+use juniper::{InputValue, Variables};
+use paddlers_shared_lib::api::persisted_queries::*;
+use paddlers_shared_lib::civilization::CivilizationPerk;
+use paddlers_shared_lib::schema::*;
+
+/// What the frontend queries need to know about a seeded world
+struct World {
+    player: uuid::Uuid,
+    village: VillageKey,
+    worker: WorkerKey,
+    x: i32,
+}
+
+#[test]
+#[ignore]
+fn query_count_is_independent_of_data_size() {
+    let url = paddlers_shared_lib::get_db_url();
+    let conn = Rc::new(PgConnection::establish(&url).expect("DB connection"));
+    diesel::sql_query("CREATE EXTENSION IF NOT EXISTS pg_stat_statements")
+        .execute(&*conn)
+        .expect("pg_stat_statements must be available to count queries");
+    // Nothing is committed, the seeded data disappears with the connection
+    conn.begin_test_transaction().unwrap();
+    let small = seed(&conn, 0, 1);
+    let large = seed(&conn, 1, 5);
+
+    let attacks_query = persisted_query_by_name("AttacksQuery").unwrap();
+    let (attacks, _) = run(&conn, &large, attacks_query.query);
+    let attacks = attacks
+        .as_object_value()
+        .and_then(|o| o.get_field_value("village"))
+        .and_then(|v| v.as_object_value())
+        .and_then(|o| o.get_field_value("attacks"))
+        .and_then(|a| a.as_list_value())
+        .expect("List of attacks");
+    assert_eq!(attacks.len(), 5);
+
+    for query in PERSISTED_QUERIES {
+        let (_, expected) = run(&conn, &small, query.query);
+        let (_, actual) = run(&conn, &large, query.query);
+        assert_eq!(
+            expected, actual,
+            "{} needs more DB queries with more data",
+            query.operation_name
+        );
+    }
+}
+
+/// Executes a query as the world's player, returns the result and the number of DB queries
+fn run(conn: &Rc<PgConnection>, world: &World, query: &str) -> (juniper::Value, usize) {
+    let db = Rc::new(RequestDb::new(conn.clone()));
+    // Default limits must be sufficient for all queries of the frontend
+    let limits = QueryLimits::from(&Config::default());
+    let ctx = Context::player_context(db, world.player, limits).expect("Seeded player");
+    let mut vars = Variables::new();
+    let mut set = |name: &str, value: i32| {
+        if query.contains(&format!("${}", name)) {
+            vars.insert(name.to_owned(), InputValue::scalar(value));
+        }
+    };
+    set("village_id", world.village.num() as i32);
+    set("workerId", world.worker.num() as i32);
+    set("lowX", world.x - 1);
+    set("highX", world.x + 1);
+    set("offset", 0);
+    let before = executed_statements(conn);
+    let (value, errors) = juniper::execute(query, None, &new_schema(), &vars, &ctx).unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    // The first count has been executed in between
+    let queries = executed_statements(conn) - before - 1;
+    (value, queries as usize)
+}
+
+#[derive(QueryableByName)]
+struct StatementCount {
+    #[sql_type = "diesel::sql_types::BigInt"]
+    calls: i64,
+}
+
+/// Statements the database has executed so far, on all connections
+fn executed_statements(conn: &PgConnection) -> i64 {
+    diesel::sql_query(
+        "SELECT COALESCE(SUM(calls), 0)::BIGINT AS calls FROM pg_stat_statements \
+         WHERE dbid = (SELECT oid FROM pg_database WHERE datname = current_database())",
+    )
+    .get_result::<StatementCount>(conn)
+    .expect("Counting statements")
+    .calls
+}
+
+/// Creates a player with a village that has n of every kind of object the frontend queries
+fn seed(conn: &PgConnection, world: i32, n: usize) -> World {
+    let now = chrono::Utc::now().naive_utc();
+    // Far away from regular villages on the map
+    let x = -10_000 * (world + 1);
+    let new_player = |i: usize| NewPlayer {
+        uuid: uuid::Uuid::from_u128(0x9add_1e25_0000 + (world as u128) * 1000 + i as u128),
+        karma: 0,
+        display_name: format!("Query Counter {}", i),
+        utc_offset_minutes: 0,
+    };
+    let player: Player = diesel::insert_into(players::table)
+        .values(&new_player(0))
+        .get_result(conn)
+        .unwrap();
+    let stream: Stream = diesel::insert_into(streams::table)
+        .values(&NewStream {
+            start_x: x as f32,
+            control_points: vec![],
+        })
+        .get_result(conn)
+        .unwrap();
+    let new_village = |player: &Player, y: usize| NewVillage {
+        x: x as f32,
+        y: y as f32,
+        stream_id: stream.id,
+        player_id: Some(player.id),
+        faith: None,
+    };
+    let village: Village = diesel::insert_into(villages::table)
+        .values(&new_village(&player, 0))
+        .get_result(conn)
+        .unwrap();
+    let mut worker = None;
+
+    for i in 1..=n {
+        // Visitors coming from other players' villages
+        let neighbour: Player = diesel::insert_into(players::table)
+            .values(&new_player(i))
+            .get_result(conn)
+            .unwrap();
+        let origin: Village = diesel::insert_into(villages::table)
+            .values(&new_village(&neighbour, i))
+            .get_result(conn)
+            .unwrap();
+        let visitor: Hobo = diesel::insert_into(hobos::table)
+            .values(&new_hobo(origin.id, None))
+            .get_result(conn)
+            .unwrap();
+        diesel::insert_into(effects::table)
+            .values(&NewEffect {
+                hobo_id: visitor.id,
+                attribute: HoboAttributeType::Health,
+                strength: Some(1),
+                start_time: Some(now),
+            })
+            .execute(conn)
+            .unwrap();
+        let attack: Attack = diesel::insert_into(attacks::table)
+            .values(&NewAttack {
+                departure: now,
+                arrival: now,
+                origin_village_id: Some(origin.id),
+                destination_village_id: village.id,
+            })
+            .get_result(conn)
+            .unwrap();
+        diesel::insert_into(attacks_to_hobos::table)
+            .values(&AttackToHobo {
+                attack_id: attack.id,
+                hobo_id: visitor.id,
+                satisfied: None,
+                released: None,
+                cheers: 0,
+                cheered_until: None,
+            })
+            .execute(conn)
+            .unwrap();
+        let report: VisitReport = diesel::insert_into(visit_reports::table)
+            .values(&NewVisitReport {
+                village_id: village.id,
+                karma: 1,
+                sender: Some(visitor.id),
+            })
+            .get_result(conn)
+            .unwrap();
+        diesel::insert_into(rewards::table)
+            .values(&NewReward {
+                visit_report_id: report.id,
+                resource_type: ResourceType::Feathers,
+                amount: 1,
+            })
+            .execute(conn)
+            .unwrap();
+
+        // Residents of the village
+        let nest: Building = diesel::insert_into(buildings::table)
+            .values(&NewBuilding {
+                x: i as i32,
+                y: 0,
+                building_type: BuildingType::Tree,
+                building_range: None,
+                attack_power: None,
+                attacks_per_cycle: None,
+                creation: now,
+                village_id: village.id,
+                lv: 1,
+            })
+            .get_result(conn)
+            .unwrap();
+        diesel::insert_into(hobos::table)
+            .values(&new_hobo(village.id, Some(nest.id)))
+            .execute(conn)
+            .unwrap();
+        let w: Worker = diesel::insert_into(workers::table)
+            .values(&NewWorker {
+                home: village.id,
+                x: i as i32,
+                y: 1,
+                unit_type: UnitType::Basic,
+                color: Some(UnitColor::Yellow),
+                speed: 0.1,
+                mana: None,
+                level: 1,
+                exp: 0,
+            })
+            .get_result(conn)
+            .unwrap();
+        diesel::insert_into(tasks::table)
+            .values(&NewTask {
+                worker_id: w.id,
+                task_type: TaskType::Idle,
+                x: i as i32,
+                y: 1,
+                start_time: Some(now),
+                target_hobo_id: None,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(abilities::table)
+            .values(&NewAbility {
+                ability_type: AbilityType::Work,
+                worker_id: w.id,
+            })
+            .execute(conn)
+            .unwrap();
+        worker = Some(w.key());
+
+        // Quests of the player
+        let quest: Quest = diesel::insert_into(quests::table)
+            .values(&NewQuest {
+                quest_key: format!("query-count-{}-{}", world, i),
+                karma_condition: None,
+                pop_condition: None,
+                follow_up_quest: None,
+            })
+            .get_result(conn)
+            .unwrap();
+        diesel::insert_into(quest_to_player::table)
+            .values(&QuestToPlayer {
+                quest_id: quest.id,
+                player_id: player.id,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(quest_res_rewards::table)
+            .values(&NewQuestResReward {
+                quest_id: quest.id,
+                resource_type: ResourceType::Sticks,
+                amount: 1,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(quest_res_conditions::table)
+            .values(&NewQuestResCondition {
+                quest_id: quest.id,
+                resource_type: ResourceType::Logs,
+                amount: 1,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(quest_building_conditions::table)
+            .values(&NewQuestBuildingCondition {
+                quest_id: quest.id,
+                building_type: BuildingType::Tree,
+                amount: 1,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(quest_worker_conditions::table)
+            .values(&NewQuestWorkerCondition {
+                quest_id: quest.id,
+                task_type: TaskType::Idle,
+                amount: 1,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(quest_stat_conditions::table)
+            .values(&NewQuestStatCondition {
+                quest_id: quest.id,
+                stat_type: QuestStatType::WelcomedVisitors,
+                amount: 1,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(quest_stat_conditions::table)
+            .values(&NewQuestStatCondition {
+                quest_id: quest.id,
+                stat_type: QuestStatType::WorkerLevel,
+                amount: 1,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(quest_progress::table)
+            .values(&QuestProgress {
+                quest_id: quest.id,
+                player_id: player.id,
+                stat_type: QuestStatType::WelcomedVisitors,
+                amount: 1,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(quest_worker_rewards::table)
+            .values(&QuestWorkerReward {
+                quest_id: quest.id,
+                mana: 1,
+                experience: 1,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(quest_perk_rewards::table)
+            .values(&QuestPerkReward {
+                quest_id: quest.id,
+                perk: CivilizationPerk::Invitation,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(quest_building_rewards::table)
+            .values(&QuestBuildingReward {
+                quest_id: quest.id,
+                building_type: BuildingType::Temple,
+            })
+            .execute(conn)
+            .unwrap();
+    }
+    World {
+        player: player.uuid,
+        village: village.key(),
+        worker: worker.expect("At least one worker"),
+        x,
+    }
+}
+
+fn new_hobo(home: i64, nest: Option<i64>) -> NewHobo {
+    NewHobo {
+        hp: 1,
+        home,
+        color: Some(UnitColor::Yellow),
+        speed: 0.1,
+        hurried: false,
+        nest,
+        level: 0,
+    }
+}
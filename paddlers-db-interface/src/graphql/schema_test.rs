@@ -0,0 +1,104 @@
+//! Checks that the schema used by the frontend matches the schema served by the db-interface.
+//!
+//! The persisted query version is derived from the frontend schema file, a server schema that changed without
+//! regenerating the file with `update_gql_schema.sh` would leave outdated clients undetected.
+//! Requires a database, run with `cargo test -- --ignored`
+
+use super::*;
+use diesel::prelude::*;
+use juniper::IntrospectionFormat;
+use serde_json::Value as Json;
+use std::collections::BTreeSet;
+
+const FRONTEND_SCHEMA: &str = include_str!("../../../paddlers-frontend/api/schema.json");
+
+#[test]
+#[ignore]
+fn frontend_schema_is_up_to_date() {
+    let url = paddlers_shared_lib::get_db_url();
+    let conn = PgConnection::establish(&url).expect("DB connection");
+    let ctx = Context::new(
+        RequestDb::new(Box::new(conn)),
+        None,
+        (&Config::default()).into(),
+    )
+    .unwrap();
+    let (served, errors) =
+        juniper::introspect(&new_schema(), &ctx, IntrospectionFormat::default()).unwrap();
+    assert!(errors.is_empty());
+    let served = serde_json::to_value(&served).unwrap();
+    let frontend: Json = serde_json::from_str(FRONTEND_SCHEMA).unwrap();
+
+    let served = signature(&served["__schema"]);
+    let frontend = signature(&frontend["data"]["__schema"]);
+    let missing: Vec<_> = served.difference(&frontend).collect();
+    let outdated: Vec<_> = frontend.difference(&served).collect();
+    assert!(
+        missing.is_empty() && outdated.is_empty(),
+        "Frontend schema is outdated, run update_gql_schema.sh.\nMissing: {:#?}\nNo longer served: {:#?}",
+        missing,
+        outdated
+    );
+}
+
+/// One line per type, field, argument and enum value, independent of the order in the introspection result
+fn signature(schema: &Json) -> BTreeSet<String> {
+    let mut lines = BTreeSet::new();
+    for t in list(&schema["types"]) {
+        let name = t["name"].as_str().unwrap_or_default();
+        // Built-in introspection types depend on the server library, not on Paddlers
+        if name.starts_with("__") {
+            continue;
+        }
+        lines.insert(format!(
+            "{} {}",
+            t["kind"].as_str().unwrap_or_default(),
+            name
+        ));
+        for field in list(&t["fields"]).chain(list(&t["inputFields"])) {
+            lines.insert(format!(
+                "{}.{}: {}",
+                name,
+                field["name"].as_str().unwrap_or_default(),
+                type_ref(&field["type"])
+            ));
+            for arg in list(&field["args"]) {
+                lines.insert(format!(
+                    "{}.{}({}: {})",
+                    name,
+                    field["name"].as_str().unwrap_or_default(),
+                    arg["name"].as_str().unwrap_or_default(),
+                    type_ref(&arg["type"])
+                ));
+            }
+        }
+        for key in &["enumValues", "possibleTypes", "interfaces"] {
+            for value in list(&t[*key]) {
+                lines.insert(format!(
+                    "{} {} {}",
+                    name,
+                    key,
+                    value["name"].as_str().unwrap_or_default()
+                ));
+            }
+        }
+    }
+    for root in &["queryType", "mutationType", "subscriptionType"] {
+        if let Some(name) = schema[*root]["name"].as_str() {
+            lines.insert(format!("{} {}", root, name));
+        }
+    }
+    lines
+}
+
+fn list(value: &Json) -> impl Iterator<Item = &Json> {
+    value.as_array().into_iter().flatten()
+}
+
+fn type_ref(t: &Json) -> String {
+    match t["kind"].as_str() {
+        Some("NON_NULL") => format!("{}!", type_ref(&t["ofType"])),
+        Some("LIST") => format!("[{}]", type_ref(&t["ofType"])),
+        _ => t["name"].as_str().unwrap_or_default().to_owned(),
+    }
+}
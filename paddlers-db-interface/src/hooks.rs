@@ -1,20 +1,25 @@
 use super::DbConn;
 
-use juniper::FieldError;
+use juniper::{FieldError, InputValue};
 use juniper_rocket::{self, GraphQLRequest, GraphQLResponse};
 use rocket::http::Status;
 use rocket::request::{self, FromRequest, Request};
 use rocket::response::content;
 use rocket::Outcome;
 use rocket::State;
+use rocket_contrib::json::Json;
 
-use crate::graphql::Schema;
+use crate::game_master::GameMaster;
+use crate::graphql::{Context, QueryLimits, Schema};
+use paddlers_shared_lib::api::persisted_queries::*;
 use paddlers_shared_lib::prelude::{Config, PadlApiError};
 use paddlers_shared_lib::user_authentication::*;
 
 #[derive(Debug)]
 pub struct UserInfo {
     user: Option<PadlUser>,
+    /// Forwarded to the game-master for mutations
+    token: Option<String>,
 }
 
 #[get("/", rank = 6)]
@@ -32,9 +37,18 @@ pub fn get_graphql_handler(
     connection: DbConn,
     request: GraphQLRequest,
     schema: State<Schema>,
+    config: State<Config>,
+    game_master: State<GameMaster>,
     user_info: UserInfo,
 ) -> GraphQLResponse {
-    generic_graphql_handler(connection, request, schema, user_info)
+    generic_graphql_handler(
+        connection,
+        request,
+        schema,
+        &config,
+        &game_master,
+        user_info,
+    )
 }
 
 #[post("/", data = "<request>")]
@@ -42,19 +56,86 @@ pub fn post_graphql_handler(
     connection: DbConn,
     request: GraphQLRequest,
     schema: State<Schema>,
+    config: State<Config>,
+    game_master: State<GameMaster>,
     user_info: UserInfo,
 ) -> GraphQLResponse {
-    generic_graphql_handler(connection, request, schema, user_info)
+    generic_graphql_handler(
+        connection,
+        request,
+        schema,
+        &config,
+        &game_master,
+        user_info,
+    )
+}
+
+/// Executes a query of the frontend, identified by its ID
+#[post("/persisted", data = "<request>")]
+pub fn persisted_graphql_handler(
+    connection: DbConn,
+    request: Json<PersistedQueryRequest<Option<InputValue>>>,
+    schema: State<Schema>,
+    config: State<Config>,
+    game_master: State<GameMaster>,
+    user_info: UserInfo,
+) -> GraphQLResponse {
+    let request = request.into_inner();
+    if request.schema_version != SCHEMA_VERSION {
+        return client_outdated("The client was built for another schema version.");
+    }
+    let query = match persisted_query(&request.id) {
+        Some(query) => query,
+        None => return client_outdated("Unknown persisted query."),
+    };
+    let ctx = match graphql_context(connection, &config, &game_master, user_info) {
+        Ok(ctx) => ctx,
+        Err(response) => return response,
+    };
+    let request = juniper::http::GraphQLRequest::new(
+        query.query.to_owned(),
+        request.operation_name,
+        request.variables,
+    );
+    let response = request.execute(&schema, &ctx);
+    let status = if response.is_ok() {
+        Status::Ok
+    } else {
+        Status::BadRequest
+    };
+    GraphQLResponse::custom(status, serde_json::to_value(&response).unwrap())
 }
 
 fn generic_graphql_handler(
     connection: DbConn,
     request: GraphQLRequest,
     schema: State<Schema>,
+    config: &Config,
+    game_master: &GameMaster,
     user_info: UserInfo,
 ) -> GraphQLResponse {
-    if let Some(player_ctx) = crate::graphql::Context::new(connection, user_info.user) {
-        request.execute(&schema, &player_ctx)
+    if config.graphql_persisted_queries_only {
+        return client_outdated("Only persisted queries are accepted.");
+    }
+    match graphql_context(connection, config, game_master, user_info) {
+        Ok(ctx) => request.execute(&schema, &ctx),
+        Err(response) => response,
+    }
+}
+
+fn graphql_context(
+    connection: DbConn,
+    config: &Config,
+    game_master: &GameMaster,
+    user_info: UserInfo,
+) -> Result<Context, GraphQLResponse> {
+    let db = crate::sql::RequestDb::new(connection);
+    let limits = QueryLimits::from(config);
+    if let Some(mut player_ctx) = Context::new(db, user_info.user, limits) {
+        if let (true, Some(token)) = (config.graphql_mutations, &user_info.token) {
+            player_ctx = player_ctx.with_game_master(game_master.with_token(token));
+        }
+        Ok(player_ctx)
     } else {
         // Lookup error code from shared lib that frontend understands
         let n = PadlApiError::PlayerNotCreated as i32;
@@ -66,10 +147,17 @@ fn generic_graphql_handler(
         //       Either way, the HTTP code should not be considered by
         //       the frontend too much, the errors in the response are
         //       what really counts.
-        juniper_rocket::GraphQLResponse::error(err)
+        Err(juniper_rocket::GraphQLResponse::error(err))
     }
 }
 
+/// Tells the client to reload, which fetches the frontend that matches the current schema
+fn client_outdated(reason: &str) -> GraphQLResponse {
+    let n = PadlApiError::ClientOutdated as i32;
+    let err = FieldError::new(reason, graphql_value!({ "padlcode": n }));
+    juniper_rocket::GraphQLResponse::error(err)
+}
+
 impl<'a, 'r> FromRequest<'a, 'r> for UserInfo {
     type Error = AuthenticationError;
 
@@ -78,14 +166,20 @@ impl<'a, 'r> FromRequest<'a, 'r> for UserInfo {
             Some(s) => {
                 let config = request.guard::<State<Config>>().expect("Config broken");
                 match PadlUser::from_token(s, &config) {
-                    Ok(user) => Outcome::Success(UserInfo { user: Some(user) }),
+                    Ok(user) => Outcome::Success(UserInfo {
+                        user: Some(user),
+                        token: Some(s.to_owned()),
+                    }),
                     Err(e) => {
                         println!("{}", e);
                         Outcome::Failure((Status::Unauthorized, e))
                     }
                 }
             }
-            None => Outcome::Success(UserInfo { user: None }),
+            None => Outcome::Success(UserInfo {
+                user: None,
+                token: None,
+            }),
         }
     }
 }
@@ -7,9 +7,12 @@ extern crate rocket_contrib;
 #[macro_use]
 extern crate juniper;
 
+mod game_master;
 mod graphql;
 mod hooks;
+mod notifications;
 mod sql;
+mod subscriptions;
 
 use paddlers_shared_lib::config::Config;
 use rocket::http::Method;
@@ -63,14 +66,30 @@ fn main() {
     .to_cors()
     .expect("CORS creation failed");
 
+    if config.graphql_mutations && config.graphql_persisted_queries_only {
+        eprintln!("GraphQL mutations are enabled but only persisted queries are accepted, mutations will be rejected.");
+    }
+
+    subscriptions::start(&config);
+
     rocket::custom(rocket_config)
         .manage(graphql::new_schema())
+        .manage(game_master::GameMaster::new(
+            &config,
+            rocket_config.workers as usize / 2,
+        ))
         .manage(config)
         .attach(DbConn::fairing())
         .attach(cors)
         .mount(
             "/graphql",
-            routes![index, graphiql, get_graphql_handler, post_graphql_handler],
+            routes![
+                index,
+                graphiql,
+                get_graphql_handler,
+                post_graphql_handler,
+                persisted_graphql_handler
+            ],
         )
         .launch();
 }
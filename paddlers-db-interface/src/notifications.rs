@@ -0,0 +1,40 @@
+//! Receives the change events published by the game-master, see `paddlers_shared_lib::notifications`.
+
+use paddlers_shared_lib::notifications::{ChangeEvent, CHANGE_CHANNEL};
+use postgres::fallible_iterator::FallibleIterator;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::Duration;
+
+/// Time to wait before reconnecting after the connection has been lost
+pub const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Calls the handler for each change event on a background thread.
+/// Each listener uses its own DB connection, which is reestablished when it fails.
+/// A panicking handler only loses the event it panicked on, the listener continues with the next one.
+pub fn listen(db_url: &str, mut handler: impl FnMut(ChangeEvent) + Send + 'static) {
+    let db_url = db_url.to_owned();
+    std::thread::spawn(move || loop {
+        if let Err(e) = receive(&db_url, &mut handler) {
+            eprintln!("Listening for change events failed: {}", e);
+        }
+        std::thread::sleep(RECONNECT_DELAY);
+    });
+}
+
+fn receive(db_url: &str, handler: &mut impl FnMut(ChangeEvent)) -> Result<(), postgres::Error> {
+    let mut client = postgres::Client::connect(db_url, postgres::NoTls)?;
+    client.batch_execute(&format!("LISTEN {}", CHANGE_CHANNEL))?;
+    let mut notifications = client.notifications();
+    let mut iter = notifications.blocking_iter();
+    while let Some(notification) = iter.next()? {
+        match ChangeEvent::from_payload(notification.payload()) {
+            Some(event) => {
+                if catch_unwind(AssertUnwindSafe(|| handler(event))).is_err() {
+                    eprintln!("Handling {:?} panicked", event);
+                }
+            }
+            None => eprintln!("Unknown change event: {}", notification.payload()),
+        }
+    }
+    Ok(())
+}
@@ -1,8 +1,55 @@
-use crate::DbConn;
 use diesel::prelude::*;
+use paddlers_shared_lib::prelude::{PlayerKey, VillageKey};
+use paddlers_shared_lib::schema::{players, villages};
+use std::ops::Deref;
 
-impl paddlers_shared_lib::sql::GameDB for DbConn {
+/// DB connection used while resolving a single GraphQL request.
+/// Pooled connections from Rocket and plain connections in tests are both accepted.
+pub struct RequestDb {
+    conn: Box<dyn Deref<Target = PgConnection>>,
+}
+
+impl RequestDb {
+    pub fn new(conn: impl Deref<Target = PgConnection> + 'static) -> Self {
+        RequestDb {
+            conn: Box::new(conn),
+        }
+    }
+}
+
+impl paddlers_shared_lib::sql::GameDB for RequestDb {
+    fn dbconn(&self) -> &PgConnection {
+        &self.conn
+    }
+}
+
+/// DB connection for work outside of Rocket's request handling
+pub struct BackgroundDb(PgConnection);
+
+impl BackgroundDb {
+    pub fn connect(db_url: &str) -> ConnectionResult<Self> {
+        PgConnection::establish(db_url).map(BackgroundDb)
+    }
+    /// The player owning the village, None for villages without a player
+    pub fn village_owner(&self, village: VillageKey) -> QueryResult<Option<uuid::Uuid>> {
+        villages::table
+            .filter(villages::id.eq(village.num()))
+            .inner_join(players::table)
+            .select(players::uuid)
+            .first(&self.0)
+            .optional()
+    }
+    pub fn player_uuid(&self, player: PlayerKey) -> QueryResult<Option<uuid::Uuid>> {
+        players::table
+            .find(player.num())
+            .select(players::uuid)
+            .first(&self.0)
+            .optional()
+    }
+}
+
+impl paddlers_shared_lib::sql::GameDB for BackgroundDb {
     fn dbconn(&self) -> &PgConnection {
-        &**self
+        &self.0
     }
 }
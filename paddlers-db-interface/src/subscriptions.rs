@@ -0,0 +1,266 @@
+//! WebSocket pushing live updates about a player's villages.
+//!
+//! The socket only notifies clients which data changed, the data itself is still read through the regular GraphQL queries.
+//! This way, clients do not have to poll all queries periodically.
+//! Changes are forwarded from the change events published by the game-master.
+//!
+//! Why not GraphQL subscriptions:
+//!     Juniper supports subscriptions only from 0.15 on, served by an async web server through `juniper_graphql_ws`.
+//!     This interface runs on Juniper 0.14 and Rocket 0.4, which is synchronous and has no WebSocket support.
+//!     Moving to subscriptions would mean migrating the whole GraphQL interface to another server and Juniper version.
+//!     Since the updates carry no data, a plain socket next to the GraphQL interface, on its own port, is sufficient.
+//!
+//! Protocol:
+//!     1) Client opens the socket and sends a `VillageSubscription` (JSON)
+//!     2) Server replies with `VillageUpdate::Subscribed`, or closes the socket if authentication failed
+//!     3) Server sends a `VillageUpdate` (JSON) whenever something changed
+//!     4) Server sends `VillageUpdate::KeepAlive` every `KEEP_ALIVE_MS`, clients treat a quiet socket as lost
+
+use crate::notifications::{self, RECONNECT_DELAY};
+use crate::sql::BackgroundDb;
+use diesel::QueryResult;
+use paddlers_shared_lib::api::subscriptions::{VillageSubscription, VillageUpdate};
+use paddlers_shared_lib::notifications::ChangeEvent;
+use paddlers_shared_lib::prelude::*;
+use paddlers_shared_lib::user_authentication::PadlUser;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+type Subscribers = Arc<Mutex<HashMap<u32, Subscriber>>>;
+
+/// Interval of `VillageUpdate::KeepAlive` messages
+const KEEP_ALIVE_MS: u64 = 15_000;
+const KEEP_ALIVE: ws::util::Token = ws::util::Token(1);
+
+struct Subscriber {
+    out: ws::Sender,
+    uuid: uuid::Uuid,
+}
+
+/// Handles a single socket
+struct Connection {
+    out: ws::Sender,
+    config: Config,
+    subscribers: Subscribers,
+}
+
+/// Starts the WebSocket server and forwards change events to it in the background
+pub fn start(config: &Config) {
+    let subscribers: Subscribers = Default::default();
+
+    let mut forwarder = Forwarder {
+        db_url: config.db_url.clone(),
+        db: None,
+    };
+    let notified = subscribers.clone();
+    notifications::listen(&config.db_url, move |event| {
+        if let Some((player, update)) = forwarder.route(event) {
+            dispatch(&notified, player, update);
+        }
+    });
+
+    let address = (config.graphql_service_name.clone(), config.graphql_ws_port);
+    let config = config.clone();
+    std::thread::spawn(move || {
+        let result = ws::listen(address, |out| Connection {
+            out,
+            config: config.clone(),
+            subscribers: subscribers.clone(),
+        });
+        if let Err(e) = result {
+            eprintln!("Subscription socket failed: {}", e);
+        }
+    });
+}
+
+impl ws::Handler for Connection {
+    fn on_open(&mut self, _shake: ws::Handshake) -> ws::Result<()> {
+        self.out.timeout(KEEP_ALIVE_MS, KEEP_ALIVE)
+    }
+    fn on_timeout(&mut self, event: ws::util::Token) -> ws::Result<()> {
+        if event == KEEP_ALIVE {
+            send(&self.out, VillageUpdate::KeepAlive)?;
+            self.out.timeout(KEEP_ALIVE_MS, KEEP_ALIVE)?;
+        }
+        Ok(())
+    }
+    fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
+        let request: VillageSubscription = match serde_json::from_slice(&msg.into_data()) {
+            Ok(request) => request,
+            Err(_) => return self.out.close(ws::CloseCode::Unsupported),
+        };
+        match PadlUser::from_token(&request.authorization, &self.config) {
+            Ok(user) => {
+                lock(&self.subscribers).insert(
+                    self.out.connection_id(),
+                    Subscriber {
+                        out: self.out.clone(),
+                        uuid: user.uuid,
+                    },
+                );
+                send(&self.out, VillageUpdate::Subscribed)
+            }
+            Err(e) => {
+                println!("{}", e);
+                self.out.close(ws::CloseCode::Policy)
+            }
+        }
+    }
+    fn on_close(&mut self, _code: ws::CloseCode, _reason: &str) {
+        lock(&self.subscribers).remove(&self.out.connection_id());
+    }
+}
+
+/// Looks up which player a change event concerns, on its own DB connection
+struct Forwarder {
+    db_url: String,
+    /// Dropped after a failed query, reconnected on the next event
+    db: Option<BackgroundDb>,
+}
+
+impl Forwarder {
+    /// The player to notify and the update to send, None if nobody has to be notified.
+    /// Reconnects until the DB is reachable. An event is dropped if it fails again on a fresh connection.
+    fn route(&mut self, event: ChangeEvent) -> Option<(uuid::Uuid, VillageUpdate)> {
+        for _attempt in 0..2 {
+            match lookup(self.connection(), event) {
+                Ok(routed) => return routed,
+                Err(e) => {
+                    eprintln!("Forwarding {:?} failed: {}", event, e);
+                    self.db = None;
+                }
+            }
+        }
+        None
+    }
+    fn connection(&mut self) -> &BackgroundDb {
+        while self.db.is_none() {
+            match BackgroundDb::connect(&self.db_url) {
+                Ok(db) => self.db = Some(db),
+                Err(e) => {
+                    eprintln!("DB connection for subscriptions failed: {}", e);
+                    std::thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        }
+        self.db.as_ref().unwrap()
+    }
+}
+
+fn lookup(
+    db: &BackgroundDb,
+    event: ChangeEvent,
+) -> QueryResult<Option<(uuid::Uuid, VillageUpdate)>> {
+    let (player, update) = match event {
+        ChangeEvent::Attacks(v) => (db.village_owner(v)?, VillageUpdate::Attacks(v)),
+        ChangeEvent::VisitReports(v) => (db.village_owner(v)?, VillageUpdate::Reports(v)),
+        ChangeEvent::Resources(v) => (db.village_owner(v)?, VillageUpdate::Resources(v)),
+        ChangeEvent::Quests(p) => (db.player_uuid(p)?, VillageUpdate::Quests),
+        ChangeEvent::Player(p) => (db.player_uuid(p)?, VillageUpdate::PlayerInfo),
+    };
+    // Villages without a player, e.g. anarchists, have no subscribers
+    Ok(player.map(|uuid| (uuid, update)))
+}
+
+/// Sends the update to all sockets of the player
+fn dispatch(subscribers: &Subscribers, player: uuid::Uuid, update: VillageUpdate) {
+    for sub in lock(subscribers).values() {
+        if sub.uuid == player {
+            // Errors are ignored, closed sockets are removed by the handler
+            send(&sub.out, update).ok();
+        }
+    }
+}
+
+/// The map of subscribers stays usable even if a thread panicked while holding the lock
+fn lock(subscribers: &Subscribers) -> MutexGuard<HashMap<u32, Subscriber>> {
+    subscribers.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn send(out: &ws::Sender, update: VillageUpdate) -> ws::Result<()> {
+    out.send(serde_json::to_string(&update).expect("Serializing update"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::prelude::*;
+    use paddlers_shared_lib::models::*;
+    use paddlers_shared_lib::schema::{players, streams, villages};
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn change_events_are_routed_to_the_village_owner() {
+        let url = paddlers_shared_lib::get_db_url();
+        let conn = PgConnection::establish(&url).expect("DB connection");
+        // Committed, the listener reads it on its own connection
+        let uuid = uuid::Uuid::from_u128(0x9add_1e25_0001_0000);
+        let player: Player = diesel::insert_into(players::table)
+            .values(&NewPlayer {
+                uuid,
+                karma: 0,
+                display_name: "Subscriber".to_owned(),
+                utc_offset_minutes: 0,
+            })
+            .get_result(&conn)
+            .unwrap();
+        let stream: Stream = diesel::insert_into(streams::table)
+            .values(&NewStream {
+                start_x: -20_000.0,
+                control_points: vec![],
+            })
+            .get_result(&conn)
+            .unwrap();
+        let village: Village = diesel::insert_into(villages::table)
+            .values(&NewVillage {
+                x: -20_000.0,
+                y: 0.0,
+                stream_id: stream.id,
+                player_id: Some(player.id),
+                faith: None,
+            })
+            .get_result(&conn)
+            .unwrap();
+        let village_key = VillageKey(village.id);
+
+        let (sender, receiver) = mpsc::channel();
+        let mut forwarder = Forwarder {
+            db_url: url.clone(),
+            db: None,
+        };
+        let mut first = true;
+        notifications::listen(&url, move |event| {
+            if first {
+                first = false;
+                panic!("The listener must survive a panicking handler");
+            }
+            sender.send(forwarder.route(event)).ok();
+        });
+
+        // Events published before the listener is ready are lost, thus they are repeated
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut routed = None;
+        while routed.is_none() && Instant::now() < deadline {
+            ChangeEvent::Resources(village_key).notify(&conn);
+            routed = receiver
+                .recv_timeout(Duration::from_millis(100))
+                .ok()
+                .flatten()
+                .filter(|(player, _)| *player == uuid);
+        }
+
+        diesel::delete(villages::table.find(village.id))
+            .execute(&conn)
+            .unwrap();
+        diesel::delete(streams::table.find(stream.id))
+            .execute(&conn)
+            .unwrap();
+        diesel::delete(players::table.find(player.id))
+            .execute(&conn)
+            .unwrap();
+        assert_eq!(routed, Some((uuid, VillageUpdate::Resources(village_key))));
+    }
+}
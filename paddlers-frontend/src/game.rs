@@ -76,12 +76,13 @@ impl Game {
         net_chan: Receiver<NetMsg>,
         loaded_data: LoadedData,
     ) -> PadlResult<Self> {
-        let player_info = game_data.player_info;
+        let player_info = game_data.player_info.clone();
         let town_context = TownContextManager::new(player_info.clone());
-        let mut world = crate::init::init_world(player_info);
+        let mut world = crate::init::init_world(player_info, game_data.story_graph);
         let now = utc_now();
         let shaders = Shaders::load(display, &sprites);
         world.insert::<Now>(Now(now));
+        world.insert(game_data.visit_rewards);
 
         world.maintain();
         let mut game = Game {
@@ -102,6 +103,7 @@ impl Game {
             active_test: None,
         };
         game.prepare_town_resources();
+        game.init_map();
         game.load_village_info(game_data.village_info)?;
         game.load_buildings_from_net_response(game_data.buildings_response)?;
         game.town_mut().refresh_attacker_direction();
@@ -121,12 +123,11 @@ impl Game {
         )?;
         game.world.write_resource::<PlayerState>().hobo_population = Some(n);
         game.refresh_visitor_gate();
-        game.load_attacking_hobos(game_data.attacking_hobos)?;
+        game.load_attacking_hobos(game_data.attacking_hobos, 0)?;
         game.load_player_info(game_data.player_info)?;
 
         game.world.maintain();
         game.town_world_mut().maintain();
-        game.init_map();
 
         Ok(game)
     }
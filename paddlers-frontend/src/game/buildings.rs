@@ -1,7 +1,7 @@
 use crate::game::town::TownContext;
 use crate::game::{
     components::*,
-    fight::{Aura, Range},
+    fight::{Aura, Cheering, Range},
     forestry::ForestComponent,
     input::Clickable,
     movement::Position,
@@ -17,7 +17,10 @@ use crate::gui::{
 use chrono::NaiveDateTime;
 use paddle::utc_now;
 use paddlers_shared_lib::{civilization::CivilizationPerk, prelude::*};
-use paddlers_shared_lib::{game_mechanics::attributes::Attributes, graphql_types::*};
+use paddlers_shared_lib::{
+    game_mechanics::{attributes::Attributes, town::CheeringBuilding},
+    graphql_types::*,
+};
 use specs::prelude::*;
 use specs::world::EntitiesRes;
 
@@ -91,6 +94,18 @@ impl Town {
             }
         }
 
+        // Some attacks per cycle && Some ap => Cheers visitors repeatedly
+        if let (Some(attacks_per_cycle), Some(ap), Some(r)) = (attacks_per_cycle, ap, range) {
+            let building = CheeringBuilding {
+                tile: tile_index,
+                range: r,
+                strength: ap as i32,
+                attacks_per_cycle: attacks_per_cycle as u32,
+                built: created.into(),
+            };
+            builder = builder.with(Cheering::new(building, utc_now().into()));
+        }
+
         match bt {
             BuildingType::BundlingStation => {
                 builder = builder
@@ -264,7 +264,7 @@ impl DialogueFrame {
         self.load(msg.scene, msg.slide, &state.locale);
     }
     fn receive_new_story_state(&mut self, state: &mut Game, msg: &NewStoryState) {
-        state.set_story_state(msg.new_story_state);
+        state.set_story_state(msg.new_story_state.clone());
         state.load_story_state().nuts_check();
     }
     fn left_click(&mut self, state: &mut Game, pos: Vector) {
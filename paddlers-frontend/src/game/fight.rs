@@ -1,5 +1,10 @@
 // use paddle::quicksilver_compat::*;
-use crate::game::{game_event_manager::GameEvent, movement::Position, town::Town};
+use crate::game::{
+    components::NetObj, game_event_manager::GameEvent, movement::Position, town::Town,
+};
+use crate::gui::ui_state::Now;
+use paddlers_shared_lib::game_mechanics::town::{CheeringBuilding, DefendingWorker};
+use paddlers_shared_lib::shared_types::Timestamp;
 use specs::prelude::*;
 use specs::storage::BTreeStorage;
 use specs::world::Index;
@@ -35,6 +40,30 @@ impl Aura {
     }
 }
 
+/// Building that cheers the visitors in range closest to satisfaction, once per cycle
+#[derive(Component, Debug)]
+#[storage(HashMapStorage)]
+pub struct Cheering {
+    pub building: CheeringBuilding,
+    /// Cheers up to this point in time have been applied to visitors already
+    pub applied_until: Timestamp,
+}
+impl Cheering {
+    pub fn new(building: CheeringBuilding, now: Timestamp) -> Self {
+        Cheering {
+            building,
+            applied_until: now,
+        }
+    }
+}
+
+/// Worker doing the defend task, acts like an aura while it stays
+#[derive(Component, Debug)]
+#[storage(HashMapStorage)]
+pub struct Defending {
+    pub defender: DefendingWorker,
+}
+
 #[derive(Component, Debug)]
 #[storage(BTreeStorage)]
 pub struct Health {
@@ -82,11 +111,27 @@ impl<'a> System<'a> for FightSystem {
     type SystemData = (
         Entities<'a>,
         ReadStorage<'a, Aura>,
+        WriteStorage<'a, Cheering>,
+        ReadStorage<'a, Defending>,
         ReadStorage<'a, Position>,
+        ReadStorage<'a, NetObj>,
         WriteStorage<'a, Health>,
+        Read<'a, Now>,
     );
 
-    fn run(&mut self, (entities, aura, position, mut health): Self::SystemData) {
+    fn run(
+        &mut self,
+        (
+            entities,
+            aura,
+            mut cheering,
+            defending,
+            position,
+            netobj,
+            mut health,
+            now,
+        ): Self::SystemData,
+    ) {
         // It's not necessary to recalculate every frame
         self.counter = (self.counter + 1) % 30;
         if self.counter != 1 {
@@ -116,5 +161,44 @@ impl<'a> System<'a> for FightSystem {
                 }
             }
         }
+
+        // Defenders use the same id space as auras, each visitor is affected once per defender
+        for (did, d) in (&entities, &defending).join() {
+            for (hid, p, h) in (&entities, &position, &mut health).join() {
+                if !d.defender.in_range(Town::find_tile(p.area.pos)) {
+                    continue;
+                }
+                if let Err(i) = h.aura_effects.binary_search(&did.id()) {
+                    (*h).make_happy(d.defender.strength as i64, hid);
+                    (*h).aura_effects.insert(i, did.id());
+                }
+            }
+        }
+
+        // Same rule as in the shared `IDefendingTown::cheer_damage`: Visitors closest to satisfaction first, then by id
+        let now: Timestamp = now.0.into();
+        for c in (&mut cheering).join() {
+            let from = Timestamp::from_us(c.applied_until.micros() + 1);
+            for _ in c.building.cheer_times(from, now) {
+                let mut candidates: Vec<(i64, i64, Entity)> =
+                    (&entities, &position, &health, &netobj)
+                        .join()
+                        .filter(|(_, p, h, _)| {
+                            h.hp > 0 && c.building.in_range(Town::find_tile(p.area.pos))
+                        })
+                        .map(|(e, _, h, n)| (h.hp, n.id, e))
+                        .collect();
+                candidates.sort();
+                for (_, _, e) in candidates
+                    .into_iter()
+                    .take(c.building.attacks_per_cycle as usize)
+                {
+                    if let Some(h) = health.get_mut(e) {
+                        h.make_happy(c.building.strength as i64, e);
+                    }
+                }
+            }
+            c.applied_until = now;
+        }
     }
 }
@@ -105,7 +105,7 @@ impl Game {
                 }
             }
             GameEvent::HttpBuyProphet => {
-                if let Some(info) = self.player().info {
+                if let Some(info) = &self.player().info {
                     let player: PlayerInfo = info.clone();
                     crate::game::town::purchase_prophet(&player)?;
                 }
@@ -221,7 +221,9 @@ impl Game {
                     choice,
                 };
                 nuts::send_to::<RestApiState, _>(t);
-                paddle::share(crate::game::dialogue::NewStoryState { new_story_state });
+                paddle::share(crate::game::dialogue::NewStoryState {
+                    new_story_state: new_story_state.clone(),
+                });
                 if let Some(choice) = choice {
                     self.handle_story_trigger(StoryTrigger::DialogueChoice(choice));
                 } else {
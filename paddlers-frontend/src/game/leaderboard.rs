@@ -2,7 +2,7 @@ use crate::game::Game;
 use crate::gui::utils::colors::DARK_BLUE;
 use crate::gui::utils::draw_image;
 use crate::gui::z::Z_UI_MENU;
-use crate::net::NetMsg;
+use crate::net::{NetMsg, PageRequest};
 use crate::prelude::*;
 use div::doc;
 use paddle::FrameHandle;
@@ -14,10 +14,18 @@ use web_sys::{Element, Node};
 pub(crate) struct LeaderboardFrame {
     pane: div::DivHandle,
     table: Node,
+    /// Players on the displayed page
     players_by_karma: Vec<(String, i64)>,
     page_size: usize,
     current_page: usize,
     total_pages: usize,
+    /// Rank of the first player on the displayed page
+    first_rank: usize,
+    start_cursor: Option<String>,
+    end_cursor: Option<String>,
+    has_next_page: bool,
+    has_previous_page: bool,
+    loading: bool,
     header: [Element; 3],
     footer: [Element; 3],
 }
@@ -80,6 +88,12 @@ impl LeaderboardFrame {
             page_size: 15, // TODO: find a fitting size dynamically
             current_page: 0,
             total_pages: 1,
+            first_rank: 1,
+            start_cursor: None,
+            end_cursor: None,
+            has_next_page: false,
+            has_previous_page: false,
+            loading: false,
             header: [header_left, header_middle, header_right],
             footer: [prev_button, page_node, next_button],
         })
@@ -111,22 +125,48 @@ impl LeaderboardFrame {
 
     pub fn network_message(&mut self, _state: &mut Game, msg: &NetMsg) {
         match msg {
-            NetMsg::Leaderboard(offset, list, total_players) => {
-                self.total_pages = (total_players + self.page_size - 1) / self.page_size;
-
-                let required_len = offset + list.len();
-                if self.players_by_karma.len() < required_len {
-                    self.players_by_karma
-                        .resize(required_len, Default::default())
-                }
-                for (i, (name, karma)) in list.into_iter().enumerate() {
-                    self.players_by_karma[offset + i] = (name.clone(), *karma);
+            NetMsg::Leaderboard(page, data) => {
+                self.loading = false;
+                let page_info = &data.page_info;
+                match page {
+                    PageRequest::First => {
+                        self.current_page = 0;
+                        self.first_rank = 1;
+                    }
+                    // Players may have left the leaderboard in the meantime, stay on the current page then
+                    PageRequest::After(_) if data.nodes.is_empty() => {
+                        self.has_next_page = false;
+                        return;
+                    }
+                    PageRequest::Before(_) if data.nodes.is_empty() => {
+                        self.has_previous_page = false;
+                        return;
+                    }
+                    PageRequest::After(_) => {
+                        self.current_page += 1;
+                        self.first_rank += self.players_by_karma.len();
+                    }
+                    PageRequest::Before(_) => {
+                        self.current_page = self.current_page.saturating_sub(1);
+                        self.first_rank = self.first_rank.saturating_sub(data.nodes.len()).max(1);
+                    }
                 }
-                if self.current_page * self.page_size < required_len
-                    && (self.current_page + 1) * self.page_size > *offset
-                {
-                    self.reload().nuts_check();
+                if !page_info.has_previous_page {
+                    self.current_page = 0;
+                    self.first_rank = 1;
                 }
+                let total_players = data.total_count as usize;
+                self.total_pages = ((total_players + self.page_size - 1) / self.page_size).max(1);
+                self.players_by_karma = data
+                    .nodes
+                    .iter()
+                    .map(|player| (player.display_name.clone(), player.karma))
+                    .collect();
+                self.start_cursor = page_info.start_cursor.clone();
+                self.end_cursor = page_info.end_cursor.clone();
+                self.has_next_page = page_info.has_next_page;
+                self.has_previous_page = page_info.has_previous_page;
+                self.reload().nuts_check();
             }
             _ => {}
         }
@@ -138,10 +178,8 @@ impl LeaderboardFrame {
             self.table.append_child(element)?;
         }
 
-        let start = self.current_page * self.page_size;
-        let end = (start + self.page_size).min(self.players_by_karma.len());
-        for (i, (name, karma)) in self.players_by_karma[start..end].iter().enumerate() {
-            self.insert_row(start + i + 1, &name, *karma)?;
+        for (i, (name, karma)) in self.players_by_karma.iter().enumerate() {
+            self.insert_row(self.first_rank + i, &name, *karma)?;
         }
         self.footer[1].set_text_content(Some(&format!(
             "Page {} / {}",
@@ -156,22 +194,24 @@ impl LeaderboardFrame {
         Ok(())
     }
     fn is_loading(&self) -> bool {
-        let start = self.current_page * self.page_size;
-        self.players_by_karma
-            .get(start)
-            .map(|(name, _karma)| name.len() == 0)
-            .unwrap_or(true)
+        self.loading
     }
     fn next_page(&mut self, _state: &mut Game, _msg: &EvNextPage) {
-        self.current_page = (self.current_page + 1).min(self.total_pages - 1);
-        self.request_current_page();
+        if let (true, Some(cursor)) = (self.has_next_page, &self.end_cursor) {
+            self.request_page(PageRequest::After(cursor.clone()));
+        }
     }
     fn prev_page(&mut self, _state: &mut Game, _msg: &EvPrevPage) {
-        self.current_page = self.current_page.saturating_sub(1);
-        self.request_current_page();
+        if let (true, Some(cursor)) = (self.has_previous_page, &self.start_cursor) {
+            self.request_page(PageRequest::Before(cursor.clone()));
+        }
     }
-    fn request_current_page(&mut self) {
-        crate::net::request_leaderboard(self.current_page as i64, self.page_size as i64);
+    fn request_page(&mut self, page: PageRequest) {
+        // Pages are relative to the displayed page, thus only one request at the time
+        if !self.loading {
+            self.loading = true;
+            crate::net::request_leaderboard(page, self.page_size as i64);
+        }
     }
 }
 
@@ -196,7 +236,9 @@ impl Frame for LeaderboardFrame {
         }
     }
     fn enter(&mut self, _state: &mut Self::State) {
-        self.request_current_page();
+        // Always start from the top, also when an earlier response got lost
+        self.loading = false;
+        self.request_page(PageRequest::First);
         self.pane.show().nuts_check();
     }
     fn leave(&mut self, _state: &mut Self::State) {
@@ -2,6 +2,7 @@ mod map_frame;
 mod map_position;
 mod map_segment;
 mod map_tesselation;
+mod travelling_visitors;
 mod village_meta;
 
 use crate::net::authentication::keycloak_preferred_name;
@@ -18,6 +19,7 @@ use specs::prelude::*;
 
 pub(crate) use map_frame::MapFrame;
 pub use map_position::MapPosition;
+pub use travelling_visitors::TravellingVisitors;
 pub use village_meta::VillageMetaInfo;
 
 /// Helper struct to combine private and shared map state
@@ -34,6 +36,7 @@ pub struct GlobalMapPrivateState {
     grid_mesh: AbstractMesh,
     segments: Vec<MapSegment>,
     villages: Vec<VillageMetaInfo>,
+    travelling_visitors: Vec<TravellingVisitors>,
     view_width: i32,
     loaded: (i32, i32),
 }
@@ -59,6 +62,7 @@ impl<'a> GlobalMap<'a> {
             grid_mesh,
             segments: vec![],
             villages: vec![],
+            travelling_visitors: vec![],
             view_width: w,
             loaded: (0, -1),
         };
@@ -218,6 +222,9 @@ impl GlobalMapPrivateState {
 
         self.villages.extend(villages.into_iter());
     }
+    pub fn add_travelling_visitors(&mut self, visitors: TravellingVisitors) {
+        self.travelling_visitors.push(visitors);
+    }
 }
 
 impl GlobalMapSharedState {
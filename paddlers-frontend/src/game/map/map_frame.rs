@@ -43,6 +43,7 @@ impl Frame for MapFrame {
     const HEIGHT: u32 = MAIN_AREA_H;
 
     fn draw(&mut self, state: &mut Self::State, window: &mut DisplayArea, _timestamp: f64) {
+        let now = state.world.fetch::<Now>().0;
         let (sprites, mut map) = (
             &mut state.sprites,
             GlobalMap::combined(
@@ -55,6 +56,7 @@ impl Frame for MapFrame {
         map.draw_grid(window);
         map.draw_water(window, &Self::area());
         map.draw_villages(window, sprites);
+        map.draw_travelling_visitors(window, sprites, now);
     }
     fn pointer(&mut self, state: &mut Self::State, event: PointerEvent) {
         self.mouse.track_pointer_event(&event);
@@ -0,0 +1,89 @@
+//! Visitor groups on their way to the player's village, shown on the map.
+
+use super::GlobalMap;
+use crate::gui::{sprites::*, utils::*, z::*};
+use crate::net::graphql::attacks_query::{AttacksQueryVillage, AttacksQueryVillageAttacks};
+use chrono::NaiveDateTime;
+use paddle::*;
+use paddlers_shared_lib::game_mechanics::map::{StreamPath, VisitorRoute};
+
+pub struct TravellingVisitors {
+    route: VisitorRoute,
+    departure: NaiveDateTime,
+    arrival: NaiveDateTime,
+}
+
+impl TravellingVisitors {
+    /// Visitors that have not arrived yet and come from a specific village
+    pub fn from_attack(
+        home: &AttacksQueryVillage,
+        attack: &AttacksQueryVillageAttacks,
+        now: NaiveDateTime,
+    ) -> Option<Self> {
+        let origin = attack.origin_village.as_ref()?;
+        let arrival = attack.arrival();
+        if arrival <= now || attack.entered_village.is_some() {
+            return None;
+        }
+        let route = VisitorRoute::between_villages(
+            (origin.x as f32, origin.y as f32),
+            &stream_path(&origin.stream.control_points),
+            (home.x as f32, home.y as f32),
+            &stream_path(&home.stream.control_points),
+        );
+        Some(TravellingVisitors {
+            route,
+            departure: attack.departure(),
+            arrival,
+        })
+    }
+    pub fn arrived(&self, now: NaiveDateTime) -> bool {
+        self.arrival <= now
+    }
+    /// Position on the map, assuming constant speed along the route
+    fn position(&self, now: NaiveDateTime) -> (f32, f32) {
+        let total = (self.arrival - self.departure).num_milliseconds() as f32;
+        let progress = if total > 0.0 {
+            (now - self.departure).num_milliseconds() as f32 / total
+        } else {
+            1.0
+        };
+        self.route.position_at(progress)
+    }
+}
+
+fn stream_path(control_points: &[f64]) -> StreamPath {
+    let points: Vec<f32> = control_points.iter().map(|f| *f as f32).collect();
+    StreamPath::from_flat_points(&points)
+}
+
+impl<'a> GlobalMap<'a> {
+    pub(super) fn draw_travelling_visitors(
+        &mut self,
+        window: &mut DisplayArea,
+        sprites: &mut Sprites,
+        now: NaiveDateTime,
+    ) {
+        self.private.travelling_visitors.retain(|v| !v.arrived(now));
+        let size = Self::unit_length() / 2.0;
+        for visitors in &self.private.travelling_visitors {
+            let (x, y) = visitors.position(now);
+            let area = Rectangle::new(
+                (
+                    (x + self.shared.x_offset) * Self::unit_length() - size / 2.0,
+                    y * Self::unit_length() - size / 2.0,
+                ),
+                (size, size),
+            );
+            draw_image(
+                sprites,
+                window,
+                &area,
+                SpriteIndex::Simple(SingleSprite::Duck),
+                Z_VISITOR,
+                FitStrategy::Center,
+                Transform::IDENTITY,
+            );
+        }
+    }
+}
@@ -1,9 +1,10 @@
 use super::{player_info::PlayerState, *};
 use crate::game::{
-    components::*, toplevel::Signal, town::TownContext, town_resources::TownResources,
-    units::hobos::insert_hobos, units::worker_factory::create_worker_entities,
-    units::workers::Worker,
+    components::*, map::TravellingVisitors, toplevel::Signal, town::TownContext,
+    town_resources::TownResources, units::hobos::insert_hobos,
+    units::worker_factory::create_worker_entities, units::workers::Worker,
 };
+use crate::gui::ui_state::Now;
 use crate::net::game_master_api::{HttpCreatePlayer, RestApiState};
 use crate::net::graphql::query_types::{
     AttacksResponse, BuildingsResponse, HobosQueryResponse, VolatileVillageInfoResponse,
@@ -26,6 +27,7 @@ pub fn loading_update_net(
                 PadlErrorCode::GraphQlResponseError(PadlApiError::PlayerNotCreated) => {
                     nuts::send_to::<RestApiState, _>(HttpCreatePlayer);
                 }
+                PadlErrorCode::ClientOutdated => nuts::publish(e),
                 _ => {
                     paddle::println!("Network Error: {}", e);
                 }
@@ -42,7 +44,7 @@ pub fn loading_update_net(
             NetMsg::Hobos(hobos, _vid) => {
                 loader.manually_report_progress(hobos);
             }
-            NetMsg::Attacks(response) => {
+            NetMsg::Attacks(response, _) => {
                 loader.manually_report_progress(response);
             }
             NetMsg::VillageInfo(response) => {
@@ -75,11 +77,14 @@ impl Game {
             Ok(msg) => {
                 // println!("Received Network data!");
                 match msg {
-                    NetMsg::Error(e) => {
-                        paddle::println!("Network Error: {}", e);
-                    }
-                    NetMsg::Attacks(response) => {
-                        self.load_attacking_hobos(response)?;
+                    NetMsg::Error(e) => match e.err {
+                        PadlErrorCode::ClientOutdated => nuts::publish(e),
+                        _ => {
+                            paddle::println!("Network Error: {}", e);
+                        }
+                    },
+                    NetMsg::Attacks(response, min_new_id) => {
+                        self.load_attacking_hobos(response, min_new_id)?;
                         self.check_resting_queue()?;
                         self.refresh_visitor_gate();
                     }
@@ -92,8 +97,8 @@ impl Game {
                         self.world.write_resource::<PlayerState>().hobo_population =
                             Some(settled_hobos as u32);
                     }
-                    NetMsg::Leaderboard(offset, list, total) => {
-                        paddle::share(NetMsg::Leaderboard(offset, list, total));
+                    NetMsg::Leaderboard(page, response) => {
+                        paddle::share(NetMsg::Leaderboard(page, response));
                     }
                     NetMsg::Map(data, min, max) => {
                         let streams = data
@@ -184,9 +189,26 @@ impl Game {
         }
         Ok(())
     }
-    pub fn load_attacking_hobos(&mut self, data: AttacksResponse) -> PadlResult<()> {
-        for atk in data.village.attacks {
-            atk.create_entities(self)?;
+    /// Creates the entities for attacks with an id of at least `min_new_id`.
+    /// The response must contain all attacks on the village, to compute the cheers each visitor received.
+    pub fn load_attacking_hobos(
+        &mut self,
+        mut data: AttacksResponse,
+        min_new_id: i64,
+    ) -> PadlResult<()> {
+        let now = self.world.fetch::<Now>().0;
+        let attacks = std::mem::take(&mut data.village.attacks);
+        let cheers = self.cheers_on_visitors(&attacks, now.into());
+        for atk in attacks {
+            if atk.id.parse::<i64>().unwrap_or(0) < min_new_id {
+                continue;
+            }
+            if let Some(travelling) = TravellingVisitors::from_attack(&data.village, &atk, now) {
+                if let Some(map) = self.map.as_mut() {
+                    map.add_travelling_visitors(travelling);
+                }
+            }
+            atk.create_entities(self, &cheers)?;
         }
         Ok(())
     }
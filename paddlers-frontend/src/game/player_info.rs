@@ -1,9 +1,10 @@
 use crate::net::graphql::query_types::PlayerQueryResponse;
 use paddlers_shared_lib::game_mechanics::prophets::*;
+use paddlers_shared_lib::models::BuildingType;
 use paddlers_shared_lib::story::story_state::StoryState;
 use paddlers_shared_lib::{api::shop::Price, civilization::CivilizationPerks};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 /// Input directly received from Network
 pub struct PlayerInfo {
     karma: i64,
@@ -12,6 +13,8 @@ pub struct PlayerInfo {
     village_count: i64,
     story_state: StoryState,
     civilization_perks: CivilizationPerks,
+    /// Unlocked as quest rewards
+    unlocked_buildings: Vec<BuildingType>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -31,13 +34,14 @@ impl From<PlayerQueryResponse> for PlayerInfo {
             village_count: p.village_count,
             story_state: p.story_state.into(),
             civilization_perks: CivilizationPerks::decode(p.civilization as i32),
+            unlocked_buildings: p.unlocked_buildings,
         }
     }
 }
 impl PlayerState {
     #[inline]
     pub fn karma(&self) -> i64 {
-        self.info.map(|info| info.karma).unwrap_or(0)
+        self.info.as_ref().map(|info| info.karma).unwrap_or(0)
     }
     /// Number of settled hobos + workers (cached, must be computed)
     #[inline]
@@ -64,12 +68,16 @@ impl PlayerInfo {
     }
     #[inline]
     pub fn story_state(&self) -> StoryState {
-        self.story_state
+        self.story_state.clone()
     }
     #[inline]
     pub fn civilization_perks(&self) -> CivilizationPerks {
         self.civilization_perks
     }
+    #[inline]
+    pub fn unlocked_buildings(&self) -> &[BuildingType] {
+        &self.unlocked_buildings
+    }
     /// Count of current hobo prophets available to the player, either idle or on a mission
     #[allow(dead_code)]
     #[inline]
@@ -4,7 +4,11 @@ use crate::{
     prelude::TextDb,
 };
 
-use super::{quest_conditions::*, quest_rewards::ResourceReward, QuestUiTexts};
+use super::{
+    quest_conditions::*,
+    quest_rewards::{ResourceReward, SpriteReward},
+    QuestUiTexts,
+};
 use mogwai::prelude::*;
 use paddlers_shared_lib::{
     api::quests::QuestCollect,
@@ -25,8 +29,10 @@ pub(super) struct QuestComponent {
     building_conditions: Vec<BuildingCondition>,
     worker_conditions: Vec<WorkerCondition>,
     resource_conditions: Vec<ResourceCondition>,
+    stat_conditions: Vec<SimpleCondition>,
     // rewards
     resource_rewards: Vec<ResourceReward>,
+    other_rewards: Vec<SpriteReward>,
     // progress tracking
     total_conditions: usize,
     completed_conditions: usize,
@@ -53,13 +59,16 @@ impl QuestComponent {
         let building_conditions = BuildingCondition::from_quest_ref(quest, town);
         let worker_conditions = WorkerCondition::from_quest_ref(quest, town);
         let (resource_conditions, res_completed) = ResourceCondition::from_quest_ref(quest, bank);
+        let stat_conditions = SimpleCondition::from_stat_conditions(quest);
         let resource_rewards = ResourceReward::from_quest_ref(quest);
+        let other_rewards = SpriteReward::from_quest_ref(quest);
 
         let buildings_completed = building_conditions
             .iter()
             .filter(|c| c.is_complete())
             .count();
         let worker_completed = worker_conditions.iter().filter(|c| c.is_complete()).count();
+        let stats_completed = stat_conditions.iter().filter(|c| c.is_complete()).count();
 
         let karma_completed = karma_condition
             .as_ref()
@@ -76,6 +85,7 @@ impl QuestComponent {
         let completed_conditions = res_completed
             + buildings_completed
             + worker_completed
+            + stats_completed
             + karma_completed
             + pop_completed;
 
@@ -83,7 +93,8 @@ impl QuestComponent {
             + pop_condition.iter().count()
             + building_conditions.len()
             + worker_conditions.len()
-            + resource_conditions.len();
+            + resource_conditions.len()
+            + stat_conditions.len();
 
         Self {
             id: QuestKey(id),
@@ -96,7 +107,9 @@ impl QuestComponent {
             building_conditions,
             worker_conditions,
             resource_conditions,
+            stat_conditions,
             resource_rewards,
+            other_rewards,
             completed_conditions,
             total_conditions,
             init: false,
@@ -112,6 +125,9 @@ impl QuestComponent {
         for child in &mut self.resource_conditions {
             child.subscriber(sub);
         }
+        for child in &mut self.stat_conditions {
+            child.subscriber(sub);
+        }
         for child in &mut self.karma_condition {
             child.subscriber(sub);
         }
@@ -220,34 +236,46 @@ impl Component for QuestComponent {
             .branch_filter_map(completed_filter)
             .branch_map(visibility);
 
-        // Note: Until I learn a better way to display vectors of nodes  in RSX,
-        // I'll just assume a max number and use get() to optionally display each element.
-        builder!(
-        <div class="quest">
-            <h3> { &self.title } </h3>
-            <p> { &self.text } </p>
+        // Conditions and rewards are appended to their containers one by one, so that quests can have any number of them.
+        let mut conditions = builder!(
             <div class="conditions">
                 <div class="title"> { ("CONDITIONS", ui_texts_rx.branch_map(|uit| uit.conditions.clone())) }":" </div>
-                { self.karma_condition.as_ref().map(SimpleCondition::view_builder) }
-                { self.pop_condition.as_ref().map(SimpleCondition::view_builder) }
-                { self.building_conditions.get(0).map(BuildingCondition::view_builder) }
-                { self.building_conditions.get(1).map(BuildingCondition::view_builder) }
-                { self.building_conditions.get(2).map(BuildingCondition::view_builder) }
-                { self.building_conditions.get(3).map(BuildingCondition::view_builder) }
-                { self.building_conditions.get(4).map(BuildingCondition::view_builder) }
-                { self.worker_conditions.get(0).map(WorkerCondition::view_builder) }
-                { self.worker_conditions.get(1).map(WorkerCondition::view_builder) }
-                { self.worker_conditions.get(2).map(WorkerCondition::view_builder) }
-                { self.resource_conditions.get(0).map(ResourceCondition::view_builder) }
-                { self.resource_conditions.get(1).map(ResourceCondition::view_builder) }
-                { self.resource_conditions.get(2).map(ResourceCondition::view_builder) }
             </div>
+        );
+        for child in self.karma_condition.iter().chain(&self.pop_condition) {
+            conditions = conditions.with(child.view_builder());
+        }
+        for child in &self.building_conditions {
+            conditions = conditions.with(child.view_builder());
+        }
+        for child in &self.worker_conditions {
+            conditions = conditions.with(child.view_builder());
+        }
+        for child in &self.resource_conditions {
+            conditions = conditions.with(child.view_builder());
+        }
+        for child in &self.stat_conditions {
+            conditions = conditions.with(child.view_builder());
+        }
+
+        let mut rewards = builder!(
             <div class="rewards">
                 <div class="title"> { ("REWARDS", ui_texts_rx.branch_map(|uit| uit.rewards.clone())) }":" </div>
-                { self.resource_rewards.get(0).cloned().map(ResourceReward::view_builder) }
-                { self.resource_rewards.get(1).cloned().map(ResourceReward::view_builder) }
-                { self.resource_rewards.get(2).cloned().map(ResourceReward::view_builder) }
             </div>
+        );
+        for child in &self.resource_rewards {
+            rewards = rewards.with(child.clone().view_builder());
+        }
+        for child in &self.other_rewards {
+            rewards = rewards.with(child.clone().view_builder());
+        }
+
+        builder!(
+        <div class="quest">
+            <h3> { &self.title } </h3>
+            <p> { &self.text } </p>
+            { conditions }
+            { rewards }
             <div on:click=tx_event class="button" style:visibility={(visible_now,visible_receiver)}>
                 "Collect"
             </div>
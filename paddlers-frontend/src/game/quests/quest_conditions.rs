@@ -1,3 +1,11 @@
+//! Quest conditions displayed in the quest list.
+//!
+//! Conditions on statistics show the progress reported by the server, which counts game events since the quest has been assigned.
+//! Building, resource and worker conditions are about the current state of the town instead, like "have two trees" or "own 50 sticks".
+//! Demolishing a building or spending resources undoes them and the game-master checks them again when the quest is collected.
+//! The frontend keeps that state up to date anyway, thus these conditions are evaluated locally.
+//! Cumulative goals, such as gathering 100 sticks in total, use statistics like `GatheredSticks`.
+
 use crate::{
     game::town::Town,
     gui::sprites::{SingleSprite, SpriteIndex, Sprites, WithSprite},
@@ -50,6 +58,26 @@ impl SimpleCondition {
             gizmo,
         }
     }
+    /// Conditions on statistics, whose current values are computed by the server
+    pub fn from_stat_conditions(quest: &PlayerQuest) -> Vec<Self> {
+        quest
+            .conditions
+            .stats
+            .iter()
+            .map(|c| {
+                let component = QuestConditionComponent::new(
+                    c.stat_type.sprite().default(),
+                    c.amount,
+                    c.current,
+                );
+                Self {
+                    cached_current: c.current,
+                    amount: c.amount,
+                    gizmo: Gizmo::from(component),
+                }
+            })
+            .collect()
+    }
     pub fn view_builder(&self) -> ViewBuilder<HtmlElement> {
         self.gizmo.view_builder()
     }
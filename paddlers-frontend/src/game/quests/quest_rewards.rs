@@ -1,6 +1,16 @@
-use crate::{gui::gui_components::mogwai_res_node, net::graphql::PlayerQuest};
+use crate::{
+    gui::{
+        gui_components::mogwai_res_node,
+        sprites::{SingleSprite, SpriteIndex, Sprites, WithSprite},
+    },
+    net::graphql::PlayerQuest,
+    prelude::ISpriteIndex,
+};
 use mogwai::prelude::*;
-use paddlers_shared_lib::prelude::*;
+use paddlers_shared_lib::{
+    civilization::{CivilizationPerk, CivilizationPerks},
+    prelude::*,
+};
 #[derive(Clone, Debug)]
 pub struct ResourceReward {
     t: ResourceType,
@@ -10,7 +20,7 @@ pub struct ResourceReward {
 impl ResourceReward {
     pub fn from_quest_ref(quest: &PlayerQuest) -> Vec<Self> {
         let mut out = vec![];
-        let r = &quest.rewards;
+        let r = &quest.rewards.resources;
         if r.feathers > 0 {
             out.push(Self {
                 t: ResourceType::Feathers,
@@ -36,6 +46,56 @@ impl ResourceReward {
     }
 }
 
+#[derive(Clone, Debug)]
+/// Rewards other than resources: Hero mana and experience, perks and unlocked buildings
+pub struct SpriteReward {
+    sprite: SpriteIndex,
+    amount: Option<i64>,
+}
+
+impl SpriteReward {
+    pub fn from_quest_ref(quest: &PlayerQuest) -> Vec<Self> {
+        let mut out = vec![];
+        let r = &quest.rewards;
+        if r.mana > 0 {
+            out.push(Self {
+                sprite: SpriteIndex::Simple(SingleSprite::WelcomeAbility),
+                amount: Some(r.mana),
+            });
+        }
+        if r.experience > 0 {
+            out.push(Self {
+                sprite: SpriteIndex::Simple(SingleSprite::Roger),
+                amount: Some(r.experience),
+            });
+        }
+        let perks = CivilizationPerks::decode(r.perks as i32);
+        for perk in &[
+            CivilizationPerk::NestBuilding,
+            CivilizationPerk::TripleNestBuilding,
+            CivilizationPerk::Invitation,
+            CivilizationPerk::Conversion,
+        ] {
+            if perks.has(*perk) {
+                out.push(Self {
+                    sprite: perk.sprite().default(),
+                    amount: None,
+                });
+            }
+        }
+        for building in &r.buildings {
+            out.push(Self {
+                sprite: building.sprite().default(),
+                amount: None,
+            });
+        }
+        out
+    }
+    pub fn view_builder(self) -> ViewBuilder<HtmlElement> {
+        Gizmo::from(self).view_builder()
+    }
+}
+
 #[derive(Clone)]
 pub enum RewardIn {}
 
@@ -61,3 +121,31 @@ impl Component for ResourceReward {
         )
     }
 }
+
+impl Component for SpriteReward {
+    type ModelMsg = RewardIn;
+    type ViewMsg = ();
+    type DomNode = HtmlElement;
+
+    fn update(
+        &mut self,
+        _msg: &RewardIn,
+        _tx_view: &Transmitter<()>,
+        _subscriber: &Subscriber<RewardIn>,
+    ) {
+    }
+
+    #[allow(unused_braces)]
+    fn view(&self, _tx: &Transmitter<RewardIn>, _rx: &Receiver<()>) -> ViewBuilder<HtmlElement> {
+        let img = Sprites::new_image_node_builder(self.sprite);
+        let amount = self.amount.map(|n| n.to_string()).unwrap_or_default();
+        builder!(
+            <div class="reward">
+                <div class="res">
+                    <div> { amount } </div>
+                    { img }
+                </div>
+            </div>
+        )
+    }
+}
@@ -6,7 +6,9 @@ use crate::{
 };
 use crate::{net::graphql::ScheduledRequest, prelude::*};
 use paddle::NutsCheck;
-use paddlers_shared_lib::story::{story_action::StoryAction, story_state::StoryState};
+use paddlers_shared_lib::story::{
+    story_action::StoryAction, story_graph::StoryGraph, story_state::StoryState,
+};
 use paddlers_shared_lib::{specification_types::*, story::story_trigger::StoryTrigger};
 
 use super::player_info::PlayerState;
@@ -23,7 +25,8 @@ impl Game {
     }
     pub fn load_story_state(&mut self) -> PadlResult<()> {
         let story_state = self.story_state();
-        if let Some((scene, slide)) = select_dialogue_scene(story_state) {
+        let scene = select_dialogue_scene(&self.world.fetch::<StoryGraph>(), &story_state);
+        if let Some((scene, slide)) = scene {
             crate::game::game_event_manager::game_event(GameEvent::DialogueActions(vec![
                 DialogueAction::OpenScene(scene, slide),
             ]));
@@ -31,10 +34,15 @@ impl Game {
         self.load_story_triggers(&story_state)?;
         Ok(())
     }
-    // TODO: This should be called everywhere in the frontend where a story state changing action happens. And then the code should be changed to not do the full const computation every time-
+    // TODO: This should be called everywhere in the frontend where a story state changing action happens.
     pub fn handle_story_trigger(&mut self, trigger: StoryTrigger) {
         let story_state = self.story_state();
-        if let Some(t) = story_state.transition(&trigger) {
+        let transition = self
+            .world
+            .fetch::<StoryGraph>()
+            .transition(&story_state, &trigger)
+            .cloned();
+        if let Some(t) = transition {
             if t.next_state != story_state {
                 self.set_story_state(t.next_state);
                 self.load_story_state().nuts_check();
@@ -68,26 +76,9 @@ impl Game {
     }
 }
 
-pub fn select_dialogue_scene(story_state: StoryState) -> Option<(SceneIndex, SlideIndex)> {
-    match story_state {
-        StoryState::Initialized
-        | StoryState::VisitorArrived
-        | StoryState::FirstVisitorWelcomed
-        | StoryState::BuildingWatergate
-        | StoryState::WatergateBuilt
-        | StoryState::PickingPrimaryCivBonus
-        | StoryState::SolvingPrimaryCivQuestPartA
-        | StoryState::SolvingPrimaryCivQuestPartB
-        | StoryState::SolvingSecondaryQuestA
-        | StoryState::SolvingSecondaryQuestB
-        | StoryState::AllDone
-        | StoryState::VisitorQueued
-        | StoryState::WelcomeVisitorQuestStarted
-        | StoryState::UnlockingInvitationPathA
-        | StoryState::UnlockingInvitationPathB
-        | StoryState::TempleBuilt => None,
-        StoryState::ServantAccepted => Some((SceneIndex::Entrance, 5)),
-        StoryState::DialogueBalanceA => None,
-        StoryState::DialogueBalanceB => None,
-    }
+pub fn select_dialogue_scene(
+    graph: &StoryGraph,
+    story_state: &StoryState,
+) -> Option<(SceneIndex, SlideIndex)> {
+    graph.state(story_state).and_then(|s| s.scene)
 }
@@ -2,7 +2,10 @@ use crate::game::units::{attackers::Visitor, workers::Worker};
 use crate::game::{story::DialogueAction, town::Town};
 use crate::gui::ui_state::UiState;
 use crate::prelude::*;
-use paddlers_shared_lib::story::story_state::StoryState;
+use paddlers_shared_lib::story::{
+    story_graph::{StoryEntity, StoryGraph},
+    story_state::StoryState,
+};
 use specs::prelude::*;
 use specs::storage::HashMapStorage;
 
@@ -15,65 +18,24 @@ pub struct EntityTrigger {
 
 impl Game {
     pub fn load_story_triggers(&mut self, story_state: &StoryState) -> PadlResult<()> {
-        match story_state {
-            StoryState::Initialized => {
-                self.add_trigger_to_hero(EntityTrigger {
-                    actions: vec![DialogueAction::OpenScene(SceneIndex::Entrance, 0)],
-                })?;
+        let entity_trigger = self
+            .world
+            .fetch::<StoryGraph>()
+            .state(story_state)
+            .and_then(|s| s.entity_trigger.clone());
+        if let Some(entity_trigger) = entity_trigger {
+            let trigger = EntityTrigger {
+                actions: entity_trigger
+                    .scene
+                    .map(|(scene, slide)| DialogueAction::OpenScene(scene, slide))
+                    .into_iter()
+                    .collect(),
+            };
+            match entity_trigger.entity {
+                StoryEntity::Hero => self.add_trigger_to_hero(trigger)?,
+                StoryEntity::Visitor => self.add_trigger_to_visitor(trigger)?,
+                StoryEntity::Building(bt) => self.add_trigger_to_building(trigger, bt)?,
             }
-            StoryState::TempleBuilt => {
-                self.add_trigger_to_hero(EntityTrigger {
-                    actions: vec![DialogueAction::OpenScene(SceneIndex::BuildWatergate, 0)],
-                })?;
-            }
-            StoryState::WatergateBuilt => {
-                self.add_trigger_to_hero(EntityTrigger {
-                    actions: vec![DialogueAction::OpenScene(SceneIndex::ExplainWatergate, 0)],
-                })?;
-            }
-            StoryState::VisitorArrived => {
-                self.add_trigger_to_visitor(EntityTrigger {
-                    actions: vec![DialogueAction::OpenScene(SceneIndex::WelcomeVisitor, 0)],
-                })?;
-            }
-            StoryState::VisitorQueued => {
-                self.add_trigger_to_building(
-                    EntityTrigger { actions: vec![] },
-                    BuildingType::Watergate,
-                )?;
-            }
-            StoryState::PickingPrimaryCivBonus => {
-                self.add_trigger_to_hero(EntityTrigger {
-                    actions: vec![DialogueAction::OpenScene(SceneIndex::FirstChoice, 0)],
-                })?;
-            }
-            StoryState::UnlockingInvitationPathA | StoryState::UnlockingInvitationPathB => {
-                self.add_trigger_to_hero(EntityTrigger {
-                    actions: vec![DialogueAction::OpenScene(
-                        SceneIndex::UnlockingInvitation,
-                        0,
-                    )],
-                })?;
-            }
-            StoryState::DialogueBalanceA => {
-                self.add_trigger_to_hero(EntityTrigger {
-                    actions: vec![DialogueAction::OpenScene(SceneIndex::VisitorBalanceTown, 0)],
-                })?;
-            }
-            StoryState::DialogueBalanceB => {
-                self.add_trigger_to_hero(EntityTrigger {
-                    actions: vec![DialogueAction::OpenScene(SceneIndex::TownBalanceVisitor, 0)],
-                })?;
-            }
-            StoryState::FirstVisitorWelcomed
-            | StoryState::ServantAccepted
-            | StoryState::BuildingWatergate
-            | StoryState::SolvingPrimaryCivQuestPartA
-            | StoryState::SolvingPrimaryCivQuestPartB
-            | StoryState::SolvingSecondaryQuestA
-            | StoryState::SolvingSecondaryQuestB
-            | StoryState::WelcomeVisitorQuestStarted
-            | StoryState::AllDone => {}
         }
         Ok(())
     }
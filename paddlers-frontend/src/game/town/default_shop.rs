@@ -24,9 +24,10 @@ impl DefaultShop {
         let mut result = DefaultShop::default();
         let karma = player_info.karma();
         let story_state = player_info.story_state();
-        for b in BuildingType::default_shop_buildings()
-            .filter(|b| b.player_can_build(karma, story_state, player_info.civilization_perks()))
-        {
+        for b in BuildingType::default_shop_buildings().filter(|b| {
+            b.player_can_build(karma, &story_state, player_info.civilization_perks())
+                || player_info.unlocked_buildings().contains(b)
+        }) {
             result.add_building(*b);
         }
         result
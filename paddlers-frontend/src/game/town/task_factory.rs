@@ -1,6 +1,7 @@
 use super::*;
 use crate::game::components::{EntityContainer, Mana};
 use crate::prelude::*;
+use paddlers_shared_lib::api::error::GameMasterError;
 use paddlers_shared_lib::api::tasks::*;
 use paddlers_shared_lib::prelude::*;
 use specs::prelude::*;
@@ -50,7 +51,11 @@ impl Town {
                     return PadlErrorCode::NotEnoughMana.usr();
                 }
             }
-            TaskType::Defend => panic!("NIY"),
+            TaskType::Defend => {
+                if self.map[destination] != TileType::LANE {
+                    return PadlErrorCode::GameMaster(GameMasterError::DefendOffLane).usr();
+                }
+            }
             TaskType::Idle | TaskType::CollectReward | TaskType::Walk => {}
         }
 
@@ -72,10 +77,7 @@ impl Town {
                 _ => vec![],
             },
             TileType::EMPTY => vec![TaskType::Idle],
-            TileType::LANE => {
-                // TODO: Check for welcoming ability
-                vec![]
-            }
+            TileType::LANE => vec![TaskType::Defend],
         }
     }
 }
@@ -103,15 +105,16 @@ fn raw_job_execution_tasks(job: &NewTaskDescriptor, place: TileIndex) -> Vec<Raw
 
     // tasks required afterwards
     match job.0 {
-        TaskType::ChopTree | TaskType::GatherSticks | TaskType::Idle | TaskType::Walk => {
+        TaskType::ChopTree
+        | TaskType::GatherSticks
+        | TaskType::Idle
+        | TaskType::Walk
+        | TaskType::Defend => {
             // NOP
         }
         TaskType::CollectReward | TaskType::WelcomeAbility => {
             tasks.push(RawTask::new(TaskType::Idle, place));
         }
-        TaskType::Defend => {
-            panic!("Not implemented");
-        }
     }
 
     tasks
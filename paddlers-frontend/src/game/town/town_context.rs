@@ -34,7 +34,7 @@ impl TownContextManager {
     /// Load a new town context for a foreign town
     pub fn load_foreign(&mut self, v: VillageKey) {
         let home_data = self.home_town.world();
-        let player_info = *home_data.fetch::<PlayerState>().info();
+        let player_info = home_data.fetch::<PlayerState>().info().clone();
         self.foreign_town = Some(TownContext::new(player_info, v, true));
     }
     /// Remove all loaded foreign towns from the view and display home again
@@ -1,10 +1,13 @@
 use super::*;
 use crate::game::buildings::Building;
-use crate::game::fight::Aura;
-use crate::net::graphql::attacks_query::AttacksQueryVillageAttacksUnits;
+use crate::game::fight::{Aura, Cheering, Defending};
+use crate::net::graphql::attacks_query::{
+    AttacksQueryVillageAttacks, AttacksQueryVillageAttacksUnits,
+};
 use paddlers_shared_lib::game_mechanics::town::*;
 use paddlers_shared_lib::graphql_types::*;
 use specs::prelude::*;
+use std::collections::HashMap;
 
 pub(crate) struct AttackingHobo {
     pub unit: AttacksQueryVillageAttacksUnits,
@@ -40,6 +43,16 @@ impl IAttackingHobo for AttackingHobo {
             .filter(|e| e.strength.is_some())
             .fold(0, |acc, e| acc + e.strength.unwrap() as i64) as i32
     }
+    fn cheers(&self) -> i32 {
+        self.unit.info.cheers as i32
+    }
+    fn cheered_until(&self) -> Option<Timestamp> {
+        self.unit
+            .info
+            .cheered_until
+            .as_ref()
+            .map(|t| GqlTimestamp::from_string(&t).unwrap().to_chrono().into())
+    }
 }
 
 impl<'a, 'b> ITownLayoutMarker for Game {
@@ -66,4 +79,51 @@ impl<'a, 'b> IDefendingTown for Game {
         }
         out
     }
+    fn cheering_buildings(&self) -> Vec<CheeringBuilding> {
+        let world = self.town_world();
+        let cheering = world.read_component::<Cheering>();
+        (&cheering).join().map(|c| c.building.clone()).collect()
+    }
+    fn defenders(&self) -> Vec<(Self::AuraId, DefendingWorker)> {
+        let world = self.town_world();
+        let defending = world.read_component::<Defending>();
+        let entities = world.entities();
+        (&defending, &entities)
+            .join()
+            .map(|(d, e)| (e.id(), d.defender.clone()))
+            .collect()
+    }
+}
+
+impl Game {
+    /// Cheers received so far by each visitor (by hobo id) of the attacks which have entered the town
+    pub(crate) fn cheers_on_visitors(
+        &self,
+        attacks: &[AttacksQueryVillageAttacks],
+        now: Timestamp,
+    ) -> HashMap<String, i32> {
+        let mut visitors: Vec<AttackingHobo> = attacks
+            .iter()
+            .filter_map(|atk| {
+                let entered = atk.entered_village.as_ref()?;
+                let start_of_fight: Timestamp = GqlTimestamp::from_string(entered)
+                    .unwrap()
+                    .to_chrono()
+                    .into();
+                Some(atk.units.iter().map(move |unit| AttackingHobo {
+                    unit: unit.clone(),
+                    start_of_fight,
+                }))
+            })
+            .flatten()
+            .collect();
+        // Must be the same order as in the backend
+        visitors.sort_by_key(|v| v.unit.hobo.id.parse::<i64>().unwrap_or(0));
+        let cheers = self.cheer_damage(&visitors, now);
+        visitors
+            .into_iter()
+            .map(|v| v.unit.hobo.id)
+            .zip(cheers)
+            .collect()
+    }
 }
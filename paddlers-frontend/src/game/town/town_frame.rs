@@ -23,7 +23,7 @@ use paddle::{
     Transform, UniformValue,
 };
 
-use paddlers_shared_lib::story::{story_state::StoryState, story_trigger::StoryTrigger};
+use paddlers_shared_lib::story::story_trigger::StoryTrigger;
 use specs::prelude::*;
 use std::ops::Deref;
 
@@ -124,11 +124,6 @@ impl<'a, 'b> TownFrame<'a, 'b> {
                 state.home_town_world_mut().maintain();
                 state.handle_story_trigger(StoryTrigger::BuildingBuilt(*bt));
                 // TODO: Can these be integrated in specs?
-                if *bt == BuildingType::Temple {
-                    state
-                        .load_story_triggers(&StoryState::TempleBuilt)
-                        .nuts_check();
-                }
                 if *bt == BuildingType::Watergate {
                     state.town_mut().refresh_attacker_direction();
                     state.refresh_visitor_gate();
@@ -18,7 +18,11 @@ use crate::{
 };
 use chrono::NaiveDateTime;
 use paddle::NutsCheck;
-use paddlers_shared_lib::{game_mechanics::attributes::Attributes, prelude::*};
+use paddlers_shared_lib::{
+    game_mechanics::attributes::Attributes,
+    prelude::*,
+    specification_types::{HoboLevel, HoboType, RewardedVisitor, VisitRewardTable},
+};
 use specs::prelude::*;
 
 pub type GraphqlVisitingHobo = crate::net::graphql::attacks_query::AttacksQueryVillageAttacksUnits;
@@ -38,6 +42,8 @@ pub struct WaitingAttack {
     pub arrival: NaiveDateTime,
     pub shown_as_arrived: bool,
     pub hobos: Vec<GraphqlVisitingHobo>,
+    /// Rewards if all visitors are satisfied, shown before letting them in
+    pub expected_rewards: Vec<(ResourceType, i64)>,
 }
 
 impl VisitorGate {
@@ -122,6 +128,30 @@ impl Game {
             ))));
         }
     }
+    /// Rewards for a group of visitors if all of them are satisfied, rounded to whole resources
+    pub fn expected_visit_rewards(
+        &self,
+        hobos: &[GraphqlVisitingHobo],
+    ) -> Vec<(ResourceType, i64)> {
+        let visitors: Vec<RewardedVisitor> = hobos
+            .iter()
+            .map(|unit| RewardedVisitor {
+                typ: HoboType::of_color(unit.hobo.color),
+                level: HoboLevel::new(unit.hobo.level as usize),
+                hurried: unit.hobo.hurried,
+                satisfied: true,
+            })
+            .collect();
+        let (_karma, resources) = self
+            .world
+            .fetch::<VisitRewardTable>()
+            .expected_rewards(&visitors);
+        resources
+            .into_iter()
+            .map(|(rt, n)| (rt, n.round() as i64))
+            .filter(|(_, n)| *n > 0)
+            .collect()
+    }
     pub fn release_attack(&mut self, key: AttackKey) {
         let mut gate = self.home_town_world().write_resource::<VisitorGate>();
         let popped = gate.queue.remove(&key);
@@ -142,7 +172,8 @@ impl Game {
                     .nuts_check();
             }
             std::mem::drop(gate);
-            self.insert_visitors_from_active_attack(attack.hobos, now)
+            // Visitors just entering have not been cheered, yet
+            self.insert_visitors_from_active_attack(attack.hobos, now, &Default::default())
                 .nuts_check();
         }
     }
@@ -180,16 +211,20 @@ impl WaitingAttack {
         hobos: Vec<GraphqlVisitingHobo>,
         shown_as_arrived: bool,
         key: AttackKey,
+        expected_rewards: Vec<(ResourceType, i64)>,
     ) -> Self {
         Self {
             arrival,
             hobos,
             shown_as_arrived,
             key,
+            expected_rewards,
         }
     }
     fn ui_element(&self) -> UiElement {
-        UiElement::new(self.click_output()).with_render_variant(self.render_variant())
+        UiElement::new(self.click_output())
+            .with_render_variant(self.render_variant())
+            .with_hover_resources(self.expected_rewards.clone())
     }
     fn render_variant(&self) -> RenderVariant {
         let main_img = hobo_sprite_sad(self.hobos[0].hobo.color.unwrap());
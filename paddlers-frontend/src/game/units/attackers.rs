@@ -24,6 +24,7 @@ use paddle::Vector;
 use paddlers_shared_lib::graphql_types::*;
 use paddlers_shared_lib::{game_mechanics::town::*, prelude::AttackKey};
 use specs::prelude::*;
+use std::collections::HashMap;
 
 const ATTACKER_SIZE_FACTOR_X: f32 = 0.6;
 const ATTACKER_SIZE_FACTOR_Y: f32 = 0.4;
@@ -120,7 +121,11 @@ pub fn build_new_duck_entity<'a>(
 
 use crate::net::graphql::attacks_query::AttacksQueryVillageAttacks;
 impl AttacksQueryVillageAttacks {
-    pub(crate) fn create_entities<'a, 'b>(self, game: &mut Game) -> PadlResult<Vec<Entity>> {
+    pub(crate) fn create_entities<'a, 'b>(
+        self,
+        game: &mut Game,
+        cheers: &HashMap<String, i32>,
+    ) -> PadlResult<Vec<Entity>> {
         let arrival = GqlTimestamp::from_string(&self.arrival)
             .unwrap()
             .to_chrono();
@@ -139,12 +144,14 @@ impl AttacksQueryVillageAttacks {
         // Either create active attackers (AttackingHobo) and insert them as entities, or create a queued attack and store it in the visitor gate
         if let Some(entered) = self.entered_village {
             let birth_time = GqlTimestamp::from_string(&entered).unwrap().to_chrono();
-            out = game.insert_visitors_from_active_attack(self.units, birth_time)?;
+            out = game.insert_visitors_from_active_attack(self.units, birth_time, cheers)?;
         } else {
             let now = paddle::utc_now();
             let arrived = arrival <= now;
             let key = AttackKey(self.id.parse().expect("Parsing id"));
-            let waiting_attack = WaitingAttack::new(arrival, self.units, arrived, key);
+            let expected_rewards = game.expected_visit_rewards(&self.units);
+            let waiting_attack =
+                WaitingAttack::new(arrival, self.units, arrived, key, expected_rewards);
             game.queue_attack(waiting_attack);
             out = vec![];
         }
@@ -160,6 +167,7 @@ impl Game {
         &mut self,
         units: Vec<GraphqlVisitingHobo>,
         start_of_fight: NaiveDateTime,
+        cheers: &HashMap<String, i32>,
     ) -> PadlResult<Vec<Entity>> {
         let mut out = vec![];
         let now = self.world.fetch::<Now>().0;
@@ -168,7 +176,9 @@ impl Game {
                 unit,
                 start_of_fight: start_of_fight.into(),
             };
-            let effects = self.touched_auras(&unit_rep, now.into());
+            let mut effects = self.touched_auras(&unit_rep, now.into());
+            effects.append(&mut self.touched_defenders(&unit_rep, now.into()));
+            let cheered = cheers.get(&unit_rep.unit.hobo.id).copied().unwrap_or(0);
             let direction = self.town().attacker_direction;
             let builder = unit_rep.create_entity(
                 self.town_context.home_world_mut().create_entity(),
@@ -176,6 +186,7 @@ impl Game {
                 start_of_fight,
                 i,
                 effects,
+                cheered,
                 direction,
             )?;
             out.push(builder.build());
@@ -218,6 +229,7 @@ impl AttackingHobo {
         birth: NaiveDateTime,
         pos_rank: usize,
         auras: Vec<(<Game as IDefendingTown>::AuraId, i32)>,
+        cheered: i32,
         direction: AttackerDirection,
     ) -> PadlResult<specs::EntityBuilder<'a>> {
         let ul = TOWN_TILE_S as f32;
@@ -230,7 +242,7 @@ impl AttackingHobo {
         let time_until_resting = self.time_until_resting().as_duration();
 
         // Simulate all interactions with buildings for the visitor which happened in the past
-        let dmg = <Game as IDefendingTown>::damage(&auras) + self.effects_strength();
+        let dmg = <Game as IDefendingTown>::damage(&auras) + self.effects_strength() + cheered;
         let hp_left = (hp - dmg as i64).max(0);
         let aura_ids = auras.into_iter().map(|a| a.0).collect();
         let health = Health::new(hp, hp_left, aura_ids);
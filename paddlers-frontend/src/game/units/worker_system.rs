@@ -1,6 +1,7 @@
 use crate::game::{
     abilities::use_welcome_ability,
     components::*,
+    fight::Defending,
     movement::Moving,
     town::{TileIndex, Town},
     units::workers::*,
@@ -12,6 +13,7 @@ use crate::gui::render::Renderable;
 use crate::prelude::*;
 use chrono::NaiveDateTime;
 use paddle::quicksilver_compat::about_equal;
+use paddlers_shared_lib::game_mechanics::town::DefendingWorker;
 use specs::prelude::*;
 
 pub struct WorkerSystem;
@@ -62,6 +64,8 @@ impl<'a> System<'a> for WorkerSystem {
             (&entities, &mut workers, &mut velocities, &mut animations).join()
         {
             if let Some(task) = worker.poll(now.0) {
+                // Defending ends with any new task
+                lazy.remove::<Defending>(e);
                 match task.task_type {
                     TaskType::Walk => {
                         let position_now = mov.position(task.start_time);
@@ -120,6 +124,13 @@ impl<'a> System<'a> for WorkerSystem {
                             nuts::publish(e);
                         }
                     }
+                    TaskType::Defend => {
+                        mov.stand_still(task.start_time);
+                        anim.direction = Direction::Undirected;
+                        let defender =
+                            DefendingWorker::hero(task.position, task.start_time.into(), None);
+                        lazy.insert(e, Defending { defender });
+                    }
                     _ => debug_assert!(false, "Unexpected task"),
                 }
             }
@@ -16,7 +16,8 @@ use mogwai::prelude::*;
 use paddle::{DisplayArea, FrameHandle};
 use paddle::{JsError, NutsCheck};
 use paddlers_shared_lib::prelude::{VillageKey, VisitReportKey};
-use web_sys::Node;
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{Element, Node};
 
 mod report_component;
 pub use report_component::*;
@@ -26,7 +27,12 @@ struct RemoveReport(VisitReportKey);
 pub(crate) struct ReportFrame {
     pane: div::DivHandle,
     table_node: Node,
+    /// Loaded reports, newest first
     reports: Vec<(VisitReportKey, View<HtmlElement>)>,
+    /// Number of reports to collect, including those not loaded
+    total_reports: usize,
+    /// Below all letters, loads older reports
+    older_button: Element,
 }
 
 struct Report {
@@ -57,34 +63,68 @@ impl ReportFrame {
         title.set_text_content(Some("Mailbox"));
         table_node.append_child(&title)?;
 
+        let older_button = doc()?.create_element("div")?;
+        older_button.set_class_name("older-letters button");
+        older_button.set_text_content(Some("Older letters"));
+        older_button.set_attribute("hidden", "")?;
+        let callback =
+            Closure::wrap(Box::new(crate::net::request_older_reports) as Box<dyn FnMut()>);
+        older_button
+            .add_event_listener_with_callback("click", callback.as_ref().dyn_ref().unwrap())?;
+        callback.forget();
+        table_node.append_child(&older_button)?;
+
         Ok(ReportFrame {
             pane,
             table_node,
             reports: vec![],
+            total_reports: 0,
+            older_button,
         })
     }
     pub fn init_listeners(frame_handle: FrameHandle<Self>) {
         frame_handle.listen(ReportFrame::network_message);
         frame_handle.listen(ReportFrame::remove_report);
     }
+    /// Adds the report in order, older and newer reports can be loaded in any order
     fn add_report(&mut self, report: Report) -> PadlResult<()> {
         let id = report.id;
+        if self.reports.iter().any(|(key, _)| *key == id) {
+            return Ok(());
+        }
         let gizmo = Gizmo::from(report);
         let view = View::from(gizmo.view_builder());
 
         let letter_node = view.dom_ref().clone();
 
-        self.table_node.append_child(&letter_node)?;
-        self.reports.push((id, view));
+        let index = self
+            .reports
+            .iter()
+            .position(|(key, _)| *key < id)
+            .unwrap_or(self.reports.len());
+        let next_node: Node = match self.reports.get(index) {
+            Some((_, next)) => next.dom_ref().clone().into(),
+            None => self.older_button.clone().into(),
+        };
+        self.table_node
+            .insert_before(&letter_node, Some(&next_node))?;
+        self.reports.insert(index, (id, view));
         Ok(())
     }
-    fn number_of_reports(&self) -> usize {
-        self.reports.len()
+    fn update_older_button(&self) -> PadlResult<()> {
+        if self.reports.len() < self.total_reports {
+            self.older_button.remove_attribute("hidden")?;
+        } else {
+            self.older_button.set_attribute("hidden", "")?;
+        }
+        Ok(())
     }
     pub fn network_message(&mut self, _state: &mut Game, msg: &NetMsg) {
         match msg {
             NetMsg::Reports(data) => {
-                for r in &data.village.reports {
+                let connection = &data.village.reports_connection;
+                self.total_reports = connection.total_count as usize;
+                for r in &connection.nodes {
                     let id = r.id.parse().unwrap();
                     let report = if let Some(sender) = &r.sender {
                         let unit_color = match &sender.color {
@@ -101,19 +141,22 @@ impl ReportFrame {
                     };
                     self.add_report(report).nuts_check();
                 }
+                self.update_older_button().nuts_check();
             }
             _ => {}
         }
     }
     fn remove_report(&mut self, _: &mut Game, msg: &RemoveReport) {
         if let Some(index) = self.reports.iter().position(|r| r.0 == msg.0) {
-            let (_, view) = self.reports.swap_remove(index);
+            let (_, view) = self.reports.remove(index);
             self.table_node
                 .remove_child(&view.dom_ref())
                 .map_err(JsError::from_js_value)
                 .map_err(PadlError::from)
                 .nuts_check();
-            paddle::share(Signal::NewReportCount(self.number_of_reports()));
+            self.total_reports = self.total_reports.saturating_sub(1);
+            self.update_older_button().nuts_check();
+            paddle::share(Signal::NewReportCount(self.total_reports));
         }
     }
 }
@@ -20,6 +20,8 @@ pub struct UiElement {
     pub overlay: Option<(NaiveDateTime, NaiveDateTime)>,
     condition: Option<Condition>,
     on_click: Option<ClickOutput>,
+    /// Resources shown while hovering, without any condition attached
+    hover_resources: Option<Vec<(ResourceType, i64)>>,
 }
 #[derive(Clone, Debug)]
 /// A grid of UI elements.
@@ -268,7 +270,9 @@ impl UiBox {
                 // TODO: Calling draw every frame is expensive
                 res_comp.update(&cost.0)?;
                 res_comp.draw(display, area)?;
-            } else {
+            } else if let Some(resources) = &el.hover_resources {
+                res_comp.update(resources)?;
+                res_comp.draw(display, area)?;
             }
         } else {
             res_comp.update(&[])?;
@@ -351,6 +355,7 @@ impl UiElement {
             overlay: None,
             condition: None,
             on_click: Some(on_click.into()),
+            hover_resources: None,
         }
     }
     pub fn with_image(mut self, i: SpriteSet) -> Self {
@@ -397,6 +402,10 @@ impl UiElement {
         self.condition = Some(Condition::HasCivPerk(perk));
         self
     }
+    pub fn with_hover_resources(mut self, resources: Vec<(ResourceType, i64)>) -> Self {
+        self.hover_resources = Some(resources);
+        self
+    }
 
     pub fn empty() -> Self {
         UiElement {
@@ -404,6 +413,7 @@ impl UiElement {
             overlay: None,
             condition: None,
             on_click: None,
+            hover_resources: None,
         }
     }
 }
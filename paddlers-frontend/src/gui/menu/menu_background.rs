@@ -70,8 +70,7 @@ impl MenuBackgroundFrame {
     pub fn network_message(&mut self, _state: &mut Game, msg: &NetMsg) {
         match msg {
             NetMsg::Reports(data) => {
-                let new_reports = data.village.reports.len();
-                self.reports_to_collect += new_reports;
+                self.reports_to_collect = data.village.reports_connection.total_count as usize;
                 self.update_notifications();
             }
             _ => {}
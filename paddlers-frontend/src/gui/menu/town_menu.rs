@@ -204,7 +204,7 @@ impl TownMenuFrame<'_, '_> {
         msg: &crate::game::dialogue::NewStoryState,
     ) {
         // FIXME: redundant with the same call also in dialogue
-        state.set_story_state(msg.new_story_state);
+        state.set_story_state(msg.new_story_state.clone());
         DefaultShop::reload(state.town_world_mut());
     }
     pub fn signal(&mut self, state: &mut Game, e: &Signal) {
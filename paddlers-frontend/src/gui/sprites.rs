@@ -120,6 +120,8 @@ impl WithSprite for BuildingType {
             BuildingType::SingleNest => SpriteSet::Simple(SingleSprite::SingleNest),
             BuildingType::TripleNest => SpriteSet::Simple(SingleSprite::TripleNest),
             BuildingType::Watergate => SpriteSet::Simple(SingleSprite::Stone1),
+            // The choir sings in a temple, it deliberately shares the sprite
+            BuildingType::Choir => SpriteSet::Simple(SingleSprite::Temple),
         }
     }
 }
@@ -180,6 +182,23 @@ impl WithSprite for CivilizationPerk {
     }
 }
 
+use paddlers_shared_lib::prelude::QuestStatType;
+impl WithSprite for QuestStatType {
+    fn sprite(&self) -> SpriteSet {
+        SpriteSet::Simple(match self {
+            QuestStatType::WelcomedVisitors => SingleSprite::DuckHappy,
+            QuestStatType::WorkerLevel => SingleSprite::Roger,
+            QuestStatType::SettledHobos => SingleSprite::SingleNest,
+            QuestStatType::SentInvitations => SingleSprite::PerkInvitation,
+            QuestStatType::PlacedBuildings => SingleSprite::Sapling,
+            QuestStatType::GatheredSticks => SingleSprite::Sticks,
+            QuestStatType::GatheredLogs => SingleSprite::Logs,
+            QuestStatType::GatheredFeathers => SingleSprite::Feathers,
+            QuestStatType::CompletedTasks => SingleSprite::NewOrder,
+        })
+    }
+}
+
 pub trait ISpriteIndex {
     fn default(&self) -> SpriteIndex;
     fn directed(&self, d: &Direction) -> (SpriteIndex, Transform);
@@ -12,16 +12,17 @@ pub mod frame_loading;
 pub mod specs_registration;
 
 use crate::game::player_info::PlayerInfo;
+use paddlers_shared_lib::story::story_graph::StoryGraph;
 use specs::prelude::*;
 use specs_registration::{insert_global_resources, register_global_components};
 
-pub(super) fn init_world(player_info: PlayerInfo) -> World {
+pub(super) fn init_world(player_info: PlayerInfo, story_graph: StoryGraph) -> World {
     let mut world = World::new();
 
     // Components
     register_global_components(&mut world);
 
     // Resources
-    insert_global_resources(&mut world, player_info);
+    insert_global_resources(&mut world, player_info, story_graph);
     world
 }
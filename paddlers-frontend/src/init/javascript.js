@@ -3,4 +3,7 @@ export function keycloak_token() {
 }
 export function keycloak_preferred_name() {
     return window.keycloak.tokenParsed.preferred_username;
+}
+export function keycloak_subject() {
+    return window.keycloak.subject;
 }
\ No newline at end of file
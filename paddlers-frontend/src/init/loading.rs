@@ -13,7 +13,8 @@ use paddle::{
     DisplayArea, ErrorMessage, Frame, Image, LoadScheduler, LoadedData, LoadingDoneMsg,
     LoadingProgressMsg, NutsCheck, TextBoard,
 };
-use paddlers_shared_lib::specification_types::sprite_paths::SPRITE_PATHS;
+use paddlers_shared_lib::specification_types::{sprite_paths::SPRITE_PATHS, VisitRewardTable};
+use paddlers_shared_lib::story::story_graph::StoryGraph;
 use wasm_bindgen::JsCast;
 use web_sys::HtmlCanvasElement;
 
@@ -51,6 +52,8 @@ pub struct GameLoadingData {
     pub hobos_response: HobosQueryResponse,
     pub attacking_hobos: AttacksResponse,
     pub village_info: VolatileVillageInfoResponse,
+    pub visit_rewards: VisitRewardTable,
+    pub story_graph: StoryGraph,
 }
 
 impl LoadingFrame {
@@ -106,11 +109,15 @@ impl LoadingFrame {
         }
         let animations = start_loading_animations();
         let locale = start_loading_locale();
+        let visit_rewards = start_loading_visit_rewards();
+        let story_graph = start_loading_story_graph();
 
         let mut load_manager = LoadScheduler::new()
             .with_vec(images, "Drawing visuals for the game")
             .with_vec(animations, "Animating fellow Paddlers")
             .with(locale, "Writing localized texts")
+            .with(visit_rewards, "Learning what visitors give back")
+            .with(story_graph, "Writing the story")
             .with_manually_reported::<PlayerInfo>("Checking out player")
             .with_manually_reported::<WorkerResponse>("Summon working Paddlers")
             .with_manually_reported::<BuildingsResponse>("Construct buildings")
@@ -237,6 +244,18 @@ async fn start_loading_locale() -> PadlResult<TextDb> {
     Ok(tdb)
 }
 
+async fn start_loading_visit_rewards() -> PadlResult<VisitRewardTable> {
+    let binary = paddle::load_file("visit_rewards.ron").await?;
+    ron::de::from_bytes(&binary)
+        .map_err(|parser_error| PadlError::dev_err(PadlErrorCode::RonParseError(parser_error)))
+}
+
+async fn start_loading_story_graph() -> PadlResult<StoryGraph> {
+    let binary = paddle::load_file("story.ron").await?;
+    ron::de::from_bytes(&binary)
+        .map_err(|parser_error| PadlError::dev_err(PadlErrorCode::RonParseError(parser_error)))
+}
+
 const PROGRESS_BAR_AREA_Y: f32 = 667.4;
 const PROGRESS_BAR_AREA_H: f32 = 200.0;
 
@@ -263,6 +282,8 @@ impl GameLoadingData {
             hobos_response: *loaded_data.extract()?,
             attacking_hobos: *loaded_data.extract()?,
             village_info: *loaded_data.extract()?,
+            visit_rewards: (*loaded_data.extract::<PadlResult<VisitRewardTable>>()?)?,
+            story_graph: (*loaded_data.extract::<PadlResult<StoryGraph>>()?)?,
         })
     }
 }
@@ -1,6 +1,6 @@
 use crate::game::{
     components::*,
-    fight::Aura,
+    fight::{Aura, Cheering, Defending},
     player_info::{PlayerInfo, PlayerState},
     story::entity_trigger::EntityTrigger,
     town::DefaultShop,
@@ -15,13 +15,19 @@ use crate::game::{
 use crate::gui::ui_state::*;
 use crate::view::entry_view;
 use paddle::utc_now;
+use paddlers_shared_lib::story::story_graph::StoryGraph;
 use specs::prelude::*;
 
-pub(super) fn insert_global_resources(world: &mut World, player_info: PlayerInfo) {
+pub(super) fn insert_global_resources(
+    world: &mut World,
+    player_info: PlayerInfo,
+    story_graph: StoryGraph,
+) {
     world.insert(ClockTick(0));
     world.insert(Now(utc_now()));
     world.insert(UiState::new());
-    let view = entry_view(player_info.story_state());
+    let view = entry_view(&story_graph, &player_info.story_state());
+    world.insert(story_graph);
     let mut player_state = PlayerState::default();
     player_state.info = Some(player_info);
     world.insert(player_state);
@@ -57,6 +63,8 @@ pub fn insert_town_resources(world: &mut World, player_info: PlayerInfo, town: T
 pub fn register_town_components(world: &mut World) {
     world.register::<Aura>();
     world.register::<Building>();
+    world.register::<Cheering>();
+    world.register::<Defending>();
     world.register::<EntityContainer>();
     world.register::<ForestComponent>();
     world.register::<Health>();
@@ -1,5 +1,7 @@
 use js_sys::Object;
 use paddle::{ErrorMessage, JsError};
+use paddlers_shared_lib::api::error::{GameMasterError, GameMasterErrorBody};
+use paddlers_shared_lib::api::tasks::TaskError;
 use paddlers_shared_lib::prelude::PadlApiError;
 use wasm_bindgen::JsValue;
 
@@ -35,6 +37,44 @@ impl PadlError {
     pub fn dev_err(err: PadlErrorCode) -> PadlError {
         PadlError::new(err, ErrorChannel::Technical)
     }
+    /// Interprets the body of an error response from the game-master.
+    /// Errors caused by the game rules are shown to the player, all others are only logged.
+    pub fn from_game_master_response(body: String) -> PadlError {
+        let error = match serde_json::from_str::<GameMasterErrorBody>(&body) {
+            Ok(parsed) => parsed.error,
+            Err(_) => return PadlError::dev_err(PadlErrorCode::RestAPI(body)),
+        };
+        match error {
+            GameMasterError::TaskRejected(e) => e.into(),
+            GameMasterError::NotEnoughResources(_)
+            | GameMasterError::NotEnoughKarma
+            | GameMasterError::Locked
+            | GameMasterError::SpaceOccupied
+            | GameMasterError::CannotBuildHere
+            | GameMasterError::UnitBlocksSpace
+            | GameMasterError::CannotBeDeleted
+            | GameMasterError::CannotBeUpgraded
+            | GameMasterError::UnexpectedBuildingLevel { .. }
+            | GameMasterError::NotEnoughMana
+            | GameMasterError::DefendOffLane
+            | GameMasterError::CannotInterruptTask
+            | GameMasterError::VisitorQueueFull
+            | GameMasterError::HoboAlreadyVisiting
+            | GameMasterError::QuestIncomplete { .. } => {
+                PadlError::user_err(PadlErrorCode::GameMaster(error))
+            }
+            _ => PadlError::dev_err(PadlErrorCode::GameMaster(error)),
+        }
+    }
+    /// Key of a translation for the error message, if there is one
+    pub fn message_key(&self) -> Option<&'static str> {
+        match &self.err {
+            PadlErrorCode::GameMaster(e) => Some(e.message_key()),
+            PadlErrorCode::TaskRejected(e) => Some(e.message_key()),
+            PadlErrorCode::ActionDropped => Some("err-action-dropped"),
+            _ => None,
+        }
+    }
 }
 
 impl std::error::Error for PadlError {}
@@ -68,8 +108,13 @@ pub enum PadlErrorCode {
     NotReadyYet,
     PathBlocked,
     NoNetwork,
+    /// Sending an action has been given up after repeated failures
+    ActionDropped,
     NestEmpty,
     AbilityLocked,
+    TaskRejected(TaskError),
+    GameMaster(GameMasterError),
+    ClientOutdated,
     // Dev only
     DevMsg(&'static str),
     MapOverflow(TileIndex),
@@ -119,10 +164,19 @@ impl fmt::Display for PadlErrorCode {
             PadlErrorCode::NotEnoughUnits => write!(f, "Require more units."),
             PadlErrorCode::PathBlocked => write!(f, "The path is blocked."),
             PadlErrorCode::NoNetwork => write!(f, "Connection to server dropped."),
+            PadlErrorCode::ActionDropped => {
+                write!(f, "The action could not be sent to the server.")
+            }
             PadlErrorCode::NestEmpty => write!(f, "Nobody around to invite."),
             PadlErrorCode::AbilityLocked => {
                 write!(f, "Your Paddlers have not learned to do this, yet.")
             }
+            PadlErrorCode::TaskRejected(e) => write!(f, "{}", e),
+            PadlErrorCode::GameMaster(e) => write!(f, "{}", e),
+            PadlErrorCode::ClientOutdated => write!(
+                f,
+                "A new version of Paddlers is available. Please reload the page."
+            ),
             // Dev
             PadlErrorCode::DevMsg(msg) => write!(f, "Dev Error Msg: {}", msg),
             PadlErrorCode::MapOverflow(i) => write!(f, "Index is outside the map: {:?}", i),
@@ -239,3 +293,9 @@ impl From<paddlers_shared_lib::game_mechanics::town::TownError> for PadlError {
         }
     }
 }
+
+impl From<TaskError> for PadlError {
+    fn from(error: TaskError) -> Self {
+        PadlError::user_err(PadlErrorCode::TaskRejected(error))
+    }
+}
@@ -1,4 +1,6 @@
 pub mod error;
+use crate::game::Game;
+use crate::prelude::TextDb;
 use error::*;
 use paddle::*;
 use std::collections::VecDeque;
@@ -8,12 +10,15 @@ struct ErrorQueue {
 }
 
 /// Set up an error queue activity running in the background that displays any published PadlError objects.
+///
+/// Once the game has been loaded, messages of errors that have a translation are shown in the language of the player.
 pub fn init_error_handling() {
     let errq = ErrorQueue::new();
-    let errq_id = nuts::new_activity(errq);
-    errq_id.subscribe(|q, err: &PadlError| {
-        q.route_err(err);
-        q.run();
+    let errq_id = nuts::new_domained_activity(errq, &Domain::Frame);
+    errq_id.subscribe_domained(|q, domain, err: &PadlError| {
+        let locale = domain.try_get_mut::<Game>().map(|game| &game.locale);
+        q.route_err(err, locale);
+        q.run(locale);
     });
 }
 
@@ -23,15 +28,15 @@ impl ErrorQueue {
             queue: VecDeque::new(),
         }
     }
-    fn run(&mut self) {
+    fn run(&mut self, locale: Option<&TextDb>) {
         while let Some(e) = self.queue().pop_front() {
-            self.route_err(&e);
+            self.route_err(&e, locale);
         }
     }
     fn queue(&mut self) -> &mut VecDeque<PadlError> {
         &mut self.queue
     }
-    fn route_err(&self, e: &PadlError) {
+    fn route_err(&self, e: &PadlError, locale: Option<&TextDb>) {
         let err = match e.channel {
             ErrorChannel::Technical => {
                 paddle::println!("Error: {}", e);
@@ -41,7 +46,12 @@ impl ErrorQueue {
                 let err = Ok(());
                 err
             }
-            ErrorChannel::UserFacing => TextBoard::display_error_message(format!("{}", e)),
+            ErrorChannel::UserFacing => match (e.message_key(), locale) {
+                (Some(key), Some(locale)) => {
+                    TextBoard::display_error_message(locale.gettext(key).to_owned())
+                }
+                _ => TextBoard::display_error_message(format!("{}", e)),
+            },
         };
         if let Err(err) = err {
             paddle::println!("Failed to display error. Reason of failure: {:?}", err);
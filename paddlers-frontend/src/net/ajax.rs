@@ -1,4 +1,6 @@
 use crate::prelude::*;
+use graphql_client::QueryBody;
+use paddlers_shared_lib::api::{persisted_queries::*, IDEMPOTENCY_KEY_HEADER};
 use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{Request, RequestInit, RequestMode, Response};
@@ -34,8 +36,8 @@ pub fn fetch_json<I: serde::Serialize + ?Sized, O: for<'de> serde::Deserialize<'
             let data: O = json.into_serde()?;
             Ok(data)
         } else {
-            let _status_code = resp.status();
-            PadlErrorCode::RestAPI(JsFuture::from(resp.text()?).await?.as_string().unwrap()).dev()
+            let body = JsFuture::from(resp.text()?).await?.as_string().unwrap();
+            Err(PadlError::from_game_master_response(body))
         }
     }
 }
@@ -65,26 +67,84 @@ pub fn fetch_empty_response<I: serde::Serialize + ?Sized>(
         if resp.ok() {
             Ok(())
         } else {
-            let _status_code = resp.status();
-            PadlErrorCode::RestAPI(JsFuture::from(resp.text()?).await?.as_string().unwrap()).dev()
+            let body = JsFuture::from(resp.text()?).await?.as_string().unwrap();
+            Err(PadlError::from_game_master_response(body))
         }
     }
 }
 
-pub fn gql_query<I: serde::Serialize + ?Sized, O: for<'de> serde::Deserialize<'de>>(
+/// Outcome of a request that is safe to repeat
+pub enum Delivery {
+    /// Body of a successful response
+    Delivered(String),
+    /// The server refused the request, repeating it would not help
+    Rejected(PadlError),
+    /// The server could not be reached or failed temporarily
+    Failed(PadlError),
+    /// The authentication token was not accepted, the request can be repeated once the token has been renewed
+    Unauthorized,
+}
+
+/// Sends a POST request with a JSON body that the server recognizes by its idempotency key when it is repeated.
+pub async fn send_idempotent(uri: String, body: String, idempotency_key: String) -> Delivery {
+    let mut opts = RequestInit::new();
+    opts.method("POST");
+    opts.mode(RequestMode::Cors);
+    opts.body(Some(&JsValue::from_str(&body)));
+    let response = async {
+        let request = Request::new_with_str_and_init(&uri, &opts)?;
+        let headers = request.headers();
+        headers.set("Content-Type", "application/json;charset=UTF-8")?;
+        headers.set("Authorization", &keycloak_token())?;
+        headers.set(IDEMPOTENCY_KEY_HEADER, &idempotency_key)?;
+        let window = web_sys::window().unwrap();
+        let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
+        let resp: Response = resp_value.dyn_into().unwrap();
+        let text = JsFuture::from(resp.text()?)
+            .await?
+            .as_string()
+            .unwrap_or_default();
+        Ok((resp.status(), text))
+    };
+    match response.await {
+        // Fetch only fails if the server is unreachable
+        Err(e) => Delivery::Failed(e),
+        Ok((status, text)) if status < 300 => Delivery::Delivered(text),
+        Ok((401, _)) => Delivery::Unauthorized,
+        // The same request is still being processed, the body is a structured `RequestInProgress` error
+        Ok((409, text)) => Delivery::Failed(PadlError::from_game_master_response(text)),
+        Ok((status, text)) if status >= 500 || status == 408 || status == 429 => {
+            Delivery::Failed(PadlError::dev_err(PadlErrorCode::RestAPI(text)))
+        }
+        Ok((_, text)) => Delivery::Rejected(PadlError::from_game_master_response(text)),
+    }
+}
+
+/// Sends a query from api/queries by its persisted ID, rather than the full query text
+pub fn gql_query<V: serde::Serialize, O: for<'de> serde::Deserialize<'de>>(
     uri: &str,
-    request_body: &I,
+    query: &QueryBody<V>,
 ) -> impl std::future::Future<Output = PadlResult<O>> {
+    let request_body = persisted_query_by_name(query.operation_name).map(|persisted| {
+        serde_json::to_string(&PersistedQueryRequest {
+            id: persisted.id.to_owned(),
+            operation_name: Some(query.operation_name.to_owned()),
+            variables: &query.variables,
+            schema_version: SCHEMA_VERSION.to_owned(),
+        })
+        .unwrap()
+    });
     let mut opts = RequestInit::new();
     opts.method("POST");
     opts.mode(RequestMode::Cors);
-    opts.body(Some(&JsValue::from_str(
-        &serde_json::to_string(&request_body).unwrap(),
-    )));
+    opts.body(request_body.as_deref().map(JsValue::from_str).as_ref());
 
     let request = Request::new_with_str_and_init(&uri, &opts);
 
-    async {
+    async move {
+        if request_body.is_none() {
+            return PadlErrorCode::DevMsg("Query is not persisted").dev();
+        }
         let request = request?;
         let headers = request.headers();
         headers.set("Content-Type", "application/json;charset=UTF-8")?;
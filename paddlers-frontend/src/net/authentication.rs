@@ -6,4 +6,6 @@ use wasm_bindgen::prelude::wasm_bindgen;
 extern "C" {
     pub fn keycloak_token() -> String;
     pub fn keycloak_preferred_name() -> Option<String>;
+    /// UUID of the player
+    pub fn keycloak_subject() -> Option<String>;
 }
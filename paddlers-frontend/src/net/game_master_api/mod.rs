@@ -1,9 +1,11 @@
+mod request_queue;
+
 use super::{
     ajax,
     authentication::keycloak_preferred_name,
     graphql::{ForceRequest, PeriodicalSyncRequest},
     url::*,
-    RequestHobos, RequestQuests,
+    NetworkUpdate, RequestHobos, RequestQuests,
 };
 use crate::{
     game::{components::NetObj, game_event_manager::game_event},
@@ -15,6 +17,7 @@ use paddlers_shared_lib::api::{
 };
 use paddlers_shared_lib::api::{hobo::SettleHobo, story::StoryStateTransition};
 use paddlers_shared_lib::api::{quests::QuestCollect, reports::ReportCollect};
+use request_queue::{DeliveryReport, RequestQueue};
 use specs::Entity;
 use std::sync::atomic::AtomicBool;
 
@@ -34,6 +37,8 @@ pub enum GameMasterResponse {
 
 pub struct RestApiState {
     pub game_master_url: String,
+    /// Player actions that have not been confirmed by the game-master, yet
+    requests: RequestQueue,
 }
 pub struct HttpCreatePlayer;
 pub struct HttpDeleteBuilding {
@@ -59,6 +64,7 @@ impl RestApiState {
     pub fn init() {
         let rest = RestApiState {
             game_master_url: game_master_url().nuts_check().unwrap_or_default(),
+            requests: RequestQueue::restore(),
         };
         let rest_activity = nuts::new_domained_activity(rest, &Domain::Network);
         rest_activity.private_channel(Self::http_buy_prophet);
@@ -76,6 +82,8 @@ impl RestApiState {
         rest_activity.private_channel(Self::http_send_statistics);
         rest_activity.private_channel(Self::http_update_story_state);
         rest_activity.private_channel(Self::http_settle_hobo);
+        rest_activity.private_channel(Self::delivery_report);
+        rest_activity.subscribe(Self::retry);
     }
     pub fn http_place_building(
         pos: (usize, usize),
@@ -99,14 +107,12 @@ impl RestApiState {
             y: input.pos.1,
             village: input.village,
         };
-        let uri = self.game_master_url.clone() + "/shop/building";
-        let future = async move {
-            let id: i64 = ajax::fetch_json("POST", &uri, &msg).await?;
+        self.send("/shop/building", &msg, move |response| {
+            let id: i64 = serde_json::from_str(&response?)?;
             let net_obj = NetObj::building(id);
             game_event(GameEvent::NetObjId(entity, net_obj));
             Ok(())
-        };
-        spawn_future(future);
+        });
     }
 
     fn http_delete_building(&mut self, input: HttpDeleteBuilding) {
@@ -115,49 +121,41 @@ impl RestApiState {
             y: input.idx.1,
             village: input.village,
         };
-        let future = ajax::fetch_json(
-            "POST",
-            &format!("{}/shop/building/delete", self.game_master_url),
-            &msg,
-        );
-        spawn_future(future);
+        self.send("/shop/building/delete", &msg, ignore_body);
     }
 
     fn http_upgrade_building(&mut self, input: HttpUpgradeBuilding) {
-        let uri = self.game_master_url.clone() + "/shop/building/upgrade";
+        let entity = input.entity;
+        let new_level = input.current_level + 1;
         let msg = BuildingUpgrade {
             building: input.building,
             current_level: input.current_level,
         };
-        let future = async move {
-            ajax::fetch_empty_response("POST", &uri, &msg).await?;
+        self.send("/shop/building/upgrade", &msg, move |response| {
+            response?;
             game_event(GameEvent::GameMasterResponse(
-                GameMasterResponse::NewBuildingLevel(input.entity, input.current_level + 1),
+                GameMasterResponse::NewBuildingLevel(entity, new_level),
             ));
             Ok(())
-        };
-        spawn_future(future);
+        });
     }
 
     fn http_buy_prophet(&mut self, msg: ProphetPurchase) {
-        let uri = self.game_master_url.clone() + "/shop/unit/prophet";
-        let future = async move {
-            ajax::fetch_json("POST", &uri, &msg).await?;
+        self.send("/shop/unit/prophet", &msg, |response| {
+            response?;
             nuts::publish(ForceRequest::SyncAsap(PeriodicalSyncRequest::PlayerInfo));
             // TODO: Also update hobos afterwards, not only player info...
             Ok(())
-        };
-        spawn_future(future);
+        });
     }
 
     fn http_overwrite_tasks(&mut self, msg: TaskList) {
-        let uri = self.game_master_url.clone() + "/worker/overwriteTasks";
-        let future = async move {
-            ajax::fetch_empty_response("POST", &uri, &msg).await?;
-            crate::net::request_worker_tasks_update(msg.worker_id.num());
+        let worker_id = msg.worker_id.num();
+        self.send("/worker/overwriteTasks", &msg, move |response| {
+            response?;
+            crate::net::request_worker_tasks_update(worker_id);
             Ok(())
-        };
-        spawn_future(future);
+        });
     }
 
     pub fn http_send_statistics(&mut self, msg: FrontendRuntimeStatistics) {
@@ -169,7 +167,12 @@ impl RestApiState {
         if !SENT_PLAYER_CREATION.load(std::sync::atomic::Ordering::Relaxed) {
             let display_name = keycloak_preferred_name().unwrap_or("Unnamed Player".to_owned());
             let uri = self.game_master_url.clone() + "/player/create";
-            let msg = PlayerInitData { display_name };
+            // JS returns the offset from local time to UTC
+            let utc_offset_minutes = -js_sys::Date::new_0().get_timezone_offset() as i32;
+            let msg = PlayerInitData {
+                display_name,
+                utc_offset_minutes,
+            };
             let future = async move {
                 ajax::fetch_empty_response("POST", &uri, &msg).await?;
                 crate::net::request_client_state();
@@ -181,80 +184,70 @@ impl RestApiState {
     }
 
     fn http_send_attack(&mut self, msg: AttackDescriptor) {
-        let future = ajax::fetch_json(
-            "POST",
-            &format!("{}/attacks/create", self.game_master_url),
-            &msg,
-        );
-        spawn_future(future);
+        self.send("/attacks/create", &msg, ignore_body);
     }
 
     fn http_invite(&mut self, msg: InvitationDescriptor) {
-        let future = ajax::fetch_json(
-            "POST",
-            &format!("{}/attacks/invite", self.game_master_url),
-            &msg,
-        );
-        spawn_future(future);
+        self.send("/attacks/invite", &msg, ignore_body);
     }
 
     fn http_let_visitor_in(&mut self, msg: StartFightRequest) {
-        let future = ajax::fetch_empty_response(
-            "POST",
-            &format!("{}/attacks/startFight", self.game_master_url),
-            &msg,
-        );
-        spawn_future(future);
+        self.send("/attacks/startFight", &msg, ignore_body);
     }
 
     fn http_notify_visitor_satisfied(&mut self, msg: HttpNotifyVisitorSatisfied) {
-        let future = ajax::fetch_json(
-            "POST",
-            &format!(
-                "{}/attacks/notifications/visitor_satisfied",
-                &self.game_master_url
-            ),
+        self.send(
+            "/attacks/notifications/visitor_satisfied",
             &msg.hobo,
+            ignore_body,
         );
-        spawn_future(future);
     }
 
     fn http_update_story_state(&mut self, msg: StoryStateTransition) {
-        let future = ajax::fetch_empty_response(
-            "POST",
-            &format!("{}/story/transition", &self.game_master_url),
-            &msg,
-        );
-        spawn_future(future);
+        self.send("/story/transition", &msg, ignore_body);
     }
 
     fn http_collect_reward(&mut self, msg: ReportCollect) {
-        let future = ajax::fetch_json(
-            "POST",
-            &format!("{}/report/collect", &self.game_master_url),
-            &msg,
-        );
-        spawn_future(future);
+        self.send("/report/collect", &msg, ignore_body);
     }
 
     fn http_collect_quest(&mut self, msg: QuestCollect) {
-        let uri = format!("{}/quest/collect", &self.game_master_url);
-        let future = async move {
-            ajax::fetch_empty_response("POST", &uri, &msg).await?;
+        self.send("/quest/collect", &msg, |response| {
+            response?;
             nuts::publish(RequestQuests);
             Ok(())
-        };
-        spawn_future(future);
+        });
     }
     fn http_settle_hobo(&mut self, msg: SettleHobo) {
-        let uri = format!("{}/hobo/settle", &self.game_master_url);
-        let future = async move {
-            ajax::fetch_empty_response("POST", &uri, &msg).await?;
+        self.send("/hobo/settle", &msg, |response| {
+            response?;
             nuts::publish(RequestHobos);
             Ok(())
-        };
-        spawn_future(future);
+        });
+    }
+
+    /// Queues a player action, to be delivered to the game-master even if the connection is temporarily lost
+    fn send<I: serde::Serialize + ?Sized>(
+        &mut self,
+        path: &str,
+        msg: &I,
+        on_response: impl FnOnce(PadlResult<String>) -> PadlResult<()> + 'static,
+    ) {
+        let uri = self.game_master_url.clone() + path;
+        self.requests
+            .push(uri, msg, Box::new(on_response))
+            .nuts_check();
     }
+    fn delivery_report(&mut self, report: DeliveryReport) {
+        self.requests.report(report);
+    }
+    fn retry(&mut self, _: &NetworkUpdate) {
+        self.requests.send_next();
+    }
+}
+
+fn ignore_body(response: PadlResult<String>) -> PadlResult<()> {
+    response.map(|_| ())
 }
 
 fn spawn_future(future: impl std::future::Future<Output = PadlResult<()>> + 'static) {
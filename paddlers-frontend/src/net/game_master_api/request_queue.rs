@@ -0,0 +1,215 @@
+//! Queue for player actions sent to the game-master that survives lost connections.
+//!
+//! Requests are sent one at a time, in the order the player issued them.
+//! A request that fails for reasons unrelated to its content (no network, server errors) stays at the front of the queue and is retried with an exponential backoff.
+//! After `MAX_ATTEMPTS` failed attempts, the request is dropped and its handler receives the error.
+//! Each request carries an idempotency key, thus the game-master can recognize a repetition of a request it has already applied but whose response got lost.
+//!
+//! Pending requests are also kept in the local storage of the browser and are sent again after a reload.
+//! Their response handlers are gone by then, therefore the full client state is synchronized once the queue has drained.
+//! Only requests of the same player that are young enough for the game-master to still know their idempotency key are restored.
+
+use super::RestApiState;
+use crate::net::ajax::{self, Delivery};
+use crate::net::authentication::keycloak_subject;
+use crate::prelude::*;
+use paddle::NutsCheck;
+use paddlers_shared_lib::api::{error::GameMasterError, IDEMPOTENCY_KEY_TTL_SECS};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+const STORAGE_KEY: &str = "paddlers.outgoing_requests";
+const FIRST_RETRY_DELAY_MS: f64 = 1_000.0;
+const MAX_RETRY_DELAY_MS: f64 = 60_000.0;
+/// With the backoff above, requests are given up after about two minutes
+const MAX_ATTEMPTS: u32 = 8;
+
+/// Handles the body of the response to a request, or the error if the game-master rejected it
+pub type ResponseHandler = Box<dyn FnOnce(PadlResult<String>) -> PadlResult<()>>;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct StoredRequest {
+    idempotency_key: String,
+    uri: String,
+    body: String,
+    /// UUID of the player who issued the request
+    player: String,
+    /// Timestamp in ms
+    created: f64,
+}
+
+struct OutgoingRequest {
+    request: StoredRequest,
+    /// None for requests restored from the local storage
+    on_response: Option<ResponseHandler>,
+}
+
+/// Sent back to the RestApiState once the request at the front of the queue has been answered
+pub struct DeliveryReport(Delivery);
+
+pub struct RequestQueue {
+    queue: VecDeque<OutgoingRequest>,
+    in_flight: bool,
+    /// Consecutive failed attempts to send the request at the front of the queue
+    failures: u32,
+    /// Timestamp in ms before which no new attempt is made
+    next_attempt: f64,
+    /// Set when the client state may have diverged from the server, e.g. when requests got lost or have been restored
+    needs_reconciliation: bool,
+}
+
+impl RequestQueue {
+    /// Creates the queue with the requests left over from the last session
+    pub fn restore() -> Self {
+        let player = keycloak_subject();
+        let oldest = js_sys::Date::now() - IDEMPOTENCY_KEY_TTL_SECS as f64 * 1000.0;
+        let queue: VecDeque<OutgoingRequest> = load_stored_requests()
+            .into_iter()
+            .filter(|request| player.as_ref() == Some(&request.player) && request.created > oldest)
+            .map(|request| OutgoingRequest {
+                request,
+                on_response: None,
+            })
+            .collect();
+        let queue = RequestQueue {
+            needs_reconciliation: !queue.is_empty(),
+            queue,
+            in_flight: false,
+            failures: 0,
+            next_attempt: 0.0,
+        };
+        // Dropped requests must not be restored in a later session
+        queue.store().nuts_check();
+        queue
+    }
+
+    pub fn push<I: Serialize + ?Sized>(
+        &mut self,
+        uri: String,
+        request_body: &I,
+        on_response: ResponseHandler,
+    ) -> PadlResult<()> {
+        let request = StoredRequest {
+            idempotency_key: new_idempotency_key(),
+            uri,
+            body: serde_json::to_string(request_body)?,
+            player: keycloak_subject().unwrap_or_default(),
+            created: js_sys::Date::now(),
+        };
+        self.queue.push_back(OutgoingRequest {
+            request,
+            on_response: Some(on_response),
+        });
+        self.store()?;
+        self.send_next();
+        Ok(())
+    }
+
+    /// Sends the request at the front of the queue, unless another request is still awaiting a response or a retry is not due, yet.
+    pub fn send_next(&mut self) {
+        if self.in_flight || js_sys::Date::now() < self.next_attempt {
+            return;
+        }
+        if let Some(next) = self.queue.front() {
+            self.in_flight = true;
+            let StoredRequest {
+                idempotency_key,
+                uri,
+                body,
+                ..
+            } = next.request.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let delivery = ajax::send_idempotent(uri, body, idempotency_key).await;
+                nuts::send_to::<RestApiState, _>(DeliveryReport(delivery));
+            });
+        }
+    }
+
+    pub fn report(&mut self, DeliveryReport(delivery): DeliveryReport) {
+        self.in_flight = false;
+        let response = match delivery {
+            Delivery::Delivered(body) => Ok(body),
+            Delivery::Rejected(err) => Err(err),
+            Delivery::Failed(err) => {
+                let reason = match &err.err {
+                    PadlErrorCode::GameMaster(e @ GameMasterError::RequestInProgress) => {
+                        PadlErrorCode::GameMaster(e.clone())
+                    }
+                    _ => PadlErrorCode::NoNetwork,
+                };
+                if self.retry_later(reason) {
+                    return;
+                }
+                Err(err)
+            }
+            // The player should not lose the action because a token expired
+            Delivery::Unauthorized => {
+                if self.retry_later(PadlErrorCode::GameMaster(GameMasterError::NotAuthenticated)) {
+                    return;
+                }
+                Err(PadlError::dev_err(PadlErrorCode::GameMaster(
+                    GameMasterError::NotAuthenticated,
+                )))
+            }
+        };
+        self.failures = 0;
+        self.next_attempt = 0.0;
+        if let Some(request) = self.queue.pop_front() {
+            self.store().nuts_check();
+            match request.on_response {
+                Some(handler) => handler(response).nuts_check(),
+                None => response.map(|_| ()).nuts_check(),
+            };
+        }
+        if self.queue.is_empty() && self.needs_reconciliation {
+            self.needs_reconciliation = false;
+            crate::net::request_client_state();
+        }
+        self.send_next();
+    }
+
+    /// Keeps the request at the front of the queue and tells the player why it is delayed.
+    /// Returns false if the request has failed too often and has to be dropped.
+    fn retry_later(&mut self, reason: PadlErrorCode) -> bool {
+        self.failures += 1;
+        // Whether the game-master applied the request is unknown either way
+        self.needs_reconciliation = true;
+        if self.failures >= MAX_ATTEMPTS {
+            nuts::publish(PadlError::user_err(PadlErrorCode::ActionDropped));
+            return false;
+        }
+        let backoff = FIRST_RETRY_DELAY_MS * 2f64.powi(self.failures as i32 - 1);
+        self.next_attempt = js_sys::Date::now() + backoff.min(MAX_RETRY_DELAY_MS);
+        if self.failures == 1 {
+            nuts::publish(PadlError::user_err(reason));
+        }
+        true
+    }
+
+    fn store(&self) -> PadlResult<()> {
+        if let Some(storage) = local_storage() {
+            let requests: Vec<&StoredRequest> = self.queue.iter().map(|r| &r.request).collect();
+            storage.set_item(STORAGE_KEY, &serde_json::to_string(&requests)?)?;
+        }
+        Ok(())
+    }
+}
+
+fn load_stored_requests() -> Vec<StoredRequest> {
+    local_storage()
+        .and_then(|storage| storage.get_item(STORAGE_KEY).ok().flatten())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+fn new_idempotency_key() -> String {
+    format!(
+        "{:x}-{:08x}",
+        js_sys::Date::now() as u64,
+        (js_sys::Math::random() * u32::MAX as f64) as u32
+    )
+}
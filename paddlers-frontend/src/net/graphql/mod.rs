@@ -10,36 +10,58 @@ mod response;
 mod sync;
 pub use sync::{ForceRequest, PeriodicalSyncRequest, ScheduledRequest, SyncState};
 
-use super::{NetMsg, NewAttackId, NewReportId};
+use super::{NetMsg, NewAttackId, NewReportCursors};
 use http_calls::*;
 pub use query_types::*;
 pub(crate) use response::gql_extract_data;
 
 pub struct GraphQlState {
     next_attack_id: i64,
-    next_report_id: i64,
+    /// Cursor of the newest report loaded so far
+    newest_report: Option<String>,
+    /// Cursor of the oldest report loaded so far
+    oldest_report: Option<String>,
 }
 
+/// Selects a page of a list, relative to a cursor from a previous response
+#[derive(Clone, Debug)]
+pub enum PageRequest {
+    First,
+    After(String),
+    Before(String),
+}
+
+/// Number of reports loaded per request
+const REPORTS_PAGE_SIZE: i64 = 20;
+
 impl GraphQlState {
     pub(super) const fn new() -> GraphQlState {
         GraphQlState {
             next_attack_id: 0,
-            next_report_id: 0,
+            newest_report: None,
+            oldest_report: None,
         }
     }
 
     pub(super) fn update_attack_id(&mut self, id: i64) {
         self.next_attack_id = self.next_attack_id.max(id + 1);
     }
-    pub(super) fn update_report_id(&mut self, id: i64) {
-        self.next_report_id = self.next_report_id.max(id + 1);
+    pub(super) fn update_report_cursors(&mut self, msg: &NewReportCursors) {
+        if msg.newest.is_some() {
+            self.newest_report = msg.newest.clone();
+        }
+        if msg.oldest.is_some() {
+            self.oldest_report = msg.oldest.clone();
+        }
     }
 
     pub(super) fn attacks_query(&self) -> impl Future<Output = PadlResult<NetMsg>> {
         let next = self.next_attack_id;
         async move {
             let village = current_village_async().await?;
-            let response = http_read_incoming_attacks(Some(next), village).await?;
+            // All attacks are loaded, not only the new ones, because visitors in town compete for cheers.
+            // Finished attacks are deleted in the backend, so this stays a short list.
+            let response = http_read_incoming_attacks(None, village).await?;
             let max_id = response
                 .village
                 .attacks
@@ -47,7 +69,7 @@ impl GraphQlState {
                 .map(|atk| atk.id.parse().unwrap())
                 .fold(0, i64::max);
             nuts::publish(NewAttackId { id: max_id });
-            Ok(NetMsg::Attacks(response))
+            Ok(NetMsg::Attacks(response, next))
         }
     }
     pub(super) async fn resource_query() -> PadlResult<NetMsg> {
@@ -94,32 +116,38 @@ impl GraphQlState {
         Ok(NetMsg::Player(response.into()))
     }
 
-    pub async fn leaderboard_query(offset: i64, limit: i64) -> PadlResult<NetMsg> {
-        let response = http_read_leaderboard(offset, Some(limit)).await?;
-        Ok(NetMsg::Leaderboard(
-            offset as usize,
-            response
-                .players_by_karma
-                .into_iter()
-                .map(|player| (player.display_name, player.karma))
-                .collect(),
-            response.players_count as usize,
-        ))
+    pub async fn leaderboard_query(page: PageRequest, size: i64) -> PadlResult<NetMsg> {
+        let response = http_read_leaderboard(&page, size).await?;
+        Ok(NetMsg::Leaderboard(page, response))
     }
+    /// Reports newer than those loaded so far, newest first
     pub(super) fn reports_query(&self) -> impl Future<Output = PadlResult<NetMsg>> {
-        let report_id = self.next_report_id;
-        async move {
-            let village = current_village_async().await?;
-            let data: ReportsResponse = http_read_reports(Some(report_id), village).await?;
-            let max_id = data
-                .village
-                .reports
-                .iter()
-                .map(|r| r.id.parse().unwrap())
-                .fold(0, i64::max);
-            nuts::publish(NewReportId { id: max_id });
-            Ok(NetMsg::Reports(data))
-        }
+        // Reports are sorted newest first, thus newer reports are before the newest loaded report
+        let page = match &self.newest_report {
+            Some(cursor) => PageRequest::Before(cursor.clone()),
+            None => PageRequest::First,
+        };
+        Self::reports_page_query(page)
+    }
+    /// Reports older than those loaded so far
+    pub(super) fn older_reports_query(&self) -> impl Future<Output = PadlResult<NetMsg>> {
+        let page = match &self.oldest_report {
+            Some(cursor) => PageRequest::After(cursor.clone()),
+            None => PageRequest::First,
+        };
+        Self::reports_page_query(page)
+    }
+    async fn reports_page_query(page: PageRequest) -> PadlResult<NetMsg> {
+        let village = current_village_async().await?;
+        let data: ReportsResponse = http_read_reports(&page, REPORTS_PAGE_SIZE, village).await?;
+        let page_info = &data.village.reports_connection.page_info;
+        let (newest, oldest) = match page {
+            PageRequest::First => (page_info.start_cursor.clone(), page_info.end_cursor.clone()),
+            PageRequest::Before(_) => (page_info.start_cursor.clone(), None),
+            PageRequest::After(_) => (None, page_info.end_cursor.clone()),
+        };
+        nuts::publish(NewReportCursors { newest, oldest });
+        Ok(NetMsg::Reports(data))
     }
     pub async fn quests_query() -> PadlResult<NetMsg> {
         let response = http_read_quests().await?;
@@ -127,6 +155,17 @@ impl GraphQlState {
     }
 }
 
+impl PageRequest {
+    /// Pagination variables first, after, last and before, as used by all connections
+    fn variables(&self, size: i64) -> (Option<i64>, Option<String>, Option<i64>, Option<String>) {
+        match self {
+            PageRequest::First => (Some(size), None, None, None),
+            PageRequest::After(cursor) => (Some(size), Some(cursor.clone()), None, None),
+            PageRequest::Before(cursor) => (None, None, Some(size), Some(cursor.clone())),
+        }
+    }
+}
+
 pub async fn own_villages_query() -> PadlResult<Vec<VillageKey>> {
     let response = http_read_own_villages().await?;
     Ok(response
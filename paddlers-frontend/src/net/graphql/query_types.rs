@@ -5,7 +5,6 @@ use chrono::NaiveDateTime;
 use graphql_client::GraphQLQuery;
 use paddlers_shared_lib::graphql_types;
 use paddlers_shared_lib::models::*;
-use paddlers_shared_lib::story::story_state::StoryState;
 use specs::prelude::*;
 
 pub use serde::Deserialize;
@@ -22,7 +21,7 @@ fn timestamp(s: &String) -> graphql_types::GqlTimestamp {
 #[graphql(
     schema_path = "api/schema.json",
     query_path = "api/queries/attacks_query.graphql",
-    response_derives = "PartialEq",
+    response_derives = "PartialEq, Clone",
     extern_enums("UnitColor", "HoboAttributeType")
 )]
 pub struct AttacksQuery;
@@ -135,7 +134,7 @@ pub type HobosQueryUnitColor = hobos_query::UnitColor;
 #[graphql(
     schema_path = "api/schema.json",
     query_path = "api/queries/player_query.graphql",
-    extern_enums("StoryState")
+    extern_enums("BuildingType")
 )]
 pub struct PlayerQuery;
 pub type PlayerQueryRawResponse = player_query::ResponseData;
@@ -162,7 +161,7 @@ pub type PlayerVillagesResponse = player_villages_query::PlayerVillagesQueryPlay
 #[graphql(
     schema_path = "api/schema.json",
     query_path = "api/queries/player_quests_query.graphql",
-    extern_enums("BuildingType", "TaskType")
+    extern_enums("BuildingType", "TaskType", "QuestStatType")
 )]
 pub struct PlayerQuestsQuery;
 pub type QuestsRawResponse = player_quests_query::ResponseData;
@@ -176,7 +175,7 @@ pub type PlayerQuest = player_quests_query::PlayerQuestsQueryPlayerQuests;
 )]
 pub struct LeaderboardQuery;
 pub type LeaderboardRawResponse = leaderboard_query::ResponseData;
-pub type LeaderboardResponse = leaderboard_query::LeaderboardQueryScoreboard;
+pub type LeaderboardResponse = leaderboard_query::LeaderboardQueryScoreboardPlayersConnection;
 
 #[derive(GraphQLQuery)]
 #[graphql(
@@ -186,4 +185,4 @@ pub type LeaderboardResponse = leaderboard_query::LeaderboardQueryScoreboard;
 )]
 pub struct ReportsQuery;
 pub type ReportsResponse = reports_query::ResponseData;
-pub type ReportsResponseReport = reports_query::ReportsQueryVillageReports;
+pub type ReportsResponseReport = reports_query::ReportsQueryVillageReportsConnectionNodes;
@@ -72,6 +72,10 @@ pub fn gql_extract_data<DATA>(response: graphql_client::Response<DATA>) -> PadlR
                 error
             )))
         })?;
-        PadlErrorCode::GraphQlResponseError(code).dev()
+        match code {
+            // The player has to act, reloading fetches a client that matches the server
+            PadlApiError::ClientOutdated => PadlErrorCode::ClientOutdated.usr(),
+            _ => PadlErrorCode::GraphQlResponseError(code).dev(),
+        }
     }
 }
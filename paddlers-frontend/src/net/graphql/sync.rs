@@ -20,10 +20,20 @@ pub enum PeriodicalSyncRequest {
     /// The exact time when reports are generated are a bit unpredictable, thus it remains in here. Would be nice to remove it, though.
     Reports,
     /// May change from workers. Right now, this is not a client-known event.
+    /// The regular production is not pushed by the server, thus this is polled even while updates are pushed.
     Resources,
     /// For Karma changes, civ perks, etc. Could probably be removed here if all cases are handled properly.
     PlayerInfo,
 }
+impl PeriodicalSyncRequest {
+    pub fn all() -> &'static [Self] {
+        use PeriodicalSyncRequest::*;
+        &[Attacks, Reports, Resources, PlayerInfo]
+    }
+    fn polled_while_pushed(&self) -> bool {
+        *self == PeriodicalSyncRequest::Resources
+    }
+}
 /// For irregular requests that sometime need to be scheduled extra.
 /// They are queued and sent indirectly, just like forced sync requests, for two reasons.
 /// 1) Timing:
@@ -43,6 +53,8 @@ pub enum ScheduledRequest {
 pub struct SyncState {
     periodical: SyncElementList<PeriodicalSyncRequest>,
     scheduled: SyncElementList<ScheduledRequest>,
+    /// While the server pushes updates, most periodical requests are only sent when forced
+    pushed: bool,
 }
 struct SyncElementList<R: RequestDescriptor>(Vec<SyncElement<R>>);
 
@@ -57,8 +69,15 @@ impl SyncState {
                 SyncElement::new(PlayerInfo, 100),
             ]),
             scheduled: SyncElementList(Vec::new()),
+            pushed: false,
         }
     }
+    pub fn pushed(&self) -> bool {
+        self.pushed
+    }
+    pub fn set_pushed(&mut self, pushed: bool) {
+        self.pushed = pushed;
+    }
 }
 
 /// Maintains a counter after how many ticks a new request should be sent
@@ -92,10 +111,11 @@ impl NetState {
     pub fn sync_tick(&mut self) {
         // Periodical
         self.sync.periodical.send_due(&self);
+        let pushed = self.sync.pushed;
         for req in &mut self.sync.periodical.0 {
             if req.counter == 0 {
                 req.counter = req.reset_value;
-            } else {
+            } else if !pushed || req.request.polled_while_pushed() {
                 req.counter -= 1;
             }
         }
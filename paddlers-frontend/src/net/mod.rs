@@ -3,10 +3,12 @@ pub mod authentication;
 pub mod game_master_api;
 pub mod graphql;
 pub mod state;
+mod subscription;
 pub mod url;
 
 use crate::game::player_info::PlayerInfo;
 use game_master_api::RestApiState;
+pub use graphql::PageRequest;
 use graphql::{query_types::*, GraphQlState};
 use paddle::web_integration::*;
 use paddle::{Domain, NutsCheck};
@@ -14,15 +16,17 @@ use paddlers_shared_lib::prelude::VillageKey;
 use wasm_bindgen::prelude::*;
 
 use std::{future::Future, sync::mpsc::Sender};
+use subscription::LiveConnection;
 
 use crate::prelude::*;
 
 pub enum NetMsg {
-    Attacks(AttacksResponse),
+    /// All attacks on the village, with the smallest id of those not loaded before
+    Attacks(AttacksResponse, i64),
     Buildings(BuildingsResponse),
     Error(PadlError),
     Hobos(HobosQueryResponse, VillageKey),
-    Leaderboard(usize, Vec<(String, i64)>, usize),
+    Leaderboard(PageRequest, LeaderboardResponse),
     Map(MapResponse, i32, i32),
     Player(PlayerInfo),
     VillageInfo(VolatileVillageInfoResponse),
@@ -37,6 +41,12 @@ struct NetState {
     logged_in: bool,
     gql_state: GraphQlState,
     sync: graphql::SyncState,
+    /// Socket for updates pushed by the server, if connected
+    live: Option<LiveConnection>,
+    /// Network ticks until the next attempt to connect the socket
+    reconnect_counter: usize,
+    /// Network ticks since the socket received the last message
+    quiet_ticks: usize,
 }
 
 const NET_THREAD_TIMEOUT_MS: i32 = 100;
@@ -49,9 +59,10 @@ struct RequestResourceUpdate;
 struct LoggedIn;
 struct RequestQuests;
 struct RequestLeaderboard {
-    offset: i64,
-    limit: i64,
+    page: PageRequest,
+    size: i64,
 }
+struct RequestOlderReports;
 struct RequestMapRead {
     min: i32,
     max: i32,
@@ -68,8 +79,9 @@ struct RequestHobos;
 struct NewAttackId {
     id: i64,
 }
-struct NewReportId {
-    id: i64,
+struct NewReportCursors {
+    newest: Option<String>,
+    oldest: Option<String>,
 }
 
 /// Initializes state necessary for networking
@@ -105,8 +117,12 @@ pub fn request_resource_update() {
 pub fn request_player_update() {
     nuts::publish(RequestPlayerUpdate);
 }
-pub fn request_leaderboard(offset: i64, limit: i64) {
-    nuts::publish(RequestLeaderboard { offset, limit });
+pub fn request_leaderboard(page: PageRequest, size: i64) {
+    nuts::publish(RequestLeaderboard { page, size });
+}
+/// Loads reports older than those loaded so far, newer reports are synchronized automatically
+pub fn request_older_reports() {
+    nuts::publish(RequestOlderReports);
 }
 pub fn request_foreign_town(vid: VillageKey) {
     nuts::publish(RequestForeignTownUpdate { vid });
@@ -119,12 +135,16 @@ impl NetState {
             chan,
             gql_state: GraphQlState::new(),
             sync: graphql::SyncState::new(),
+            live: None,
+            reconnect_counter: 0,
+            quiet_ticks: 0,
         };
         let net_activity = nuts::new_domained_activity(ns, &Domain::Network);
         net_activity.subscribe(NetState::log_in);
         net_activity.subscribe(NetState::work);
         net_activity.subscribe(NetState::request_player_update);
         net_activity.subscribe(NetState::request_leaderboard);
+        net_activity.subscribe(NetState::request_older_reports);
         net_activity.subscribe(NetState::request_client_state);
         net_activity.subscribe(NetState::request_resource_update);
         net_activity.subscribe(NetState::request_map_read);
@@ -133,13 +153,16 @@ impl NetState {
         net_activity.subscribe(NetState::request_quests);
         net_activity.subscribe(NetState::request_hobos);
         net_activity.subscribe(NetState::update_attack_id);
-        net_activity.subscribe(NetState::update_report_id);
+        net_activity.subscribe(NetState::update_report_cursors);
         net_activity.subscribe(NetState::scheduled_update);
+        net_activity.subscribe(NetState::live_update);
+        net_activity.subscribe(NetState::live_connection_lost);
     }
 
     // For frequent updates.
     // This is called every time NetworkUpdate is being published, which happens every NET_THREAD_TIMEOUT_MS once loading has completed.
     fn work(&mut self, _: &NetworkUpdate) {
+        self.maintain_live_connection();
         self.sync_tick();
     }
 
@@ -185,7 +208,11 @@ impl NetState {
     }
 
     fn request_leaderboard(&mut self, msg: &RequestLeaderboard) {
-        self.transfer_response(GraphQlState::leaderboard_query(msg.offset, msg.limit));
+        self.transfer_response(GraphQlState::leaderboard_query(msg.page.clone(), msg.size));
+    }
+
+    fn request_older_reports(&mut self, _msg: &RequestOlderReports) {
+        self.transfer_response(self.gql_state.older_reports_query());
     }
 
     fn request_worker_tasks_update(&mut self, msg: &RequestWorkerTasksUpdate) {
@@ -215,8 +242,8 @@ impl NetState {
     fn update_attack_id(&mut self, msg: &NewAttackId) {
         self.gql_state.update_attack_id(msg.id);
     }
-    fn update_report_id(&mut self, msg: &NewReportId) {
-        self.gql_state.update_report_id(msg.id);
+    fn update_report_cursors(&mut self, msg: &NewReportCursors) {
+        self.gql_state.update_report_cursors(msg);
     }
 
     fn get_channel(&self) -> Sender<NetMsg> {
@@ -241,11 +268,11 @@ impl NetState {
 impl std::fmt::Debug for NetMsg {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Attacks(_) => write!(f, "NetMsg: Attacks"),
+            Self::Attacks(_, _) => write!(f, "NetMsg: Attacks"),
             Self::Buildings(_) => write!(f, "NetMsg: Buildings"),
             Self::Error(_) => write!(f, "NetMsg: Error"),
             Self::Hobos(_, _) => write!(f, "NetMsg: Hobos"),
-            Self::Leaderboard(_, _, _) => write!(f, "NetMsg: Leaderboard"),
+            Self::Leaderboard(_, _) => write!(f, "NetMsg: Leaderboard"),
             Self::Map(_, _, _) => write!(f, "NetMsg: Map"),
             Self::Player(_) => write!(f, "NetMsg: Player"),
             Self::VillageInfo(_) => write!(f, "NetMsg: VillageInfo"),
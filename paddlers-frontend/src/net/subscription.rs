@@ -0,0 +1,153 @@
+//! Live updates pushed by the db-interface over a WebSocket.
+//!
+//! While the subscription is active, periodical sync requests are only sent when the server reports a change.
+//! When the socket drops or stays quiet for too long, polling takes over again until a new connection has been established.
+
+use super::graphql::{ForceRequest, PeriodicalSyncRequest};
+use super::{authentication::keycloak_token, url::subscription_url, NetState, RequestQuests};
+use crate::prelude::*;
+use paddle::NutsCheck;
+use paddlers_shared_lib::api::subscriptions::{VillageSubscription, VillageUpdate};
+use wasm_bindgen::{prelude::Closure, JsCast};
+use web_sys::{MessageEvent, WebSocket};
+
+/// Ticks of the network thread to wait before trying to reconnect
+const RECONNECT_DELAY_TICKS: usize = 100;
+/// Ticks of the network thread without any message after which the socket is considered lost.
+/// The server sends a keep-alive every 15s, this allows for two missing ones.
+const QUIET_LIMIT_TICKS: usize = 450;
+
+/// Published when the server pushed an update
+pub(super) struct LiveUpdate(VillageUpdate);
+/// Published when the socket has been closed, for whatever reason
+pub(super) struct LiveConnectionLost;
+
+/// Keeps the socket and its callbacks alive
+pub(super) struct LiveConnection {
+    socket: WebSocket,
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut()>,
+}
+
+impl LiveConnection {
+    fn open() -> PadlResult<Self> {
+        let socket = WebSocket::new(&subscription_url()?)?;
+
+        let opened_socket = socket.clone();
+        let on_open = Closure::wrap(Box::new(move || {
+            let request = VillageSubscription {
+                authorization: keycloak_token(),
+            };
+            let msg = serde_json::to_string(&request).expect("Serializing subscription");
+            opened_socket
+                .send_with_str(&msg)
+                .map_err(PadlError::from)
+                .nuts_check();
+        }) as Box<dyn FnMut()>);
+        let on_message = Closure::wrap(Box::new(|event: MessageEvent| {
+            // Unknown messages are ignored, the server might be newer than the client
+            if let Some(text) = event.data().as_string() {
+                if let Ok(update) = serde_json::from_str(&text) {
+                    nuts::publish(LiveUpdate(update));
+                }
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        let on_close = Closure::wrap(Box::new(|| {
+            nuts::publish(LiveConnectionLost);
+        }) as Box<dyn FnMut()>);
+
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        Ok(LiveConnection {
+            socket,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_close: on_close,
+        })
+    }
+    fn is_closed(&self) -> bool {
+        self.socket.ready_state() == WebSocket::CLOSED
+    }
+}
+
+impl Drop for LiveConnection {
+    fn drop(&mut self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        self.socket.close().ok();
+    }
+}
+
+impl NetState {
+    /// Opens a new socket if there is none, with some delay after a failure
+    pub(super) fn maintain_live_connection(&mut self) {
+        // Closed sockets are only dropped here, not inside their own callbacks
+        if let Some(live) = &self.live {
+            self.quiet_ticks += 1;
+            if live.is_closed() || self.quiet_ticks > QUIET_LIMIT_TICKS {
+                self.live = None;
+                self.fall_back_to_polling();
+            }
+            return;
+        }
+        if !self.logged_in {
+            return;
+        }
+        if self.reconnect_counter > 0 {
+            self.reconnect_counter -= 1;
+            return;
+        }
+        match LiveConnection::open() {
+            Ok(connection) => {
+                self.live = Some(connection);
+                self.quiet_ticks = 0;
+            }
+            Err(e) => {
+                NutsCheck::<()>::nuts_check(Err(e));
+                self.reconnect_counter = RECONNECT_DELAY_TICKS;
+            }
+        }
+    }
+    pub(super) fn live_update(&mut self, msg: &LiveUpdate) {
+        self.quiet_ticks = 0;
+        // All villages of the player are reported but only the current village is synchronized
+        let sync = match msg.0 {
+            VillageUpdate::Subscribed => {
+                self.sync.set_pushed(true);
+                // Changes between the last poll and the subscription could have been missed
+                self.force_full_sync();
+                return;
+            }
+            VillageUpdate::Attacks(_) => PeriodicalSyncRequest::Attacks,
+            VillageUpdate::Reports(_) => PeriodicalSyncRequest::Reports,
+            VillageUpdate::Resources(_) => PeriodicalSyncRequest::Resources,
+            VillageUpdate::PlayerInfo => PeriodicalSyncRequest::PlayerInfo,
+            VillageUpdate::Quests => {
+                self.request_quests(&RequestQuests);
+                return;
+            }
+            VillageUpdate::KeepAlive => return,
+        };
+        self.scheduled_update(&ForceRequest::SyncAsap(sync));
+    }
+    pub(super) fn live_connection_lost(&mut self, _msg: &LiveConnectionLost) {
+        self.fall_back_to_polling();
+    }
+    /// Polling takes over until the socket has been reconnected
+    fn fall_back_to_polling(&mut self) {
+        self.reconnect_counter = RECONNECT_DELAY_TICKS;
+        if self.sync.pushed() {
+            self.sync.set_pushed(false);
+            self.force_full_sync();
+        }
+    }
+    fn force_full_sync(&mut self) {
+        for req in PeriodicalSyncRequest::all() {
+            self.scheduled_update(&ForceRequest::SyncAsap(*req));
+        }
+    }
+}
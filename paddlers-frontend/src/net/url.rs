@@ -12,6 +12,20 @@ pub fn graphql_url() -> PadlResult<String> {
         _ => Ok(format!("https://{}/graphql/", &host)),
     }
 }
+/// Endpoint for the queries in api/queries, which are sent by ID
+pub fn persisted_query_url() -> PadlResult<String> {
+    Ok(format!("{}persisted", graphql_url()?))
+}
+pub fn subscription_url() -> PadlResult<String> {
+    // host includes port, hostname does not
+    let hostname = hostname()?;
+    let host = host()?;
+    match hostname.as_str() {
+        "localhost" => Ok(format!("ws://{}/graphql/subscriptions", &host)),
+        "10.42.0.1" => Ok(format!("ws://{}/graphql/subscriptions", &host)),
+        _ => Ok(format!("wss://{}/graphql/subscriptions", &host)),
+    }
+}
 pub fn game_master_url() -> PadlResult<String> {
     // host includes port, hostname does not
     let hostname = hostname()?;
@@ -1,5 +1,5 @@
 use crate::game::story::select_dialogue_scene;
-use paddlers_shared_lib::story::story_state::StoryState;
+use paddlers_shared_lib::story::{story_graph::StoryGraph, story_state::StoryState};
 
 use crate::gui::ui_state::UiState;
 use crate::prelude::*;
@@ -39,8 +39,8 @@ impl Game {
     }
 }
 
-pub fn entry_view(story_state: StoryState) -> UiView {
-    if select_dialogue_scene(story_state).is_some() {
+pub fn entry_view(graph: &StoryGraph, story_state: &StoryState) -> UiView {
+    if select_dialogue_scene(graph, story_state).is_some() {
         UiView::Dialogue
     } else {
         UiView::Town
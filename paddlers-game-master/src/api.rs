@@ -14,13 +14,15 @@ pub(crate) use reports::collect_report_rewards;
 pub(crate) use story::story_transition;
 
 use crate::authentication::Authentication;
+use crate::game_master::economy_worker::WorkerProductionChanged;
 use crate::setup::initialize_new_player_account;
-use crate::StringErr;
+use crate::{ApiError, GameMasterResult};
 use actix_web::{web, HttpResponse, Responder};
 // use futures::Future;
 use paddlers_shared_lib::sql::GameDB;
 use paddlers_shared_lib::{
     api::{
+        error::{GameMasterError, GameObject},
         keys::{VillageKey, WorkerKey},
         shop::{BuildingDeletion, BuildingPurchase, BuildingUpgrade, ProphetPurchase},
         tasks::TaskList,
@@ -38,26 +40,20 @@ pub(crate) async fn purchase_prophet(
     actors: web::Data<crate::ActorAddresses>,
     body: web::Json<ProphetPurchase>,
     mut auth: Authentication,
-) -> HttpResponse {
+) -> Result<HttpResponse, ApiError> {
     let village = body.village;
     std::mem::drop(body);
-    let result = web::block(move || {
+    web::block(move || {
         let db: crate::db::DB = pool.get_ref().into();
-        check_owns_village0(&db, &auth, village)?;
-        let result = db.try_buy_prophet(
-            village,
-            &actors,
-            auth.player_object(&db).ok_or("No such player".to_owned())?,
-        );
-        result
+        check_owns_village(&db, &auth, village)?;
+        let player = auth
+            .player_object(&db)
+            .ok_or(GameMasterError::PlayerNotCreated)?;
+        db.try_buy_prophet(village, &actors, player)
     })
-    .await;
-
-    match result {
-        Err(_) => HttpResponse::InternalServerError().into(),
-        Ok(Err(msg)) => HttpResponse::Forbidden().body(msg).into(),
-        Ok(Ok(())) => HttpResponse::Ok().into(),
-    }
+    .await
+    .map_err(|e| GameMasterError::Internal(e.to_string()))??;
+    Ok(HttpResponse::Ok().into())
 }
 
 pub(crate) async fn purchase_building(
@@ -65,79 +61,64 @@ pub(crate) async fn purchase_building(
     body: web::Json<BuildingPurchase>,
     mut auth: Authentication,
     addr: web::Data<crate::ActorAddresses>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let db: crate::db::DB = pool.get_ref().into();
 
     let building = body.building_type.into();
-    if let Err(err) = check_owns_village(&db, &auth, body.village) {
-        return err;
-    }
-    let player = auth.player_object(&db).expect("no player");
+    check_owns_village(&db, &auth, body.village)?;
+    let player = auth
+        .player_object(&db)
+        .ok_or(GameMasterError::PlayerNotCreated)?;
     if !db.player_allowed_to_build(building, body.village, player) {
-        return HttpResponse::BadRequest()
-            .body("Player not allowed to build")
-            .into();
+        return Err(GameMasterError::Locked.into());
     }
 
-    db.try_buy_building(building, (body.x, body.y), body.village, player, addr)
-        .map_or_else(
-            |e| HttpResponse::InternalServerError().body(e),
-            |id| HttpResponse::Ok().json(id).into(),
-        )
+    let id = db.try_buy_building(building, (body.x, body.y), body.village, player, addr)?;
+    Ok(HttpResponse::Ok().json(id))
 }
 
 pub async fn delete_building(
     pool: web::Data<crate::db::Pool>,
     body: web::Json<BuildingDeletion>,
     auth: Authentication,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let db: crate::db::DB = pool.get_ref().into();
 
-    if let Err(err) = check_owns_village(&db, &auth, body.village) {
-        return err;
-    }
-
-    if let Some(building) =
-        db.find_building_by_coordinates(body.x as i32, body.y as i32, body.village)
-    {
-        if building.building_type.can_be_deleted() {
-            db.delete_building(&building);
-            HttpResponse::Ok().into()
-        } else {
-            HttpResponse::BadRequest().body("This building cannot be deleted".to_owned())
-        }
-    } else {
-        HttpResponse::BadRequest().body(format!("No building at {}|{}", body.x, body.y))
+    check_owns_village(&db, &auth, body.village)?;
+    let building = db
+        .find_building_by_coordinates(body.x as i32, body.y as i32, body.village)
+        .ok_or(GameMasterError::NotFound(GameObject::Building))?;
+    if !building.building_type.can_be_deleted() {
+        return Err(GameMasterError::CannotBeDeleted.into());
     }
+    db.delete_building(&building);
+    Ok(HttpResponse::Ok().into())
 }
 pub async fn upgrade_building(
     pool: web::Data<crate::db::Pool>,
     body: web::Json<BuildingUpgrade>,
     auth: Authentication,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let db: crate::db::DB = pool.get_ref().into();
 
-    if let Some(building) = db.building(body.building) {
-        if let Err(err) = check_owns_village(&db, &auth, building.village()) {
-            return err;
+    let building = db
+        .building(body.building)
+        .ok_or(GameMasterError::NotFound(GameObject::Building))?;
+    check_owns_village(&db, &auth, building.village())?;
+    if building.lv as usize != body.current_level {
+        return Err(GameMasterError::UnexpectedBuildingLevel {
+            expected: body.current_level,
+            actual: building.lv as usize,
         }
-        if building.lv as usize == body.current_level {
-            if let Some(price) = building.building_type.upgrade_cost(building.lv as usize) {
-                if let Err(err) = db.try_spend(&price, building.village()) {
-                    return HttpResponse::BadRequest().body(err);
-                }
-            } else {
-                return HttpResponse::BadRequest().body("Cannot be upgraded.".to_owned());
-            }
-            db.set_building_level(building.key(), body.current_level as i32 + 1);
-            HttpResponse::Ok().into()
-        } else {
-            HttpResponse::BadRequest()
-                .body("Unexpected building level. Duplicate request?".to_owned())
-        }
-    } else {
-        HttpResponse::BadRequest().body("No such building".to_owned())
+        .into());
     }
+    let price = building
+        .building_type
+        .upgrade_cost(building.lv as usize)
+        .ok_or(GameMasterError::CannotBeUpgraded)?;
+    db.try_spend(&price, building.village())?;
+    db.set_building_level(building.key(), body.current_level as i32 + 1);
+    Ok(HttpResponse::Ok().into())
 }
 
 pub(super) async fn overwrite_tasks(
@@ -145,69 +126,62 @@ pub(super) async fn overwrite_tasks(
     body: web::Json<TaskList>,
     addr: web::Data<crate::ActorAddresses>,
     auth: Authentication,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let db: crate::db::DB = pool.get_ref().into();
-    if let Err(err) = check_owns_worker(&db, &auth, body.worker_id) {
-        return err;
-    }
+    check_owns_worker(&db, &auth, body.worker_id)?;
 
-    match crate::worker_actions::validate_task_list(&db, &body.0) {
-        Ok(validated) => {
-            for upd in validated.update_tasks {
-                db.update_task(&upd);
-            }
-            crate::worker_actions::replace_worker_tasks(
-                &db,
-                &addr.town_worker,
-                body.worker_id,
-                &validated.new_tasks,
-                validated.village_id,
-            );
-        }
-        Err(e) => {
-            println!("Task creation failed. {} \n Body: {:?}", e, body.0);
-            return HttpResponse::BadRequest().body("Couldn't create tasks");
-        }
+    let validated = crate::worker_actions::validate_task_list(&db, &body.0)?;
+    for upd in validated.update_tasks {
+        db.update_task(&upd);
     }
-    HttpResponse::Ok().into()
+    crate::worker_actions::replace_worker_tasks(
+        &db,
+        &addr.town_worker,
+        body.worker_id,
+        &validated.new_tasks,
+        validated.village_id,
+    );
+    addr.econ_worker
+        .do_send(WorkerProductionChanged(body.worker_id));
+    Ok(HttpResponse::Ok().into())
 }
 
 /// Must be called by an identified user (via JWT) before using any other Game-Master or GQL services
 pub(super) async fn new_player(
     pool: web::Data<crate::db::Pool>,
+    addr: web::Data<crate::ActorAddresses>,
     auth: Authentication,
     body: web::Json<PlayerInitData>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let db: crate::db::DB = pool.get_ref().into();
-    if let Err(msg) = initialize_new_player_account(&db, auth.user.uuid, &body) {
-        HttpResponse::InternalServerError().body(msg)
-    } else {
-        HttpResponse::Ok().into()
+    initialize_new_player_account(&db, auth.user.uuid, &body).map_err(GameMasterError::Internal)?;
+    // The new hero starts regenerating mana
+    if let Some(player) = db.player_by_uuid(auth.user.uuid) {
+        for village in db.player_villages(player.key()) {
+            for worker in db.workers(village.key()) {
+                addr.econ_worker
+                    .do_send(WorkerProductionChanged(worker.key()));
+            }
+        }
     }
+    Ok(HttpResponse::Ok().into())
 }
 
-fn check_owns_worker(
-    db: &crate::db::DB,
-    auth: &Authentication,
-    v: WorkerKey,
-) -> Result<(), HttpResponse> {
+fn check_owns_worker(db: &crate::db::DB, auth: &Authentication, v: WorkerKey) -> GameMasterResult {
     if db.worker_owned_by(v, auth.user.uuid) {
         Ok(())
     } else {
-        Err(HttpResponse::Forbidden().body(format!("Worker not owned by player")))
-    }
-}
-fn check_owns_village0(db: &crate::db::DB, auth: &Authentication, v: VillageKey) -> StringErr {
-    if db.village_owned_by(v, auth.user.uuid) {
-        Ok(())
-    } else {
-        Err("Village not owned by player".to_owned())
+        Err(GameMasterError::NotOwned(GameObject::Worker))
     }
 }
 fn check_owns_village(
     db: &crate::db::DB,
     auth: &Authentication,
     v: VillageKey,
-) -> Result<(), HttpResponse> {
-    check_owns_village0(db, auth, v).map_err(|msg| HttpResponse::Forbidden().body(msg))
+) -> GameMasterResult {
+    if db.village_owned_by(v, auth.user.uuid) {
+        Ok(())
+    } else {
+        Err(GameMasterError::NotOwned(GameObject::Village))
+    }
 }
@@ -1,12 +1,13 @@
-use super::check_owns_village0;
+use super::check_owns_village;
 use crate::game_master::attack_funnel::PlannedAttack;
 use crate::game_master::event::Event;
 use crate::game_master::town_worker::TownWorkerEventMsg;
-use crate::StringError;
+use crate::ApiError;
 use crate::{authentication::Authentication, game_master::story_worker::StoryWorkerMessage};
 use actix_web::{web, HttpResponse};
 use paddlers_shared_lib::{
     api::attacks::{AttackDescriptor, InvitationDescriptor, StartFightRequest},
+    api::error::{GameMasterError, GameObject},
     civilization::CivilizationPerk,
 };
 use paddlers_shared_lib::{prelude::*, story::story_trigger::StoryTrigger};
@@ -16,7 +17,7 @@ pub(crate) async fn create_attack(
     actors: web::Data<crate::ActorAddresses>,
     body: web::Json<AttackDescriptor>,
     auth: Authentication,
-) -> Result<HttpResponse, StringError> {
+) -> Result<HttpResponse, ApiError> {
     let pool0 = pool.clone();
     let pool1 = pool.clone();
     let attack = body.0;
@@ -32,24 +33,26 @@ pub(crate) async fn create_attack(
             match db.hobo(hobo_key) {
                 Some(hobo) => {
                     if hobo.home != home_id {
-                        Err("Hobo not from this village")
+                        Err(GameMasterError::NotOwned(GameObject::Hobo))
                     } else {
                         Ok(hobo)
                     }
                 }
-                None => Err("Invalid hobo"),
+                None => Err(GameMasterError::NotFound(GameObject::Hobo)),
             }
         })
         .collect::<Result<Vec<_>, _>>()?;
 
     let db: crate::db::DB = pool0.get_ref().into();
-    check_owns_village0(&db, &auth, from_key)?;
+    check_owns_village(&db, &auth, from_key)?;
     let destination_village = db
         .village_at(x as f32, y as f32)
-        .ok_or("Invalid target village")?;
+        .ok_or(GameMasterError::NotFound(GameObject::Village))?;
 
     let db: crate::db::DB = pool1.get_ref().into();
-    let origin_village = db.village(from_key).ok_or("Owned village doesn't exist")?;
+    let origin_village = db
+        .village(from_key)
+        .ok_or(GameMasterError::NotFound(GameObject::Village))?;
 
     let pa = PlannedAttack {
         origin_village: Some(origin_village),
@@ -57,12 +60,17 @@ pub(crate) async fn create_attack(
         hobos,
         fixed_travel_time_s: None,
         subject_to_visitor_queue_limit: false,
+        invited_by: None,
     };
 
     actors
         .attack_funnel
-        .try_send(pa)
-        .or(Err("failed to send to attack funnel"))?;
+        .send(pa)
+        .await
+        .map_err(|e| {
+            GameMasterError::Internal(format!("Sending to attack funnel failed: {:?}", e))
+        })?
+        .map_err(GameMasterError::from)?;
     Ok(HttpResponse::Ok().into())
 }
 
@@ -71,31 +79,29 @@ pub(crate) async fn welcome_visitor(
     body: web::Json<StartFightRequest>,
     mut auth: Authentication,
     addr: web::Data<crate::ActorAddresses>,
-) -> HttpResponse {
+) -> Result<HttpResponse, ApiError> {
     let db: crate::db::DB = pool.get_ref().into();
     let destination_village = body.destination;
     let attack = body.attack;
-    if !db.village_owned_by(destination_village, auth.user.uuid) {
-        return HttpResponse::Forbidden().body("Village not owned by player");
-    }
+    check_owns_village(&db, &auth, destination_village)?;
     if let Some(player) = auth.player_object(&db) {
         addr.story_worker.do_send(StoryWorkerMessage::new_verified(
             player.key(),
-            player.story_state,
+            player.story_state.clone(),
             StoryTrigger::LetVisitorIn,
         ));
     }
     db.start_fight(attack, Some(destination_village));
-    HttpResponse::Ok().into()
+    Ok(HttpResponse::Ok().into())
 }
 pub(crate) async fn visitor_satisfied_notification(
     body: web::Json<HoboKey>,
     addr: web::Data<crate::ActorAddresses>,
-) -> Result<&'static str, StringError> {
+) -> Result<&'static str, ApiError> {
     let event = Event::CheckVisitorHp { hobo_id: body.0 };
     addr.town_worker
-        .try_send(TownWorkerEventMsg(event, chrono::Utc::now()))
-        .map_err(|e| format!("Send failed: {:?}", e))?;
+        .try_send(TownWorkerEventMsg(event, crate::clock::now()))
+        .map_err(|e| GameMasterError::Internal(format!("Send failed: {:?}", e)))?;
     Ok("")
 }
 
@@ -104,34 +110,42 @@ pub(crate) async fn new_invitation(
     body: web::Json<InvitationDescriptor>,
     mut auth: Authentication,
     addr: web::Data<crate::ActorAddresses>,
-) -> Result<HttpResponse, StringError> {
+) -> Result<HttpResponse, ApiError> {
     // Check that request is valid and forward request to actor
     let db: crate::db::DB = pool.get_ref().into();
-    let origin_vid = db.building(body.nest).ok_or("Nest not found")?.village();
+    let origin_vid = db
+        .building(body.nest)
+        .ok_or(GameMasterError::NotFound(GameObject::Building))?
+        .village();
     let origin_village = db.village(origin_vid);
-    let destination_village = db.village(body.to).ok_or("Village not found")?;
+    let destination_village = db
+        .village(body.to)
+        .ok_or(GameMasterError::NotFound(GameObject::Village))?;
     let hobos = db.idle_hobos_in_nest(body.nest);
-    if !auth
+    let player = auth
         .player_object(&db)
-        .ok_or_else(|| "No such player".to_owned())?
+        .ok_or(GameMasterError::PlayerNotCreated)?;
+    if !player
         .civilization_perks()
         .has(CivilizationPerk::Invitation)
     {
-        return Err("Invitations not unlocked".into());
-    }
-    if !db.village_owned_by(destination_village.key(), auth.user.uuid) {
-        return Err("Village not owned by player".into());
+        return Err(GameMasterError::Locked.into());
     }
+    let destination_village_key = destination_village.key();
+    check_owns_village(&db, &auth, destination_village_key)?;
     let atk = PlannedAttack {
         origin_village,
         destination_village,
         hobos,
         fixed_travel_time_s: None,
         subject_to_visitor_queue_limit: true,
+        invited_by: Some(player.key()),
     };
     addr.attack_funnel
-        .try_send(atk)
-        .map_err(|e| format!("Spawning attack failed: {:?}", e))?;
+        .send(atk)
+        .await
+        .map_err(|e| GameMasterError::Internal(format!("Spawning attack failed: {:?}", e)))?
+        .map_err(GameMasterError::from)?;
 
     Ok(HttpResponse::Ok().into())
 }
@@ -1,6 +1,11 @@
-use crate::{authentication::Authentication, db::DB};
+use crate::{authentication::Authentication, db::DB, ApiError};
 use actix_web::*;
-use paddlers_shared_lib::{api::hobo::SettleHobo, prelude::*};
+use paddlers_shared_lib::{
+    api::error::{GameMasterError, GameObject},
+    api::hobo::SettleHobo,
+    prelude::*,
+    specification_types::HoboLevel,
+};
 
 use super::check_owns_village;
 
@@ -8,30 +13,24 @@ pub(crate) async fn settle_hobo(
     pool: web::Data<crate::db::Pool>,
     body: web::Json<SettleHobo>,
     auth: Authentication,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let db: crate::db::DB = pool.get_ref().into();
 
     let building = db
         .building(body.nest)
-        .ok_or_else(|| HttpResponse::BadRequest().body("No such building"));
-    if let Err(err) = building {
-        return err;
-    }
-    let building = building.unwrap();
+        .ok_or(GameMasterError::NotFound(GameObject::Building))?;
 
     match building.building_type {
         BuildingType::SingleNest | BuildingType::TripleNest => {
             // OK
         }
         _ => {
-            return HttpResponse::BadRequest().body("Not a nest, cannot settle a Paddler here!");
+            return Err(GameMasterError::InvalidRequest("Not a nest".to_owned()).into());
         }
     }
-    if let Err(err) = check_owns_village(&db, &auth, building.village()) {
-        return err;
-    }
+    check_owns_village(&db, &auth, building.village())?;
     db.settle_hobo(building.village(), building.key());
-    HttpResponse::Ok().into()
+    Ok(HttpResponse::Ok().into())
 }
 
 impl DB {
@@ -43,6 +42,7 @@ impl DB {
             speed: 0.1,
             hurried: false,
             nest: Some(nest_id.num()),
+            level: HoboLevel::of_anarchist_hp(5, false).num() as i32,
         });
     }
 }
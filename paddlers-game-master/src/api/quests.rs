@@ -1,14 +1,15 @@
 use crate::game_master::story_worker::StoryWorkerMessage;
-use crate::{authentication::Authentication, db::CollectQuestMessage};
+use crate::{authentication::Authentication, db::CollectQuestMessage, ApiError, GameMasterResult};
 use actix_web::{web, HttpResponse};
 use paddlers_shared_lib::{
+    api::error::{GameMasterError, GameObject},
     api::quests::QuestCollect,
     prelude::{Player, Quest, QuestKey},
     story::story_trigger::StoryTrigger,
 };
 use paddlers_shared_lib::{
     keys::SqlKey,
-    prelude::{GameDB, VillageKey},
+    prelude::{GameDB, PlayerKey, VillageKey},
 };
 
 pub(crate) async fn collect_quest(
@@ -16,19 +17,16 @@ pub(crate) async fn collect_quest(
     body: web::Json<QuestCollect>,
     auth: Authentication,
     addr: web::Data<crate::ActorAddresses>,
-) -> HttpResponse {
-    let result = collect_quest_impl(pool, body, auth, addr).await;
-    match result {
-        Err(msg) => HttpResponse::Forbidden().body(msg).into(),
-        Ok(()) => HttpResponse::Ok().into(),
-    }
+) -> Result<HttpResponse, ApiError> {
+    collect_quest_impl(pool, body, auth, addr).await?;
+    Ok(HttpResponse::Ok().into())
 }
 pub(crate) async fn collect_quest_impl(
     pool: web::Data<crate::db::Pool>,
     body: web::Json<QuestCollect>,
     mut auth: Authentication,
     addr: web::Data<crate::ActorAddresses>,
-) -> Result<(), String> {
+) -> GameMasterResult {
     // Check that quest is active and all conditions are met, then forward request to DB actor
     let db: crate::db::DB = pool.get_ref().into();
     let player_key = auth.player_key(&db)?;
@@ -37,18 +35,13 @@ pub(crate) async fn collect_quest_impl(
         .player_quests(player_key)
         .into_iter()
         .find(|q| q.key() == quest_key)
-        .ok_or_else(|| "Player does not have this quest".to_string())?;
+        .ok_or(GameMasterError::NotFound(GameObject::Quest))?;
     let village = db.player_villages(player_key)[0]; // Assuming one village per player
     let player = auth
         .player_object(&db)
-        .ok_or_else(|| "No player?".to_owned())?;
+        .ok_or(GameMasterError::PlayerNotCreated)?;
 
-    // TODO (performance) avoid sequential DB lookups throughout checks
-    check_building_conditions(&db, quest_key, village.key())?;
-    check_resource_conditions(&db, quest_key, village.key())?;
-    check_karma_conditions(&quest, &player)?;
-    check_pop_conditions(&db, &quest, village.key())?;
-    check_worker_conditions(&db, quest_key, village.key())?;
+    check_quest_conditions(&db, &quest, &player, village.key())?;
 
     let follow_up_quest = quest.follow_up_quest.map(|name| {
         db.quest_by_name(
@@ -65,10 +58,9 @@ pub(crate) async fn collect_quest_impl(
         village: village.key(),
         follow_up_quest,
     };
-    addr.db_actor
-        .send(msg)
-        .await
-        .map_err(|e| format!("Quest collection spawn failed: {:?}", e))?;
+    addr.db_actor.send(msg).await.map_err(|e| {
+        GameMasterError::Internal(format!("Quest collection spawn failed: {:?}", e))
+    })?;
 
     let quest_id = quest
         .quest_key
@@ -76,22 +68,38 @@ pub(crate) async fn collect_quest_impl(
         .expect("Couldn't parse QuestName from value found in DB");
     let msg = StoryWorkerMessage::new_verified(
         player_key,
-        player.story_state,
+        player.story_state.clone(),
         StoryTrigger::FinishedQuest(quest_id),
     );
     addr.story_worker
         .send(msg)
         .await
-        .map_err(|e| format!("Quest finished spawn failed: {:?}", e))?;
+        .map_err(|e| GameMasterError::Internal(format!("Quest finished spawn failed: {:?}", e)))?;
 
     Ok(())
 }
 
+fn check_quest_conditions(
+    db: &crate::db::DB,
+    quest: &Quest,
+    player: &Player,
+    village: VillageKey,
+) -> GameMasterResult {
+    // TODO (performance) avoid sequential DB lookups throughout checks
+    check_building_conditions(db, quest.key(), village)?;
+    check_resource_conditions(db, quest.key(), village)?;
+    check_karma_conditions(quest, player)?;
+    check_pop_conditions(db, quest, village)?;
+    check_worker_conditions(db, quest.key(), village)?;
+    check_stat_conditions(db, quest.key(), player.key(), village)?;
+    Ok(())
+}
+
 fn check_building_conditions(
     db: &crate::db::DB,
     quest_key: QuestKey,
     village: VillageKey,
-) -> Result<(), std::string::String> {
+) -> GameMasterResult {
     let building_conditions = db.quest_building_conditions(quest_key);
     if building_conditions.len() > 0 {
         let buildings = db.buildings(village);
@@ -103,7 +111,7 @@ fn check_building_conditions(
                 }
             }
             if n > 0 {
-                return Err("Missing ".to_owned() + &condition.building_type.to_string());
+                return Err(missing(condition.building_type.to_string()));
             }
         }
     }
@@ -114,7 +122,7 @@ fn check_worker_conditions(
     db: &crate::db::DB,
     quest_key: QuestKey,
     village_key: VillageKey,
-) -> Result<(), std::string::String> {
+) -> GameMasterResult {
     let worker_conditions = db.quest_worker_conditions(quest_key);
     if worker_conditions.len() > 0 {
         for condition in worker_conditions {
@@ -123,7 +131,7 @@ fn check_worker_conditions(
                 .len() as i64)
                 < condition.amount
             {
-                return Err("Missing ".to_owned() + &condition.task_type.to_string() + " workers");
+                return Err(missing(condition.task_type.to_string() + " workers"));
             }
         }
     }
@@ -134,22 +142,40 @@ fn check_resource_conditions(
     db: &crate::db::DB,
     quest_key: QuestKey,
     village_key: VillageKey,
-) -> Result<(), std::string::String> {
+) -> GameMasterResult {
     let res_conditions = db.quest_res_conditions(quest_key);
     if res_conditions.len() > 0 {
         for condition in res_conditions {
             if db.resource(condition.resource_type, village_key) < condition.amount {
-                return Err("Missing ".to_owned() + &condition.resource_type.to_string());
+                return Err(missing(condition.resource_type.to_string()));
             }
         }
     }
     Ok(())
 }
 
-fn check_karma_conditions(quest: &Quest, player: &Player) -> Result<(), std::string::String> {
+fn check_stat_conditions(
+    db: &crate::db::DB,
+    quest_key: QuestKey,
+    player_key: PlayerKey,
+    village_key: VillageKey,
+) -> GameMasterResult {
+    for condition in db.quest_stat_conditions(quest_key) {
+        let current = match db.village_stat(condition.stat_type, village_key) {
+            Some(value) => value,
+            None => db.quest_progress(quest_key, player_key, condition.stat_type),
+        };
+        if current < condition.amount {
+            return Err(missing(condition.stat_type.to_string()));
+        }
+    }
+    Ok(())
+}
+
+fn check_karma_conditions(quest: &Quest, player: &Player) -> GameMasterResult {
     if let Some(karma_required) = quest.karma_condition {
         if player.karma < karma_required {
-            return Err("Need more Karma".to_owned());
+            return Err(missing("Karma".to_owned()));
         }
     }
     Ok(())
@@ -159,12 +185,205 @@ fn check_pop_conditions(
     db: &crate::db::DB,
     quest: &Quest,
     village_key: VillageKey,
-) -> Result<(), std::string::String> {
+) -> GameMasterResult {
     if let Some(pop_required) = quest.pop_condition {
         let pop = db.settled_hobo_count(village_key) + db.worker_count(village_key);
         if pop < pop_required {
-            return Err("Need more Population".to_owned());
+            return Err(missing("Population".to_owned()));
         }
     }
     Ok(())
 }
+
+fn missing(what: String) -> GameMasterError {
+    GameMasterError::QuestIncomplete { missing: what }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DB;
+    use crate::setup::initialize_new_player_account;
+    use diesel::prelude::*;
+    use paddlers_shared_lib::api::PlayerInitData;
+    use paddlers_shared_lib::civilization::CivilizationPerk;
+    use paddlers_shared_lib::prelude::*;
+    use paddlers_shared_lib::schema::*;
+
+    /// Nothing is committed, the test data disappears with the connection
+    fn test_db() -> DB {
+        let db: DB = (&DB::new_pool()).into();
+        db.dbconn().begin_test_transaction().unwrap();
+        db
+    }
+
+    fn new_player(db: &DB) -> (Player, VillageKey) {
+        let uuid = uuid::Uuid::new_v4();
+        let info = PlayerInitData {
+            display_name: "Quest Test".to_owned(),
+            utc_offset_minutes: 0,
+        };
+        initialize_new_player_account(db, uuid, &info).unwrap();
+        let player = db.player_by_uuid(uuid).unwrap();
+        let village = db.player_villages(player.key())[0].key();
+        (player, village)
+    }
+
+    fn insert_quest(db: &DB, quest_key: &str, karma_condition: Option<i64>) -> Quest {
+        diesel::insert_into(quests::table)
+            .values(&NewQuest {
+                quest_key: quest_key.to_owned(),
+                karma_condition,
+                pop_condition: None,
+                follow_up_quest: None,
+            })
+            .get_result(db.dbconn())
+            .unwrap()
+    }
+
+    fn is_incomplete(result: GameMasterResult) -> bool {
+        match result {
+            Err(GameMasterError::QuestIncomplete { .. }) => true,
+            _ => false,
+        }
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn collecting_requires_all_conditions() {
+        let db = test_db();
+        let (player, village) = new_player(&db);
+        let quest = insert_quest(&db, "CollectQuestConditionsTest", Some(player.karma + 10));
+        let stations = db
+            .buildings(village)
+            .iter()
+            .filter(|b| b.building_type == BuildingType::BundlingStation)
+            .count() as i64;
+        diesel::insert_into(quest_building_conditions::table)
+            .values(&NewQuestBuildingCondition {
+                quest_id: quest.id,
+                building_type: BuildingType::BundlingStation,
+                amount: stations + 1,
+            })
+            .execute(db.dbconn())
+            .unwrap();
+        diesel::insert_into(quest_res_conditions::table)
+            .values(&NewQuestResCondition {
+                quest_id: quest.id,
+                resource_type: ResourceType::Feathers,
+                amount: db.resource(ResourceType::Feathers, village) + 100,
+            })
+            .execute(db.dbconn())
+            .unwrap();
+        diesel::insert_into(quest_stat_conditions::table)
+            .values(&NewQuestStatCondition {
+                quest_id: quest.id,
+                stat_type: QuestStatType::WelcomedVisitors,
+                amount: 2,
+            })
+            .execute(db.dbconn())
+            .unwrap();
+        db.assign_player_quest(player.key(), quest.key()).unwrap();
+        let check = |db: &DB| {
+            let player = db.player(player.key()).unwrap();
+            check_quest_conditions(db, &quest, &player, village)
+        };
+        assert!(is_incomplete(check(&db)));
+
+        db.insert_building(&NewBuilding {
+            x: 0,
+            y: 0,
+            building_type: BuildingType::BundlingStation,
+            building_range: None,
+            attack_power: None,
+            attacks_per_cycle: None,
+            creation: db.now(),
+            village_id: village.num(),
+            lv: 0,
+        });
+        assert!(is_incomplete(check(&db)));
+
+        db.add_resource(ResourceType::Feathers, village, 100)
+            .unwrap();
+        assert!(is_incomplete(check(&db)));
+
+        db.add_karma(player.key(), 10).unwrap();
+        assert!(is_incomplete(check(&db)));
+
+        db.add_quest_progress(player.key(), QuestStatType::WelcomedVisitors, 1);
+        assert!(is_incomplete(check(&db)));
+
+        db.add_quest_progress(player.key(), QuestStatType::WelcomedVisitors, 1);
+        assert_eq!(check(&db), Ok(()));
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn collecting_grants_all_rewards() {
+        let db = test_db();
+        let (player, village) = new_player(&db);
+        let quest = insert_quest(&db, "CollectQuestRewardsTest", None);
+        let follow_up = insert_quest(&db, "CollectQuestFollowUpTest", None);
+        diesel::insert_into(quest_res_rewards::table)
+            .values(&NewQuestResReward {
+                quest_id: quest.id,
+                resource_type: ResourceType::Logs,
+                amount: 25,
+            })
+            .execute(db.dbconn())
+            .unwrap();
+        diesel::insert_into(quest_worker_rewards::table)
+            .values(&QuestWorkerReward {
+                quest_id: quest.id,
+                mana: 5,
+                experience: 1,
+            })
+            .execute(db.dbconn())
+            .unwrap();
+        diesel::insert_into(quest_perk_rewards::table)
+            .values(&QuestPerkReward {
+                quest_id: quest.id,
+                perk: CivilizationPerk::Invitation,
+            })
+            .execute(db.dbconn())
+            .unwrap();
+        diesel::insert_into(quest_building_rewards::table)
+            .values(&QuestBuildingReward {
+                quest_id: quest.id,
+                building_type: BuildingType::Choir,
+            })
+            .execute(db.dbconn())
+            .unwrap();
+        db.assign_player_quest(player.key(), quest.key()).unwrap();
+        let mut hero = db.hero(village).unwrap();
+        hero.mana = Some(0);
+        db.update_worker(&hero);
+        let logs = db.resource(ResourceType::Logs, village);
+
+        db.collect_quest(&CollectQuestMessage {
+            player: player.key(),
+            quest: quest.key(),
+            village,
+            follow_up_quest: Some(follow_up.key()),
+        });
+
+        assert_eq!(db.resource(ResourceType::Logs, village), logs + 25);
+        let rewarded_hero = db.hero(village).unwrap();
+        assert_eq!(rewarded_hero.mana, Some(5));
+        assert!((rewarded_hero.level, rewarded_hero.exp) > (hero.level, hero.exp));
+        let perks = db.player(player.key()).unwrap().civilization_perks();
+        assert!(perks.has(CivilizationPerk::Invitation));
+        assert!(db
+            .unlocked_buildings(player.key())
+            .contains(&BuildingType::Choir));
+        let quests: Vec<QuestKey> = db
+            .player_quests(player.key())
+            .iter()
+            .map(|q| q.key())
+            .collect();
+        assert!(!quests.contains(&quest.key()));
+        assert!(quests.contains(&follow_up.key()));
+    }
+}
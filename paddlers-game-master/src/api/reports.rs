@@ -1,8 +1,9 @@
 use crate::authentication::Authentication;
 use crate::db::CollectReportRewardsMessage;
-use crate::StringError;
+use crate::ApiError;
 use actix_web::{web, HttpResponse};
 use futures_util::TryFutureExt;
+use paddlers_shared_lib::api::error::{GameMasterError, GameObject};
 use paddlers_shared_lib::api::reports::ReportCollect;
 use paddlers_shared_lib::prelude::*;
 
@@ -11,12 +12,14 @@ pub(crate) async fn collect_report_rewards(
     body: web::Json<ReportCollect>,
     auth: Authentication,
     addr: web::Data<crate::ActorAddresses>,
-) -> Result<HttpResponse, StringError> {
+) -> Result<HttpResponse, ApiError> {
     // Check that request is valid and forward request to actor
     let db: crate::db::DB = pool.get_ref().into();
     for rid in body.0.reports {
-        let report = db.report(rid).ok_or("No such report")?;
-        super::check_owns_village0(&db, &auth, report.village())?;
+        let report = db
+            .report(rid)
+            .ok_or(GameMasterError::NotFound(GameObject::Report))?;
+        super::check_owns_village(&db, &auth, report.village())?;
         spawn_report_collection(&addr, report).await;
     }
     Ok(HttpResponse::Ok().into())
@@ -1,7 +1,8 @@
 use crate::db::DB;
-use crate::StringErr;
+use crate::GameMasterResult;
 use crate::{buildings::BuildingFactory, game_master::story_worker::StoryWorkerMessage};
 use paddlers_shared_lib::{
+    api::error::GameMasterError,
     api::shop::*,
     game_mechanics::attributes::Attributes,
     game_mechanics::town::{TOWN_LANE_Y, TOWN_Y},
@@ -17,14 +18,15 @@ impl DB {
         village: VillageKey,
         player: &Player,
         addr: actix_web::web::Data<crate::ActorAddresses>,
-    ) -> Result<i64, String> {
+    ) -> GameMasterResult<i64> {
         self.building_has_space(typ, pos, village)
-            .map(|_| self.try_spend(&typ.price(), village))
+            .and_then(|_| self.try_spend(&typ.price(), village))
             .map(|_| self.insert_building(&BuildingFactory::new(typ, pos, village)))
             .map(|b| {
+                self.add_quest_progress(player.key(), QuestStatType::PlacedBuildings, 1);
                 addr.story_worker.do_send(StoryWorkerMessage::new_verified(
                     player.key(),
-                    player.story_state,
+                    player.story_state.clone(),
                     StoryTrigger::BuildingBuilt(typ),
                 ));
                 b.id
@@ -36,7 +38,7 @@ impl DB {
         typ: BuildingType,
         pos: (usize, usize),
         village: VillageKey,
-    ) -> StringErr {
+    ) -> GameMasterResult {
         // Check conflict with existing building
         let (w, h) = typ.size();
         debug_assert_eq!(w, 1, "Not implemented yet");
@@ -49,13 +51,13 @@ impl DB {
             debug_assert_eq!(h, 1, "Not implemented yet");
             let (x, y) = (other.x as usize, other.y as usize);
             if x == x0 && y == y0 {
-                return Err("Space occupied".to_owned());
+                return Err(GameMasterError::SpaceOccupied);
             }
         }
 
         // Check conflict with map
         if (y0 == TOWN_LANE_Y && typ != BuildingType::Watergate) || y0 >= TOWN_Y {
-            return Err("Cannot build here".to_owned());
+            return Err(GameMasterError::CannotBuildHere);
         }
 
         // Check conflict with stationary units
@@ -63,7 +65,7 @@ impl DB {
         let (x0, y0) = (pos.0 as i32, pos.1 as i32);
         for w in workers {
             if w.x == x0 && w.y == y0 {
-                return Err("Unit blocks space".to_owned());
+                return Err(GameMasterError::UnitBlocksSpace);
             }
         }
         // Check conflict with walking units
@@ -73,7 +75,7 @@ impl DB {
             let mut worker_y = w.y;
             for task in self.worker_tasks(w.key()) {
                 if is_between(x0, worker_x, task.x) || is_between(y0, worker_y, task.y) {
-                    return Err("Walking unit blocks space".to_owned());
+                    return Err(GameMasterError::UnitBlocksSpace);
                 }
                 worker_x = task.x;
                 worker_y = task.y;
@@ -89,9 +91,9 @@ impl DB {
     ) -> bool {
         typ.player_can_build(
             player.karma,
-            player.story_state,
+            &player.story_state,
             player.civilization_perks(),
-        )
+        ) || self.unlocked_buildings(player.key()).contains(&typ)
     }
 }
 
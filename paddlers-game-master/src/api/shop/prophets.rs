@@ -1,16 +1,19 @@
 use crate::db::{DeferredDbStatement, DB};
-use crate::{ActorAddresses, StringErr};
-use paddlers_shared_lib::{api::shop::*, game_mechanics::prophets::*, prelude::*};
+use crate::{ActorAddresses, GameMasterResult};
+use paddlers_shared_lib::{
+    api::error::GameMasterError, api::shop::*, game_mechanics::prophets::*, prelude::*,
+    specification_types::HoboLevel,
+};
 
 impl DB {
-    fn check_prophet_conditions(&self, p: &Player) -> Result<Price, String> {
+    fn check_prophet_conditions(&self, p: &Player) -> GameMasterResult<Price> {
         let karma = p.karma;
         let prophets_alive = self.player_prophets_count(p.uuid);
         let villlages_owned = self.player_village_count(p.key());
 
         let total_prophets = prophets_alive + villlages_owned - 1;
         if prophets_allowed(karma) <= total_prophets {
-            return Err("Not enough Karma".to_owned());
+            return Err(GameMasterError::NotEnoughKarma);
         }
         Ok(prophet_cost(total_prophets))
     }
@@ -20,14 +23,14 @@ impl DB {
         village: VillageKey,
         addrs: &ActorAddresses,
         p: &Player,
-    ) -> StringErr {
+    ) -> GameMasterResult {
         self.check_prophet_conditions(p)
             .and_then(|cost| self.try_spend(&cost, village))
             .and_then(|()| {
                 addrs
                     .db_actor
                     .try_send(DeferredDbStatement::NewProphet(village))
-                    .map_err(|e| format!("{}", e))
+                    .map_err(|e| GameMasterError::Internal(e.to_string()))
             })
     }
 
@@ -39,6 +42,7 @@ impl DB {
             speed: 0.05,
             hurried: true,
             nest: None,
+            level: HoboLevel::of_anarchist_hp(10, true).num() as i32,
         };
         self.insert_hobo(&prophet);
     }
@@ -2,7 +2,9 @@
 
 use crate::db::DB;
 use crate::{authentication::Authentication, game_master::story_worker::StoryWorkerMessage};
-use actix_web::{web, HttpResponse, Responder};
+use crate::{ApiError, GameMasterResult};
+use actix_web::{web, HttpResponse};
+use paddlers_shared_lib::api::error::GameMasterError;
 use paddlers_shared_lib::prelude::*;
 use paddlers_shared_lib::{api::story::StoryStateTransition, story::story_trigger::StoryTrigger};
 
@@ -11,20 +13,13 @@ pub(crate) async fn story_transition(
     body: web::Json<StoryStateTransition>,
     mut auth: Authentication,
     addr: web::Data<crate::ActorAddresses>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let db: crate::db::DB = pool.get_ref().into();
-    if let Some(player) = auth.player_object(&db) {
-        db.try_execute_story_transition(player, body.0, addr)
-            .map_or_else(
-                |e| HttpResponse::InternalServerError().body(e),
-                |_| HttpResponse::Ok().into(),
-            )
-    } else {
-        HttpResponse::BadRequest().body(format!(
-            "No such player in the database: {}",
-            auth.user.uuid
-        ))
-    }
+    let player = auth
+        .player_object(&db)
+        .ok_or(GameMasterError::PlayerNotCreated)?;
+    db.try_execute_story_transition(player, body.0, addr)?;
+    Ok(HttpResponse::Ok().into())
 }
 
 impl DB {
@@ -33,13 +28,13 @@ impl DB {
         player: &Player,
         msg: StoryStateTransition,
         addr: web::Data<crate::ActorAddresses>,
-    ) -> Result<(), String> {
+    ) -> GameMasterResult {
         let claimed_story_state = msg.now;
         if claimed_story_state != player.story_state {
-            return Err(format!(
-                "Invalid story state: {:?}, database has: {:?}",
-                claimed_story_state, player.story_state
-            ));
+            return Err(GameMasterError::StoryStateMismatch {
+                client: claimed_story_state,
+                server: player.story_state.clone(),
+            });
         }
 
         let trigger = if let Some(choice) = msg.choice {
@@ -1,13 +1,14 @@
 use std::future::{ready, Ready};
 
 use actix_web::dev::Payload;
-use actix_web::error::{ErrorBadRequest, ErrorUnauthorized};
 use actix_web::web::Data;
 use actix_web::{Error, FromRequest, HttpRequest};
 
 use crate::db::DB;
+use crate::{ApiError, GameMasterResult};
+use paddlers_shared_lib::api::error::GameMasterError;
 use paddlers_shared_lib::prelude::*;
-use paddlers_shared_lib::user_authentication::PadlUser;
+use paddlers_shared_lib::user_authentication::{AuthenticationError, PadlUser};
 
 pub struct Authentication {
     pub user: PadlUser,
@@ -24,23 +25,35 @@ impl FromRequest for Authentication {
             let config: &Config = req.app_data::<Data<Config>>().expect("Need config");
             match req.headers().get(actix_web::http::header::AUTHORIZATION) {
                 Some(auth_header) => match auth_header.to_str() {
-                    Ok(token) => match PadlUser::from_token(token, &config) {
+                    Ok(token) => match verify_token(token, &config) {
                         Ok(user) => Ok(Authentication {
                             user,
                             _private: (),
                             cached_player: None,
                         }),
-                        Err(e) => Err(ErrorUnauthorized(e))?,
+                        Err(_e) => Err(ApiError(GameMasterError::NotAuthenticated))?,
                     },
-                    Err(_e) => Err(ErrorBadRequest("Unable to parse token"))?,
+                    Err(_e) => Err(ApiError(GameMasterError::NotAuthenticated))?,
                 },
-                None => Err(ErrorUnauthorized("No Authorization Token provided"))?,
+                None => Err(ApiError(GameMasterError::NotAuthenticated))?,
             }
         }
         ready(authenticate(req))
     }
 }
 
+#[cfg(not(test))]
+fn verify_token(token: &str, config: &Config) -> Result<PadlUser, AuthenticationError> {
+    PadlUser::from_token(token, config)
+}
+/// In tests, players authenticate with their plain UUID as token
+#[cfg(test)]
+fn verify_token(token: &str, _config: &Config) -> Result<PadlUser, AuthenticationError> {
+    uuid::Uuid::parse_str(token)
+        .map(PadlUser::unverified)
+        .map_err(|_| AuthenticationError::InvalidSubject)
+}
+
 impl Authentication {
     pub(crate) fn player_object(&mut self, db: &DB) -> Option<&Player> {
         if self.cached_player.is_none() {
@@ -48,9 +61,9 @@ impl Authentication {
         }
         self.cached_player.as_ref()
     }
-    pub(crate) fn player_key(&mut self, db: &DB) -> Result<PlayerKey, String> {
+    pub(crate) fn player_key(&mut self, db: &DB) -> GameMasterResult<PlayerKey> {
         self.player_object(&db)
-            .ok_or("No such player".to_owned())
+            .ok_or(GameMasterError::PlayerNotCreated)
             .map(|p| p.key())
     }
 }
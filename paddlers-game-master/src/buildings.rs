@@ -4,7 +4,7 @@ pub struct BuildingFactory;
 
 impl BuildingFactory {
     pub fn new(typ: BuildingType, pos: (usize, usize), village: VillageKey) -> NewBuilding {
-        let now = chrono::Utc::now().naive_utc();
+        let now = crate::clock::naive_now();
         NewBuilding {
             x: pos.0 as i32,
             y: pos.1 as i32,
@@ -0,0 +1,234 @@
+//! The game clock of the game-master.
+//!
+//! All game logic reads the current time through this module instead of calling `chrono::Utc::now()` directly.
+//! By default, the clock follows the system time.
+//! For testing, the clock can run faster than real time or it can be completely driven by hand.
+//!
+//! The mode is selected with the environment variable `GAME_CLOCK`:
+//!     - `real` (default)
+//!     - `fast-forward:<factor>`, e.g. `fast-forward:60` for one game minute per second
+//!     - `manual`, time only moves when advanced through `Clock::advance`
+//!
+//! Simulated clocks are only available in builds with the feature `local_test`, other builds always run in real time.
+//! With a simulated clock, the route `/clock/advance` is available to skip game time.
+//! It requires no authentication, which is why it must never be reachable on a production server.
+//! Note that other services (db-interface, frontend) always use the real time.
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use once_cell::sync::OnceCell;
+use paddlers_shared_lib::api::{clock::ClockAdvance, error::GameMasterError};
+use std::sync::Mutex;
+
+static CLOCK: OnceCell<Clock> = OnceCell::new();
+
+/// Shortest interval actors are allowed to sleep between polls, regardless of the clock speed
+const MIN_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClockMode {
+    RealTime,
+    FastForward(f64),
+    Manual,
+}
+
+pub struct Clock {
+    mode: ClockMode,
+    state: Mutex<ClockState>,
+}
+
+struct ClockState {
+    /// Real time at which the current game time origin has been set
+    real_origin: DateTime<Utc>,
+    /// Game time at `real_origin`
+    game_origin: DateTime<Utc>,
+}
+
+impl Clock {
+    pub fn new(mode: ClockMode, start: DateTime<Utc>) -> Self {
+        Clock {
+            mode,
+            state: Mutex::new(ClockState {
+                real_origin: Utc::now(),
+                game_origin: start,
+            }),
+        }
+    }
+    pub fn from_env() -> Self {
+        let mode = std::env::var("GAME_CLOCK")
+            .ok()
+            .map(|s| {
+                ClockMode::parse(&s).unwrap_or_else(|| {
+                    eprintln!("Invalid GAME_CLOCK value '{}', using real time", s);
+                    ClockMode::RealTime
+                })
+            })
+            .unwrap_or(ClockMode::RealTime);
+        if mode != ClockMode::RealTime && !cfg!(feature = "local_test") {
+            eprintln!("GAME_CLOCK is only supported with the feature local_test, using real time");
+            return Clock::new(ClockMode::RealTime, Utc::now());
+        }
+        Clock::new(mode, Utc::now())
+    }
+    pub fn mode(&self) -> ClockMode {
+        self.mode
+    }
+    /// Saturates at the latest representable time rather than overflowing.
+    /// This cannot be reached by advancing the clock, only by running fast-forward for an absurdly long time.
+    pub fn now(&self) -> DateTime<Utc> {
+        let state = self.state.lock().unwrap();
+        match self.mode {
+            ClockMode::RealTime => Utc::now() + (state.game_origin - state.real_origin),
+            ClockMode::FastForward(factor) => {
+                let real_elapsed = (Utc::now() - state.real_origin)
+                    .to_std()
+                    .unwrap_or_default();
+                std::time::Duration::try_from_secs_f64(real_elapsed.as_secs_f64() * factor)
+                    .ok()
+                    .and_then(|game_elapsed| Duration::from_std(game_elapsed).ok())
+                    .and_then(|game_elapsed| state.game_origin.checked_add_signed(game_elapsed))
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC)
+            }
+            ClockMode::Manual => state.game_origin,
+        }
+    }
+    /// Moves the game time forward without waiting.
+    /// Not available with a real-time clock.
+    pub fn advance(&self, d: Duration) -> Result<DateTime<Utc>, &'static str> {
+        if self.mode == ClockMode::RealTime {
+            return Err("Cannot advance a real-time clock");
+        }
+        if d < Duration::zero() {
+            return Err("Game time cannot go backwards");
+        }
+        let mut state = self.state.lock().unwrap();
+        state.game_origin = state
+            .game_origin
+            .checked_add_signed(d)
+            .ok_or("Game time out of range")?;
+        Ok(match self.mode {
+            ClockMode::Manual => state.game_origin,
+            _ => {
+                drop(state);
+                self.now()
+            }
+        })
+    }
+    /// Converts a duration in game time to the real duration an actor should wait before polling again.
+    pub fn real_interval(&self, game_interval: std::time::Duration) -> std::time::Duration {
+        match self.mode {
+            ClockMode::RealTime | ClockMode::Manual => game_interval,
+            ClockMode::FastForward(factor) => game_interval.div_f64(factor).max(MIN_POLL_INTERVAL),
+        }
+    }
+}
+
+impl ClockMode {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "real" => Some(ClockMode::RealTime),
+            "manual" => Some(ClockMode::Manual),
+            _ => {
+                let factor: f64 = s.strip_prefix("fast-forward:")?.parse().ok()?;
+                if factor > 0.0 {
+                    Some(ClockMode::FastForward(factor))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Sets the clock used by the game-master. Must be called before any actor is started.
+pub fn install(clock: Clock) {
+    if CLOCK.set(clock).is_err() {
+        panic!("Game clock installed twice");
+    }
+}
+
+/// The installed game clock, or a real-time clock if none has been installed
+pub fn clock() -> &'static Clock {
+    CLOCK.get_or_init(|| Clock::new(ClockMode::RealTime, Utc::now()))
+}
+
+pub fn now() -> DateTime<Utc> {
+    clock().now()
+}
+
+pub fn naive_now() -> NaiveDateTime {
+    now().naive_utc()
+}
+
+pub fn real_interval(game_interval: std::time::Duration) -> std::time::Duration {
+    clock().real_interval(game_interval)
+}
+
+/// Lets tests move the game time forward. Only routed when the clock is simulated.
+pub(crate) async fn advance_clock(
+    body: web::Json<ClockAdvance>,
+) -> Result<HttpResponse, crate::ApiError> {
+    let now = body
+        .seconds
+        .checked_mul(1000)
+        .map(Duration::milliseconds)
+        .ok_or("Game time out of range")
+        .and_then(|d| clock().advance(d))
+        .map_err(|msg| GameMasterError::InvalidRequest(msg.to_owned()))?;
+    Ok(HttpResponse::Ok().body(now.to_rfc3339()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn manual_clock_only_moves_when_advanced() {
+        let start = Utc.with_ymd_and_hms(2021, 2, 21, 12, 0, 0).unwrap();
+        let clock = Clock::new(ClockMode::Manual, start);
+        assert_eq!(clock.now(), start);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert_eq!(clock.now(), start);
+        clock.advance(Duration::hours(24)).unwrap();
+        assert_eq!(clock.now(), start + Duration::hours(24));
+        assert!(clock.advance(Duration::seconds(-1)).is_err());
+        assert!(clock.advance(Duration::max_value()).is_err());
+        assert_eq!(clock.now(), start + Duration::hours(24));
+    }
+
+    #[test]
+    fn fast_forward_clock_scales_time() {
+        let start = Utc.with_ymd_and_hms(2021, 2, 21, 12, 0, 0).unwrap();
+        let clock = Clock::new(ClockMode::FastForward(60.0), start);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(clock.now() >= start + Duration::seconds(6));
+        assert_eq!(
+            clock.real_interval(std::time::Duration::from_secs(60)),
+            std::time::Duration::from_secs(1)
+        );
+        assert!(Clock::new(ClockMode::RealTime, start)
+            .advance(Duration::seconds(1))
+            .is_err());
+    }
+
+    #[test]
+    fn fast_forward_clock_saturates() {
+        let start = Utc.with_ymd_and_hms(2021, 2, 21, 12, 0, 0).unwrap();
+        let clock = Clock::new(ClockMode::FastForward(f64::MAX), start);
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        assert_eq!(clock.now(), DateTime::<Utc>::MAX_UTC);
+    }
+
+    #[test]
+    fn parse_clock_mode() {
+        assert_eq!(ClockMode::parse("real"), Some(ClockMode::RealTime));
+        assert_eq!(ClockMode::parse("manual"), Some(ClockMode::Manual));
+        assert_eq!(
+            ClockMode::parse("fast-forward:60"),
+            Some(ClockMode::FastForward(60.0))
+        );
+        assert_eq!(ClockMode::parse("fast-forward:0"), None);
+        assert_eq!(ClockMode::parse("fast"), None);
+    }
+}
@@ -39,4 +39,7 @@ impl GameDB for DB {
     fn dbconn(&self) -> &PgConnection {
         &self.0
     }
+    fn now(&self) -> chrono::NaiveDateTime {
+        crate::clock::naive_now()
+    }
 }
@@ -19,18 +19,6 @@ impl Handler<DeferredDbStatement> for DbActor {
             DeferredDbStatement::NewProphet(village) => {
                 self.db().add_prophet(village);
             }
-            DeferredDbStatement::NewAttack(planned_atk) => {
-                let attack = self.db().insert_attack(&planned_atk.attack);
-                for hobo in planned_atk.hobos.iter() {
-                    let atu = AttackToHobo {
-                        attack_id: attack.id,
-                        hobo_id: hobo.num(),
-                        satisfied: None,
-                        released: None,
-                    };
-                    self.db().insert_attack_to_hobo(&atu);
-                }
-            }
             DeferredDbStatement::AssignQuest(player, quest_name) => {
                 let db = self.db();
                 // Potential for optimization: Keep quest assignment cached in memory (probably in a separate actor)
@@ -95,12 +83,16 @@ impl Handler<CollectReportRewardsMessage> for DbActor {
         let report = msg.0;
         let village = report.village();
         let db = self.db();
-        for (resource_type, n) in db.rewards(report.key()) {
+        let rewards = db.rewards(report.key());
+        for &(resource_type, n) in &rewards {
             if let Err(e) = db.add_resource(resource_type, village, n) {
                 eprintln!("Reward collection failed: {}", e);
             }
         }
         if let Some(player) = db.player_by_village(village) {
+            for (resource_type, n) in rewards {
+                db.add_quest_progress(player.key(), QuestStatType::gathered(resource_type), n);
+            }
             match db.add_karma(player.key(), report.karma) {
                 Err(e) => eprintln!("Karma reward collection failed: {}", e),
                 Ok(_) => {}
@@ -1,21 +1,51 @@
 use super::{CollectQuestMessage, DbActor};
+use crate::db::DB;
+use crate::worker_actions::worker_updates::MutWorkerDBEntity;
 use actix::{Handler, SyncContext};
-use paddlers_shared_lib::prelude::GameDB;
+use paddlers_shared_lib::game_mechanics::worker::hero_max_mana;
+use paddlers_shared_lib::prelude::{GameDB, SqlKey};
 
 impl Handler<CollectQuestMessage> for DbActor {
     type Result = ();
     fn handle(&mut self, msg: CollectQuestMessage, _ctx: &mut SyncContext<Self>) -> Self::Result {
-        let db = self.db();
-        for reward in db.quest_res_rewards(msg.quest) {
-            if let Err(e) = db.add_resource(reward.resource_type, msg.village, reward.amount) {
+        self.db().collect_quest(&msg);
+    }
+}
+
+impl DB {
+    /// Grants all rewards of a quest, assigns the follow-up quest and removes the collected quest from the player
+    pub(crate) fn collect_quest(&self, msg: &CollectQuestMessage) {
+        for reward in self.quest_res_rewards(msg.quest) {
+            if let Err(e) = self.add_resource(reward.resource_type, msg.village, reward.amount) {
                 eprintln!("Reward collection failed: {}", e);
             }
         }
+        if let Some(reward) = self.quest_worker_reward(msg.quest) {
+            if let Some(mut hero) = self.hero(msg.village) {
+                if reward.experience > 0 {
+                    hero.add_exp(reward.experience as i32);
+                    self.update_worker(&hero);
+                }
+                if reward.mana > 0 {
+                    self.add_worker_mana(hero.key(), reward.mana as i32, hero_max_mana());
+                }
+            }
+        }
+        for reward in self.quest_perk_rewards(msg.quest) {
+            if let Err(e) = self.unlock_civ_perk(msg.player, reward.perk) {
+                eprintln!("Unlocking perk as quest reward failed: {}", e);
+            }
+        }
+        for reward in self.quest_building_rewards(msg.quest) {
+            if let Err(e) = self.unlock_building(msg.player, reward.building_type) {
+                eprintln!("Unlocking building as quest reward failed: {}", e);
+            }
+        }
         if let Some(quest) = msg.follow_up_quest {
-            if let Err(e) = db.assign_player_quest(msg.player, quest) {
+            if let Err(e) = self.assign_player_quest(msg.player, quest) {
                 eprintln!("Assigning follow up quest failed: {}", e);
             }
         }
-        db.delete_player_quest(msg.player, msg.quest);
+        self.delete_player_quest(msg.player, msg.quest);
     }
 }
@@ -12,7 +12,6 @@ use paddlers_shared_lib::{
 pub enum DeferredDbStatement {
     AddMana(PlayerKey, i16),
     AssignQuest(PlayerKey, QuestName),
-    NewAttack(ScheduledAttack),
     NewProphet(VillageKey),
     PlayerUpdate(PlayerKey, StoryState),
     UnlockCivPerk(PlayerKey, CivilizationPerk),
@@ -20,11 +19,6 @@ pub enum DeferredDbStatement {
 impl Message for DeferredDbStatement {
     type Result = ();
 }
-#[derive(Debug)]
-pub struct ScheduledAttack {
-    pub attack: NewAttack,
-    pub hobos: Vec<HoboKey>,
-}
 
 pub struct NewHoboMessage(pub NewHobo);
 #[derive(Debug)]
@@ -1,11 +1,33 @@
 use super::*;
 use diesel::*;
 use paddlers_shared_lib::{
-    civilization::CivilizationPerk, civilization::CivilizationPerks, models::dsl, schema::*,
-    story::story_state::StoryState,
+    civilization::CivilizationPerk, civilization::CivilizationPerks, models::dsl,
+    notifications::ChangeEvent, schema::*, story::story_state::StoryState,
 };
 
+/// Progress of the quests of player `$1`, see `add_open_quest_progress`
+const PLAYER_QUEST_PROGRESS: &str =
+    "UPDATE quest_progress SET amount = quest_progress.amount + $3 \
+    FROM quest_stat_conditions \
+    WHERE quest_progress.player_id = $1 AND quest_progress.stat_type = $2 \
+    AND quest_stat_conditions.quest_id = quest_progress.quest_id \
+    AND quest_stat_conditions.stat_type = quest_progress.stat_type \
+    AND quest_progress.amount < quest_stat_conditions.amount";
+/// Progress of the quests of the player owning village `$1`, see `add_open_quest_progress`
+const VILLAGE_QUEST_PROGRESS: &str =
+    "UPDATE quest_progress SET amount = quest_progress.amount + $3 \
+    FROM quest_stat_conditions, villages \
+    WHERE villages.id = $1 AND quest_progress.player_id = villages.player_id \
+    AND quest_progress.stat_type = $2 \
+    AND quest_stat_conditions.quest_id = quest_progress.quest_id \
+    AND quest_stat_conditions.stat_type = quest_progress.stat_type \
+    AND quest_progress.amount < quest_stat_conditions.amount";
+
 impl DB {
+    /// Tells other services about the change, see `paddlers_shared_lib::notifications`
+    pub fn notify(&self, event: ChangeEvent) {
+        event.notify(self.dbconn());
+    }
     pub fn insert_player(&self, u: &NewPlayer) -> QueryResult<Player> {
         diesel::insert_into(players::dsl::players)
             .values(u)
@@ -13,9 +35,11 @@ impl DB {
     }
     pub fn set_story_state(&self, p: PlayerKey, story_state: StoryState) -> QueryResult<Player> {
         let target = players::table.find(p.num());
-        diesel::update(target)
+        let player = diesel::update(target)
             .set(players::story_state.eq(story_state))
-            .get_result(self.dbconn())
+            .get_result(self.dbconn())?;
+        self.notify(ChangeEvent::Player(p));
+        Ok(player)
     }
     pub fn unlock_civ_perk(&self, p: PlayerKey, perk: CivilizationPerk) -> QueryResult<Player> {
         let target = players::table.find(p.num());
@@ -24,14 +48,34 @@ impl DB {
         let mask = new_perks.encode() as i64;
         // This should be done with bitwise or (|) instead of an additional lookup. It seems not supported in diesel out-of-the-box. However, `diesel_infix_operator!` should offer a solution, but is it worth it? (I tried for ~30min and then gave up.)
         let old_value = self.player(p).unwrap().civ_perks;
-        diesel::update(target)
+        let player = diesel::update(target)
             .set(players::civ_perks.eq(mask | old_value))
-            .get_result(self.dbconn())
+            .get_result(self.dbconn())?;
+        self.notify(ChangeEvent::Player(p));
+        Ok(player)
+    }
+    pub fn unlock_building(&self, p: PlayerKey, building_type: BuildingType) -> QueryResult<usize> {
+        let unlocked = UnlockedBuilding {
+            player_id: p.num(),
+            building_type,
+        };
+        let n = diesel::insert_into(unlocked_buildings::dsl::unlocked_buildings)
+            .values(&unlocked)
+            .on_conflict_do_nothing()
+            .execute(self.dbconn())?;
+        self.notify(ChangeEvent::Player(p));
+        Ok(n)
     }
 
-    pub fn delete_attack_hobos(&self, atk: AttackKey) {
+    /// Deletes the visitors of the attack that do not live in a nest.
+    /// Hobos in nests of anarchist villages can be invited, they stay.
+    pub fn delete_spawned_visitors(&self, atk: AttackKey) {
         // Performance: This is a lot of sequential queries, could be reduced to one
-        for hobo in self.attack_hobos(atk) {
+        for hobo in self
+            .attack_hobos(atk)
+            .into_iter()
+            .filter(|h| h.nest.is_none())
+        {
             let result = diesel::delete(&hobo).execute(self.dbconn());
             if result.is_err() {
                 println!("Couldn't delete hobo {:?}", hobo);
@@ -42,23 +86,30 @@ impl DB {
     pub fn start_fight(&self, atk: AttackKey, village_filter: Option<VillageKey>) {
         let query = diesel::update(attacks::table)
             .filter(attacks::id.eq(atk.num()))
-            .set(attacks::entered_destination.eq(diesel::dsl::now.at_time_zone("UTC").nullable()));
-        let result;
+            .set(attacks::entered_destination.eq(self.now()));
+        let result: QueryResult<Vec<Attack>>;
         if let Some(VillageKey(vid)) = village_filter {
             result = query
                 .filter(attacks::destination_village_id.eq(vid))
-                .execute(self.dbconn())
+                .get_results(self.dbconn())
         } else {
-            result = query.execute(self.dbconn())
+            result = query.get_results(self.dbconn())
         };
-        if result.is_err() {
-            println!("Couldn't start fight {:?}", atk);
+        match result {
+            Ok(attacks) => {
+                for attack in attacks {
+                    self.notify(ChangeEvent::Attacks(attack.destination()));
+                }
+            }
+            Err(_) => println!("Couldn't start fight {:?}", atk),
         }
     }
     pub fn delete_attack(&self, atk: &Attack) {
         let result = diesel::delete(atk).execute(self.dbconn());
         if result.is_err() {
             println!("Couldn't delete attack {:?}", atk);
+        } else {
+            self.notify(ChangeEvent::Attacks(atk.destination()));
         }
     }
 
@@ -87,28 +138,59 @@ impl DB {
             .execute(self.dbconn())
             .expect("Updating worker for mana");
     }
-    pub fn insert_attack(&self, new_attack: &NewAttack) -> Attack {
-        diesel::insert_into(attacks::dsl::attacks)
+    pub fn insert_attack(&self, new_attack: &NewAttack) -> QueryResult<Attack> {
+        let attack: Attack = diesel::insert_into(attacks::dsl::attacks)
             .values(new_attack)
-            .get_result(self.dbconn())
-            .expect("Inserting attack")
-    }
-    pub fn insert_attack_to_hobo(&self, atu: &AttackToHobo) {
+            .get_result(self.dbconn())?;
+        self.notify(ChangeEvent::Attacks(attack.destination()));
+        Ok(attack)
+    }
+    /// Locks the village row until the end of the current transaction
+    pub fn lock_village(&self, v: VillageKey) -> QueryResult<Village> {
+        villages::table
+            .find(v.num())
+            .for_update()
+            .first(self.dbconn())
+    }
+    /// Returns one of the given hobos that is currently part of an attack, if there is any
+    pub fn any_hobo_in_attack(&self, hobos: &[HoboKey]) -> QueryResult<Option<HoboKey>> {
+        let ids: Vec<i64> = hobos.iter().map(HoboKey::num).collect();
+        attacks_to_hobos::table
+            .filter(attacks_to_hobos::hobo_id.eq_any(ids))
+            .select(attacks_to_hobos::hobo_id)
+            .first::<i64>(self.dbconn())
+            .optional()
+            .map(|id| id.map(HoboKey))
+    }
+    pub fn insert_attack_to_hobo(&self, atu: &AttackToHobo) -> QueryResult<usize> {
         diesel::insert_into(attacks_to_hobos::dsl::attacks_to_hobos)
             .values(atu)
             .execute(self.dbconn())
-            .expect("Inserting attack to hobo");
     }
     pub fn insert_resource(&self, res: &Resource) -> QueryResult<usize> {
-        diesel::insert_into(dsl::resources)
+        let n = diesel::insert_into(dsl::resources)
             .values(res)
-            .execute(self.dbconn())
+            .execute(self.dbconn())?;
+        self.notify(ChangeEvent::Resources(VillageKey(res.village_id)));
+        Ok(n)
     }
     pub fn add_resource(
         &self,
         rt: ResourceType,
         vk: VillageKey,
         plus: i64,
+    ) -> QueryResult<Resource> {
+        let resource = self.add_produced_resource(rt, vk, plus)?;
+        self.notify(ChangeEvent::Resources(vk));
+        Ok(resource)
+    }
+    /// Like `add_resource` but without a change event.
+    /// Workers produce continuously, clients poll for that instead of being notified on every economy tick.
+    pub fn add_produced_resource(
+        &self,
+        rt: ResourceType,
+        vk: VillageKey,
+        plus: i64,
     ) -> QueryResult<Resource> {
         let target = resources::table.find((rt, vk.num()));
         diesel::update(target)
@@ -117,9 +199,11 @@ impl DB {
     }
     pub fn add_karma(&self, p: PlayerKey, plus: i64) -> QueryResult<Player> {
         let target = players::table.find(p.num());
-        diesel::update(target)
+        let player = diesel::update(target)
             .set(players::karma.eq(players::karma + plus))
-            .get_result(self.dbconn())
+            .get_result(self.dbconn())?;
+        self.notify(ChangeEvent::Player(p));
+        Ok(player)
     }
     pub fn insert_building(&self, new_building: &NewBuilding) -> Building {
         diesel::insert_into(buildings::dsl::buildings)
@@ -164,7 +248,7 @@ impl DB {
     }
     pub fn flush_task_queue(&self, worker_id: WorkerKey) {
         diesel::delete(tasks::table.filter(tasks::worker_id.eq(worker_id.num())))
-            .filter(tasks::start_time.gt(diesel::dsl::now.at_time_zone("UTC")))
+            .filter(tasks::start_time.gt(self.now()))
             .execute(self.dbconn())
             .expect("Deleting task");
     }
@@ -195,7 +279,7 @@ impl DB {
     pub fn update_ability_used_timestamp(&self, worker: WorkerKey, at: AbilityType) {
         let target = abilities::table.find((at, worker.num()));
         diesel::update(target)
-            .set(abilities::last_used.eq(diesel::dsl::now.at_time_zone("UTC").nullable()))
+            .set(abilities::last_used.eq(self.now()))
             .execute(self.dbconn())
             .expect("Updating ability timestamp");
     }
@@ -208,7 +292,7 @@ impl DB {
     pub fn update_worker_flag_timestamp_now(&self, w: WorkerKey, f: WorkerFlagType) {
         let target = worker_flags::table.find((w.num(), f));
         diesel::update(target)
-            .set(worker_flags::last_update.eq(diesel::dsl::now.at_time_zone("UTC")))
+            .set(worker_flags::last_update.eq(self.now()))
             .execute(self.dbconn())
             .expect("Updating flag timestamp to now");
     }
@@ -224,17 +308,41 @@ impl DB {
             .execute(self.dbconn())
             .expect("Updating flag timestamp");
     }
+    pub fn worker_flag(&self, worker: WorkerKey, f: WorkerFlagType) -> Option<WorkerFlag> {
+        worker_flags::table
+            .find((worker.num(), f))
+            .first(self.dbconn())
+            .optional()
+            .expect("Error loading data")
+    }
     pub fn worker_flags(&self, worker: WorkerKey) -> Vec<WorkerFlag> {
         worker_flags::table
             .filter(worker_flags::worker_id.eq(worker.num()))
             .get_results(self.dbconn())
             .expect("Error loading data")
     }
+    pub fn insert_scheduled_event(&self, e: &NewScheduledEvent) -> QueryResult<ScheduledEvent> {
+        diesel::insert_into(scheduled_events::dsl::scheduled_events)
+            .values(e)
+            .get_result(self.dbconn())
+    }
+    pub fn delete_scheduled_event(&self, id: i64) -> QueryResult<usize> {
+        diesel::delete(scheduled_events::table.find(id)).execute(self.dbconn())
+    }
+    /// All persisted events, the earliest due first
+    pub fn scheduled_events(&self) -> Vec<ScheduledEvent> {
+        scheduled_events::table
+            .order_by(scheduled_events::due.asc())
+            .load(self.dbconn())
+            .expect("Error loading scheduled events")
+    }
     pub fn insert_visit_report(&self, vr: NewVisitReport) -> VisitReport {
-        diesel::insert_into(visit_reports::dsl::visit_reports)
+        let report: VisitReport = diesel::insert_into(visit_reports::dsl::visit_reports)
             .values(vr)
             .get_result(self.dbconn())
-            .expect("Inserting visit report")
+            .expect("Inserting visit report");
+        self.notify(ChangeEvent::VisitReports(report.village()));
+        report
     }
     pub fn insert_visit_report_rewards(&self, rewards: Vec<NewReward>) {
         diesel::insert_into(rewards::dsl::rewards)
@@ -246,6 +354,8 @@ impl DB {
         let result = diesel::delete(obj).execute(self.dbconn());
         if result.is_err() {
             println!("Couldn't delete {:?}", obj);
+        } else {
+            self.notify(ChangeEvent::VisitReports(obj.village()));
         }
     }
     pub fn set_satisfied(&self, hid: HoboKey, aid: AttackKey, satisfied: bool) {
@@ -255,21 +365,95 @@ impl DB {
             .execute(self.dbconn())
             .expect("setting satisfied");
     }
+    pub fn set_cheers(&self, hid: HoboKey, aid: AttackKey, cheers: i32) {
+        let target = attacks_to_hobos::table.find((aid.num(), hid.num()));
+        diesel::update(target)
+            .set(attacks_to_hobos::cheers.eq(cheers))
+            .execute(self.dbconn())
+            .expect("setting cheers");
+    }
+    /// Marks the cheers of all visitors of the attacks as counted until the given time
+    pub fn set_cheered_until(&self, attacks: &[AttackKey], t: chrono::NaiveDateTime) {
+        let ids: Vec<i64> = attacks.iter().map(|a| a.num()).collect();
+        diesel::update(attacks_to_hobos::table.filter(attacks_to_hobos::attack_id.eq_any(ids)))
+            .set(attacks_to_hobos::cheered_until.eq(t))
+            .execute(self.dbconn())
+            .expect("setting cheered until");
+    }
     pub fn release_resting_visitor(&self, hid: HoboKey, aid: AttackKey) {
         let target = attacks_to_hobos::table.find((aid.num(), hid.num()));
         diesel::update(target)
-            .set(attacks_to_hobos::released.eq(diesel::dsl::now.at_time_zone("UTC").nullable()))
+            .set(attacks_to_hobos::released.eq(self.now()))
             .execute(self.dbconn())
             .expect("setting released");
     }
+    /// Assigns the quest together with its progress counters, a quest is never visible without them
     pub fn assign_player_quest(&self, p: PlayerKey, q: QuestKey) -> QueryResult<usize> {
-        let qtp = QuestToPlayer {
-            player_id: p.num(),
-            quest_id: q.num(),
-        };
-        diesel::insert_into(quest_to_player::dsl::quest_to_player)
-            .values(qtp)
+        let result = self.dbconn().transaction(|| {
+            let qtp = QuestToPlayer {
+                player_id: p.num(),
+                quest_id: q.num(),
+            };
+            let n = diesel::insert_into(quest_to_player::dsl::quest_to_player)
+                .values(qtp)
+                .execute(self.dbconn())?;
+            let progress: Vec<QuestProgress> = self
+                .quest_stat_conditions(q)
+                .into_iter()
+                .filter(|c| c.stat_type.is_tracked())
+                .map(|c| QuestProgress {
+                    quest_id: q.num(),
+                    player_id: p.num(),
+                    stat_type: c.stat_type,
+                    amount: 0,
+                })
+                .collect();
+            if !progress.is_empty() {
+                diesel::insert_into(quest_progress::table)
+                    .values(&progress)
+                    .on_conflict_do_nothing()
+                    .execute(self.dbconn())?;
+            }
+            Ok(n)
+        });
+        if result.is_ok() {
+            self.notify(ChangeEvent::Quests(p));
+        }
+        result
+    }
+    /// Counts an event for all quests of the player that track the statistic
+    pub fn add_quest_progress(&self, p: PlayerKey, stat: QuestStatType, amount: i64) {
+        if self.add_open_quest_progress(PLAYER_QUEST_PROGRESS, p.num(), stat, amount) > 0 {
+            self.notify(ChangeEvent::Quests(p));
+        }
+    }
+    /// Counts an event of a village, the owner is only looked up if a quest has made progress
+    pub fn add_village_quest_progress(&self, v: VillageKey, stat: QuestStatType, amount: i64) {
+        if self.add_open_quest_progress(VILLAGE_QUEST_PROGRESS, v.num(), stat, amount) > 0 {
+            if let Some(player) = self.player_by_village(v) {
+                self.notify(ChangeEvent::Quests(player.key()));
+            }
+        }
+    }
+    /// Updates the counters of a statistic that have not reached the goal of their quest condition, yet.
+    /// Frequent events, like gathering resources, thus only cost one UPDATE without any effect while no quest waits for them.
+    /// `query` is one of the statements below, `owner` is bound to its first parameter
+    fn add_open_quest_progress(
+        &self,
+        query: &'static str,
+        owner: i64,
+        stat: QuestStatType,
+        amount: i64,
+    ) -> usize {
+        diesel::sql_query(query)
+            .bind::<sql_types::BigInt, _>(owner)
+            .bind::<paddlers_shared_lib::models::Quest_stat_type, _>(stat)
+            .bind::<sql_types::BigInt, _>(amount)
             .execute(self.dbconn())
+            .unwrap_or_else(|e| {
+                eprintln!("Updating quest progress failed: {}", e);
+                0
+            })
     }
     pub fn delete_player_quest(&self, p: PlayerKey, q: QuestKey) {
         diesel::delete(
@@ -279,5 +463,146 @@ impl DB {
         )
         .execute(self.dbconn())
         .expect("Deleting quest association");
+        self.notify(ChangeEvent::Quests(p));
+    }
+    /// Returns the number of inserted rows, zero if the player already used the key
+    pub fn insert_idempotency_key(&self, record: &IdempotencyKey) -> QueryResult<usize> {
+        diesel::insert_into(idempotency_keys::table)
+            .values(record)
+            .on_conflict_do_nothing()
+            .execute(self.dbconn())
+    }
+    pub fn idempotency_key(&self, player: uuid::Uuid, key: &str) -> QueryResult<IdempotencyKey> {
+        idempotency_keys::table
+            .find((player, key))
+            .get_result(self.dbconn())
+    }
+    pub fn record_idempotent_response(
+        &self,
+        player: uuid::Uuid,
+        key: &str,
+        status: i16,
+        content_type: Option<String>,
+        body: Vec<u8>,
+        expires: chrono::NaiveDateTime,
+    ) -> QueryResult<usize> {
+        diesel::update(idempotency_keys::table.find((player, key)))
+            .set((
+                idempotency_keys::status.eq(status),
+                idempotency_keys::content_type.eq(content_type),
+                idempotency_keys::response_body.eq(body),
+                idempotency_keys::expires.eq(expires),
+            ))
+            .execute(self.dbconn())
+    }
+    pub fn delete_idempotency_key(&self, player: uuid::Uuid, key: &str) -> QueryResult<usize> {
+        diesel::delete(idempotency_keys::table.find((player, key))).execute(self.dbconn())
+    }
+    pub fn delete_expired_idempotency_keys(
+        &self,
+        player: uuid::Uuid,
+        now: chrono::NaiveDateTime,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            idempotency_keys::table
+                .filter(idempotency_keys::player_uuid.eq(player))
+                .filter(idempotency_keys::expires.le(now)),
+        )
+        .execute(self.dbconn())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::setup::initialize_new_player_account;
+    use paddlers_shared_lib::api::PlayerInitData;
+
+    /// Nothing is committed, the test data disappears with the connection
+    fn test_db() -> DB {
+        let db: DB = (&DB::new_pool()).into();
+        db.dbconn().begin_test_transaction().unwrap();
+        db
+    }
+
+    fn new_player(db: &DB) -> (PlayerKey, VillageKey) {
+        let uuid = uuid::Uuid::new_v4();
+        let info = PlayerInitData {
+            display_name: "Quest Progress Test".to_owned(),
+            utc_offset_minutes: 0,
+        };
+        initialize_new_player_account(db, uuid, &info).unwrap();
+        let player = db.player_by_uuid(uuid).unwrap();
+        let village = db.player_villages(player.key())[0].key();
+        (player.key(), village)
+    }
+
+    /// A quest that wants three welcomed visitors, ten gathered sticks and a hero on level two
+    fn insert_quest(db: &DB) -> QuestKey {
+        let quest: Quest = diesel::insert_into(quests::table)
+            .values(&NewQuest {
+                quest_key: "QuestProgressTest".to_owned(),
+                karma_condition: None,
+                pop_condition: None,
+                follow_up_quest: None,
+            })
+            .get_result(db.dbconn())
+            .unwrap();
+        let conditions = [
+            (QuestStatType::WelcomedVisitors, 3),
+            (QuestStatType::GatheredSticks, 10),
+            (QuestStatType::WorkerLevel, 2),
+        ];
+        for &(stat_type, amount) in &conditions {
+            diesel::insert_into(quest_stat_conditions::table)
+                .values(&NewQuestStatCondition {
+                    quest_id: quest.id,
+                    stat_type,
+                    amount,
+                })
+                .execute(db.dbconn())
+                .unwrap();
+        }
+        quest.key()
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn quest_progress_is_counted_until_the_goal() {
+        let db = test_db();
+        let quest = insert_quest(&db);
+        let (player, village) = new_player(&db);
+        let (other_player, _) = new_player(&db);
+
+        // Events before the assignment do not count
+        db.add_quest_progress(player, QuestStatType::WelcomedVisitors, 1);
+        db.assign_player_quest(player, quest).unwrap();
+        db.assign_player_quest(other_player, quest).unwrap();
+        let progress = |p, stat| db.quest_progress(quest, p, stat);
+        assert_eq!(progress(player, QuestStatType::WelcomedVisitors), 0);
+
+        db.add_quest_progress(player, QuestStatType::WelcomedVisitors, 1);
+        assert_eq!(progress(player, QuestStatType::WelcomedVisitors), 1);
+        assert_eq!(progress(other_player, QuestStatType::WelcomedVisitors), 0);
+
+        // The event that reaches the goal is counted completely, later ones are ignored
+        db.add_quest_progress(player, QuestStatType::WelcomedVisitors, 5);
+        db.add_quest_progress(player, QuestStatType::WelcomedVisitors, 1);
+        assert_eq!(progress(player, QuestStatType::WelcomedVisitors), 6);
+
+        db.add_village_quest_progress(village, QuestStatType::GatheredSticks, 4);
+        assert_eq!(progress(player, QuestStatType::GatheredSticks), 4);
+        assert_eq!(progress(other_player, QuestStatType::GatheredSticks), 0);
+
+        // Statistics that are looked up when the quest is checked have no counter
+        db.add_quest_progress(player, QuestStatType::WorkerLevel, 1);
+        let counters: i64 = quest_progress::table
+            .filter(quest_progress::player_id.eq(player.num()))
+            .filter(quest_progress::quest_id.eq(quest.num()))
+            .count()
+            .get_result(db.dbconn())
+            .unwrap();
+        assert_eq!(counters, 2);
     }
 }
@@ -13,7 +13,12 @@ use crate::game_master::town_worker::{TownWorker, TownWorkerEventMsg};
 use crate::{db::*, town_view::TownView};
 use actix::prelude::*;
 use chrono::{offset::TimeZone, NaiveDateTime, Utc};
-use paddlers_shared_lib::game_mechanics::{map::map_distance, town::defence::IAttackingHobo};
+use diesel::Connection;
+use paddlers_shared_lib::api::error::GameMasterError;
+use paddlers_shared_lib::game_mechanics::{
+    map::{StreamPath, VisitorRoute},
+    town::defence::IAttackingHobo,
+};
 use paddlers_shared_lib::prelude::*;
 use std::ops::Add;
 
@@ -21,7 +26,6 @@ const MIN_DELAY_BETWEEN_ATTACKS: i64 = 20;
 
 pub struct AttackFunnel {
     dbpool: Pool,
-    db_actor: Addr<DbActor>,
     town_worker: Addr<TownWorker>,
 }
 
@@ -43,65 +47,50 @@ pub struct PlannedAttack {
     pub hobos: Vec<Hobo>,
     pub fixed_travel_time_s: Option<i32>,
     pub subject_to_visitor_queue_limit: bool,
+    /// Player who has sent an invitation, counted for quests once the funnel has accepted the attack
+    pub invited_by: Option<PlayerKey>,
 }
 impl Message for PlannedAttack {
-    type Result = ();
+    type Result = Result<(), SlotReservationError>;
 }
 
 impl Handler<PlannedAttack> for AttackFunnel {
-    type Result = ();
+    type Result = Result<(), SlotReservationError>;
 
     fn handle(&mut self, msg: PlannedAttack, _ctx: &mut Context<Self>) -> Self::Result {
         let db = self.db();
-        let vid = msg.destination_village.key();
-
-        // Check that the visitor queue capacity is respected
-        if msg.subject_to_visitor_queue_limit {
-            let active_attacks = db.attacks_not_entered_count(vid);
-            let town_context = TownView::load_village(&db, vid);
-            if town_context.state.visitor_capacity() <= active_attacks {
-                eprintln!("Attempted to invite more than allowed. Request ignored.");
-                return;
-            }
-        }
-
-        // Checks and attack creation must be sequential
-        // TODO (Correctness): Somehow efficiently check that hobos are not attacking already
-        let unit_count = msg.hobos.len();
         let unhurried = msg
             .hobos
             .iter()
             .cloned()
             .filter(|h| !h.hurried)
             .collect::<Vec<_>>();
-        let hobos = msg.hobos.into_iter().map(|h| h.key()).collect();
+        let hobos: Vec<HoboKey> = msg.hobos.iter().map(|h| h.key()).collect();
 
         let travel_time;
         if let Some(s) = msg.fixed_travel_time_s {
             travel_time = s as i64;
         } else if let Some(v0) = msg.origin_village {
-            let v1 = msg.destination_village;
-            let distance = map_distance((v0.x, v0.y), (v1.x, v1.y));
-            let seconds = 20.0 * distance;
-            travel_time = MIN_DELAY_BETWEEN_ATTACKS.max(seconds as i64);
+            let route = db.visitor_route(&v0, &msg.destination_village);
+            travel_time = MIN_DELAY_BETWEEN_ATTACKS.max(route.travel_time_s() as i64);
         } else {
             travel_time = MIN_DELAY_BETWEEN_ATTACKS;
         }
-        let now = chrono::Utc::now().naive_utc();
-        let earliest_arrival = now.add(chrono::Duration::seconds(travel_time));
-        let arrival = Self::next_timeslot(&db, vid, unit_count, earliest_arrival);
-        let attack = NewAttack {
+        let now = crate::clock::naive_now();
+        let request = SlotRequest {
+            destination: msg.destination_village.key(),
+            origin: msg.origin_village.map(|v| v.key()),
+            hobos: &hobos,
             departure: now,
-            arrival: arrival,
-            origin_village_id: msg.origin_village.map(|k| k.id),
-            destination_village_id: msg.destination_village.id,
+            earliest_arrival: now.add(chrono::Duration::seconds(travel_time)),
+            subject_to_visitor_queue_limit: msg.subject_to_visitor_queue_limit,
         };
-
-        // Put new attack in DB
-        let pa = ScheduledAttack { attack, hobos };
-        self.db_actor.do_send(DeferredDbStatement::NewAttack(pa));
-        // Everything until here must be sequential
-        // FIXME: the deferred DB statement is not sequential and it would be possible that two attacks receive the same time slot!
+        let arrival = db.reserve_attack_slot(request)?.arrival;
+        if let Some(player) = msg.invited_by {
+            if !hobos.is_empty() {
+                db.add_quest_progress(player, QuestStatType::SentInvitations, 1);
+            }
+        }
 
         // Validate the resting queue the attack arrives, unless there is no unhurried hobo
         if unhurried.len() > 0 {
@@ -127,57 +116,319 @@ impl Handler<PlannedAttack> for AttackFunnel {
                 Utc.from_utc_datetime(&event_time),
             ));
         }
+        Ok(())
     }
 }
 
 impl AttackFunnel {
-    pub fn new(dbpool: Pool, db_actor: Addr<DbActor>, town_worker: Addr<TownWorker>) -> Self {
+    pub fn new(dbpool: Pool, town_worker: Addr<TownWorker>) -> Self {
         AttackFunnel {
             dbpool,
-            db_actor,
             town_worker,
         }
     }
     fn db(&self) -> DB {
         (&self.dbpool).into()
     }
-    fn next_timeslot(
-        db: &DB,
-        vid: VillageKey,
-        unit_count: usize,
-        mut earliest: NaiveDateTime,
-    ) -> NaiveDateTime {
-        // TODO (Optimization): These are potentially many DB queries
-
-        // Query returns attacks sorted by arrival date
-        let already_running_attacks = db.attacks(vid, None);
-        let duration = Self::attack_duration(unit_count);
-        let mut i = 0;
-        let len = already_running_attacks.len();
-        while i < len {
-            let atk = &already_running_attacks[i];
-            let n = db.attack_hobos(atk.key()).len();
-            let d = Self::attack_duration(n);
-            if atk.arrival + d <= earliest {
-                // No conflict with i, i is earlier than new attack
-                i += 1;
-            } else if atk.arrival < earliest + duration {
-                // Conflict with i, need to delay new attack to be after i
-                i += 1;
-                earliest = atk.arrival + d;
-            } else {
-                // No overlap and i is entirely afterwards
-                //  => thanks to sorted input we can stop here
-                break;
+}
+
+/// Everything needed to reserve an arrival time slot for a new attack
+struct SlotRequest<'a> {
+    destination: VillageKey,
+    origin: Option<VillageKey>,
+    hobos: &'a [HoboKey],
+    departure: NaiveDateTime,
+    earliest_arrival: NaiveDateTime,
+    subject_to_visitor_queue_limit: bool,
+}
+
+/// Reason why an attack has not been accepted by the funnel
+#[derive(Debug)]
+pub enum SlotReservationError {
+    VisitorQueueFull,
+    HoboAlreadyVisiting(HoboKey),
+    Db(diesel::result::Error),
+}
+
+impl DB {
+    /// Checks the consistency rules, picks the next free arrival time slot and inserts the attack.
+    ///
+    /// Everything happens in one transaction which locks the destination village.
+    /// Thus, concurrent reservations for the same village are serialized and cannot receive overlapping slots.
+    fn reserve_attack_slot(&self, request: SlotRequest) -> Result<Attack, SlotReservationError> {
+        self.dbconn().transaction(|| {
+            self.lock_village(request.destination)?;
+
+            // Check that the visitor queue capacity is respected
+            if request.subject_to_visitor_queue_limit {
+                let active_attacks = self.attacks_not_entered_count(request.destination);
+                let town_context = TownView::load_village(self, request.destination);
+                if town_context.state.visitor_capacity() <= active_attacks {
+                    return Err(SlotReservationError::VisitorQueueFull);
+                }
+            }
+            if let Some(hobo) = self.any_hobo_in_attack(request.hobos)? {
+                return Err(SlotReservationError::HoboAlreadyVisiting(hobo));
+            }
+
+            // Query returns attacks sorted by arrival date
+            let occupied: Vec<_> = self
+                .attacks(request.destination, None)
+                .into_iter()
+                .map(|atk| {
+                    let n = self.attack_hobos(atk.key()).len();
+                    (atk.arrival, attack_duration(n))
+                })
+                .collect();
+            let arrival = next_timeslot(
+                &occupied,
+                attack_duration(request.hobos.len()),
+                request.earliest_arrival,
+            );
+
+            let attack = self.insert_attack(&NewAttack {
+                departure: request.departure,
+                arrival,
+                origin_village_id: request.origin.map(|k| k.num()),
+                destination_village_id: request.destination.num(),
+            })?;
+            for hobo in request.hobos {
+                self.insert_attack_to_hobo(&AttackToHobo {
+                    attack_id: attack.id,
+                    hobo_id: hobo.num(),
+                    satisfied: None,
+                    released: None,
+                    cheers: 0,
+                    cheered_until: None,
+                })?;
+            }
+            Ok(attack)
+        })
+    }
+}
+
+/// Finds the earliest arrival at or after `earliest` which does not overlap with any of the occupied slots.
+/// Slots are given as (arrival, duration) and must be sorted by arrival.
+fn next_timeslot(
+    occupied: &[(NaiveDateTime, chrono::Duration)],
+    duration: chrono::Duration,
+    mut earliest: NaiveDateTime,
+) -> NaiveDateTime {
+    for (arrival, d) in occupied {
+        if *arrival + *d <= earliest {
+            // No conflict, slot is earlier than new attack
+        } else if *arrival < earliest + duration {
+            // Conflict, need to delay new attack to be after the slot
+            earliest = *arrival + *d;
+        } else {
+            // No overlap and the slot is entirely afterwards
+            //  => thanks to sorted input we can stop here
+            break;
+        }
+    }
+    earliest
+}
+
+fn attack_duration(units: usize) -> chrono::Duration {
+    // Assumptions:
+    //  A) ~0.2 speed <=> 5s per tile
+    //  B) Two units parallel to each other
+    let seconds = (units + 1) * 5 / 2;
+    chrono::Duration::seconds(seconds as i64)
+}
+
+impl std::fmt::Display for SlotReservationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VisitorQueueFull => write!(f, "Attempted to invite more than allowed"),
+            Self::HoboAlreadyVisiting(hobo) => {
+                write!(f, "{:?} is already part of another visit", hobo)
             }
+            Self::Db(e) => write!(f, "DB error: {}", e),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for SlotReservationError {
+    fn from(e: diesel::result::Error) -> Self {
+        Self::Db(e)
+    }
+}
+
+impl From<SlotReservationError> for GameMasterError {
+    fn from(e: SlotReservationError) -> Self {
+        match e {
+            SlotReservationError::VisitorQueueFull => GameMasterError::VisitorQueueFull,
+            SlotReservationError::HoboAlreadyVisiting(_) => GameMasterError::HoboAlreadyVisiting,
+            SlotReservationError::Db(e) => GameMasterError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl DB {
+    /// Route along the streams for visitors travelling between two villages
+    pub fn visitor_route(&self, origin: &Village, destination: &Village) -> VisitorRoute {
+        let origin_stream = self.stream(StreamKey(origin.stream_id));
+        let destination_stream = self.stream(StreamKey(destination.stream_id));
+        VisitorRoute::between_villages(
+            (origin.x, origin.y),
+            &StreamPath::new(origin_stream.start_x, &origin_stream.control_points),
+            (destination.x, destination.y),
+            &StreamPath::new(
+                destination_stream.start_x,
+                &destination_stream.control_points,
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn assert_no_overlap(mut slots: Vec<(NaiveDateTime, Duration)>) {
+        slots.sort();
+        for pair in slots.windows(2) {
+            assert!(
+                pair[0].0 + pair[0].1 <= pair[1].0,
+                "Overlapping slots {:?} and {:?}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn timeslots_do_not_overlap() {
+        let t0 = NaiveDateTime::from_timestamp_opt(1_600_000_000, 0).unwrap();
+        let mut occupied: Vec<(NaiveDateTime, Duration)> = vec![];
+        for i in 0..50 {
+            let duration = attack_duration(i % 7);
+            let earliest = t0 + Duration::seconds((i as i64 * 13) % 40);
+            let arrival = next_timeslot(&occupied, duration, earliest);
+            assert!(arrival >= earliest);
+            occupied.push((arrival, duration));
+            occupied.sort();
         }
-        earliest
+        assert_no_overlap(occupied);
+    }
+
+    #[test]
+    fn free_timeslot_is_used_immediately() {
+        let t0 = NaiveDateTime::from_timestamp_opt(1_600_000_000, 0).unwrap();
+        let occupied = vec![(t0 + Duration::seconds(100), attack_duration(3))];
+        assert_eq!(next_timeslot(&occupied, attack_duration(1), t0), t0);
+        let blocked = t0 + Duration::seconds(99);
+        assert_eq!(
+            next_timeslot(&occupied, attack_duration(1), blocked),
+            t0 + Duration::seconds(100) + attack_duration(3)
+        );
     }
-    fn attack_duration(units: usize) -> chrono::Duration {
-        // Assumptions:
-        //  A) ~0.2 speed <=> 5s per tile
-        //  B) Two units parallel to each other
-        let seconds = (units + 1) * 5 / 2;
-        chrono::Duration::seconds(seconds as i64)
+
+    /// Sends many attacks at one village in parallel, through the same route the frontend uses.
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[actix_web::test]
+    #[ignore]
+    async fn concurrent_attacks_receive_distinct_timeslots() {
+        use crate::setup::{delete_player_account, initialize_new_player_account};
+        use crate::ActorAddresses;
+        use actix_web::http::header::AUTHORIZATION;
+        use actix_web::{test, web::Data, App};
+        use futures_util::future::join_all;
+        use paddlers_shared_lib::api::{attacks::AttackDescriptor, PlayerInitData};
+        use paddlers_shared_lib::config::Config;
+        const N: usize = 16;
+
+        let pool = DB::new_pool();
+        let db: DB = (&pool).into();
+        let new_player = || {
+            let uuid = uuid::Uuid::new_v4();
+            let info = PlayerInitData {
+                display_name: "Attack Funnel Test".to_owned(),
+                utc_offset_minutes: 0,
+            };
+            initialize_new_player_account(&db, uuid, &info).unwrap();
+            let player = db.player_by_uuid(uuid).unwrap();
+            let village = db.player_villages(player.key()).remove(0);
+            (uuid, player, village)
+        };
+        let (sender, sender_player, origin) = new_player();
+        let (_, destination_player, destination) = new_player();
+        let hobos: Vec<HoboKey> = (0..N + 1)
+            .map(|_| {
+                db.insert_hobo(&NewHobo {
+                    hp: 1,
+                    home: origin.id,
+                    color: Some(UnitColor::Yellow),
+                    speed: 0.1,
+                    hurried: true,
+                    nest: None,
+                    level: 0,
+                })
+                .key()
+            })
+            .collect();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(ActorAddresses::start(&pool)))
+                .app_data(Data::new(Config::default()))
+                .app_data(Data::new(pool.clone()))
+                .configure(crate::routes),
+        )
+        .await;
+        let requests = (0..N).map(|i| {
+            // Every second request also sends the last hobo, only one of them may succeed
+            let mut units = vec![hobos[i]];
+            if i % 2 == 1 {
+                units.push(hobos[N]);
+            }
+            let request = test::TestRequest::post()
+                .uri("/attacks/create")
+                .insert_header((AUTHORIZATION, sender.to_string()))
+                .set_json(&AttackDescriptor {
+                    from: origin.key(),
+                    to: (destination.x as i32, destination.y as i32),
+                    units,
+                })
+                .to_request();
+            test::call_service(&app, request)
+        });
+        let statuses: Vec<_> = join_all(requests)
+            .await
+            .into_iter()
+            .map(|res| res.status())
+            .collect();
+
+        let with_shared_hobo = statuses
+            .iter()
+            .enumerate()
+            .filter(|(i, status)| i % 2 == 1 && status.is_success())
+            .count();
+        let attacks = db.attacks(destination.key(), None);
+        let slots = attacks
+            .iter()
+            .map(|atk| {
+                (
+                    atk.arrival,
+                    attack_duration(db.attack_hobos(atk.key()).len()),
+                )
+            })
+            .collect();
+
+        // Clean up before asserting
+        delete_player_account(&db, sender_player.key());
+        delete_player_account(&db, destination_player.key());
+
+        assert!(statuses.iter().step_by(2).all(|s| s.is_success()));
+        assert_eq!(with_shared_hobo, 1);
+        // Other visitors may arrive at the same time, they must not overlap either
+        let sent = attacks
+            .iter()
+            .filter(|atk| atk.origin_village_id == Some(origin.id))
+            .count();
+        assert_eq!(sent, N / 2 + 1);
+        assert_no_overlap(slots);
     }
 }
@@ -4,10 +4,14 @@ use crate::db::*;
 use crate::game_master::attack_funnel::{AttackFunnel, PlannedAttack};
 use actix::prelude::*;
 use futures_util::future::join_all;
-use paddlers_shared_lib::specification_types::{HoboLevel, HoboType};
+use paddlers_shared_lib::game_mechanics::map::MAP_MAX_X;
+use paddlers_shared_lib::specification_types::HoboType;
 use paddlers_shared_lib::{prelude::*, specification_types::VisitorDefinition};
 use rand::Rng;
 
+/// Initial distance (in x direction) in which anarchist villages are considered as origin of an attack
+const ANARCHIST_SEARCH_RANGE: f32 = 20.0;
+
 pub struct AttackSpawner {
     dbpool: Pool,
     db_actor: Addr<DbActor>,
@@ -26,10 +30,10 @@ impl Actor for AttackSpawner {
     }
 }
 
-/// Randomized attack from no specific origin
+/// Attack from a random anarchist village
 pub(super) struct SendAnarchistAttack {
     pub village: VillageKey,
-    pub level: HoboLevel,
+    pub visitors: Vec<VisitorDefinition>,
 }
 
 impl Message for SendAnarchistAttack {
@@ -39,7 +43,7 @@ impl Handler<SendAnarchistAttack> for AttackSpawner {
     type Result = ();
 
     fn handle(&mut self, msg: SendAnarchistAttack, _ctx: &mut Context<Self>) -> Self::Result {
-        self.spawn_anarchists(msg.village, msg.level);
+        self.spawn_anarchists(msg.village, msg.visitors);
     }
 }
 
@@ -81,30 +85,33 @@ impl AttackSpawner {
         }
     }
 
-    fn spawn_anarchists(&self, village: VillageKey, level: HoboLevel) {
-        // Send a random number of weak and hurried hobos + 1 stronger which will rest until satisfied or replaced
+    fn spawn_anarchists(&self, village: VillageKey, hobos: Vec<VisitorDefinition>) {
         let mut rng = rand::thread_rng();
-        let n = rng.gen_range(2, 4);
-
-        // weak hobos
-        let mut hobos: Vec<VisitorDefinition> = (0..n)
-            .map(|_| VisitorDefinition {
-                typ: HoboType::DefaultRandom,
-                level,
-                hurried: true,
-                hp: None,
-            })
-            .collect();
-        hobos.push(VisitorDefinition {
-            typ: HoboType::DefaultRandom,
-            level,
-            hurried: false,
-            hp: None,
-        });
-        self.spawn_anonymous(
-            village, village, /* TODO: pick an anarchist village instead */
-            hobos, None, true,
-        );
+        let db: DB = (&self.dbpool).into();
+        let origin = db
+            .village(village)
+            .and_then(|destination| Self::pick_anarchist_village(&db, &destination, &mut rng))
+            .map(|v| v.key())
+            .unwrap_or(village);
+        self.spawn_anonymous(village, origin, hobos, None, true);
+    }
+    /// Picks a random anarchist village close to the destination, widening the search if necessary
+    fn pick_anarchist_village<R: Rng>(
+        db: &DB,
+        destination: &Village,
+        rng: &mut R,
+    ) -> Option<Village> {
+        let mut range = ANARCHIST_SEARCH_RANGE;
+        loop {
+            let candidates = db.anarchist_villages(destination.x - range, destination.x + range);
+            if !candidates.is_empty() {
+                return Some(candidates[rng.gen_range(0, candidates.len())]);
+            }
+            if range >= MAP_MAX_X as f32 {
+                return None;
+            }
+            range *= 2.0;
+        }
     }
     fn spawn_anonymous(
         &self,
@@ -135,6 +142,7 @@ impl AttackSpawner {
                     home: origin.num(),
                     hurried: def.hurried,
                     nest: None,
+                    level: def.level.num() as i32,
                 };
                 let msg = NewHoboMessage(hobo);
                 self.db_actor.send(msg)
@@ -151,15 +159,23 @@ impl AttackSpawner {
             }
             let hobos = hobos.into_iter().map(|h| h.unwrap().0).collect();
             let db: DB = (&pool).into();
+            let origin_village = if origin != destination {
+                db.village(origin)
+            } else {
+                None
+            };
             let pa = PlannedAttack {
-                origin_village: None,
+                origin_village,
                 destination_village: db.village(destination).unwrap(),
                 hobos: hobos,
                 fixed_travel_time_s,
                 subject_to_visitor_queue_limit,
+                invited_by: None,
             };
-            if let Err(e) = attack_funnel.send(pa).await {
-                eprintln!("Attack spawn failed: {:?}", e);
+            match attack_funnel.send(pa).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("Attack rejected: {}", e),
+                Err(e) => eprintln!("Attack spawn failed: {:?}", e),
             }
         };
         Arbiter::current().spawn(planned_attack);
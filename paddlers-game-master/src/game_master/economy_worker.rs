@@ -1,75 +1,205 @@
 use crate::db::*;
 use actix::prelude::*;
+use chrono::{Duration, NaiveDateTime};
 use paddlers_shared_lib::game_mechanics::worker::*;
 use paddlers_shared_lib::prelude::*;
+use std::collections::{BTreeSet, HashMap};
 
 /// Actor for calculating gathered regular events on workers (resource collection, mana regeneration)
+///
+/// For every worker flag, the time when the next resource or mana point is produced is computed upfront.
+/// The database is only accessed when such a production event is due or when a worker has changed.
+/// Flag timestamps in the database remain the source of truth, the schedule is rebuilt from them on start.
 pub struct EconomyWorker {
     dbpool: Pool,
+    schedule: ProductionSchedule,
+}
+
+/// Notifies the `EconomyWorker` that a worker's task or mana has changed, so that its next production event must be recomputed
+#[derive(Debug)]
+pub struct WorkerProductionChanged(pub WorkerKey);
+
+impl Message for WorkerProductionChanged {
+    type Result = ();
 }
 
 impl EconomyWorker {
     pub fn new(dbpool: Pool) -> Self {
-        EconomyWorker { dbpool: dbpool }
+        EconomyWorker {
+            dbpool: dbpool,
+            schedule: ProductionSchedule::default(),
+        }
     }
     fn db(&self) -> DB {
         (&self.dbpool).into()
     }
     fn work(&mut self, ctx: &mut Context<Self>) {
-        let db = &self.db();
-
+        let db = self.db();
+        self.produce_due(&db, crate::clock::naive_now());
+        ctx.run_later(
+            crate::clock::real_interval(std::time::Duration::from_millis(5000)),
+            Self::work,
+        );
+    }
+    /// Handles all production events that are due, returns how many there were
+    fn produce_due(&mut self, db: &DB, now: NaiveDateTime) -> usize {
+        let mut events = 0;
+        while let Some((worker, flag)) = self.schedule.pop_due(now) {
+            let next = match flag {
+                WorkerFlagType::ManaRegeneration => regenerate_mana(db, worker, now),
+                WorkerFlagType::Work => collect_resources(db, worker, now),
+            };
+            if let Some(time) = next {
+                self.schedule.set(worker, flag, time);
+            }
+            events += 1;
+        }
+        events
+    }
+    fn fill_schedule(&mut self) {
+        let db = self.db();
+        self.schedule_all(&db);
+    }
+    fn schedule_all(&mut self, db: &DB) {
         for village in db.all_player_villages() {
-            let village_id = village.key();
-            let workers = db.workers(village_id);
-            let now = chrono::Utc::now().naive_utc();
-            for w in workers {
-                for flag in db.worker_flags(w.key()) {
-                    match flag.flag_type {
-                        WorkerFlagType::ManaRegeneration => {
-                            let mana_regen = hero_mana_regeneration_per_hour();
-                            let interval_ms = 3_600_000 / mana_regen as i64;
-                            let new_mana =
-                                (now - flag.last_update).num_milliseconds() / interval_ms;
-                            if new_mana > 0 {
-                                let new_time = flag.last_update
-                                    + chrono::Duration::milliseconds(interval_ms * new_mana);
-                                db.update_worker_flag_timestamp(
-                                    w.key(),
-                                    WorkerFlagType::ManaRegeneration,
-                                    new_time,
-                                );
-                                db.add_worker_mana(w.key(), new_mana as i32, hero_max_mana());
-                            }
-                        }
-                        WorkerFlagType::Work => {
-                            let task = db.current_task(w.key()).expect("Must have a job");
-                            if let Some((res, rate)) =
-                                hero_resource_collection_per_hour(task.task_type)
-                            {
-                                let gathered = rate
-                                    * (now - flag.last_update).num_milliseconds() as f32
-                                    / 3_600_000.0;
-                                let n = gathered.floor();
-                                if n > 0.0 {
-                                    let time_rewarded_s = n * 3_600.0 / rate;
-                                    let new_time = flag.last_update
-                                        + chrono::Duration::seconds(time_rewarded_s as i64);
-                                    db.update_worker_flag_timestamp(
-                                        w.key(),
-                                        WorkerFlagType::Work,
-                                        new_time,
-                                    );
-                                    db.add_resource(res, village_id, n as i64)
-                                        .expect("Adding resources");
-                                }
-                            }
-                        }
+            for w in db.workers(village.key()) {
+                self.schedule_worker(db, &w);
+            }
+        }
+    }
+    /// (Re)computes when the worker produces next, based on its current task and the flag timestamps
+    fn schedule_worker(&mut self, db: &DB, worker: &Worker) {
+        self.schedule.remove(worker.key());
+        for flag in db.worker_flags(worker.key()) {
+            let rate = match flag.flag_type {
+                WorkerFlagType::ManaRegeneration => {
+                    if worker.mana.unwrap_or(hero_max_mana()) >= hero_max_mana() {
+                        continue;
                     }
+                    hero_mana_regeneration_per_hour() as f32
                 }
-            }
+                WorkerFlagType::Work => {
+                    match db
+                        .current_task(worker.key())
+                        .and_then(|task| hero_resource_collection_per_hour(task.task_type))
+                    {
+                        Some((_res, rate)) => rate,
+                        None => continue,
+                    }
+                }
+            };
+            self.schedule.set(
+                worker.key(),
+                flag.flag_type,
+                flag.last_update + production_interval(rate),
+            );
         }
+    }
+}
+
+/// Adds the mana regenerated since the last update.
+/// Returns when the next mana point is due, or None if the worker has reached the maximum.
+fn regenerate_mana(db: &DB, w: WorkerKey, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let flag = db.worker_flag(w, WorkerFlagType::ManaRegeneration)?;
+    let worker = db.worker_priv(w)?;
+    let mana = worker.mana.unwrap_or(0);
+    let p = Production::since(
+        flag.last_update,
+        hero_mana_regeneration_per_hour() as f32,
+        now,
+    );
+    if p.units > 0 {
+        db.update_worker_flag_timestamp(w, WorkerFlagType::ManaRegeneration, p.last_update);
+        db.add_worker_mana(w, p.units as i32, hero_max_mana());
+    }
+    if mana + p.units as i32 >= hero_max_mana() {
+        None
+    } else {
+        Some(p.next)
+    }
+}
+
+/// Adds the resources gathered with the current task since the last update.
+/// Returns when the next resource is due, or None if the current task does not produce anything.
+fn collect_resources(db: &DB, w: WorkerKey, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let flag = db.worker_flag(w, WorkerFlagType::Work)?;
+    let task = db.current_task(w)?;
+    let (res, rate) = hero_resource_collection_per_hour(task.task_type)?;
+    let p = Production::since(flag.last_update, rate, now);
+    if p.units > 0 {
+        let worker = db.worker_priv(w)?;
+        db.update_worker_flag_timestamp(w, WorkerFlagType::Work, p.last_update);
+        db.add_produced_resource(res, worker.home(), p.units)
+            .expect("Adding resources");
+        db.add_village_quest_progress(worker.home(), QuestStatType::gathered(res), p.units);
+    }
+    Some(p.next)
+}
+
+/// Time it takes to produce one unit at the given rate
+fn production_interval(rate_per_hour: f32) -> Duration {
+    Duration::milliseconds((3_600_000.0 / rate_per_hour) as i64)
+}
+
+/// Outcome of producing at a constant rate over a period of time
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Production {
+    /// Number of fully produced units
+    units: i64,
+    /// When the last full unit has been produced, to be stored as new flag timestamp
+    last_update: NaiveDateTime,
+    /// When the next unit will be produced
+    next: NaiveDateTime,
+}
+
+impl Production {
+    fn since(last_update: NaiveDateTime, rate_per_hour: f32, now: NaiveDateTime) -> Self {
+        let interval_ms = production_interval(rate_per_hour).num_milliseconds().max(1);
+        let units = (now - last_update).num_milliseconds().max(0) / interval_ms;
+        let last_update = last_update + Duration::milliseconds(interval_ms * units);
+        Production {
+            units,
+            last_update,
+            next: last_update + Duration::milliseconds(interval_ms),
+        }
+    }
+}
 
-        ctx.run_later(std::time::Duration::from_millis(5000), Self::work);
+/// The next production event of each worker flag, ordered by time.
+/// Each flag has at most one event scheduled, setting a new time replaces the old one.
+#[derive(Default)]
+struct ProductionSchedule {
+    queue: BTreeSet<(NaiveDateTime, WorkerKey, WorkerFlagType)>,
+    scheduled: HashMap<(WorkerKey, WorkerFlagType), NaiveDateTime>,
+}
+
+impl ProductionSchedule {
+    fn set(&mut self, worker: WorkerKey, flag: WorkerFlagType, time: NaiveDateTime) {
+        if let Some(old) = self.scheduled.insert((worker, flag), time) {
+            self.queue.remove(&(old, worker, flag));
+        }
+        self.queue.insert((time, worker, flag));
+    }
+    fn remove(&mut self, worker: WorkerKey) {
+        for flag in &[WorkerFlagType::ManaRegeneration, WorkerFlagType::Work] {
+            if let Some(old) = self.scheduled.remove(&(worker, *flag)) {
+                self.queue.remove(&(old, worker, *flag));
+            }
+        }
+    }
+    /// Removes and returns the next event if it is due
+    fn pop_due(&mut self, now: NaiveDateTime) -> Option<(WorkerKey, WorkerFlagType)> {
+        let first = *self.queue.iter().next()?;
+        if first.0 > now {
+            return None;
+        }
+        self.queue.remove(&first);
+        self.scheduled.remove(&(first.1, first.2));
+        Some((first.1, first.2))
+    }
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.queue.len()
     }
 }
 
@@ -78,6 +208,7 @@ impl Actor for EconomyWorker {
 
     fn started(&mut self, ctx: &mut Context<Self>) {
         //    println!("Economy Worker started");
+        self.fill_schedule();
         self.work(ctx);
     }
 
@@ -85,3 +216,251 @@ impl Actor for EconomyWorker {
         //    println!("Economy Worker stopped");
     }
 }
+
+impl Handler<WorkerProductionChanged> for EconomyWorker {
+    type Result = ();
+    fn handle(&mut self, msg: WorkerProductionChanged, _ctx: &mut Context<Self>) {
+        let db = self.db();
+        if let Some(worker) = db.worker_priv(msg.0) {
+            self.schedule_worker(&db, &worker);
+        } else {
+            self.schedule.remove(msg.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use diesel::prelude::*;
+    use paddlers_shared_lib::schema::*;
+    use std::collections::HashSet;
+
+    fn t0() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2021, 2, 22)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn production_keeps_remainder_for_next_unit() {
+        // Two sticks per hour => one every 30 minutes
+        let p = Production::since(t0(), 2.0, t0() + Duration::minutes(75));
+        assert_eq!(p.units, 2);
+        assert_eq!(p.last_update, t0() + Duration::minutes(60));
+        assert_eq!(p.next, t0() + Duration::minutes(90));
+
+        let p = Production::since(t0(), 2.0, t0() + Duration::minutes(29));
+        assert_eq!(p.units, 0);
+        assert_eq!(p.last_update, t0());
+        assert_eq!(p.next, t0() + Duration::minutes(30));
+    }
+
+    #[test]
+    fn schedule_holds_one_event_per_flag() {
+        let mut schedule = ProductionSchedule::default();
+        let w = WorkerKey(1);
+        schedule.set(w, WorkerFlagType::Work, t0() + Duration::minutes(10));
+        schedule.set(w, WorkerFlagType::Work, t0() + Duration::minutes(5));
+        schedule.set(w, WorkerFlagType::ManaRegeneration, t0());
+        assert_eq!(schedule.len(), 2);
+        assert_eq!(
+            schedule.pop_due(t0()),
+            Some((w, WorkerFlagType::ManaRegeneration))
+        );
+        assert_eq!(schedule.pop_due(t0()), None);
+        assert_eq!(
+            schedule.pop_due(t0() + Duration::minutes(5)),
+            Some((w, WorkerFlagType::Work))
+        );
+        schedule.set(w, WorkerFlagType::Work, t0());
+        schedule.remove(w);
+        assert_eq!(schedule.pop_due(t0() + Duration::hours(1)), None);
+    }
+
+    /// Runs the production of many villages with one hero each through the database, over one hour of game time.
+    /// Checks that only producing workers cause database work, unlike polling every village on each tick.
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn production_touches_only_producing_workers() {
+        const VILLAGES: usize = 2_000;
+        /// Interval at which the economy worker used to load all villages
+        const POLL_INTERVAL_S: i64 = 5;
+        let pool = DB::new_pool();
+        let db: DB = (&pool).into();
+        // Nothing is committed, the seeded data disappears with the connection
+        db.dbconn().begin_test_transaction().unwrap();
+        let t0 = db.now();
+        let (villages, workers) = seed_villages(&db, VILLAGES, t0);
+
+        let mut economy = EconomyWorker::new(pool.clone());
+        economy.schedule_all(&db);
+
+        let mut events = 0;
+        let end = t0 + Duration::hours(1);
+        let mut now = t0;
+        while now <= end {
+            events += economy.produce_due(&db, now);
+            now = now + Duration::seconds(POLL_INTERVAL_S);
+        }
+
+        let sticks: i64 = resources::table
+            .filter(resources::village_id.eq_any(&villages))
+            .filter(resources::resource_type.eq(ResourceType::Sticks))
+            .select(diesel::dsl::sum(resources::amount))
+            .first::<Option<i64>>(db.dbconn())
+            .unwrap()
+            .unwrap_or(0);
+        let expected_sticks: i64 = (0..VILLAGES)
+            .filter(|i| i % 20 == 0)
+            .map(|i| Production::since(last_update(t0, i), 2.0, end).units)
+            .sum();
+        let seeded_flags = economy
+            .schedule
+            .scheduled
+            .keys()
+            .filter(|(w, _)| workers.contains(w))
+            .count();
+        assert_eq!(sticks, expected_sticks);
+        // Idle heroes at full mana are not scheduled and never touch the database after the start
+        assert_eq!(seeded_flags, VILLAGES / 5);
+        // Each event handles one producing flag, polling would have loaded each village on every tick
+        let polled_villages = VILLAGES as i64 * (end - t0).num_seconds() / POLL_INTERVAL_S;
+        assert!(
+            (events as i64) * 10 < polled_villages,
+            "{} production events are not much less than {} polled villages",
+            events,
+            polled_villages
+        );
+    }
+
+    fn last_update(t0: NaiveDateTime, i: usize) -> NaiveDateTime {
+        t0 - Duration::seconds(i as i64 % 3600)
+    }
+
+    /// Inserts villages with one hero each.
+    /// Some heroes gather sticks, some chop trees, some regenerate mana, the rest idles.
+    fn seed_villages(db: &DB, n: usize, t0: NaiveDateTime) -> (Vec<i64>, HashSet<WorkerKey>) {
+        // Bulk inserts are split to stay below the limit of bind parameters per statement
+        const CHUNK: usize = 1000;
+        let stream = db
+            .insert_streams(&[NewStream {
+                start_x: -20_000.0,
+                control_points: vec![],
+            }])
+            .remove(0);
+        let mut villages = vec![];
+        let mut workers = HashSet::new();
+        for chunk in (0..n).collect::<Vec<_>>().chunks(CHUNK) {
+            let new_players: Vec<_> = chunk
+                .iter()
+                .map(|i| NewPlayer {
+                    uuid: uuid::Uuid::new_v4(),
+                    karma: 0,
+                    display_name: format!("Load Test {}", i),
+                    utc_offset_minutes: 0,
+                })
+                .collect();
+            let players: Vec<Player> = diesel::insert_into(players::table)
+                .values(&new_players)
+                .get_results(db.dbconn())
+                .unwrap();
+            let new_villages: Vec<_> = chunk
+                .iter()
+                .zip(&players)
+                .map(|(i, p)| NewVillage {
+                    x: -20_000.0 - *i as f32,
+                    y: 1.0,
+                    stream_id: stream.id,
+                    player_id: Some(p.id),
+                    faith: None,
+                })
+                .collect();
+            let chunk_villages = db.insert_villages(&new_villages);
+            let new_resources: Vec<_> = chunk_villages
+                .iter()
+                .flat_map(|v| {
+                    vec![ResourceType::Sticks, ResourceType::Logs]
+                        .into_iter()
+                        .map(move |resource_type| Resource {
+                            resource_type,
+                            amount: 0,
+                            village_id: v.id,
+                        })
+                })
+                .collect();
+            diesel::insert_into(resources::table)
+                .values(&new_resources)
+                .execute(db.dbconn())
+                .unwrap();
+            let new_workers: Vec<_> = chunk
+                .iter()
+                .zip(&chunk_villages)
+                .map(|(i, v)| NewWorker {
+                    unit_type: UnitType::Hero,
+                    x: 5,
+                    y: 2,
+                    color: None,
+                    speed: 0.5,
+                    home: v.id,
+                    mana: match i % 20 {
+                        2 | 3 => Some(0),
+                        _ => Some(hero_max_mana()),
+                    },
+                    level: 1,
+                    exp: 0,
+                })
+                .collect();
+            let chunk_workers: Vec<Worker> = diesel::insert_into(workers::table)
+                .values(&new_workers)
+                .get_results(db.dbconn())
+                .unwrap();
+            let new_tasks: Vec<_> = chunk
+                .iter()
+                .zip(&chunk_workers)
+                .map(|(i, w)| NewTask {
+                    worker_id: w.id,
+                    task_type: match i % 20 {
+                        0 => TaskType::GatherSticks,
+                        1 => TaskType::ChopTree,
+                        _ => TaskType::Idle,
+                    },
+                    x: 5,
+                    y: 2,
+                    start_time: Some(t0 - Duration::hours(1)),
+                    target_hobo_id: None,
+                })
+                .collect();
+            db.insert_tasks(&new_tasks);
+            let flags: Vec<_> = chunk
+                .iter()
+                .zip(&chunk_workers)
+                .flat_map(|(i, w)| {
+                    let last_update = last_update(t0, *i);
+                    vec![
+                        WorkerFlag {
+                            worker_id: w.id,
+                            flag_type: WorkerFlagType::Work,
+                            last_update,
+                        },
+                        WorkerFlag {
+                            worker_id: w.id,
+                            flag_type: WorkerFlagType::ManaRegeneration,
+                            last_update,
+                        },
+                    ]
+                })
+                .collect();
+            diesel::insert_into(worker_flags::table)
+                .values(&flags)
+                .execute(db.dbconn())
+                .unwrap();
+            villages.extend(chunk_villages.iter().map(|v| v.id));
+            workers.extend(chunk_workers.iter().map(|w| w.key()));
+        }
+        (villages, workers)
+    }
+}
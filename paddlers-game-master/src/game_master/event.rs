@@ -24,7 +24,8 @@ pub enum Event {
 }
 
 impl Event {
-    pub(super) fn run(&self, db: &DB) -> Option<(Event, DateTime<Utc>)> {
+    /// Executes the event that was due at the given time and returns the follow-up event, if any
+    pub(super) fn run(&self, db: &DB, due: DateTime<Utc>) -> Option<(Event, DateTime<Utc>)> {
         match self {
             Self::WorkerTask { task_id } => {
                 let res = finish_task(db, *task_id, None, None);
@@ -46,7 +47,7 @@ impl Event {
                 None
             }
             Self::CheckVisitorHp { hobo_id } => {
-                let now = chrono::Utc::now().naive_utc();
+                let now = crate::clock::naive_now();
                 for (atk, _info) in db.hobo_attack_info(*hobo_id) {
                     // Performance: Checking the entire attack is a bit of an overkill (was the easiest to implement without code duplication)
                     db.maybe_evaluate_attack(&atk, now);
@@ -54,10 +55,56 @@ impl Event {
                 None
             }
             Self::PayTaxes => {
-                db.pay_taxes_to_all_players();
-                Some((Self::PayTaxes, EventQueue::next_tax_collection()))
+                // Collections missed while the game-master was down are paid now
+                let (missed, next) = EventQueue::due_tax_collections(due, crate::clock::now());
+                for _ in 0..missed {
+                    db.pay_taxes_to_all_players();
+                }
+                Some((Self::PayTaxes, next))
+            }
+        }
+    }
+    /// Representation of the event in the database, to persist it until it is executed
+    pub(super) fn to_db(&self, due: DateTime<Utc>) -> NewScheduledEvent {
+        let mut row = NewScheduledEvent {
+            event_type: ScheduledEventType::PayTaxes,
+            due: due.naive_utc(),
+            task_id: None,
+            village_id: None,
+            hobo_id: None,
+        };
+        match self {
+            Self::WorkerTask { task_id } => {
+                row.event_type = ScheduledEventType::WorkerTask;
+                row.task_id = Some(task_id.num());
+            }
+            Self::CheckRestingVisitors { village_id } => {
+                row.event_type = ScheduledEventType::CheckRestingVisitors;
+                row.village_id = Some(village_id.num());
             }
+            Self::CheckVisitorHp { hobo_id } => {
+                row.event_type = ScheduledEventType::CheckVisitorHp;
+                row.hobo_id = Some(hobo_id.num());
+            }
+            Self::PayTaxes => {}
         }
+        row
+    }
+    /// Restores an event from the database. Returns None if the row is incomplete.
+    pub(super) fn from_db(row: &ScheduledEvent) -> Option<(Self, DateTime<Utc>)> {
+        let event = match row.event_type {
+            ScheduledEventType::WorkerTask => Self::WorkerTask {
+                task_id: TaskKey(row.task_id?),
+            },
+            ScheduledEventType::CheckRestingVisitors => Self::CheckRestingVisitors {
+                village_id: VillageKey(row.village_id?),
+            },
+            ScheduledEventType::CheckVisitorHp => Self::CheckVisitorHp {
+                hobo_id: HoboKey(row.hobo_id?),
+            },
+            ScheduledEventType::PayTaxes => Self::PayTaxes,
+        };
+        Some((event, Utc.from_utc_datetime(&row.due)))
     }
     pub(crate) fn load_next_worker_task(
         db: &DB,
@@ -75,3 +122,38 @@ impl Event {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Restoring from the database yields the same events that have been persisted
+    #[test]
+    fn db_representation_round_trip() {
+        let due = Utc.with_ymd_and_hms(2021, 2, 20, 12, 0, 0).unwrap();
+        let events = [
+            Event::WorkerTask {
+                task_id: TaskKey(1),
+            },
+            Event::CheckRestingVisitors {
+                village_id: VillageKey(2),
+            },
+            Event::CheckVisitorHp {
+                hobo_id: HoboKey(3),
+            },
+            Event::PayTaxes,
+        ];
+        for (id, event) in events.iter().enumerate() {
+            let new = event.to_db(due);
+            let row = ScheduledEvent {
+                id: id as i64,
+                event_type: new.event_type,
+                due: new.due,
+                task_id: new.task_id,
+                village_id: new.village_id,
+                hobo_id: new.hobo_id,
+            };
+            assert_eq!(Event::from_db(&row), Some((*event, due)));
+        }
+    }
+}
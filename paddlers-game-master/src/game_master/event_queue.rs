@@ -1,18 +1,27 @@
 use super::event::Event;
+use crate::db::DB;
 use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use paddlers_shared_lib::prelude::{GameDB, ScheduledEvent};
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 
 /// For delayed execution of events.
 /// This queue is being poled constantly by the `TownWorker`
+///
+/// Each event is also stored in the database until it has been executed.
+/// After a restart, the queue is restored from there and events that are overdue are executed in order.
+/// The effect of an event and the removal of its persisted copy are committed together, thus each event takes effect exactly once.
 pub struct EventQueue {
     queue: BinaryHeap<TimedEvent>,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-struct TimedEvent {
-    time: DateTime<Utc>,
-    event: Event,
+pub struct TimedEvent {
+    pub time: DateTime<Utc>,
+    pub event: Event,
+    /// Id of the persisted copy in the database
+    id: i64,
 }
 
 impl EventQueue {
@@ -21,38 +30,94 @@ impl EventQueue {
             queue: BinaryHeap::new(),
         }
     }
-    pub fn add_event(&mut self, event: Event, time: DateTime<Utc>) {
-        self.queue.push(TimedEvent {
-            time: time,
-            event: event,
-        });
+    /// Persists the event and adds it to the queue
+    pub fn add_event(&mut self, db: &DB, event: Event, time: DateTime<Utc>) {
+        let timed = TimedEvent::persist(db, event, time).expect("Inserting scheduled event");
+        self.queue.push(timed);
     }
-    /// Returns the next event in the queue if it is due
-    pub fn poll_event(&mut self) -> Option<Event> {
+    /// Adds events that have been persisted in an earlier run.
+    /// Returns all events that have been restored.
+    pub fn restore(&mut self, db: &DB, persisted: &[ScheduledEvent]) -> Vec<Event> {
+        let mut restored = vec![];
+        for row in persisted {
+            if let Some((event, time)) = Event::from_db(row) {
+                self.queue.push(TimedEvent {
+                    time,
+                    event,
+                    id: row.id,
+                });
+                restored.push(event);
+            } else {
+                eprintln!("Dropping invalid scheduled event: {:?}", row);
+                if let Err(e) = db.delete_scheduled_event(row.id) {
+                    eprintln!("Couldn't delete scheduled event {}: {}", row.id, e);
+                }
+            }
+        }
+        restored
+    }
+    /// Returns the next event in the queue if it is due.
+    /// The returned event must be passed to `execute` afterwards.
+    pub fn poll_event(&mut self) -> Option<TimedEvent> {
         let next = self.queue.peek();
         if let Some(evt) = next {
-            if evt.time <= chrono::Utc::now() {
-                return self.queue.pop().map(|te| te.event);
+            if evt.time <= crate::clock::now() {
+                return self.queue.pop();
             }
         }
         None
     }
+    /// Runs a polled event and schedules its follow-up event.
+    /// Both, together with removing the persisted copy of the event, happen in one transaction.
+    /// If the transaction fails, the event stays persisted and is put back into the queue.
+    pub fn execute(&mut self, db: &DB, timed: TimedEvent) -> QueryResult<()> {
+        let result = db.dbconn().transaction(|| {
+            let next = match timed.event.run(db, timed.time) {
+                Some((event, time)) => Some(TimedEvent::persist(db, event, time)?),
+                None => None,
+            };
+            db.delete_scheduled_event(timed.id)?;
+            Ok(next)
+        });
+        match result {
+            Ok(next) => {
+                self.queue.extend(next);
+                Ok(())
+            }
+            Err(e) => {
+                self.queue.push(timed);
+                Err(e)
+            }
+        }
+    }
     #[allow(dead_code)]
     pub fn time_of_next_event(&self) -> Option<&DateTime<Utc>> {
         self.queue.peek().map(|te| &te.time)
     }
 }
 
+impl TimedEvent {
+    fn persist(db: &DB, event: Event, time: DateTime<Utc>) -> QueryResult<Self> {
+        let row = db.insert_scheduled_event(&event.to_db(time))?;
+        Ok(TimedEvent {
+            time,
+            event,
+            id: row.id,
+        })
+    }
+}
+
 // Necessary traits for Binary Heap
 
 impl Ord for TimedEvent {
     fn cmp(&self, other: &TimedEvent) -> Ordering {
         // Flipped order to make it a Min-Heap
-        // With event as tie-break
+        // With event and then insertion order as tie-break
         other
             .time
             .cmp(&self.time)
             .then_with(|| self.event.cmp(&other.event))
+            .then_with(|| other.id.cmp(&self.id))
     }
 }
 impl PartialOrd for TimedEvent {
@@ -60,3 +125,140 @@ impl PartialOrd for TimedEvent {
         Some(self.cmp(other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::setup::initialize_new_player_account;
+    use chrono::Duration;
+    use paddlers_shared_lib::api::PlayerInitData;
+    use paddlers_shared_lib::prelude::*;
+    use paddlers_shared_lib::schema::scheduled_events;
+
+    /// Nothing is committed, persisted events disappear with the connection
+    fn test_db() -> DB {
+        let db: DB = (&DB::new_pool()).into();
+        db.dbconn().begin_test_transaction().unwrap();
+        db
+    }
+
+    /// Creates a player and returns its hero
+    fn test_hero(db: &DB) -> Worker {
+        let uuid = uuid::Uuid::new_v4();
+        let info = PlayerInitData {
+            display_name: "Event Queue Test".to_owned(),
+            utc_offset_minutes: 0,
+        };
+        initialize_new_player_account(db, uuid, &info).unwrap();
+        let player = db.player_by_uuid(uuid).unwrap();
+        let village = db.player_villages(player.key())[0];
+        db.workers(village.key()).remove(0)
+    }
+
+    fn persisted_ids(db: &DB) -> Vec<i64> {
+        scheduled_events::table
+            .select(scheduled_events::id)
+            .load(db.dbconn())
+            .unwrap()
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn restored_events_run_in_order() {
+        let db = test_db();
+        let now = crate::clock::now();
+        let events = [
+            (
+                Event::CheckRestingVisitors {
+                    village_id: VillageKey(1),
+                },
+                now - Duration::seconds(1),
+            ),
+            (
+                Event::CheckVisitorHp {
+                    hobo_id: HoboKey(2),
+                },
+                now - Duration::seconds(3),
+            ),
+            (Event::PayTaxes, now - Duration::seconds(2)),
+        ];
+        let mut before_restart = EventQueue::new();
+        for (event, time) in &events {
+            before_restart.add_event(&db, *event, *time);
+        }
+        let ids: Vec<i64> = before_restart.queue.iter().map(|te| te.id).collect();
+
+        let persisted: Vec<ScheduledEvent> = db
+            .scheduled_events()
+            .into_iter()
+            .filter(|row| ids.contains(&row.id))
+            .collect();
+        let mut after_restart = EventQueue::new();
+        after_restart.restore(&db, &persisted);
+        let polled: Vec<Event> = std::iter::from_fn(|| after_restart.poll_event())
+            .map(|te| te.event)
+            .collect();
+
+        assert_eq!(polled, vec![events[1].0, events[2].0, events[0].0]);
+    }
+
+    /// An event that is persisted twice, e.g. because it was restored and derived from the tasks again, takes effect once.
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn replayed_worker_task_takes_effect_once() {
+        let db = test_db();
+        let hero = test_hero(&db);
+        let (current, _) = db.current_and_next_task(hero.key());
+        let current = current.unwrap();
+        db.insert_task(&NewTask {
+            worker_id: hero.id,
+            task_type: TaskType::Idle,
+            x: current.x,
+            y: current.y,
+            start_time: Some(db.now()),
+            target_hobo_id: None,
+        });
+        let event = Event::WorkerTask {
+            task_id: current.key(),
+        };
+        let time = crate::clock::now() - Duration::seconds(1);
+
+        let mut queue = EventQueue::new();
+        queue.add_event(&db, event, time);
+        queue.add_event(&db, event, time);
+        let ids: Vec<i64> = queue.queue.iter().map(|te| te.id).collect();
+        while let Some(timed) = queue.poll_event() {
+            queue.execute(&db, timed).unwrap();
+        }
+
+        assert_eq!(db.worker_tasks(hero.key()).len(), 1);
+        assert!(db.task(current.key()).is_none());
+        assert!(queue.queue.is_empty());
+        assert!(persisted_ids(&db).iter().all(|id| !ids.contains(id)));
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn missed_tax_collection_is_caught_up() {
+        let db = test_db();
+        let now = crate::clock::now();
+        let missed = EventQueue::next_tax_collection() - Duration::days(3);
+        let mut queue = EventQueue::new();
+        queue.add_event(&db, Event::PayTaxes, missed);
+        let old_id = queue.queue.peek().unwrap().id;
+
+        let timed = queue.poll_event().unwrap();
+        queue.execute(&db, timed).unwrap();
+
+        let next = queue.queue.peek().unwrap();
+        assert_eq!(next.event, Event::PayTaxes);
+        assert!(next.time > now);
+        assert!(next.time <= now + Duration::days(1));
+        let ids = persisted_ids(&db);
+        assert!(ids.contains(&next.id));
+        assert!(!ids.contains(&old_id));
+    }
+}
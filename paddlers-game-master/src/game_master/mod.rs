@@ -3,10 +3,14 @@ pub(super) mod attack_spawn;
 pub(super) mod economy_worker;
 pub(super) mod event;
 mod event_queue;
+mod specification_source;
+pub(super) mod story_graph;
 pub(super) mod story_worker;
 mod taxes;
 mod town_defence;
 pub(super) mod town_worker;
+mod visit_director;
+pub(super) mod visit_rewards;
 
 use crate::db::*;
 use crate::game_master::attack_spawn::{AttackSpawner, SendAnarchistAttack};
@@ -14,31 +18,37 @@ use actix::prelude::*;
 use chrono::NaiveDateTime;
 use paddlers_shared_lib::game_mechanics::town::TOWN_X;
 use paddlers_shared_lib::prelude::*;
-use paddlers_shared_lib::specification_types::HoboLevel;
 use paddlers_shared_lib::sql::GameDB;
 use paddlers_shared_lib::sql_db::keys::SqlKey;
-use paddlers_shared_lib::story::story_state::StoryState;
-use rand::RngCore;
 use std::time::Duration;
+use visit_director::{ClassicDirector, VisitDirector, VisitDirectorSource};
 
 pub struct GameMaster {
     last_attack: NaiveDateTime,
     dbpool: Pool,
     attacker_addr: Addr<AttackSpawner>,
     current_batch: Option<VillageBatch>,
+    director: Box<dyn VisitDirector>,
+    director_source: VisitDirectorSource,
 }
 /// Keeps partial progress when checking if an attack to villages is required
 struct VillageBatch {
     villages: Vec<Village>,
-    seed: usize,
 }
 impl GameMaster {
     pub fn new(dbpool: Pool, attacker_addr: &Addr<AttackSpawner>) -> Self {
+        let mut director_source = VisitDirectorSource::from_env();
+        let director = director_source.reload().unwrap_or_else(|| {
+            println!("No visit director definition found, using the classic director");
+            Box::new(ClassicDirector)
+        });
         GameMaster {
             last_attack: NaiveDateTime::from_timestamp_opt(0, 0).unwrap(),
             dbpool: dbpool,
             attacker_addr: attacker_addr.clone(),
             current_batch: None,
+            director,
+            director_source,
         }
     }
 }
@@ -64,44 +74,51 @@ impl GameMaster {
         check_attacks(&db);
 
         if self.current_batch.is_none() {
-            let now = chrono::Utc::now().naive_utc();
-            if now - self.last_attack >= chrono::Duration::seconds(40) {
+            let now = crate::clock::naive_now();
+            if now - self.last_attack >= self.director.cycle() {
                 self.last_attack = now;
+                if let Some(director) = self.director_source.reload() {
+                    self.director = director;
+                }
                 self.load_new_batch(&db);
             }
         }
 
         self.continue_batch(&db);
 
-        ctx.run_later(Duration::from_secs(1), Self::game_cycle);
+        ctx.run_later(
+            crate::clock::real_interval(Duration::from_secs(1)),
+            Self::game_cycle,
+        );
     }
     fn load_new_batch(&mut self, db: &DB) {
-        let mut rng = rand::thread_rng();
         self.current_batch = Some(VillageBatch {
             villages: db.all_player_villages(),
-            seed: rng.next_u64() as usize,
         });
     }
     // Continues working on batch until the attack mailbox is full
     fn continue_batch(&mut self, db: &DB) {
+        let mut rng = rand::thread_rng();
+        let now = crate::clock::naive_now();
         if let Some(batch) = self.current_batch.as_mut() {
             while let Some(village) = batch.villages.pop() {
                 let vid = village.key();
                 let ongoing_attacks = db.attacks_count(vid, None);
-                if should_send_attack(ongoing_attacks, batch.seed, vid) {
-                    if let Some(player_info) = db.player_by_village(vid) {
-                        if let Some(anarchist_level) = repetitive_attack_strength(&player_info) {
-                            match self.attacker_addr.try_send(SendAnarchistAttack {
-                                village: vid,
-                                level: anarchist_level,
-                            }) {
-                                Err(SendError::Closed(_msg)) => panic!("Attack funnel closed"),
-                                Err(SendError::Full(_msg)) => {
-                                    batch.villages.push(village);
-                                    return;
-                                }
-                                Ok(()) => { /* NOP */ }
+                if let Some(player_info) = db.player_by_village(vid) {
+                    if let Some(visitors) =
+                        self.director
+                            .plan_visit(&player_info, ongoing_attacks, now, &mut rng)
+                    {
+                        match self.attacker_addr.try_send(SendAnarchistAttack {
+                            village: vid,
+                            visitors,
+                        }) {
+                            Err(SendError::Closed(_msg)) => panic!("Attack funnel closed"),
+                            Err(SendError::Full(_msg)) => {
+                                batch.villages.push(village);
+                                return;
                             }
+                            Ok(()) => { /* NOP */ }
                         }
                     }
                 }
@@ -111,40 +128,10 @@ impl GameMaster {
     }
 }
 
-fn should_send_attack(ongoing_attacks: usize, random_number: usize, vid: VillageKey) -> bool {
-    match ongoing_attacks {
-        0 => true,
-        n => (random_number + vid.0 as usize) % (n * n + 9) == 0,
-    }
-}
-fn repetitive_attack_strength(player: &Player) -> Option<HoboLevel> {
-    match player.story_state {
-        StoryState::Initialized
-        | StoryState::ServantAccepted
-        | StoryState::TempleBuilt
-        | StoryState::WatergateBuilt
-        | StoryState::BuildingWatergate
-        | StoryState::PickingPrimaryCivBonus
-        | StoryState::SolvingPrimaryCivQuestPartA
-        | StoryState::SolvingPrimaryCivQuestPartB
-        | StoryState::SolvingSecondaryQuestA
-        | StoryState::SolvingSecondaryQuestB
-        | StoryState::DialogueBalanceA
-        | StoryState::DialogueBalanceB
-        | StoryState::VisitorQueued
-        | StoryState::WelcomeVisitorQuestStarted
-        | StoryState::VisitorArrived
-        | StoryState::UnlockingInvitationPathA
-        | StoryState::UnlockingInvitationPathB
-        | StoryState::FirstVisitorWelcomed => None,
-        StoryState::AllDone => Some(HoboLevel::anarchist(player.karma)),
-    }
-}
-
 fn check_attacks(db: &DB) {
     for village in db.all_player_villages() {
         let attacks = db.attacks_that_entered(village.key(), None);
-        let now = chrono::Utc::now().naive_utc();
+        let now = crate::clock::naive_now();
         for atk in attacks.iter() {
             if let Some(fight_start) = atk.entered_destination {
                 if fight_start + chrono::Duration::seconds(2 * TOWN_X as i64) < now {
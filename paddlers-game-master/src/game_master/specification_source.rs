@@ -0,0 +1,88 @@
+//! Loading of the specification files in `specification/` that the game-master depends on.
+//!
+//! Each file is reloaded when it changes, thus game balance can be tuned while the game-master is running.
+//! An invalid definition is reported and the previously loaded one stays in use.
+//! The frontend fetches the same files when a player opens the game, it picks up changes on the next page load.
+
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// A RON file a definition is loaded from, remembers the last modification to detect changes
+pub(crate) struct SpecificationSource<T> {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    validate: fn(&T) -> Result<(), String>,
+}
+
+impl<T: DeserializeOwned> SpecificationSource<T> {
+    /// Path from the environment variable `var`, or `default_path` if it is not set
+    pub fn from_env(var: &str, default_path: &str, validate: fn(&T) -> Result<(), String>) -> Self {
+        let path = std::env::var(var).unwrap_or_else(|_| default_path.to_owned());
+        SpecificationSource {
+            path: path.into(),
+            modified: None,
+            validate,
+        }
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Returns a new definition if the file has changed since the last call.
+    /// Invalid definitions are reported and ignored.
+    pub fn reload(&mut self) -> Option<T> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok()?;
+        if self.modified == Some(modified) {
+            return None;
+        }
+        self.modified = Some(modified);
+        match load_definition(&self.path, self.validate) {
+            Ok(definition) => {
+                println!("Specification loaded from {}", self.path.display());
+                Some(definition)
+            }
+            Err(e) => {
+                eprintln!("Invalid specification in {}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+pub(crate) fn load_definition<T: DeserializeOwned>(
+    path: &Path,
+    validate: fn(&T) -> Result<(), String>,
+) -> Result<T, String> {
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let definition: T = ron::de::from_reader(file).map_err(|e| e.to_string())?;
+    validate(&definition)?;
+    Ok(definition)
+}
+
+/// A definition shared by all actors, checked for changes whenever it is accessed.
+pub(crate) struct SharedSpecification<T> {
+    state: Mutex<(SpecificationSource<T>, Option<Arc<T>>)>,
+}
+
+impl<T: DeserializeOwned> SharedSpecification<T> {
+    pub fn new(source: SpecificationSource<T>) -> Self {
+        SharedSpecification {
+            state: Mutex::new((source, None)),
+        }
+    }
+    /// The latest valid definition. Panics if no valid definition has been loaded, yet.
+    pub fn get(&self) -> Arc<T> {
+        let mut state = self.state.lock().unwrap();
+        let (source, current) = &mut *state;
+        if let Some(definition) = source.reload() {
+            *current = Some(Arc::new(definition));
+        }
+        match current {
+            Some(definition) => definition.clone(),
+            None => panic!("No valid specification in {}", source.path().display()),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! The story graph that defines all story state transitions.
+//!
+//! The graph is loaded from `specification/story.ron`, another file can be selected with the environment variable `STORY_DEFINITION`.
+
+use super::specification_source::{SharedSpecification, SpecificationSource};
+use once_cell::sync::Lazy;
+use paddlers_shared_lib::story::story_graph::StoryGraph;
+use std::sync::Arc;
+
+const DEFAULT_DEFINITION_PATH: &str = "specification/story.ron";
+
+static STORY_GRAPH: Lazy<SharedSpecification<StoryGraph>> = Lazy::new(|| {
+    SharedSpecification::new(SpecificationSource::from_env(
+        "STORY_DEFINITION",
+        DEFAULT_DEFINITION_PATH,
+        validate_graph,
+    ))
+});
+
+/// The latest valid story graph. Panics if no valid definition has ever been loaded.
+pub(crate) fn story_graph() -> Arc<StoryGraph> {
+    STORY_GRAPH.get()
+}
+
+fn validate_graph(graph: &StoryGraph) -> Result<(), String> {
+    graph.validate().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_master::specification_source::load_definition;
+    use paddlers_shared_lib::story::{story_state::StoryState, story_trigger::StoryTrigger};
+
+    #[test]
+    fn specified_story_graph() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../")
+            .join(DEFAULT_DEFINITION_PATH);
+        let graph = load_definition(&path, validate_graph).expect("Valid story graph");
+        let t = graph
+            .transition(
+                &StoryState::INITIALIZED,
+                &StoryTrigger::DialogueStoryTrigger,
+            )
+            .expect("Transition out of initial state");
+        assert_eq!(t.next_state, StoryState::SERVANT_ACCEPTED);
+
+        let queued = StoryState::new("visitor_queued");
+        assert!(graph
+            .transition(&queued, &StoryTrigger::LetVisitorIn)
+            .is_some());
+        assert!(graph
+            .transition(&queued, &StoryTrigger::DialogueStoryTrigger)
+            .is_none());
+    }
+}
@@ -6,6 +6,7 @@ use paddlers_shared_lib::{
 };
 
 use super::attack_spawn::{AttackSpawner, SendAnonymousAttack};
+use super::story_graph::story_graph;
 
 /// Actor for performing story state transitions
 pub struct StoryWorker {
@@ -60,7 +61,8 @@ impl StoryWorkerMessage {
 impl Handler<StoryWorkerMessage> for StoryWorker {
     type Result = ();
     fn handle(&mut self, msg: StoryWorkerMessage, _ctx: &mut Context<Self>) {
-        let transition = msg.confirmed_current_story_state.transition(&msg.trigger);
+        let graph = story_graph();
+        let transition = graph.transition(&msg.confirmed_current_story_state, &msg.trigger);
         if transition.is_none() {
             eprintln!(
                 "Invalid transition attempt: {:?} --( {:?} )--> ???",
@@ -69,8 +71,8 @@ impl Handler<StoryWorkerMessage> for StoryWorker {
             return;
         }
         let t = transition.unwrap();
-        for action in t.actions.into_iter() {
-            match action {
+        for action in &t.actions {
+            match action.clone() {
                 paddlers_shared_lib::story::story_action::StoryAction::StartQuest(q) => {
                     self.db_actor
                         .do_send(DeferredDbStatement::AssignQuest(msg.player, q));
@@ -119,8 +121,10 @@ impl Handler<StoryWorkerMessage> for StoryWorker {
             }
         }
         if t.next_state != msg.confirmed_current_story_state {
-            self.db_actor
-                .do_send(DeferredDbStatement::PlayerUpdate(msg.player, t.next_state));
+            self.db_actor.do_send(DeferredDbStatement::PlayerUpdate(
+                msg.player,
+                t.next_state.clone(),
+            ));
         }
     }
 }
@@ -11,7 +11,7 @@ use rand::Rng;
 
 impl EventQueue {
     pub fn next_tax_collection() -> DateTime<Utc> {
-        let now = chrono::Utc::now().naive_utc() + Duration::seconds(10);
+        let now = crate::clock::naive_now() + Duration::seconds(10);
         let tonight = now
             .with_hour(23)
             .unwrap()
@@ -23,8 +23,21 @@ impl EventQueue {
             .unwrap();
         Utc.from_utc_datetime(&tonight)
     }
-    pub fn schedule_tax_collection(&mut self) {
-        self.add_event(Event::PayTaxes, Self::next_tax_collection())
+    /// Counts the daily collections from `due` until `now` and returns the time of the next collection after that
+    pub(super) fn due_tax_collections(
+        due: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> (usize, DateTime<Utc>) {
+        let mut count = 0;
+        let mut next = due;
+        while next <= now {
+            count += 1;
+            next = next + Duration::days(1);
+        }
+        (count, next)
+    }
+    pub fn schedule_tax_collection(&mut self, db: &DB) {
+        self.add_event(db, Event::PayTaxes, Self::next_tax_collection())
     }
 }
 
@@ -61,3 +74,21 @@ impl DB {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missed_tax_collections_are_counted() {
+        let due = Utc.with_ymd_and_hms(2021, 2, 17, 23, 59, 59).unwrap();
+        let now = Utc.with_ymd_and_hms(2021, 2, 20, 12, 0, 0).unwrap();
+        let (count, next) = EventQueue::due_tax_collections(due, now);
+        assert_eq!(count, 3);
+        assert_eq!(next, Utc.with_ymd_and_hms(2021, 2, 20, 23, 59, 59).unwrap());
+
+        let (count, next) = EventQueue::due_tax_collections(due, due);
+        assert_eq!(count, 1);
+        assert_eq!(next, Utc.with_ymd_and_hms(2021, 2, 18, 23, 59, 59).unwrap());
+    }
+}
@@ -28,16 +28,19 @@
 //!
 //! Effects that must be taken into consideration:
 //!     * Defensive towers (flowers etc) which are only available by computing proximity
+//!     * Cheering buildings (choir etc), which depend on all visitors in the town at the same time
 //!     * Direct effects on units, from abilities, which are explicitly stored on the db
 //!
 //! When a unit is defeated or leaves otherwise, it still has to stick around in the database until all units of the group are done.
 //! This can be marked in the db using the status on each HoboToAttack.
 
+use super::visit_rewards::reward_table;
 use crate::db::DB;
 use crate::town_view::TownView;
 use chrono::NaiveDateTime;
 use paddlers_shared_lib::game_mechanics::town::*;
 use paddlers_shared_lib::prelude::*;
+use paddlers_shared_lib::specification_types::{HoboLevel, HoboType, RewardRng, RewardedVisitor};
 
 pub(crate) struct AttackingHobo<'a> {
     hobo: &'a Hobo,
@@ -49,55 +52,179 @@ pub(crate) struct AttackingHobo<'a> {
 impl DB {
     /// Checks if all visitors have already left (or been satisfied).
     /// If so, the visit is evaluated and a report with rewards is generated.
-    pub fn maybe_evaluate_attack(&self, atk: &Attack, now: NaiveDateTime) {
-        let now: Timestamp = now.into();
+    pub fn maybe_evaluate_attack(&self, atk: &Attack, evaluation_time: NaiveDateTime) {
+        let now: Timestamp = evaluation_time.into();
         let village = atk.destination();
-        let active_units = self.attack_hobos_active_with_attack_info(atk);
         let town = TownView::load_village(&self, village);
 
-        for (hobo, info) in &active_units {
-            let effects = self.effects_on_hobo(hobo.key());
-            let unit = AttackingHobo {
-                hobo: hobo,
+        // Cheering buildings split their attention among all visitors in town, not only those of this attack.
+        // Satisfied visitors are included, too, as they have been competing for attention before.
+        let other_attacks = self.attacks_that_entered(village, None);
+        let attacks: Vec<&Attack> = std::iter::once(atk)
+            .chain(other_attacks.iter().filter(|other| other.id != atk.id))
+            .collect();
+        let mut visitors = vec![];
+        for attack in &attacks {
+            for (hobo, info) in self.attack_hobos_with_attack_info(attack) {
+                let effects = self.effects_on_hobo(hobo.key());
+                visitors.push((hobo, info, effects, attack));
+            }
+        }
+        visitors.sort_by_key(|(hobo, ..)| hobo.id);
+        let units: Vec<AttackingHobo> = visitors
+            .iter()
+            .map(|(hobo, info, effects, attack)| AttackingHobo {
+                hobo,
                 attack_to_hobo: info,
-                effects: &effects,
-                attack: atk,
-            };
-            if town.hp_left(&unit, now) == 0 {
+                effects,
+                attack,
+            })
+            .collect();
+        let cheers = town.cheer_damage(&units, now);
+        let hp_left = town.hp_left_in_town(&units, &cheers, now);
+
+        // Store the cheers, so that the next evaluation only adds the cycles from now on
+        for (unit, cheers) in units.iter().zip(&cheers) {
+            if unit.attack_to_hobo.cheers != *cheers {
+                self.set_cheers(unit.hobo.key(), unit.attack.key(), *cheers);
+            }
+        }
+        let attack_keys: Vec<AttackKey> = attacks.iter().map(|a| a.key()).collect();
+        self.set_cheered_until(&attack_keys, evaluation_time);
+
+        let mut newly_satisfied = 0;
+        for (unit, hp) in units.iter().zip(hp_left) {
+            if unit.attack.id != atk.id || unit.attack_to_hobo.satisfied.is_some() {
+                continue;
+            }
+            let hobo = unit.hobo;
+            if hp == 0 {
                 self.set_satisfied(hobo.key(), atk.key(), true);
-                if !hobo.hurried && info.released.is_none() {
+                newly_satisfied += 1;
+                if !hobo.hurried && unit.attack_to_hobo.released.is_none() {
                     self.release_resting_visitor(hobo.key(), atk.key());
                 }
-            } else if town.hobo_left_town(&unit, now) {
+            } else if town.hobo_left_town(unit, now) {
                 self.set_satisfied(hobo.key(), atk.key(), false);
             }
         }
+        if newly_satisfied > 0 {
+            self.add_village_quest_progress(
+                village,
+                QuestStatType::WelcomedVisitors,
+                newly_satisfied,
+            );
+        }
 
         // Check if all are satisfied or have left otherwise, then finish visit
         if self.attack_done(atk) {
             self.generate_report(atk);
-            if atk.origin_village_id.is_none() {
-                self.delete_attack_hobos(atk.key());
+            if self.visitors_spawned_for(atk) {
+                self.delete_spawned_visitors(atk.key());
             }
             self.delete_attack(atk);
         }
     }
 
-    fn generate_report(&self, atk: &Attack) {
-        let happy_hobos = self.attack_hobos_satisfied(atk);
+    /// Visitors of story events and from anarchist villages are spawned for a single visit.
+    /// Visitors sent by a player have a home to return to.
+    fn visitors_spawned_for(&self, atk: &Attack) -> bool {
+        match atk.origin() {
+            None => true,
+            Some(origin) => self.player_by_village(origin).is_none(),
+        }
+    }
 
-        let mut report = NewVisitReport {
-            sender: happy_hobos.first().map(|hobo| hobo.id),
-            village_id: atk.destination_village_id,
-            karma: 0,
+    /// The tile a visitor is on at the given time, using the same path model as the hp computations.
+    /// None if the hobo is not visiting the village at that time.
+    pub fn visitor_tile(
+        &self,
+        town: &TownView,
+        hobo: &Hobo,
+        village: VillageKey,
+        t: NaiveDateTime,
+    ) -> Option<TileIndex> {
+        let (attack, info) = self
+            .hobo_attack_info(hobo.key())
+            .into_iter()
+            .find(|(attack, _)| attack.destination() == village)?;
+        let unit = AttackingHobo {
+            hobo,
+            attack_to_hobo: &info,
+            effects: &[],
+            attack: &attack,
         };
+        let t: Timestamp = t.into();
+        if town.hobo_left_town(&unit, t) {
+            return None;
+        }
+        town.visitor_tile(&unit, t)
+    }
+    /// When a worker stops defending, the happiness it gave to visitors still in town is stored as effect on them.
+    /// Afterwards, the worker is no longer listed as defender in the `TownView`.
+    pub fn persist_defence(
+        &self,
+        village: VillageKey,
+        worker: WorkerKey,
+        defender: DefendingWorker,
+        end: NaiveDateTime,
+    ) {
+        let mut town = TownView::load_village(&self, village);
+        town.defenders.retain(|(id, _)| *id != worker.num());
+        town.defenders.push((worker.num(), defender));
+        let end: Timestamp = end.into();
 
-        report.karma = happy_hobos.len() as i64;
+        for attack in self.attacks_that_entered(village, None) {
+            for (hobo, info) in self.attack_hobos_active_with_attack_info(&attack) {
+                let unit = AttackingHobo {
+                    hobo: &hobo,
+                    attack_to_hobo: &info,
+                    effects: &[],
+                    attack: &attack,
+                };
+                let touched = town
+                    .touched_defenders(&unit, end)
+                    .into_iter()
+                    .find(|(id, _)| *id == worker.num());
+                if let Some((_, strength)) = touched {
+                    self.insert_effect(&NewEffect {
+                        hobo_id: hobo.id,
+                        attribute: HoboAttributeType::Health,
+                        strength: Some(strength),
+                        start_time: None,
+                    });
+                }
+            }
+        }
+    }
 
-        use std::ops::Add;
-        let feathers = happy_hobos.iter().map(reward_feathers).fold(0, i64::add);
-        let sticks = happy_hobos.iter().map(reward_sticks).fold(0, i64::add);
-        let logs = happy_hobos.iter().map(reward_logs).fold(0, i64::add);
+    fn generate_report(&self, atk: &Attack) {
+        let mut visitors = self.attack_hobos_with_attack_info(atk);
+        visitors.sort_by_key(|(hobo, _)| hobo.id);
+        let rewarded: Vec<RewardedVisitor> = visitors
+            .iter()
+            .map(|(hobo, info)| RewardedVisitor {
+                typ: HoboType::of_color(hobo.color),
+                level: HoboLevel::new(hobo.level as usize),
+                hurried: hobo.hurried,
+                satisfied: info.satisfied == Some(true),
+            })
+            .collect();
+        // Seeded by the attack, such that the outcome of a visit is reproducible
+        let mut rng = RewardRng::new(atk.id as u64);
+        let rewards = reward_table().roll(&rewarded, &mut rng);
+
+        let report = NewVisitReport {
+            sender: visitors
+                .iter()
+                .find(|(_, info)| info.satisfied == Some(true))
+                .map(|(hobo, _)| hobo.id),
+            village_id: atk.destination_village_id,
+            karma: rewards.karma,
+        };
+        let feathers = rewards.amount(ResourceType::Feathers);
+        let sticks = rewards.amount(ResourceType::Sticks);
+        let logs = rewards.amount(ResourceType::Logs);
 
         if report.karma + feathers + sticks + logs != 0 {
             self.add_new_report(report, feathers, sticks, logs);
@@ -132,35 +259,6 @@ impl DB {
     }
 }
 
-/// TODO [0.1.5]
-fn reward_feathers(unit: &Hobo) -> i64 {
-    let f = if unit.hurried {
-        (2.0 + unit.hp as f32 * unit.speed / 4.0).log2().floor()
-    } else {
-        (2.0 + unit.hp as f32 / 16.0).log2().ceil()
-    };
-    // println!("Unit {:?} gives {} feathers", unit, f);
-    f as i64
-}
-
-/// TODO [0.1.5]
-fn reward_sticks(unit: &Hobo) -> i64 {
-    if unit.id % 20 == 0 {
-        5
-    } else {
-        0
-    }
-}
-
-/// TODO [0.1.5]
-fn reward_logs(unit: &Hobo) -> i64 {
-    if unit.id % 60 == 11 {
-        5
-    } else {
-        0
-    }
-}
-
 impl<'a> IAttackingHobo for AttackingHobo<'a> {
     fn max_hp(&self) -> u32 {
         self.hobo.hp as u32
@@ -184,6 +282,12 @@ impl<'a> IAttackingHobo for AttackingHobo<'a> {
             .filter(|e| e.strength.is_some())
             .fold(0, |acc, e| acc + e.strength.unwrap() as i64) as i32
     }
+    fn cheers(&self) -> i32 {
+        self.attack_to_hobo.cheers
+    }
+    fn cheered_until(&self) -> Option<Timestamp> {
+        self.attack_to_hobo.cheered_until.map(From::from)
+    }
 }
 
 impl ITownLayoutMarker for TownView {
@@ -210,4 +314,117 @@ impl IDefendingTown for TownView {
         }
         auras
     }
+    fn cheering_buildings(&self) -> Vec<CheeringBuilding> {
+        self.cheering_buildings.clone()
+    }
+    fn defenders(&self) -> Vec<(Self::AuraId, DefendingWorker)> {
+        self.defenders.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::setup::initialize_new_player_account;
+    use diesel::prelude::*;
+    use paddlers_shared_lib::api::PlayerInitData;
+    use paddlers_shared_lib::schema::*;
+
+    /// Sends a group of visitors from an anarchist village and lets them leave again.
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn anarchist_visitors_are_deleted_after_visit() {
+        const N: usize = 3;
+        let pool = DB::new_pool();
+        let db: DB = (&pool).into();
+
+        let uuid = uuid::Uuid::new_v4();
+        let info = PlayerInitData {
+            display_name: "Town Defence Test".to_owned(),
+            utc_offset_minutes: 0,
+        };
+        initialize_new_player_account(&db, uuid, &info).unwrap();
+        let player = db.player_by_uuid(uuid).unwrap();
+        let destination = db.player_villages(player.key())[0];
+        let stream = db
+            .insert_streams(&[NewStream {
+                start_x: -200.0,
+                control_points: vec![],
+            }])
+            .remove(0);
+        let anarchists = db.insert_villages(&[NewVillage {
+            x: -200.0,
+            y: 1.0,
+            stream_id: stream.id,
+            player_id: None,
+            faith: None,
+        }])[0];
+        let count_anarchist_hobos = || -> i64 {
+            hobos::table
+                .filter(hobos::home.eq(anarchists.id))
+                .count()
+                .get_result(db.dbconn())
+                .unwrap()
+        };
+
+        let now = db.now();
+        let attack = db
+            .insert_attack(&NewAttack {
+                departure: now,
+                arrival: now,
+                origin_village_id: Some(anarchists.id),
+                destination_village_id: destination.id,
+            })
+            .unwrap();
+        for _ in 0..N {
+            let hobo = db.insert_hobo(&NewHobo {
+                hp: 1000,
+                home: anarchists.id,
+                color: Some(UnitColor::Yellow),
+                speed: 0.1,
+                hurried: true,
+                nest: None,
+                level: 0,
+            });
+            db.insert_attack_to_hobo(&AttackToHobo {
+                attack_id: attack.id,
+                hobo_id: hobo.id,
+                satisfied: None,
+                released: None,
+                cheers: 0,
+                cheered_until: None,
+            })
+            .unwrap();
+        }
+        assert_eq!(count_anarchist_hobos(), N as i64);
+
+        db.start_fight(attack.key(), None);
+        let attack = db
+            .attacks(destination.key(), None)
+            .into_iter()
+            .find(|a| a.id == attack.id)
+            .unwrap();
+        // Long after all visitors have walked through the town
+        db.maybe_evaluate_attack(&attack, now + chrono::Duration::days(1));
+        let hobos_left = count_anarchist_hobos();
+        let attack_left = db
+            .attacks(destination.key(), None)
+            .iter()
+            .any(|a| a.id == attack.id);
+
+        // Clean up before asserting
+        diesel::delete(hobos::table.filter(hobos::home.eq(anarchists.id)))
+            .execute(db.dbconn())
+            .unwrap();
+        diesel::delete(villages::table.find(anarchists.id))
+            .execute(db.dbconn())
+            .unwrap();
+        diesel::delete(streams::table.find(stream.id))
+            .execute(db.dbconn())
+            .unwrap();
+
+        assert!(!attack_left);
+        assert_eq!(hobos_left, 0);
+    }
 }
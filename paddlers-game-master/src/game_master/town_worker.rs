@@ -1,21 +1,25 @@
+use super::economy_worker::{EconomyWorker, WorkerProductionChanged};
 use super::event::*;
 use super::event_queue::*;
 use crate::db::*;
 use actix::prelude::*;
 use chrono::prelude::*;
 use paddlers_shared_lib::prelude::*;
+use std::collections::BTreeSet;
 
 /// Actor for moving around workers inside the town
 pub struct TownWorker {
     dbpool: Pool,
     event_queue: EventQueue,
+    economy_worker: Addr<EconomyWorker>,
 }
 
 impl TownWorker {
-    pub fn new(dbpool: Pool) -> Self {
+    pub fn new(dbpool: Pool, economy_worker: Addr<EconomyWorker>) -> Self {
         TownWorker {
             dbpool: dbpool,
             event_queue: EventQueue::new(),
+            economy_worker,
         }
         .with_filled_event_queue()
     }
@@ -23,24 +27,49 @@ impl TownWorker {
         (&self.dbpool).into()
     }
     fn work(&mut self, ctx: &mut Context<Self>) {
-        while let Some(event) = self.event_queue.poll_event() {
-            let res = event.run(&self.db());
-            if let Some((next_event, time)) = res {
-                self.event_queue.add_event(next_event, time);
+        while let Some(timed) = self.event_queue.poll_event() {
+            let db = self.db();
+            // Finishing a task changes what the worker produces
+            let changed_worker = match timed.event {
+                Event::WorkerTask { task_id } => db.task(task_id).map(|t| t.worker()),
+                _ => None,
+            };
+            if let Err(e) = self.event_queue.execute(&db, timed) {
+                // Retried on the next call
+                eprintln!("Executing {:?} failed: {}", timed.event, e);
+                break;
+            }
+            if let Some(worker) = changed_worker {
+                self.economy_worker.do_send(WorkerProductionChanged(worker));
             }
         }
-        ctx.run_later(std::time::Duration::from_millis(100), Self::work);
+        ctx.run_later(
+            crate::clock::real_interval(std::time::Duration::from_millis(100)),
+            Self::work,
+        );
     }
+    /// Restores persisted events and fills in what can be derived from the current DB state.
+    /// Events that became due while the game-master was down are executed in order on the first poll.
     fn with_filled_event_queue(mut self) -> Self {
         let db = self.db();
+        let persisted = db.scheduled_events();
+        let restored: BTreeSet<Event> = self
+            .event_queue
+            .restore(&db, &persisted)
+            .into_iter()
+            .collect();
         for village in db.all_player_villages() {
             for unit in db.workers(village.key()) {
                 if let Some((event, time)) = Event::load_next_worker_task(&db, unit.key()) {
-                    self.event_queue.add_event(event, time);
+                    if !restored.contains(&event) {
+                        self.event_queue.add_event(&db, event, time);
+                    }
                 }
             }
         }
-        self.event_queue.schedule_tax_collection();
+        if !restored.contains(&Event::PayTaxes) {
+            self.event_queue.schedule_tax_collection(&db);
+        }
         self
     }
 }
@@ -67,6 +96,7 @@ impl Message for TownWorkerEventMsg {
 impl Handler<TownWorkerEventMsg> for TownWorker {
     type Result = ();
     fn handle(&mut self, msg: TownWorkerEventMsg, _ctx: &mut Context<Self>) {
-        self.event_queue.add_event(msg.0, msg.1);
+        let db = self.db();
+        self.event_queue.add_event(&db, msg.0, msg.1);
     }
 }
@@ -0,0 +1,184 @@
+//! Decides when and which anarchist visitors are sent to player villages.
+//!
+//! The default director is configured by `specification/visit_director.ron`.
+//! Another file can be selected with the environment variable `VISIT_DIRECTOR_DEFINITION`.
+//! The file is reloaded when it changes, thus the pressure on players can be tuned while the game-master is running.
+//! If no valid definition is available, the built-in `ClassicDirector` is used.
+
+use super::specification_source::SpecificationSource;
+use chrono::{NaiveDateTime, Timelike};
+use paddlers_shared_lib::prelude::*;
+use paddlers_shared_lib::specification_types::{
+    HoboLevel, HoboType, VisitDirectorDefinition, VisitorDefinition,
+};
+use paddlers_shared_lib::story::story_state::StoryState;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+const DEFAULT_DEFINITION_PATH: &str = "specification/visit_director.ron";
+
+pub(super) trait VisitDirector {
+    /// Time between two rounds in which all player villages are considered for a visit
+    fn cycle(&self) -> chrono::Duration;
+    /// Visitors to send to a player village right now, if any
+    fn plan_visit(
+        &self,
+        player: &Player,
+        ongoing_visits: usize,
+        now: NaiveDateTime,
+        rng: &mut dyn RngCore,
+    ) -> Option<Vec<VisitorDefinition>>;
+}
+
+/// Hard-coded behaviour, used when no definition file is available.
+/// After the story is completed, sends 2-3 hurried and one unhurried visitor, less likely the more are on their way.
+pub(super) struct ClassicDirector;
+
+impl VisitDirector for ClassicDirector {
+    fn cycle(&self) -> chrono::Duration {
+        chrono::Duration::seconds(40)
+    }
+    fn plan_visit(
+        &self,
+        player: &Player,
+        ongoing_visits: usize,
+        _now: NaiveDateTime,
+        rng: &mut dyn RngCore,
+    ) -> Option<Vec<VisitorDefinition>> {
+        if player.story_state != StoryState::ALL_DONE {
+            return None;
+        }
+        if ongoing_visits > 0 && rng.gen_range(0, ongoing_visits * ongoing_visits + 9) != 0 {
+            return None;
+        }
+        let level = HoboLevel::anarchist(player.karma);
+        let n = rng.gen_range(2, 4);
+        let mut visitors: Vec<VisitorDefinition> = (0..n)
+            .map(|_| VisitorDefinition::new(HoboType::DefaultRandom, level, true))
+            .collect();
+        visitors.push(VisitorDefinition::new(
+            HoboType::DefaultRandom,
+            level,
+            false,
+        ));
+        Some(visitors)
+    }
+}
+
+/// Director driven by a `VisitDirectorDefinition`
+pub(super) struct ConfiguredDirector {
+    definition: VisitDirectorDefinition,
+}
+
+impl VisitDirector for ConfiguredDirector {
+    fn cycle(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.definition.cycle_s as i64)
+    }
+    fn plan_visit(
+        &self,
+        player: &Player,
+        ongoing_visits: usize,
+        now: NaiveDateTime,
+        rng: &mut dyn RngCore,
+    ) -> Option<Vec<VisitorDefinition>> {
+        if !self.definition.is_active(&player.story_state) {
+            return None;
+        }
+        if let Some(quiet) = &self.definition.quiet_hours {
+            let minute_of_day = now.num_seconds_from_midnight() as i32 / 60;
+            if quiet.contains(minute_of_day, player.utc_offset_minutes) {
+                return None;
+            }
+        }
+        let band = self.definition.karma_band(player.karma)?;
+        if rng.gen::<f32>() >= band.spawn_chance(ongoing_visits) {
+            return None;
+        }
+        let level = HoboLevel::anarchist(player.karma);
+        let size = rng.gen_range(band.group_size.0, band.group_size.1 + 1);
+        let hurried = band.hurried_visitors(size);
+        let visitors = (0..size)
+            .map(|i| {
+                let typ = *band.hobo_types.choose(rng)?;
+                Some(VisitorDefinition::new(typ, level, i < hurried))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(visitors)
+    }
+}
+
+/// The file a director is loaded from
+pub(super) struct VisitDirectorSource(SpecificationSource<VisitDirectorDefinition>);
+
+impl VisitDirectorSource {
+    pub fn from_env() -> Self {
+        VisitDirectorSource(SpecificationSource::from_env(
+            "VISIT_DIRECTOR_DEFINITION",
+            DEFAULT_DEFINITION_PATH,
+            VisitDirectorDefinition::validate,
+        ))
+    }
+    /// Returns a new director if the definition file has changed since the last call.
+    /// Invalid definitions are reported and ignored.
+    pub fn reload(&mut self) -> Option<Box<dyn VisitDirector>> {
+        let definition = self.0.reload()?;
+        Some(Box::new(ConfiguredDirector { definition }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_master::specification_source::load_definition;
+    use chrono::NaiveDate;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn player(karma: i64, utc_offset_minutes: i32) -> Player {
+        Player {
+            id: 1,
+            uuid: uuid::Uuid::nil(),
+            karma,
+            display_name: "Tester".to_owned(),
+            story_state: StoryState::ALL_DONE,
+            civ_perks: 0,
+            utc_offset_minutes,
+        }
+    }
+
+    fn specified_director() -> ConfiguredDirector {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../")
+            .join(DEFAULT_DEFINITION_PATH);
+        ConfiguredDirector {
+            definition: load_definition(&path, VisitDirectorDefinition::validate)
+                .expect("Valid visit director definition"),
+        }
+    }
+
+    #[test]
+    fn specified_director_sends_unhurried_leader() {
+        let director = specified_director();
+        let mut rng = StdRng::seed_from_u64(0);
+        let noon = NaiveDate::from_ymd(2021, 2, 23).and_hms(12, 0, 0);
+        let visitors = director
+            .plan_visit(&player(0, 0), 0, noon, &mut rng)
+            .expect("Villages without visitors are always visited");
+        assert!(!visitors.is_empty());
+        assert_eq!(visitors.iter().filter(|v| !v.hurried).count(), 1);
+    }
+
+    #[test]
+    fn no_visits_during_quiet_hours() {
+        let director = specified_director();
+        let quiet = director
+            .definition
+            .quiet_hours
+            .expect("Quiet hours defined");
+        let mut rng = StdRng::seed_from_u64(0);
+        // Start of quiet hours for a player in UTC+2
+        let now = NaiveDate::from_ymd(2021, 2, 23).and_hms((quiet.from_hour + 22) % 24, 0, 0);
+        assert!(director
+            .plan_visit(&player(0, 120), 0, now, &mut rng)
+            .is_none());
+    }
+}
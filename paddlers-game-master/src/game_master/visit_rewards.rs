@@ -0,0 +1,57 @@
+//! Rewards given to villages for satisfied visitors.
+//!
+//! The table is loaded from `specification/visit_rewards.ron`, another file can be selected with the environment variable `VISIT_REWARDS_DEFINITION`.
+
+use super::specification_source::{SharedSpecification, SpecificationSource};
+use once_cell::sync::Lazy;
+use paddlers_shared_lib::specification_types::VisitRewardTable;
+use std::sync::Arc;
+
+const DEFAULT_DEFINITION_PATH: &str = "specification/visit_rewards.ron";
+
+static REWARD_TABLE: Lazy<SharedSpecification<VisitRewardTable>> = Lazy::new(|| {
+    SharedSpecification::new(SpecificationSource::from_env(
+        "VISIT_REWARDS_DEFINITION",
+        DEFAULT_DEFINITION_PATH,
+        VisitRewardTable::validate,
+    ))
+});
+
+/// The latest valid reward table. Panics if no valid definition has ever been loaded.
+pub(crate) fn reward_table() -> Arc<VisitRewardTable> {
+    REWARD_TABLE.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_master::specification_source::load_definition;
+    use paddlers_shared_lib::prelude::*;
+    use paddlers_shared_lib::specification_types::{
+        HoboLevel, HoboType, RewardRng, RewardedVisitor,
+    };
+
+    #[test]
+    fn specified_table_rewards_satisfied_visitors() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../")
+            .join(DEFAULT_DEFINITION_PATH);
+        let table =
+            load_definition(&path, VisitRewardTable::validate).expect("Valid visit reward table");
+        let visitor = |hurried, satisfied| RewardedVisitor {
+            typ: HoboType::Yellow,
+            level: HoboLevel::of_anarchist_hp(2, hurried),
+            hurried,
+            satisfied,
+        };
+        let happy = [visitor(true, true), visitor(false, true)];
+        let rewards = table.roll(&happy, &mut RewardRng::new(0));
+        assert_eq!(rewards.karma, 2);
+        assert!(rewards.amount(ResourceType::Feathers) >= 2);
+
+        let unhappy = [visitor(true, false), visitor(false, false)];
+        let rewards = table.roll(&unhappy, &mut RewardRng::new(0));
+        assert_eq!(rewards.karma, 0);
+        assert!(rewards.resources.is_empty());
+    }
+}
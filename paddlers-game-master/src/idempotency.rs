@@ -0,0 +1,674 @@
+//! Replays the response to a repeated request instead of applying a player action twice.
+//!
+//! The frontend attaches a key to each action, see `IDEMPOTENCY_KEY_HEADER`, and keeps the key when it retries the action.
+//! The first response for a key is recorded per player and returned again for all later requests with the same key, until it expires.
+//! Keys are stored in the database, thus they survive restarts of the game-master.
+//! A key is bound to the method, path and body of its first request, reusing it for a different request is rejected.
+//! Requests without a key are handled as usual.
+
+use crate::authentication::Authentication;
+use crate::db::{Pool, DB};
+use crate::ApiError;
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::{
+    header::{HeaderValue, CONTENT_TYPE},
+    Method, StatusCode,
+};
+use actix_web::web::{self, Bytes, Data};
+use actix_web::{Error, FromRequest, HttpResponse, ResponseError};
+use chrono::NaiveDateTime;
+use diesel::result::QueryResult;
+use diesel::Connection;
+use futures_util::future::LocalBoxFuture;
+use paddlers_shared_lib::api::error::GameMasterError;
+use paddlers_shared_lib::api::{IDEMPOTENCY_KEY_HEADER, IDEMPOTENCY_KEY_TTL_SECS};
+use paddlers_shared_lib::prelude::*;
+use sha2::{Digest, Sha256};
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Added to responses that have been recorded earlier
+pub const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+/// Clients keep unsent actions across reloads, thus keys must be remembered for a while
+const RESPONSE_TTL: Duration = Duration::from_secs(IDEMPOTENCY_KEY_TTL_SECS);
+/// A request that takes longer is assumed to have been aborted and may be executed again
+const PROCESSING_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedResponse {
+    status: StatusCode,
+    content_type: Option<HeaderValue>,
+    body: Bytes,
+}
+
+/// Everything about a request that must be the same when it is repeated with the same key
+#[derive(Clone, Debug)]
+pub struct RequestFingerprint {
+    method: String,
+    path: String,
+    body_hash: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Claim {
+    /// First request with this key, it must be executed and its response recorded
+    New,
+    /// The first request with this key has not been answered, yet
+    Processing,
+    Replay(RecordedResponse),
+    /// The key has been used for a different request
+    Mismatch,
+}
+
+/// Reserves the key for the request, unless it has been used before
+pub fn claim(
+    db: &DB,
+    user: uuid::Uuid,
+    key: &str,
+    request: &RequestFingerprint,
+    now: NaiveDateTime,
+) -> QueryResult<Claim> {
+    db.dbconn().transaction(|| {
+        db.delete_expired_idempotency_keys(user, now)?;
+        let record = IdempotencyKey {
+            player_uuid: user,
+            idempotency_key: key.to_owned(),
+            method: request.method.clone(),
+            path: request.path.clone(),
+            body_hash: request.body_hash.clone(),
+            status: None,
+            content_type: None,
+            response_body: None,
+            expires: later(now, PROCESSING_TIMEOUT),
+        };
+        if db.insert_idempotency_key(&record)? > 0 {
+            return Ok(Claim::New);
+        }
+        let existing = db.idempotency_key(user, key)?;
+        if !request.matches(&existing) {
+            return Ok(Claim::Mismatch);
+        }
+        Ok(match RecordedResponse::from_record(existing) {
+            Some(response) => Claim::Replay(response),
+            None => Claim::Processing,
+        })
+    })
+}
+/// Records the response to a claimed key, to be replayed for all repetitions of the request
+pub fn complete(
+    db: &DB,
+    user: uuid::Uuid,
+    key: &str,
+    response: &RecordedResponse,
+    now: NaiveDateTime,
+) -> QueryResult<()> {
+    db.record_idempotent_response(
+        user,
+        key,
+        response.status.as_u16() as i16,
+        response
+            .content_type
+            .as_ref()
+            .and_then(|c| c.to_str().ok())
+            .map(str::to_owned),
+        response.body.to_vec(),
+        later(now, RESPONSE_TTL),
+    )
+    .map(|_| ())
+}
+/// Forgets a claimed key without a response, a repeated request will be executed again
+pub fn release(db: &DB, user: uuid::Uuid, key: &str) -> QueryResult<()> {
+    db.delete_idempotency_key(user, key).map(|_| ())
+}
+
+fn later(t: NaiveDateTime, d: Duration) -> NaiveDateTime {
+    t + chrono::Duration::from_std(d).expect("Duration out of range")
+}
+
+impl RequestFingerprint {
+    pub fn new(method: &Method, path: &str, body: &[u8]) -> Self {
+        RequestFingerprint {
+            method: method.to_string(),
+            path: path.to_owned(),
+            body_hash: Sha256::digest(body).to_vec(),
+        }
+    }
+    fn matches(&self, record: &IdempotencyKey) -> bool {
+        self.method == record.method
+            && self.path == record.path
+            && self.body_hash == record.body_hash
+    }
+}
+
+impl RecordedResponse {
+    /// None if the request has not been answered, yet
+    fn from_record(record: IdempotencyKey) -> Option<Self> {
+        Some(RecordedResponse {
+            status: StatusCode::from_u16(record.status? as u16).ok()?,
+            content_type: record
+                .content_type
+                .and_then(|c| HeaderValue::from_str(&c).ok()),
+            body: Bytes::from(record.response_body.unwrap_or_default()),
+        })
+    }
+    fn replay(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status);
+        if let Some(content_type) = &self.content_type {
+            response.insert_header((CONTENT_TYPE, content_type.clone()));
+        }
+        response
+            .insert_header((REPLAYED_HEADER, "true"))
+            .body(self.body.clone())
+    }
+}
+
+/// Runs the DB operation in the thread pool for blocking calls
+async fn with_db<T, F>(pool: Data<Pool>, f: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce(&DB) -> QueryResult<T> + Send + 'static,
+{
+    web::block(move || {
+        let db: DB = pool.get_ref().into();
+        f(&db)
+    })
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?
+    .map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// Puts a body that has been read by the middleware back into the request
+fn bytes_to_payload(bytes: Bytes) -> actix_web::dev::Payload {
+    let (_, mut payload) = actix_http::h1::Payload::create(true);
+    payload.unread_data(bytes);
+    payload.into()
+}
+
+/// Middleware that records the responses of all POST requests carrying a key in the database of the pool found in the app data
+pub struct Idempotency;
+
+impl<S, B> Transform<S, ServiceRequest> for Idempotency
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = IdempotencyMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(IdempotencyMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct IdempotencyMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for IdempotencyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        Box::pin(async move {
+            let key = req
+                .headers()
+                .get(IDEMPOTENCY_KEY_HEADER)
+                .and_then(|key| key.to_str().ok())
+                .map(str::to_owned);
+            let pool = req.app_data::<Data<Pool>>().cloned();
+            let (key, pool) = match (key, pool) {
+                (Some(key), Some(pool)) if req.method() == Method::POST => (key, pool),
+                _ => return service.call(req).await.map(|res| res.map_into_boxed_body()),
+            };
+            // Unauthenticated requests are rejected by the handlers, nothing to record for them
+            let user = match Authentication::extract(req.request()).await {
+                Ok(auth) => auth.user.uuid,
+                Err(_) => return service.call(req).await.map(|res| res.map_into_boxed_body()),
+            };
+            // The body is read to compare it with earlier requests, then handed on to the handler
+            let body = req.extract::<Bytes>().await?;
+            let request = RequestFingerprint::new(req.method(), req.path(), &body);
+            req.set_payload(bytes_to_payload(body));
+
+            let claimed_key = key.clone();
+            let now = crate::clock::naive_now();
+            let claimed = with_db(pool.clone(), move |db| {
+                claim(db, user, &claimed_key, &request, now)
+            })
+            .await?;
+            match claimed {
+                Claim::New => {}
+                Claim::Processing => {
+                    let error = ApiError(GameMasterError::RequestInProgress);
+                    return Ok(req.into_response(error.error_response()));
+                }
+                Claim::Replay(recorded) => return Ok(req.into_response(recorded.replay())),
+                Claim::Mismatch => {
+                    let error = ApiError(GameMasterError::InvalidRequest(
+                        "Idempotency key has been used for a different request".to_owned(),
+                    ));
+                    return Ok(req.into_response(error.error_response()));
+                }
+            }
+
+            let release_key =
+                |pool: Data<Pool>, key: String| with_db(pool, move |db| release(db, user, &key));
+            let res = match service.call(req).await {
+                Ok(res) => res,
+                Err(e) => {
+                    release_key(pool, key).await?;
+                    return Err(e);
+                }
+            };
+            // Server errors may be temporary, a retry should get another chance
+            if res.status().is_server_error() {
+                release_key(pool, key).await?;
+                return Ok(res.map_into_boxed_body());
+            }
+            let content_type = res.headers().get(CONTENT_TYPE).cloned();
+            let (req, res) = res.into_parts();
+            let (res, res_body) = res.into_parts();
+            let recorded = match body::to_bytes(res_body).await {
+                Ok(bytes) => RecordedResponse {
+                    status: res.status(),
+                    content_type,
+                    body: bytes,
+                },
+                Err(_) => {
+                    release_key(pool, key).await?;
+                    return Err(actix_web::error::ErrorInternalServerError(
+                        "Failed reading response body",
+                    ));
+                }
+            };
+            let to_record = recorded.clone();
+            let now = crate::clock::naive_now();
+            with_db(pool, move |db| complete(db, user, &key, &to_record, now)).await?;
+            let res = res.set_body(BoxBody::new(recorded.body));
+            Ok(ServiceResponse::new(req, res))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::setup::{delete_player_account, initialize_new_player_account};
+    use crate::ActorAddresses;
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::{test, App};
+    use paddlers_shared_lib::api::{
+        hobo::SettleHobo,
+        quests::QuestCollect,
+        reports::ReportCollect,
+        shop::{BuildingPurchase, BuildingUpgrade, ProphetPurchase},
+        PlayerInitData,
+    };
+    use paddlers_shared_lib::generated::QuestName;
+
+    fn recorded(body: &'static str) -> RecordedResponse {
+        RecordedResponse {
+            status: StatusCode::OK,
+            content_type: Some(HeaderValue::from_static("application/json")),
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    fn fingerprint(body: &str) -> RequestFingerprint {
+        RequestFingerprint::new(&Method::POST, "/shop/building", body.as_bytes())
+    }
+
+    /// Nothing is committed, the recorded keys disappear with the connection
+    fn test_db() -> DB {
+        let db: DB = (&DB::new_pool()).into();
+        db.dbconn().begin_test_transaction().unwrap();
+        db
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn repeated_key_is_replayed() {
+        let db = test_db();
+        let user = uuid::Uuid::new_v4();
+        let request = fingerprint("{}");
+        let t0 = chrono::Utc::now().naive_utc();
+        assert_eq!(claim(&db, user, "a", &request, t0), Ok(Claim::New));
+        assert_eq!(claim(&db, user, "a", &request, t0), Ok(Claim::Processing));
+        complete(&db, user, "a", &recorded("7"), t0).unwrap();
+        assert_eq!(
+            claim(&db, user, "a", &request, later(t0, Duration::from_secs(10))),
+            Ok(Claim::Replay(recorded("7")))
+        );
+        assert_eq!(claim(&db, user, "b", &request, t0), Ok(Claim::New));
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn keys_are_separate_per_player() {
+        let db = test_db();
+        let request = fingerprint("{}");
+        let t0 = chrono::Utc::now().naive_utc();
+        let alice = uuid::Uuid::new_v4();
+        let bob = uuid::Uuid::new_v4();
+        assert_eq!(claim(&db, alice, "a", &request, t0), Ok(Claim::New));
+        complete(&db, alice, "a", &recorded("alice"), t0).unwrap();
+        assert_eq!(claim(&db, bob, "a", &request, t0), Ok(Claim::New));
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn keys_expire() {
+        let db = test_db();
+        let user = uuid::Uuid::new_v4();
+        let request = fingerprint("{}");
+        let t0 = chrono::Utc::now().naive_utc();
+        claim(&db, user, "aborted", &request, t0).unwrap();
+        assert_eq!(
+            claim(
+                &db,
+                user,
+                "aborted",
+                &request,
+                later(t0, PROCESSING_TIMEOUT)
+            ),
+            Ok(Claim::New)
+        );
+        claim(&db, user, "done", &request, t0).unwrap();
+        complete(&db, user, "done", &recorded(""), t0).unwrap();
+        assert_eq!(
+            claim(&db, user, "done", &request, later(t0, RESPONSE_TTL)),
+            Ok(Claim::New)
+        );
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn released_key_is_executed_again() {
+        let db = test_db();
+        let user = uuid::Uuid::new_v4();
+        let request = fingerprint("{}");
+        let t0 = chrono::Utc::now().naive_utc();
+        claim(&db, user, "a", &request, t0).unwrap();
+        release(&db, user, "a").unwrap();
+        assert_eq!(claim(&db, user, "a", &request, t0), Ok(Claim::New));
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn key_is_bound_to_its_request() {
+        let db = test_db();
+        let user = uuid::Uuid::new_v4();
+        let t0 = chrono::Utc::now().naive_utc();
+        claim(&db, user, "a", &fingerprint(r#"{"x":1}"#), t0).unwrap();
+        complete(&db, user, "a", &recorded("1"), t0).unwrap();
+        assert_eq!(
+            claim(&db, user, "a", &fingerprint(r#"{"x":2}"#), t0),
+            Ok(Claim::Mismatch)
+        );
+        let other_path = RequestFingerprint::new(&Method::POST, "/hobo/settle", br#"{"x":1}"#);
+        assert_eq!(claim(&db, user, "a", &other_path, t0), Ok(Claim::Mismatch));
+        assert_eq!(
+            claim(&db, user, "a", &fingerprint(r#"{"x":1}"#), t0),
+            Ok(Claim::Replay(recorded("1")))
+        );
+    }
+
+    fn new_player(db: &DB) -> (uuid::Uuid, Player, VillageKey) {
+        let uuid = uuid::Uuid::new_v4();
+        let info = PlayerInitData {
+            display_name: "Idempotency Test".to_owned(),
+            utc_offset_minutes: 0,
+        };
+        initialize_new_player_account(db, uuid, &info).unwrap();
+        let player = db.player_by_uuid(uuid).unwrap();
+        let village = db.player_villages(player.key())[0].key();
+        for res in &[
+            ResourceType::Feathers,
+            ResourceType::Sticks,
+            ResourceType::Logs,
+        ] {
+            db.add_resource(*res, village, 5000).unwrap();
+        }
+        (uuid, player, village)
+    }
+
+    /// Removes a player created by `new_player` together with its recorded idempotency keys
+    fn delete_player(db: &DB, uuid: uuid::Uuid, player: &Player) {
+        use diesel::prelude::*;
+        use paddlers_shared_lib::schema::idempotency_keys;
+        diesel::delete(idempotency_keys::table.filter(idempotency_keys::player_uuid.eq(uuid)))
+            .execute(db.dbconn())
+            .expect("Deleting idempotency keys");
+        delete_player_account(db, player.key());
+    }
+
+    fn insert_building(db: &DB, village: VillageKey, building_type: BuildingType) -> BuildingKey {
+        db.insert_building(&NewBuilding {
+            x: 0,
+            y: 0,
+            building_type,
+            building_range: None,
+            attack_power: None,
+            attacks_per_cycle: None,
+            creation: db.now(),
+            village_id: village.num(),
+            lv: 1,
+        })
+        .key()
+    }
+
+    /// Sends the same action twice with one idempotency key, through all routes of the game-master.
+    /// Checks that the second response is a replay of the first and returns its status.
+    async fn send_twice(
+        pool: &Pool,
+        player: uuid::Uuid,
+        path: &str,
+        body: impl serde::Serialize,
+    ) -> StatusCode {
+        let app = test::init_service(
+            App::new()
+                .wrap(Idempotency)
+                .app_data(Data::new(ActorAddresses::start(pool)))
+                .app_data(Data::new(Config::default()))
+                .app_data(Data::new(pool.clone()))
+                .configure(crate::routes),
+        )
+        .await;
+        let request = || {
+            test::TestRequest::post()
+                .uri(path)
+                .insert_header((AUTHORIZATION, player.to_string()))
+                .insert_header((IDEMPOTENCY_KEY_HEADER, "idempotency-test"))
+                .set_json(&body)
+                .to_request()
+        };
+        let first = test::call_service(&app, request()).await;
+        let status = first.status();
+        assert!(!first.headers().contains_key(REPLAYED_HEADER));
+        let first_body = test::read_body(first).await;
+
+        let second = test::call_service(&app, request()).await;
+        assert_eq!(second.status(), status);
+        assert!(second.headers().contains_key(REPLAYED_HEADER));
+        assert_eq!(test::read_body(second).await, first_body);
+        status
+    }
+
+    /// A player action and its effect on the game state
+    struct Action {
+        path: &'static str,
+        /// Prepares the player's village and returns the request body
+        setup: fn(&DB, &Player, VillageKey) -> serde_json::Value,
+        /// Game state that the action changes, read before and after the action
+        measure: fn(&DB, &Player, VillageKey) -> i64,
+        /// Expected change of the measured state when the action is applied once
+        change: i64,
+    }
+
+    fn hello_world(db: &DB) -> Quest {
+        use diesel::prelude::*;
+        use paddlers_shared_lib::schema::*;
+        let name = QuestName::HelloWorld;
+        db.quest_by_name(name).unwrap_or_else(|_| {
+            diesel::insert_into(quests::table)
+                .values(&NewQuest {
+                    quest_key: name.unique_string().to_owned(),
+                    karma_condition: None,
+                    pop_condition: None,
+                    follow_up_quest: None,
+                })
+                .get_result(db.dbconn())
+                .unwrap()
+        })
+    }
+
+    fn actions() -> Vec<Action> {
+        let prophet_price = paddlers_shared_lib::game_mechanics::prophets::prophet_cost(0)
+            .0
+            .iter()
+            .find(|(res, _)| *res == ResourceType::Feathers)
+            .unwrap()
+            .1;
+        vec![
+            Action {
+                path: "/shop/building",
+                setup: |db, player, village| {
+                    db.add_karma(player.key(), 1).unwrap();
+                    serde_json::to_value(BuildingPurchase {
+                        village,
+                        building_type: BuildingType::Tree,
+                        x: 0,
+                        y: 0,
+                    })
+                    .unwrap()
+                },
+                measure: |db, _, village| {
+                    db.buildings(village)
+                        .into_iter()
+                        .filter(|b| b.building_type == BuildingType::Tree)
+                        .count() as i64
+                },
+                change: 1,
+            },
+            Action {
+                path: "/shop/building/upgrade",
+                setup: |db, _, village| {
+                    let building = insert_building(db, village, BuildingType::Watergate);
+                    serde_json::to_value(BuildingUpgrade {
+                        building,
+                        current_level: 1,
+                    })
+                    .unwrap()
+                },
+                measure: |db, _, village| db.buildings(village).iter().map(|b| b.lv as i64).sum(),
+                change: 1,
+            },
+            Action {
+                path: "/shop/unit/prophet",
+                setup: |db, player, village| {
+                    db.add_karma(player.key(), 1000).unwrap();
+                    serde_json::to_value(ProphetPurchase { village }).unwrap()
+                },
+                measure: |db, _, village| db.resource(ResourceType::Feathers, village),
+                change: -prophet_price,
+            },
+            Action {
+                path: "/report/collect",
+                setup: |db, _, village| {
+                    let report = db.insert_visit_report(NewVisitReport {
+                        village_id: village.num(),
+                        karma: 0,
+                        sender: None,
+                    });
+                    db.insert_visit_report_rewards(vec![NewReward {
+                        visit_report_id: report.id,
+                        resource_type: ResourceType::Feathers,
+                        amount: 10,
+                    }]);
+                    serde_json::to_value(ReportCollect {
+                        reports: vec![report.key()],
+                    })
+                    .unwrap()
+                },
+                measure: |db, _, village| db.resource(ResourceType::Feathers, village),
+                change: 10,
+            },
+            Action {
+                path: "/quest/collect",
+                setup: |db, player, _| {
+                    // Satisfies the karma condition of the specification
+                    db.add_karma(player.key(), 1).unwrap();
+                    let quest = hello_world(db);
+                    if !db
+                        .player_quests(player.key())
+                        .iter()
+                        .any(|q| q.id == quest.id)
+                    {
+                        db.assign_player_quest(player.key(), quest.key()).unwrap();
+                    }
+                    serde_json::to_value(QuestCollect { quest: quest.key() }).unwrap()
+                },
+                measure: |db, player, _| {
+                    let quest = hello_world(db);
+                    db.player_quests(player.key())
+                        .iter()
+                        .filter(|q| q.id == quest.id)
+                        .count() as i64
+                },
+                change: -1,
+            },
+            Action {
+                path: "/hobo/settle",
+                setup: |db, _, village| {
+                    let nest = insert_building(db, village, BuildingType::SingleNest);
+                    serde_json::to_value(SettleHobo { nest }).unwrap()
+                },
+                measure: |db, _, village| db.settled_hobo_count(village),
+                change: 1,
+            },
+        ]
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[actix_web::test]
+    #[ignore]
+    async fn actions_are_idempotent() {
+        let pool = DB::new_pool();
+        let db: DB = (&pool).into();
+        for action in actions() {
+            let (uuid, player, village) = new_player(&db);
+            let body = (action.setup)(&db, &player, village);
+            let before = (action.measure)(&db, &player, village);
+            let status = send_twice(&pool, uuid, action.path, body).await;
+            let after = (action.measure)(&db, &player, village);
+            delete_player(&db, uuid, &player);
+            assert!(status.is_success(), "{} failed: {}", action.path, status);
+            assert_eq!(
+                after,
+                before + action.change,
+                "{} not applied exactly once",
+                action.path
+            );
+        }
+    }
+}
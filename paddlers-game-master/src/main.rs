@@ -5,8 +5,10 @@ extern crate env_logger;
 mod api;
 mod authentication;
 mod buildings;
+mod clock;
 mod db;
 mod game_master;
+mod idempotency;
 mod resource_system;
 mod setup;
 mod statistics;
@@ -18,7 +20,7 @@ use actix_cors::Cors;
 use actix_web::{
     http::header,
     web::{self, Data},
-    App, HttpServer, ResponseError,
+    App, HttpResponse, HttpServer, ResponseError,
 };
 use db::*;
 use game_master::{
@@ -27,6 +29,7 @@ use game_master::{
 };
 use paddlers_shared_lib::api::{
     attacks::{InvitationDescriptor, StartFightRequest},
+    clock::ClockAdvance,
     hobo::SettleHobo,
     quests::QuestCollect,
     reports::ReportCollect,
@@ -37,6 +40,7 @@ use paddlers_shared_lib::prelude::HoboKey;
 use paddlers_shared_lib::{
     api::{
         attacks::AttackDescriptor,
+        error::GameMasterError,
         shop::{BuildingDeletion, BuildingPurchase, ProphetPurchase},
         statistics::FrontendRuntimeStatistics,
         tasks::TaskList,
@@ -44,25 +48,34 @@ use paddlers_shared_lib::{
     config::Config,
 };
 
-type StringErr = Result<(), String>;
+type GameMasterResult<T = ()> = Result<T, GameMasterError>;
 
 struct ActorAddresses {
     _game_master: Addr<GameMaster>,
     town_worker: Addr<TownWorker>,
-    _econ_worker: Addr<EconomyWorker>,
+    econ_worker: Addr<EconomyWorker>,
     _attack_worker: Addr<AttackSpawner>,
     db_actor: Addr<DbActor>,
     attack_funnel: Addr<AttackFunnel>,
     story_worker: Addr<StoryWorker>,
 }
 
+/// Error of a handler, sent to the client as [`GameMasterErrorBody`]
 #[derive(Debug)]
-struct StringError(String);
+struct ApiError(GameMasterError);
 
 #[actix_web::main]
 async fn main() {
     std::env::set_var("RUST_LOG", "actix_web=info");
     env_logger::init();
+    clock::install(clock::Clock::from_env());
+    let simulated_clock = clock::clock().mode() != clock::ClockMode::RealTime;
+    if simulated_clock {
+        println!("Running with simulated clock: {:?}", clock::clock().mode());
+    }
+    // Fail early if the reward table or the story graph is broken, rather than on the first finished visit
+    game_master::visit_rewards::reward_table();
+    game_master::story_graph::story_graph();
 
     let dbpool: Pool = DB::new_pool();
     let conn: DB = (&dbpool.clone()).into();
@@ -73,115 +86,35 @@ async fn main() {
     let origin = config.frontend_origin.clone();
     let base_url = config.game_master_service_name.clone();
 
-    // Start some DB actors in separate threads - they will be blocking
-    let db = dbpool.clone();
-    let db_actor = SyncArbiter::start(2, move || DbActor::new(db.clone()));
-
-    // Spawn all "normal" actors onto the actix system
-    let town_worker_actor = TownWorker::new(dbpool.clone()).start();
-    let attack_funnel =
-        AttackFunnel::new(dbpool.clone(), db_actor.clone(), town_worker_actor.clone()).start();
-    let attack_worker =
-        AttackSpawner::new(dbpool.clone(), db_actor.clone(), attack_funnel.clone()).start();
-    let gm_actor = GameMaster::new(dbpool.clone(), &attack_worker).start();
-    let econ_worker = EconomyWorker::new(dbpool.clone()).start();
-    let story_worker = StoryWorker::new(db_actor.clone(), attack_worker.clone()).start();
+    let actors = Data::new(ActorAddresses::start(&dbpool));
 
     // Also spawn the HTTP server on the same runtime
     HttpServer::new(move || {
         App::new()
+            .wrap(idempotency::Idempotency)
             .wrap(
                 Cors::default()
                     .allowed_origin(&origin)
                     .allowed_methods(vec!["POST"])
                     .allowed_headers(vec![header::AUTHORIZATION, header::ACCEPT])
                     .allowed_header(header::CONTENT_TYPE)
+                    .allowed_header(paddlers_shared_lib::api::IDEMPOTENCY_KEY_HEADER)
                     .max_age(3600 * 24),
             )
             .wrap(actix_web::middleware::Logger::default())
-            .app_data(Data::new(ActorAddresses {
-                _game_master: gm_actor.clone(),
-                town_worker: town_worker_actor.clone(),
-                _econ_worker: econ_worker.clone(),
-                _attack_worker: attack_worker.clone(),
-                db_actor: db_actor.clone(),
-                attack_funnel: attack_funnel.clone(),
-                story_worker: story_worker.clone(),
-            }))
+            .app_data(actors.clone())
             .app_data(Data::new(config.clone()))
             .app_data(Data::new(dbpool.clone()))
-            .route("/", web::get().to(api::index))
-            .service(web::resource("/player/create").route(web::post().to(api::new_player)))
-            .service(
-                web::resource("/shop/building")
-                    .app_data(Data::new(web::Json::<BuildingPurchase>))
-                    .route(web::post().to(api::purchase_building)),
-            )
-            .service(
-                web::resource("/shop/building/delete")
-                    .app_data(Data::new(web::Json::<BuildingDeletion>))
-                    .route(web::post().to(api::delete_building)),
-            )
-            .service(
-                web::resource("/shop/building/upgrade")
-                    .app_data(Data::new(web::Json::<BuildingUpgrade>))
-                    .route(web::post().to(api::upgrade_building)),
-            )
-            .service(
-                web::resource("/shop/unit/prophet")
-                    .app_data(Data::new(web::Json::<ProphetPurchase>))
-                    .route(web::post().to(api::purchase_prophet)),
-            )
-            .service(
-                web::resource("/worker/overwriteTasks")
-                    .app_data(Data::new(web::Json::<TaskList>))
-                    .route(web::post().to(api::overwrite_tasks)),
-            )
-            .service(
-                web::resource("/attacks/create")
-                    .app_data(Data::new(web::Json::<AttackDescriptor>))
-                    .route(web::post().to(api::create_attack)),
-            )
-            .service(
-                web::resource("/attacks/startFight")
-                    .app_data(Data::new(web::Json::<StartFightRequest>))
-                    .route(web::post().to(api::welcome_visitor)),
-            )
-            .service(
-                web::resource("/attacks/invite")
-                    .app_data(Data::new(web::Json::<InvitationDescriptor>))
-                    .route(web::post().to(api::new_invitation)),
-            )
-            .service(
-                web::resource("/attacks/notifications/visitor_satisfied")
-                    .app_data(Data::new(web::Json::<HoboKey>))
-                    .route(web::post().to(api::visitor_satisfied_notification)),
-            )
-            .service(
-                web::resource("/report/collect")
-                    .app_data(Data::new(web::Json::<ReportCollect>))
-                    .route(web::post().to(api::collect_report_rewards)),
-            )
-            .service(
-                web::resource("/story/transition")
-                    .app_data(Data::new(web::Json::<StoryStateTransition>))
-                    .route(web::post().to(api::story_transition)),
-            )
-            .service(
-                web::resource("/stats")
-                    .app_data(Data::new(web::Json::<FrontendRuntimeStatistics>))
-                    .route(web::post().to(statistics::new_frontend_info)),
-            )
-            .service(
-                web::resource("/quest/collect")
-                    .app_data(Data::new(web::Json::<QuestCollect>))
-                    .route(web::post().to(api::collect_quest)),
-            )
-            .service(
-                web::resource("/hobo/settle")
-                    .app_data(Data::new(web::Json::<SettleHobo>))
-                    .route(web::post().to(api::settle_hobo)),
-            )
+            .configure(routes)
+            .configure(|cfg| {
+                if simulated_clock {
+                    cfg.service(
+                        web::resource("/clock/advance")
+                            .app_data(Data::new(web::Json::<ClockAdvance>))
+                            .route(web::post().to(clock::advance_clock)),
+                    );
+                }
+            })
     })
     .disable_signals()
     .bind(&base_url)
@@ -193,26 +126,134 @@ async fn main() {
     println!("Listening on {}", base_url);
 }
 
-impl ResponseError for StringError {
-    fn status_code(&self) -> actix_web::http::StatusCode {
-        actix_web::http::StatusCode::FORBIDDEN
+/// Endpoints of the game-master, excluding those only available for debugging
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(json_config());
+    cfg.route("/", web::get().to(api::index))
+        .service(web::resource("/player/create").route(web::post().to(api::new_player)))
+        .service(
+            web::resource("/shop/building")
+                .app_data(Data::new(web::Json::<BuildingPurchase>))
+                .route(web::post().to(api::purchase_building)),
+        )
+        .service(
+            web::resource("/shop/building/delete")
+                .app_data(Data::new(web::Json::<BuildingDeletion>))
+                .route(web::post().to(api::delete_building)),
+        )
+        .service(
+            web::resource("/shop/building/upgrade")
+                .app_data(Data::new(web::Json::<BuildingUpgrade>))
+                .route(web::post().to(api::upgrade_building)),
+        )
+        .service(
+            web::resource("/shop/unit/prophet")
+                .app_data(Data::new(web::Json::<ProphetPurchase>))
+                .route(web::post().to(api::purchase_prophet)),
+        )
+        .service(
+            web::resource("/worker/overwriteTasks")
+                .app_data(Data::new(web::Json::<TaskList>))
+                .route(web::post().to(api::overwrite_tasks)),
+        )
+        .service(
+            web::resource("/attacks/create")
+                .app_data(Data::new(web::Json::<AttackDescriptor>))
+                .route(web::post().to(api::create_attack)),
+        )
+        .service(
+            web::resource("/attacks/startFight")
+                .app_data(Data::new(web::Json::<StartFightRequest>))
+                .route(web::post().to(api::welcome_visitor)),
+        )
+        .service(
+            web::resource("/attacks/invite")
+                .app_data(Data::new(web::Json::<InvitationDescriptor>))
+                .route(web::post().to(api::new_invitation)),
+        )
+        .service(
+            web::resource("/attacks/notifications/visitor_satisfied")
+                .app_data(Data::new(web::Json::<HoboKey>))
+                .route(web::post().to(api::visitor_satisfied_notification)),
+        )
+        .service(
+            web::resource("/report/collect")
+                .app_data(Data::new(web::Json::<ReportCollect>))
+                .route(web::post().to(api::collect_report_rewards)),
+        )
+        .service(
+            web::resource("/story/transition")
+                .app_data(Data::new(web::Json::<StoryStateTransition>))
+                .route(web::post().to(api::story_transition)),
+        )
+        .service(
+            web::resource("/stats")
+                .app_data(Data::new(web::Json::<FrontendRuntimeStatistics>))
+                .route(web::post().to(statistics::new_frontend_info)),
+        )
+        .service(
+            web::resource("/quest/collect")
+                .app_data(Data::new(web::Json::<QuestCollect>))
+                .route(web::post().to(api::collect_quest)),
+        )
+        .service(
+            web::resource("/hobo/settle")
+                .app_data(Data::new(web::Json::<SettleHobo>))
+                .route(web::post().to(api::settle_hobo)),
+        );
+}
+
+impl ActorAddresses {
+    /// Spawns all actors onto the actix system
+    fn start(dbpool: &Pool) -> Self {
+        // Start some DB actors in separate threads - they will be blocking
+        let db = dbpool.clone();
+        let db_actor = SyncArbiter::start(2, move || DbActor::new(db.clone()));
+
+        let econ_worker = EconomyWorker::new(dbpool.clone()).start();
+        let town_worker = TownWorker::new(dbpool.clone(), econ_worker.clone()).start();
+        let attack_funnel = AttackFunnel::new(dbpool.clone(), town_worker.clone()).start();
+        let attack_worker =
+            AttackSpawner::new(dbpool.clone(), db_actor.clone(), attack_funnel.clone()).start();
+        let game_master = GameMaster::new(dbpool.clone(), &attack_worker).start();
+        let story_worker = StoryWorker::new(db_actor.clone(), attack_worker.clone()).start();
+        ActorAddresses {
+            _game_master: game_master,
+            town_worker,
+            econ_worker,
+            _attack_worker: attack_worker,
+            db_actor,
+            attack_funnel,
+            story_worker,
+        }
     }
 }
 
-impl std::fmt::Display for StringError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+/// Malformed request bodies are answered like all other rejected requests
+fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|err, _req| {
+        ApiError(GameMasterError::InvalidRequest(err.to_string())).into()
+    })
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        actix_web::http::StatusCode::from_u16(self.0.http_status())
+            .unwrap_or(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR)
+    }
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(self.0.body())
     }
 }
 
-impl From<String> for StringError {
-    fn from(value: String) -> Self {
-        StringError(value)
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
-impl From<&str> for StringError {
-    fn from(value: &str) -> Self {
-        StringError(value.to_owned())
+impl From<GameMasterError> for ApiError {
+    fn from(value: GameMasterError) -> Self {
+        ApiError(value)
     }
 }
@@ -1,5 +1,5 @@
-use crate::{db::DB, StringErr};
-use paddlers_shared_lib::{api::shop::*, prelude::*};
+use crate::{db::DB, GameMasterResult};
+use paddlers_shared_lib::{api::error::GameMasterError, api::shop::*, prelude::*};
 
 impl DB {
     pub fn init_resources(&self, vid: VillageKey) {
@@ -19,7 +19,7 @@ impl DB {
         }
     }
 
-    pub fn try_spend(&self, p: &Price, village: VillageKey) -> StringErr {
+    pub fn try_spend(&self, p: &Price, village: VillageKey) -> GameMasterResult {
         self.can_afford(p, village)?;
         self.spend(p, village);
         Ok(())
@@ -31,10 +31,10 @@ impl DB {
                 .expect("Unchecked spending resources");
         }
     }
-    pub fn can_afford(&self, p: &Price, village: VillageKey) -> StringErr {
+    pub fn can_afford(&self, p: &Price, village: VillageKey) -> GameMasterResult {
         for (res, n) in p.0.iter() {
             if self.resource((*res).into(), village) < *n {
-                return Err(format!("Not enough {}", res));
+                return Err(GameMasterError::NotEnoughResources((*res).into()));
             }
         }
         Ok(())
@@ -21,7 +21,7 @@ pub(crate) fn initialize_new_player_account(
     uuid: uuid::Uuid,
     info: &PlayerInitData,
 ) -> Result<(), String> {
-    let result = db.new_player(info.display_name.clone(), uuid);
+    let result = db.new_player(info.display_name.clone(), uuid, info.utc_offset_minutes);
     if let Err(PlayerCreationError::AlreadyExists) = result {
         println!("Warning: Tried to create player account that already exists");
         Ok(())
@@ -32,6 +32,34 @@ pub(crate) fn initialize_new_player_account(
     }
 }
 
+/// Deletes a player created by a test, together with its villages and everything in them
+#[cfg(test)]
+pub(crate) fn delete_player_account(db: &DB, player: PlayerKey) {
+    use diesel::prelude::*;
+    use paddlers_shared_lib::schema::*;
+    let village_ids: Vec<i64> = villages::table
+        .filter(villages::player_id.eq(player.num()))
+        .select(villages::id)
+        .load(db.dbconn())
+        .expect("Loading villages");
+    // Attacks are the only references to villages that are not deleted with them
+    diesel::delete(
+        attacks::table.filter(
+            attacks::destination_village_id
+                .eq_any(&village_ids)
+                .or(attacks::origin_village_id.eq_any(&village_ids)),
+        ),
+    )
+    .execute(db.dbconn())
+    .expect("Deleting attacks");
+    diesel::delete(villages::table.filter(villages::id.eq_any(&village_ids)))
+        .execute(db.dbconn())
+        .expect("Deleting villages");
+    diesel::delete(players::table.find(player.num()))
+        .execute(db.dbconn())
+        .expect("Deleting player");
+}
+
 impl DB {
     pub fn db_scripts_by_env(&self) -> Result<(), Box<dyn std::error::Error>> {
         dotenv().ok();
@@ -50,17 +78,18 @@ impl DB {
             if let Ok(player) = self.new_player(
                 TEST_PLAYER_NAME.to_owned(),
                 uuid::Uuid::parse_str(TEST_PLAYER_UUID).unwrap(),
+                0,
             ) {
                 let village = self.player_villages(player.key())[0];
                 self.add_prophet(village.key());
                 self.add_karma(player.key(), 50000).unwrap();
-                self.set_story_state(player.key(), StoryState::FirstVisitorWelcomed)?;
+                self.set_story_state(player.key(), StoryState::FIRST_VISITOR_WELCOMED)?;
                 self.insert_temple(village.key());
             }
             for i in 0..ADDITIONAL_PLAYERS {
                 let player =
-                    self.new_player(format!("Generated_Tester_{}", i), uuid::Uuid::new_v4())?;
-                self.set_story_state(player.key(), StoryState::Initialized)?;
+                    self.new_player(format!("Generated_Tester_{}", i), uuid::Uuid::new_v4(), 0)?;
+                self.set_story_state(player.key(), StoryState::INITIALIZED)?;
             }
         }
         Ok(())
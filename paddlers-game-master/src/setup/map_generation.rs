@@ -22,7 +22,7 @@ impl NewMap {
         let mut streams = vec![];
         let mut lcg = Lcg::new(seed);
 
-        let start_y = 5.5;
+        let start_y = MAIN_RIVER_Y;
         let dx = MAP_STREAM_AREA_W;
         for i in 0..STREAMS {
             let b = (4 * i) as f32;
@@ -34,6 +34,7 @@ impl NewMap {
     }
 }
 
+/// The first control point is the start on the main river
 fn new_stream(start: (f32, f32), max_dx: f32, max_y: f32, lcg: &mut Lcg) -> NewStream {
     let mut control_points = vec![];
 
@@ -5,6 +5,7 @@ use crate::db::DB;
 use crate::setup::map_generation::Lcg;
 use paddlers_shared_lib::game_mechanics::town::*;
 use paddlers_shared_lib::prelude::*;
+use paddlers_shared_lib::specification_types::HoboLevel;
 
 const HOBOS_PER_TOWN: usize = 3;
 
@@ -107,6 +108,7 @@ impl DB {
             speed,
             hurried,
             nest: Some(nest_id.num()),
+            level: HoboLevel::of_anarchist_hp(hp, hurried).num() as i32,
         });
     }
 }
@@ -8,11 +8,13 @@ impl DB {
         &self,
         display_name: String,
         uuid: uuid::Uuid,
+        utc_offset_minutes: i32,
     ) -> Result<Player, PlayerCreationError> {
         let player = NewPlayer {
             display_name: display_name,
             karma: 0,
             uuid,
+            utc_offset_minutes,
         };
         let player = self.insert_player(&player).map_err(|err| {
             if let diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _info) =
@@ -67,12 +69,12 @@ impl DB {
         self.insert_worker_flag(WorkerFlag {
             worker_id: worker.id,
             flag_type: WorkerFlagType::ManaRegeneration,
-            last_update: chrono::Utc::now().naive_utc(),
+            last_update: crate::clock::naive_now(),
         });
         self.insert_worker_flag(WorkerFlag {
             worker_id: worker.id,
             flag_type: WorkerFlagType::Work,
-            last_update: chrono::Utc::now().naive_utc(),
+            last_update: crate::clock::naive_now(),
         });
         worker
     }
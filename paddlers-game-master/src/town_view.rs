@@ -12,16 +12,20 @@ pub struct TownView {
     pub map: TownMap,
     pub state: TownState<i64>,
     pub buildings_with_aura: Vec<Building>,
+    pub cheering_buildings: Vec<CheeringBuilding>,
+    /// Workers currently doing the defend task, with their worker id
+    pub defenders: Vec<(i64, DefendingWorker)>,
 }
 
 impl TownView {
     pub(crate) fn load_village(db: &DB, village: VillageKey) -> Self {
         let mut map = TownMap::new(TownLayout::Basic);
         let mut state = TownState::new();
-        let now = chrono::Utc::now().naive_utc();
+        let now = crate::clock::naive_now();
 
         let buildings = db.buildings(village);
         let mut buildings_with_aura = vec![];
+        let mut cheering_buildings = vec![];
         for b in buildings {
             let idx = (b.x as usize, b.y as usize);
             map[idx] = TownTileType::BUILDING(b.building_type);
@@ -36,20 +40,33 @@ impl TownView {
                 _ => 0,
             };
             state.forest_size += forest_supply;
-            if b.attacks_per_cycle.is_none()
-                && b.building_range.is_some()
-                && b.attack_power.is_some()
-            {
-                buildings_with_aura.push(b);
+            match (b.building_range, b.attack_power, b.attacks_per_cycle) {
+                (Some(_), Some(_), None) => buildings_with_aura.push(b),
+                (Some(range), Some(strength), Some(attacks_per_cycle)) => {
+                    cheering_buildings.push(CheeringBuilding {
+                        tile: idx,
+                        range,
+                        strength,
+                        attacks_per_cycle: attacks_per_cycle as u32,
+                        built: b.creation.into(),
+                    })
+                }
+                _ => {}
             }
         }
 
         let workers = db.workers(village);
+        let mut defenders = vec![];
         for worker in workers {
             if let Some(task) = db.current_task(worker.key()) {
                 state
                     .register_task_begin(task.task_type)
                     .expect("Current DB state invalid");
+                if task.task_type == TaskType::Defend {
+                    let tile = (task.x as usize, task.y as usize);
+                    let defender = DefendingWorker::hero(tile, task.start_time.into(), None);
+                    defenders.push((worker.id, defender));
+                }
             } else {
                 println!("Warning: worker without task: {:?}", worker);
             }
@@ -59,6 +76,8 @@ impl TownView {
             map,
             state,
             buildings_with_aura,
+            cheering_buildings,
+            defenders,
         }
     }
 
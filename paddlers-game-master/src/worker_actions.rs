@@ -4,16 +4,18 @@
 //! For now, I am still don't really know how I want it to look like.
 
 mod worker_abilities;
-mod worker_updates;
+pub(crate) mod worker_updates;
 
 use crate::db::DB;
 use crate::game_master::event::*;
 use crate::game_master::town_worker::*;
 use crate::town_view::*;
+use crate::GameMasterResult;
 use actix::prelude::*;
 use chrono::offset::TimeZone;
 use chrono::{DateTime, Duration, NaiveDateTime, Utc};
-use paddlers_shared_lib::api::tasks::*;
+use paddlers_shared_lib::api::{error::*, tasks::*};
+use paddlers_shared_lib::game_mechanics::town::{DefendingWorker, TownTileType};
 use paddlers_shared_lib::game_mechanics::worker::*;
 use paddlers_shared_lib::prelude::*;
 use worker_abilities::*;
@@ -30,14 +32,13 @@ pub struct ValidatedTaskList {
     pub update_tasks: Vec<Task>,
     pub village_id: VillageKey,
 }
-pub(crate) fn validate_task_list(
-    db: &DB,
-    tl: &TaskList,
-) -> Result<ValidatedTaskList, Box<dyn std::error::Error>> {
+pub(crate) fn validate_task_list(db: &DB, tl: &TaskList) -> GameMasterResult<ValidatedTaskList> {
     let worker_id = tl.worker_id;
 
     // Load relevant data into memory
-    let mut worker = db.worker_priv(worker_id).ok_or("Worker does not exist")?;
+    let mut worker = db
+        .worker_priv(worker_id)
+        .ok_or(GameMasterError::NotFound(GameObject::Worker))?;
     let village_id = VillageKey(worker.home);
     let mut town = TownView::load_village(db, village_id);
 
@@ -46,7 +47,7 @@ pub(crate) fn validate_task_list(
         .current_task(worker.key())
         .expect("Must have a current task");
     let mut timestamp =
-        interrupt_task(&mut current_task, &worker).ok_or("Cannot interrupt current task.")?;
+        interrupt_task(&mut current_task, &worker).ok_or(GameMasterError::CannotInterruptTask)?;
     worker.x = current_task.x;
     worker.y = current_task.y;
 
@@ -56,10 +57,11 @@ pub(crate) fn validate_task_list(
     for task in tl.tasks.iter() {
         // Validate target hobo exists if there is one
         if let Some(target_id) = task.target {
-            db.hobo(HoboKey(target_id)).ok_or("No such hobo id")?;
+            db.hobo(HoboKey(target_id))
+                .ok_or(GameMasterError::NotFound(GameObject::Hobo))?;
         }
 
-        validate_ability(db, task.task_type, worker_id, timestamp)?;
+        validate_ability(db, &town, task, worker_id, village_id, timestamp)?;
 
         let new_task = NewTask {
             worker_id: worker_id.num(),
@@ -70,7 +72,8 @@ pub(crate) fn validate_task_list(
             target_hobo_id: task.target,
         };
         simulate_begin_task(&new_task, &mut town, &mut worker)?;
-        let duration = simulate_finish_task(&new_task, &mut town, &mut worker)?;
+        let duration = simulate_finish_task(&new_task, &mut town, &mut worker)
+            .map_err(GameMasterError::InvalidRequest)?;
         tasks.push(new_task);
         timestamp += duration;
     }
@@ -111,12 +114,12 @@ fn interrupt_task(current_task: &mut Task, worker: &Worker) -> Option<NaiveDateT
         | TaskType::Defend
         | TaskType::GatherSticks
         | TaskType::CollectReward => {
-            let now = chrono::Utc::now().naive_utc();
+            let now = crate::clock::naive_now();
             Some(now)
         }
         TaskType::Walk => {
             let speed = unit_speed_to_worker_tiles_per_second(worker.speed) as f64;
-            let time_so_far: Duration = Utc::now().naive_utc() - current_task.start_time;
+            let time_so_far: Duration = crate::clock::naive_now() - current_task.start_time;
             let steps = (speed * time_so_far.num_microseconds().unwrap() as f64 / 1_000_000.0)
                 .ceil() as i32;
             let total_time = steps as f64 / speed;
@@ -186,6 +189,7 @@ pub(crate) fn finish_task(
         db.update_worker(&worker);
         db.update_worker_flag_timestamp_now(worker.key(), WorkerFlagType::Work);
         db.delete_task(&task);
+        db.add_village_quest_progress(worker.home(), QuestStatType::CompletedTasks, 1);
 
         Ok(Event::load_next_worker_task(db, task.worker()))
     } else {
@@ -207,6 +211,10 @@ fn apply_task_to_db(db: &DB, task: &Task, worker: &mut Worker) -> Result<(), Str
             };
             db.insert_effect(&ne);
             db.update_ability_used_timestamp(WorkerKey(worker.id), a);
+            // Regeneration pauses while mana is full and starts over now
+            if worker.mana == Some(hero_max_mana()) {
+                db.update_worker_flag_timestamp_now(worker.key(), WorkerFlagType::ManaRegeneration);
+            }
             *worker.mana.as_mut().unwrap() -= AbilityType::Welcome.mana_cost();
         }
         TaskType::CollectReward => {
@@ -227,6 +235,22 @@ fn apply_task_to_db(db: &DB, task: &Task, worker: &mut Worker) -> Result<(), Str
                 return Err(format!("No reward to collect at {},{}", task.x, task.y));
             }
         }
+        TaskType::Defend => {
+            // Defending ends when the next task starts, which can be well in the past when tasks are caught up
+            let end = db
+                .worker_tasks(worker.key())
+                .into_iter()
+                .map(|t| t.start_time)
+                .filter(|start| *start > task.start_time)
+                .min()
+                .unwrap_or_else(crate::clock::naive_now);
+            let defender = DefendingWorker::hero(
+                (task.x as usize, task.y as usize),
+                task.start_time.into(),
+                Some(end.into()),
+            );
+            db.persist_defence(worker.home(), worker.key(), defender, end);
+        }
         _ => { /* NOP */ }
     }
     Ok(())
@@ -265,7 +289,7 @@ fn simulate_finish_task<T: WorkerAction>(
             town.state.remove(&index);
             Ok(Duration::milliseconds(0))
         }
-        TaskType::Defend => Err("Task not implemented".to_owned()),
+        TaskType::Defend => Ok(Duration::milliseconds(0)),
     }
 }
 /// (Try to) apply changes to village state that happen when a worker starts a given task.
@@ -274,14 +298,15 @@ fn simulate_begin_task<T: WorkerAction>(
     task: &T,
     town: &mut TownView,
     worker: &mut Worker,
-) -> Result<(), String> {
+) -> GameMasterResult {
     match task.task_type() {
         TaskType::Idle | TaskType::Walk | TaskType::CollectReward => Ok(()),
         TaskType::GatherSticks | TaskType::ChopTree => {
             town.state
                 .register_task_begin(*task.task_type())
-                .map_err(|e| e.to_string())?;
+                .map_err(|e| GameMasterError::InvalidRequest(e.to_string()))?;
             worker_into_building(town, worker, (task.x() as usize, task.y() as usize))
+                .map_err(GameMasterError::InvalidRequest)
         }
         TaskType::WelcomeAbility => {
             if let Some(mana) = &mut worker.mana {
@@ -290,13 +315,22 @@ fn simulate_begin_task<T: WorkerAction>(
                     *mana = *mana - cost;
                     Ok(())
                 } else {
-                    Err("Not enough mana".to_owned())
+                    Err(GameMasterError::NotEnoughMana)
                 }
             } else {
-                Err("Worker has no mana but tries to use welcome ability".to_owned())
+                Err(GameMasterError::InvalidRequest(
+                    "Worker has no mana but tries to use welcome ability".to_owned(),
+                ))
+            }
+        }
+        TaskType::Defend => {
+            let index = (task.x() as usize, task.y() as usize);
+            if town.map[index] == TownTileType::LANE {
+                Ok(())
+            } else {
+                Err(GameMasterError::DefendOffLane)
             }
         }
-        TaskType::Defend => Err("Task not implemented".to_owned()),
     }
 }
 
@@ -328,3 +362,66 @@ impl WorkerAction for Task {
         self.target_hobo_id.map(HoboKey)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use paddlers_shared_lib::game_mechanics::town::{TownLayout, TownMap, TownState, TOWN_LANE_Y};
+
+    fn empty_town() -> TownView {
+        TownView {
+            map: TownMap::new(TownLayout::Basic),
+            state: TownState::new(),
+            buildings_with_aura: vec![],
+            cheering_buildings: vec![],
+            defenders: vec![],
+        }
+    }
+
+    fn hero(mana: i32) -> Worker {
+        Worker {
+            id: 1,
+            home: 1,
+            x: 0,
+            y: 0,
+            unit_type: UnitType::Hero,
+            color: None,
+            speed: 0.1,
+            mana: Some(mana),
+            level: 1,
+            exp: 0,
+        }
+    }
+
+    fn task(task_type: TaskType, x: usize, y: usize) -> NewTask {
+        NewTask {
+            worker_id: 1,
+            task_type,
+            x: x as i32,
+            y: y as i32,
+            start_time: None,
+            target_hobo_id: None,
+        }
+    }
+
+    #[test]
+    fn rejected_tasks_have_error_codes() {
+        let mut town = empty_town();
+        let mut worker = hero(0);
+        let welcome = task(TaskType::WelcomeAbility, 0, TOWN_LANE_Y);
+        assert_eq!(
+            simulate_begin_task(&welcome, &mut town, &mut worker),
+            Err(GameMasterError::NotEnoughMana)
+        );
+        let defend_off_lane = task(TaskType::Defend, 0, TOWN_LANE_Y + 1);
+        assert_eq!(
+            simulate_begin_task(&defend_off_lane, &mut town, &mut worker),
+            Err(GameMasterError::DefendOffLane)
+        );
+        let defend_on_lane = task(TaskType::Defend, 0, TOWN_LANE_Y);
+        assert_eq!(
+            simulate_begin_task(&defend_on_lane, &mut town, &mut worker),
+            Ok(())
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use crate::db::DB;
 use crate::town_view::*;
 use chrono::Duration;
+use paddlers_shared_lib::api::tasks::{RawTask, TaskError};
 use paddlers_shared_lib::game_mechanics::{town::*, worker::*};
 use paddlers_shared_lib::prelude::*;
 
@@ -39,27 +40,184 @@ pub fn worker_into_building(
     tile_state.try_add_entity().map_err(|e| e.to_string())?;
     Ok(())
 }
+/// Checks that the worker can use the ability of the task at the given time.
+/// Abilities with a target must be cast from within range of the target's position on the lane at that time.
 pub(super) fn validate_ability(
     db: &DB,
-    task_type: TaskType,
+    town: &TownView,
+    task: &RawTask,
     worker_id: WorkerKey,
+    village: VillageKey,
     now: chrono::NaiveDateTime,
-) -> Result<(), String> {
-    if let Some(ability_type) = AbilityType::from_task(&task_type) {
-        // TODO: Range checks
-        // let range = ability_type.range();
-        // TODO: Take movement of visitor into account
-
+) -> Result<(), TaskError> {
+    if let Some(ability_type) = AbilityType::from_task(&task.task_type) {
         if let Some(a) = db.worker_ability(worker_id, ability_type) {
             if let Some(last_used) = a.last_used {
                 let free_to_use = last_used + ability_type.cooldown();
                 if free_to_use > now {
-                    return Err("Cooldown not ready".to_owned());
+                    return Err(TaskError::CooldownNotReady(ability_type));
                 }
             }
         } else {
-            return Err("Worker does not have this ability".to_owned());
+            return Err(TaskError::AbilityNotAvailable(ability_type));
+        }
+
+        if let Some(target) = task.target {
+            let hobo = db.hobo(HoboKey(target)).ok_or(TaskError::TargetNotInTown)?;
+            let target_tile = db
+                .visitor_tile(town, &hobo, village, now)
+                .ok_or(TaskError::TargetNotInTown)?;
+            let distance = distance2((task.x, task.y), target_tile).sqrt();
+            let range = ability_type.range();
+            if distance > range {
+                return Err(TaskError::TargetOutOfRange {
+                    ability: ability_type,
+                    distance,
+                    range,
+                });
+            }
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::setup::initialize_new_player_account;
+    use diesel::Connection;
+    use paddlers_shared_lib::api::PlayerInitData;
+    use paddlers_shared_lib::game_mechanics::town::TOWN_LANE_Y;
+
+    /// Nothing is committed, the test data disappears with the connection
+    fn test_db() -> DB {
+        let db: DB = (&DB::new_pool()).into();
+        db.dbconn().begin_test_transaction().unwrap();
+        db
+    }
+
+    /// A hero in a new village and a visitor that has just entered the village.
+    fn hero_and_visitor(db: &DB) -> (Worker, Hobo, VillageKey) {
+        let uuid = uuid::Uuid::new_v4();
+        let info = PlayerInitData {
+            display_name: "Ability Test".to_owned(),
+            utc_offset_minutes: 0,
+        };
+        initialize_new_player_account(db, uuid, &info).unwrap();
+        let player = db.player_by_uuid(uuid).unwrap();
+        let village = db.player_villages(player.key())[0];
+        let hero = db.workers(village.key()).remove(0);
+
+        let now = db.now();
+        let attack = db
+            .insert_attack(&NewAttack {
+                departure: now,
+                arrival: now,
+                origin_village_id: None,
+                destination_village_id: village.id,
+            })
+            .unwrap();
+        let hobo = db.insert_hobo(&NewHobo {
+            hp: 10,
+            home: village.id,
+            color: Some(UnitColor::Yellow),
+            speed: 0.1,
+            hurried: true,
+            nest: None,
+            level: 0,
+        });
+        db.insert_attack_to_hobo(&AttackToHobo {
+            attack_id: attack.id,
+            hobo_id: hobo.id,
+            satisfied: None,
+            released: None,
+            cheers: 0,
+            cheered_until: None,
+        })
+        .unwrap();
+        db.start_fight(attack.key(), None);
+        (hero, hobo, village.key())
+    }
+
+    fn welcome(x: usize, y: usize, target: PadlId) -> RawTask {
+        RawTask {
+            task_type: TaskType::WelcomeAbility,
+            x,
+            y,
+            target: Some(target),
+        }
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn welcome_in_range_is_accepted() {
+        let db = test_db();
+        let (hero, hobo, village) = hero_and_visitor(&db);
+        let town = TownView::load_village(&db, village);
+        let now = db.now();
+        let (x, y) = db.visitor_tile(&town, &hobo, village, now).unwrap();
+
+        let task = welcome(x, y, hobo.id);
+        assert_eq!(
+            validate_ability(&db, &town, &task, hero.key(), village, now),
+            Ok(())
+        );
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn welcome_out_of_range_is_rejected() {
+        let db = test_db();
+        let (hero, hobo, village) = hero_and_visitor(&db);
+        let town = TownView::load_village(&db, village);
+        let now = db.now();
+        let (x, y) = db.visitor_tile(&town, &hobo, village, now).unwrap();
+        assert_eq!(y, TOWN_LANE_Y);
+
+        // Three tiles away from the lane, the range is two tiles
+        let task = welcome(x, 0, hobo.id);
+        match validate_ability(&db, &town, &task, hero.key(), village, now) {
+            Err(TaskError::TargetOutOfRange {
+                ability, distance, ..
+            }) => {
+                assert_eq!(ability, AbilityType::Welcome);
+                assert_eq!(distance, 3.0);
+            }
+            other => panic!("Expected out of range, got {:?}", other),
+        }
+    }
+
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn welcome_on_invalid_target_is_rejected() {
+        let db = test_db();
+        let (hero, hobo, village) = hero_and_visitor(&db);
+        let town = TownView::load_village(&db, village);
+        let now = db.now();
+
+        let missing_hobo = welcome(0, TOWN_LANE_Y, -1);
+        assert_eq!(
+            validate_ability(&db, &town, &missing_hobo, hero.key(), village, now),
+            Err(TaskError::TargetNotInTown)
+        );
+
+        // Visitors are only targetable while visiting the town
+        let not_visiting = db.insert_hobo(&NewHobo {
+            hp: hobo.hp,
+            home: village.num(),
+            color: hobo.color,
+            speed: hobo.speed,
+            hurried: true,
+            nest: None,
+            level: 0,
+        });
+        let not_visiting = welcome(0, TOWN_LANE_Y, not_visiting.id);
+        assert_eq!(
+            validate_ability(&db, &town, &not_visiting, hero.key(), village, now),
+            Err(TaskError::TargetNotInTown)
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Generates the list of persisted GraphQL queries from the query files of the frontend.
+//!
+//! Only runs with the `persisted_queries` feature, other crates do not need the frontend files to build.
+
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+const QUERY_DIR: &str = "../paddlers-frontend/api/queries";
+const SCHEMA: &str = "../paddlers-frontend/api/schema.json";
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    if std::env::var_os("CARGO_FEATURE_PERSISTED_QUERIES").is_none() {
+        return;
+    }
+    println!("cargo:rerun-if-changed={}", QUERY_DIR);
+    println!("cargo:rerun-if-changed={}", SCHEMA);
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(QUERY_DIR)
+        .expect("Frontend queries not found")
+        .map(|entry| entry.expect("Reading query directory").path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "graphql"))
+        .collect();
+    files.sort();
+
+    // The version changes with every change of the schema or a query, any of which can break older clients
+    let mut version = Sha256::new();
+    version.update(std::fs::read(SCHEMA).expect("Frontend schema not found"));
+
+    let mut queries = String::new();
+    for path in &files {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let text = std::fs::read_to_string(path).expect("Reading query");
+        let id = hex(&Sha256::digest(text.as_bytes()));
+        version.update(id.as_bytes());
+        writeln!(
+            queries,
+            "    PersistedQuery {{ operation_name: {:?}, id: {:?}, query: include_str!({:?}) }},",
+            operation_name(&text, path),
+            id,
+            path.canonicalize().expect("Query path"),
+        )
+        .unwrap();
+    }
+
+    let out = Path::new(&std::env::var("OUT_DIR").unwrap()).join("persisted_queries.rs");
+    let code = format!(
+        "pub const SCHEMA_VERSION: &str = {:?};\npub const PERSISTED_QUERIES: &[PersistedQuery] = &[\n{}];\n",
+        &hex(&version.finalize())[..16],
+        queries
+    );
+    std::fs::write(out, code).expect("Writing persisted queries");
+}
+
+/// Name of the single operation defined in a query file
+fn operation_name<'a>(text: &'a str, path: &Path) -> &'a str {
+    let mut words = text.split(|c: char| !c.is_alphanumeric() && c != '_');
+    words
+        .find(|w| *w == "query" || *w == "mutation")
+        .and_then(|_| words.find(|w| !w.is_empty()))
+        .unwrap_or_else(|| panic!("No named operation in {}", path.display()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
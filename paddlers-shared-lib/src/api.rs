@@ -1,17 +1,30 @@
 pub mod attacks;
+pub mod clock;
 pub mod error;
 pub mod hobo;
 pub mod keys;
+#[cfg(feature = "persisted_queries")]
+pub mod persisted_queries;
 pub mod quests;
 pub mod reports;
 pub mod shop;
 pub mod statistics;
 pub mod story;
+pub mod subscriptions;
 pub mod tasks;
 
 use serde::*;
 
+/// HTTP header with a key chosen by the client for each action.
+/// Requests repeated with the same key are applied only once by the game-master.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+/// How long the game-master recognizes a key, clients must not repeat older requests
+pub const IDEMPOTENCY_KEY_TTL_SECS: u64 = 24 * 3600;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PlayerInitData {
     pub display_name: String,
+    /// Offset of the player's local time to UTC
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
 }
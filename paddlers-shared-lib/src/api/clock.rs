@@ -0,0 +1,7 @@
+use serde::*;
+
+/// Moves the game-master clock forward. Only accepted when the game-master runs with a simulated clock.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClockAdvance {
+    pub seconds: i64,
+}
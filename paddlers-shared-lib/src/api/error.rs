@@ -1,8 +1,14 @@
+use crate::api::tasks::TaskError;
+use crate::models::ResourceType;
+use crate::story::story_state::StoryState;
+use serde::{Deserialize, Serialize};
+
 /// Defines API error codes to be sent over the network
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
 pub enum PadlApiError {
     PlayerNotCreated = 1,
+    ClientOutdated = 2,
 }
 
 impl std::error::Error for PadlApiError {}
@@ -10,6 +16,12 @@ impl std::fmt::Display for PadlApiError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             PadlApiError::PlayerNotCreated => write!(f, "The player is not in the database."),
+            PadlApiError::ClientOutdated => {
+                write!(
+                    f,
+                    "A new version of Paddlers is available, please reload the page."
+                )
+            }
         }
     }
 }
@@ -18,7 +30,270 @@ impl PadlApiError {
     pub fn try_from_num(i: u8) -> Option<Self> {
         match i {
             1 => Some(PadlApiError::PlayerNotCreated),
+            2 => Some(PadlApiError::ClientOutdated),
             _ => None,
         }
     }
 }
+
+/// Reasons for the game-master to refuse a request.
+///
+/// Sent as JSON body of error responses, see [`GameMasterErrorBody`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "code", content = "details")]
+pub enum GameMasterError {
+    /// The request cannot be understood, regardless of the game state
+    InvalidRequest(String),
+    NotAuthenticated,
+    PlayerNotCreated,
+    NotFound(GameObject),
+    NotOwned(GameObject),
+    NotEnoughResources(ResourceType),
+    NotEnoughKarma,
+    /// The player has not unlocked this feature, yet
+    Locked,
+    SpaceOccupied,
+    CannotBuildHere,
+    UnitBlocksSpace,
+    CannotBeDeleted,
+    CannotBeUpgraded,
+    /// The building is not on the level the client expected
+    UnexpectedBuildingLevel {
+        expected: usize,
+        actual: usize,
+    },
+    TaskRejected(TaskError),
+    NotEnoughMana,
+    /// Defending is only possible from a tile on the lane
+    DefendOffLane,
+    /// The worker is busy with a task that cannot be stopped at this time
+    CannotInterruptTask,
+    /// The village cannot take more visitors at the moment
+    VisitorQueueFull,
+    /// One of the hobos is already part of another visit
+    HoboAlreadyVisiting,
+    QuestIncomplete {
+        missing: String,
+    },
+    /// A request with the same idempotency key is still being processed, it can be repeated later
+    RequestInProgress,
+    /// The client is out of sync with the story state in the database
+    StoryStateMismatch {
+        client: StoryState,
+        server: StoryState,
+    },
+    Internal(String),
+}
+
+/// Game objects referenced by a request
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameObject {
+    Village,
+    Building,
+    Worker,
+    Hobo,
+    Report,
+    Quest,
+}
+
+/// JSON body of an error response of the game-master
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameMasterErrorBody {
+    #[serde(flatten)]
+    pub error: GameMasterError,
+    /// Key of the translated message for players
+    pub message_key: String,
+}
+
+impl GameMasterError {
+    pub fn http_status(&self) -> u16 {
+        match self {
+            GameMasterError::NotAuthenticated => 401,
+            GameMasterError::NotOwned(_) => 403,
+            GameMasterError::PlayerNotCreated | GameMasterError::NotFound(_) => 404,
+            GameMasterError::RequestInProgress => 409,
+            GameMasterError::Internal(_) => 500,
+            _ => 400,
+        }
+    }
+    pub fn message_key(&self) -> &'static str {
+        match self {
+            GameMasterError::InvalidRequest(_) => "err-invalid-request",
+            GameMasterError::NotAuthenticated => "err-not-authenticated",
+            GameMasterError::PlayerNotCreated => "err-player-not-created",
+            GameMasterError::NotFound(_) => "err-not-found",
+            GameMasterError::NotOwned(_) => "err-not-owned",
+            GameMasterError::NotEnoughResources(_) => "err-not-enough-resources",
+            GameMasterError::NotEnoughKarma => "err-not-enough-karma",
+            GameMasterError::Locked => "err-locked",
+            GameMasterError::SpaceOccupied => "err-space-occupied",
+            GameMasterError::CannotBuildHere => "err-cannot-build-here",
+            GameMasterError::UnitBlocksSpace => "err-unit-blocks-space",
+            GameMasterError::CannotBeDeleted => "err-cannot-be-deleted",
+            GameMasterError::CannotBeUpgraded => "err-cannot-be-upgraded",
+            GameMasterError::UnexpectedBuildingLevel { .. } => "err-unexpected-building-level",
+            GameMasterError::TaskRejected(_) => "err-task-rejected",
+            GameMasterError::NotEnoughMana => "err-not-enough-mana",
+            GameMasterError::DefendOffLane => "err-defend-off-lane",
+            GameMasterError::CannotInterruptTask => "err-cannot-interrupt-task",
+            GameMasterError::VisitorQueueFull => "err-visitor-queue-full",
+            GameMasterError::HoboAlreadyVisiting => "err-hobo-already-visiting",
+            GameMasterError::QuestIncomplete { .. } => "err-quest-incomplete",
+            GameMasterError::RequestInProgress => "err-request-in-progress",
+            GameMasterError::StoryStateMismatch { .. } => "err-story-state-mismatch",
+            GameMasterError::Internal(_) => "err-internal",
+        }
+    }
+    pub fn body(&self) -> GameMasterErrorBody {
+        GameMasterErrorBody {
+            error: self.clone(),
+            message_key: self.message_key().to_owned(),
+        }
+    }
+}
+
+impl std::error::Error for GameMasterError {}
+impl std::fmt::Display for GameMasterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GameMasterError::InvalidRequest(reason) => write!(f, "Invalid request: {}", reason),
+            GameMasterError::NotAuthenticated => write!(f, "Not authenticated."),
+            GameMasterError::PlayerNotCreated => write!(f, "The player is not in the database."),
+            GameMasterError::NotFound(obj) => write!(f, "{:?} not found.", obj),
+            GameMasterError::NotOwned(obj) => write!(f, "{:?} not owned by player.", obj),
+            GameMasterError::NotEnoughResources(res) => write!(f, "Not enough {:?}.", res),
+            GameMasterError::NotEnoughKarma => write!(f, "Not enough karma."),
+            GameMasterError::Locked => write!(f, "Not unlocked, yet."),
+            GameMasterError::SpaceOccupied => write!(f, "Space occupied."),
+            GameMasterError::CannotBuildHere => write!(f, "Cannot build here."),
+            GameMasterError::UnitBlocksSpace => write!(f, "A unit blocks the space."),
+            GameMasterError::CannotBeDeleted => write!(f, "This building cannot be deleted."),
+            GameMasterError::CannotBeUpgraded => write!(f, "This building cannot be upgraded."),
+            GameMasterError::UnexpectedBuildingLevel { expected, actual } => write!(
+                f,
+                "Expected building level {} but it is {}.",
+                expected, actual
+            ),
+            GameMasterError::TaskRejected(e) => write!(f, "{}", e),
+            GameMasterError::NotEnoughMana => write!(f, "Not enough mana."),
+            GameMasterError::DefendOffLane => write!(f, "Can only defend on the lane."),
+            GameMasterError::CannotInterruptTask => {
+                write!(f, "The current task cannot be interrupted.")
+            }
+            GameMasterError::VisitorQueueFull => write!(f, "Too many visitors are on their way."),
+            GameMasterError::HoboAlreadyVisiting => {
+                write!(f, "A visitor is already part of another visit.")
+            }
+            GameMasterError::QuestIncomplete { missing } => write!(f, "Missing {}.", missing),
+            GameMasterError::RequestInProgress => {
+                write!(f, "The request is still being processed.")
+            }
+            GameMasterError::StoryStateMismatch { client, server } => write!(
+                f,
+                "Invalid story state: {}, database has: {}",
+                client, server
+            ),
+            GameMasterError::Internal(reason) => write!(f, "Internal error: {}", reason),
+        }
+    }
+}
+
+impl From<TaskError> for GameMasterError {
+    fn from(e: TaskError) -> Self {
+        GameMasterError::TaskRejected(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AbilityType;
+
+    #[test]
+    fn error_body_round_trip() {
+        let errors = vec![
+            GameMasterError::Locked,
+            GameMasterError::NotEnoughResources(ResourceType::Feathers),
+            GameMasterError::UnexpectedBuildingLevel {
+                expected: 1,
+                actual: 2,
+            },
+            GameMasterError::TaskRejected(TaskError::TargetNotInTown),
+            GameMasterError::DefendOffLane,
+            GameMasterError::RequestInProgress,
+        ];
+        for error in errors {
+            let json = serde_json::to_value(&error.body()).unwrap();
+            assert_eq!(json["message_key"], error.message_key());
+            assert!(json.get("code").is_some());
+            let parsed: GameMasterErrorBody = serde_json::from_value(json).unwrap();
+            assert_eq!(parsed.error, error);
+        }
+    }
+
+    #[test]
+    fn all_messages_are_translated() {
+        let errors = vec![
+            GameMasterError::InvalidRequest(String::new()),
+            GameMasterError::NotAuthenticated,
+            GameMasterError::PlayerNotCreated,
+            GameMasterError::NotFound(GameObject::Village),
+            GameMasterError::NotOwned(GameObject::Village),
+            GameMasterError::NotEnoughResources(ResourceType::Feathers),
+            GameMasterError::NotEnoughKarma,
+            GameMasterError::Locked,
+            GameMasterError::SpaceOccupied,
+            GameMasterError::CannotBuildHere,
+            GameMasterError::UnitBlocksSpace,
+            GameMasterError::CannotBeDeleted,
+            GameMasterError::CannotBeUpgraded,
+            GameMasterError::UnexpectedBuildingLevel {
+                expected: 1,
+                actual: 2,
+            },
+            GameMasterError::TaskRejected(TaskError::TargetNotInTown),
+            GameMasterError::NotEnoughMana,
+            GameMasterError::DefendOffLane,
+            GameMasterError::CannotInterruptTask,
+            GameMasterError::VisitorQueueFull,
+            GameMasterError::HoboAlreadyVisiting,
+            GameMasterError::QuestIncomplete {
+                missing: String::new(),
+            },
+            GameMasterError::RequestInProgress,
+            GameMasterError::StoryStateMismatch {
+                client: StoryState::INITIALIZED,
+                server: StoryState::ALL_DONE,
+            },
+            GameMasterError::Internal(String::new()),
+        ];
+        let tasks = vec![
+            TaskError::TargetOutOfRange {
+                ability: AbilityType::Welcome,
+                distance: 2.0,
+                range: 1.0,
+            },
+            TaskError::TargetNotInTown,
+            TaskError::CooldownNotReady(AbilityType::Welcome),
+            TaskError::AbilityNotAvailable(AbilityType::Welcome),
+        ];
+        let keys = errors
+            .iter()
+            .map(GameMasterError::message_key)
+            .chain(tasks.iter().map(TaskError::message_key));
+        let locales = [
+            ("en.po", include_str!("../../../texts/en.po")),
+            ("de.po", include_str!("../../../texts/de.po")),
+        ];
+        for key in keys {
+            for (name, po) in &locales {
+                assert!(
+                    po.contains(&format!("msgid \"{}\"", key)),
+                    "{} is missing in {}",
+                    key,
+                    name
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! GraphQL queries of the frontend, known to the db-interface at build time.
+//!
+//! Instead of the query text, the frontend sends the ID of a persisted query together with the schema version it was built with.
+//! The db-interface can then refuse unknown queries and tell outdated clients to reload.
+
+use serde::{Deserialize, Serialize};
+
+pub struct PersistedQuery {
+    pub operation_name: &'static str,
+    /// SHA-256 of the query text
+    pub id: &'static str,
+    pub query: &'static str,
+}
+
+// Defines SCHEMA_VERSION and PERSISTED_QUERIES, see build.rs
+include!(concat!(env!("OUT_DIR"), "/persisted_queries.rs"));
+
+/// Request body for the execution of a persisted query
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PersistedQueryRequest<V> {
+    pub id: String,
+    #[serde(rename = "operationName")]
+    pub operation_name: Option<String>,
+    pub variables: V,
+    /// SCHEMA_VERSION of the client
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: String,
+}
+
+pub fn persisted_query(id: &str) -> Option<&'static PersistedQuery> {
+    PERSISTED_QUERIES.iter().find(|q| q.id == id)
+}
+
+pub fn persisted_query_by_name(operation_name: &str) -> Option<&'static PersistedQuery> {
+    PERSISTED_QUERIES
+        .iter()
+        .find(|q| q.operation_name == operation_name)
+}
@@ -35,6 +35,9 @@ impl Cost for BuildingType {
             BuildingType::Watergate => {
                 vec![]
             }
+            BuildingType::Choir => {
+                vec![(ResourceType::Feathers, 150), (ResourceType::Logs, 20)]
+            }
         }
     }
 }
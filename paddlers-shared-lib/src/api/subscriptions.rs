@@ -0,0 +1,30 @@
+//! Messages exchanged on the WebSocket of the db-interface that pushes live updates to players.
+//!
+//! The socket does not carry any game data itself.
+//! It only tells the client which of its regular GraphQL queries are outdated.
+
+use crate::api::keys::VillageKey;
+use serde::{Deserialize, Serialize};
+
+/// Sent by the client right after the socket has been opened
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VillageSubscription {
+    /// Same value as the Authorization header used for GraphQL requests
+    pub authorization: String,
+}
+
+/// Pushed by the server
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VillageUpdate {
+    /// The subscription has been accepted, updates will follow from now on
+    Subscribed,
+    /// Visitors are on their way or their state changed
+    Attacks(VillageKey),
+    Reports(VillageKey),
+    Resources(VillageKey),
+    Quests,
+    /// Karma, story state or civilization perks changed
+    PlayerInfo,
+    /// Sent periodically, a socket that stays quiet for longer has been lost
+    KeepAlive,
+}
@@ -44,3 +44,48 @@ impl RawTask {
         }
     }
 }
+
+/// Reason why the game-master rejects a task list, sent as JSON body of the error response
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum TaskError {
+    /// The target of an ability is too far away from the worker at cast time
+    TargetOutOfRange {
+        ability: AbilityType,
+        distance: f32,
+        range: f32,
+    },
+    /// The target of an ability is not visiting the town at cast time
+    TargetNotInTown,
+    CooldownNotReady(AbilityType),
+    AbilityNotAvailable(AbilityType),
+}
+
+impl TaskError {
+    /// Key of the translated message shown to the player
+    pub fn message_key(&self) -> &'static str {
+        match self {
+            TaskError::TargetOutOfRange { .. } => "err-target-out-of-range",
+            TaskError::TargetNotInTown => "err-target-not-in-town",
+            TaskError::CooldownNotReady(_) => "err-cooldown-not-ready",
+            TaskError::AbilityNotAvailable(_) => "err-ability-not-available",
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+impl std::fmt::Display for TaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TaskError::TargetOutOfRange {
+                distance, range, ..
+            } => write!(
+                f,
+                "The target is too far away. ({:.1} tiles, range is {:.1})",
+                distance, range
+            ),
+            TaskError::TargetNotInTown => write!(f, "The target is not in town."),
+            TaskError::CooldownNotReady(_) => write!(f, "The ability is not ready, yet."),
+            TaskError::AbilityNotAvailable(_) => write!(f, "This ability has not been learned."),
+        }
+    }
+}
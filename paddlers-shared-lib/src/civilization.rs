@@ -1,5 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "sql_db")]
+use ::diesel_derive_enum::DbEnum;
+
 #[repr(u8)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "sql_db", derive(DbEnum), DieselType = "Civ_perk_type")]
 pub enum CivilizationPerk {
     /// Allows to build (single) nests which can hold hobos
     NestBuilding,
@@ -11,9 +11,35 @@ pub struct Config {
     pub game_master_service_name: String,
     pub graphql_service_name: String,
     pub graphql_port: u16,
+    /// Port of the WebSocket that pushes live updates, next to the GraphQL interface
+    #[serde(default = "default_graphql_ws_port")]
+    pub graphql_ws_port: u16,
+    /// Deepest nesting of fields accepted in a single GraphQL query
+    #[serde(default = "default_graphql_max_depth")]
+    pub graphql_max_depth: u32,
+    /// Highest estimated cost accepted for a single GraphQL query, see the db-interface for how costs are estimated
+    #[serde(default = "default_graphql_max_cost")]
+    pub graphql_max_cost: u32,
+    /// Offer the game-master actions as GraphQL mutations, which the db-interface forwards to the game-master.
+    /// Mutations are not persisted queries, they are rejected while `graphql_persisted_queries_only` is set.
+    #[serde(default)]
+    pub graphql_mutations: bool,
+    /// Only execute the persisted queries of the frontend, rejecting everything else as outdated client
+    #[serde(default)]
+    pub graphql_persisted_queries_only: bool,
     pub keycloak_issuer: String,
 }
 
+fn default_graphql_ws_port() -> u16 {
+    65433
+}
+fn default_graphql_max_depth() -> u32 {
+    10
+}
+fn default_graphql_max_cost() -> u32 {
+    100_000
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
@@ -22,6 +48,11 @@ impl Default for Config {
             game_master_service_name: "localhost:8088".to_owned(),
             graphql_service_name: "localhost".to_owned(),
             graphql_port: 65432,
+            graphql_ws_port: default_graphql_ws_port(),
+            graphql_max_depth: default_graphql_max_depth(),
+            graphql_max_cost: default_graphql_max_cost(),
+            graphql_mutations: false,
+            graphql_persisted_queries_only: false,
             keycloak_issuer: "http://localhost:10002/auth/realms/Paddlers".to_owned(),
         }
     }
@@ -37,6 +68,26 @@ impl Config {
             game_master_service_name: env::var("GAME_MASTER_SERVICE_NAME").ok()?,
             graphql_service_name: env::var("GRAPHQL_SERVICE_NAME").ok()?,
             graphql_port: env::var("GRAPHQL_PORT").ok()?.parse().ok()?,
+            graphql_ws_port: env::var("GRAPHQL_WS_PORT")
+                .ok()
+                .and_then(|p| p.parse().ok())
+                .unwrap_or_else(default_graphql_ws_port),
+            graphql_max_depth: env::var("GRAPHQL_MAX_DEPTH")
+                .ok()
+                .and_then(|d| d.parse().ok())
+                .unwrap_or_else(default_graphql_max_depth),
+            graphql_max_cost: env::var("GRAPHQL_MAX_COST")
+                .ok()
+                .and_then(|c| c.parse().ok())
+                .unwrap_or_else(default_graphql_max_cost),
+            graphql_mutations: env::var("GRAPHQL_MUTATIONS")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or(false),
+            graphql_persisted_queries_only: env::var("GRAPHQL_PERSISTED_QUERIES_ONLY")
+                .ok()
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(false),
             keycloak_issuer: env::var("KEYCLOAK_ISSUER").ok()?,
         })
     }
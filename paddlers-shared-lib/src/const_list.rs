@@ -134,33 +134,9 @@ fn push() {
     assert_eq!(list.len(), 2);
 }
 
-// Specialized functions that are currently not possible to implement in a more generic way
-use crate::story::{story_transitions::*, story_trigger::StoryTrigger};
-impl StoryTransitionList {
-    pub const fn find(&self, key: &StoryTrigger) -> Option<StoryTransition> {
-        let mut i = 0;
-        loop {
-            if i >= Self::MAX_LEN {
-                return None;
-            }
-            if let Some(element) = self.data[i] {
-                // == IS NOT ALLOWED IN CONST FN
-                // if element.trigger == key {
-                //     return Some(element);
-                // }
-                if element.is_trigger(key) {
-                    return Some(element);
-                }
-            }
-            i += 1;
-        }
-    }
-}
-
 mod test {
     #![allow(unused_imports)]
     use super::*;
-    use crate::story::{story_state::StoryState, story_trigger::StoryChoice};
     #[test]
     fn push_to_full_length() {
         let mut list = ConstList::new();
@@ -174,27 +150,4 @@ mod test {
         }
         assert_eq!(list.len(), ConstList::<usize>::MAX_LEN);
     }
-    #[test]
-    fn find_story_trigger() {
-        let mut list = StoryTransitionList::new();
-
-        let d0 = StoryTransition::on_dialogue(StoryState::Initialized);
-        list = list.push(d0);
-
-        let c0 = StoryTransition::on_choice(StoryChoice::new(7), StoryState::Initialized);
-        list = list.push(c0);
-
-        let d = list.find(&StoryTrigger::DialogueStoryTrigger);
-        assert!(d.is_some());
-        assert_eq!(d.unwrap().trigger, d0.trigger);
-        assert_eq!(d.unwrap().actions.len(), d0.actions.len());
-
-        let c = list.find(&StoryTrigger::DialogueChoice(StoryChoice::new(7)));
-        assert!(c.is_some());
-        assert_eq!(c.unwrap().trigger, c0.trigger);
-        assert_eq!(c.unwrap().actions.len(), c0.actions.len());
-
-        let c2 = list.find(&StoryTrigger::DialogueChoice(StoryChoice::new(8)));
-        assert!(c2.is_none());
-    }
 }
@@ -15,6 +15,7 @@ impl Display for BuildingType {
             BuildingType::SingleNest => write!(f, "SingleNest"),
             BuildingType::TripleNest => write!(f, "TripleNest"),
             BuildingType::Watergate => write!(f, "Watergate"),
+            BuildingType::Choir => write!(f, "Choir"),
         }
     }
 }
@@ -13,6 +13,7 @@ impl Attributes for BuildingType {
         match self {
             BuildingType::BlueFlowers => Some(2.0),
             BuildingType::RedFlowers => Some(1.0),
+            BuildingType::Choir => Some(2.5),
             _ => None,
         }
     }
@@ -20,6 +21,7 @@ impl Attributes for BuildingType {
         match self {
             BuildingType::BlueFlowers => Some(1),
             BuildingType::RedFlowers => Some(3),
+            BuildingType::Choir => Some(2),
             _ => None,
         }
     }
@@ -36,6 +38,7 @@ impl Attributes for BuildingType {
             BuildingType::SingleNest => None,
             BuildingType::TripleNest => None,
             BuildingType::Watergate => None,
+            BuildingType::Choir => Some(1),
         }
     }
     fn visitor_queue_capacity(&self, level: u16) -> usize {
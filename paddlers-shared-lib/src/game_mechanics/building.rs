@@ -79,23 +79,24 @@ impl BuildingType {
     pub fn player_can_build(
         &self,
         karma: i64,
-        story_state: StoryState,
+        story_state: &StoryState,
         civ: CivilizationPerks,
     ) -> bool {
         match self {
-            BuildingType::BlueFlowers => story_state == StoryState::AllDone,
+            BuildingType::BlueFlowers => *story_state == StoryState::ALL_DONE,
             BuildingType::BundlingStation => {
-                (story_state == StoryState::FirstVisitorWelcomed) || karma >= 20
+                (*story_state == StoryState::FIRST_VISITOR_WELCOMED) || karma >= 20
             }
             BuildingType::PresentA => karma >= 150,
             BuildingType::PresentB => karma >= 250,
             BuildingType::RedFlowers => karma >= 200,
-            BuildingType::SawMill => story_state == StoryState::AllDone,
-            BuildingType::Temple => story_state == StoryState::ServantAccepted,
+            BuildingType::SawMill => *story_state == StoryState::ALL_DONE,
+            BuildingType::Temple => *story_state == StoryState::SERVANT_ACCEPTED,
             BuildingType::Tree => karma >= 1,
             BuildingType::SingleNest => civ.has(CivilizationPerk::NestBuilding),
             BuildingType::TripleNest => civ.has(CivilizationPerk::TripleNestBuilding),
-            BuildingType::Watergate => story_state == StoryState::BuildingWatergate,
+            BuildingType::Watergate => *story_state == StoryState::BUILDING_WATERGATE,
+            BuildingType::Choir => karma >= 300,
         }
     }
     /// Buildings that may be available at the default shop, regardless of player restrictions
@@ -112,6 +113,7 @@ impl BuildingType {
             BuildingType::SingleNest,
             BuildingType::TripleNest,
             BuildingType::Watergate,
+            BuildingType::Choir,
         ]
         .iter()
     }
@@ -129,6 +131,7 @@ impl BuildingType {
             BuildingType::SingleNest => false, // false for now, to avoid problems with associated hobos
             BuildingType::TripleNest => false, // false for now, to avoid problems with associated hobos
             BuildingType::Watergate => false,
+            BuildingType::Choir => true,
         }
     }
     pub fn worker_task(&self) -> TaskType {
@@ -1,7 +1,173 @@
 pub const MAP_H: u32 = 11;
 pub const MAP_MAX_X: u32 = 1000;
 pub const MAP_STREAM_AREA_W: f32 = 5.0;
+/// All streams branch off the main river at this height
+pub const MAIN_RIVER_Y: f32 = 5.5;
+
+/// Seconds visitors need to travel one map unit along a stream
+pub const VISITOR_SECONDS_PER_MAP_UNIT: f32 = 20.0;
+/// Seconds visitors need to change from one stream to another
+pub const STREAM_TRANSFER_SECONDS: f32 = 15.0;
 
 pub fn map_distance(a: (f32, f32), b: (f32, f32)) -> f32 {
     ((a.0 - b.0) * (a.0 - b.0) + (a.1 - b.1) * (a.1 - b.1)).sqrt()
 }
+
+/// Village coordinates are stored human-readable, this returns the center of the village in map coordinates.
+pub fn village_center(village: (f32, f32)) -> (f32, f32) {
+    (village.0 - 0.5, village.1 - 0.5)
+}
+
+/// A stream as a sequence of points, starting where it branches off the main river.
+#[derive(Clone, Debug)]
+pub struct StreamPath {
+    points: Vec<(f32, f32)>,
+}
+
+/// The way visitors take from one village to another.
+/// They follow the streams, using the main river to change between streams.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VisitorRoute {
+    pub points: Vec<(f32, f32)>,
+    /// Number of times the visitors change from one stream to another
+    pub transfers: u32,
+}
+
+impl StreamPath {
+    /// Takes a stream as stored in the database.
+    /// The control points already start on the main river, `start_x` is only used for streams without control points.
+    pub fn new(start_x: f32, control_points: &[f32]) -> Self {
+        let mut path = Self::from_flat_points(control_points);
+        if path.points.is_empty() {
+            path.points.push((start_x, MAIN_RIVER_Y));
+        }
+        path
+    }
+    /// Takes control points as provided by GraphQL, including the starting point on the main river
+    pub fn from_flat_points(control_points: &[f32]) -> Self {
+        let points = control_points
+            .chunks_exact(2)
+            .map(|t| (t[0], t[1]))
+            .collect();
+        StreamPath { points }
+    }
+    /// Streams are identified by where they branch off the main river
+    fn same_stream(&self, other: &StreamPath) -> bool {
+        self.points.first() == other.points.first()
+    }
+    /// Index of the point on the stream closest to `p`
+    fn closest_point(&self, p: (f32, f32)) -> usize {
+        self.points
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                map_distance(**a, p)
+                    .partial_cmp(&map_distance(**b, p))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(i, _)| i)
+            .unwrap_or(0)
+    }
+}
+
+impl VisitorRoute {
+    /// Route between two villages (in human-readable coordinates) and the streams they lie on
+    pub fn between_villages(
+        origin: (f32, f32),
+        origin_stream: &StreamPath,
+        destination: (f32, f32),
+        destination_stream: &StreamPath,
+    ) -> Self {
+        let origin = village_center(origin);
+        let destination = village_center(destination);
+        let i = origin_stream.closest_point(origin);
+        let j = destination_stream.closest_point(destination);
+
+        let mut points = vec![origin];
+        let transfers;
+        if origin_stream.same_stream(destination_stream) {
+            if i <= j {
+                points.extend_from_slice(&origin_stream.points[i..=j]);
+            } else {
+                points.extend(origin_stream.points[j..=i].iter().rev());
+            }
+            transfers = 0;
+        } else {
+            // Down to the main river, along the main river, and up the other stream
+            points.extend(origin_stream.points[..=i].iter().rev());
+            points.extend_from_slice(&destination_stream.points[..=j]);
+            transfers = 2;
+        }
+        points.push(destination);
+        VisitorRoute { points, transfers }
+    }
+    /// Length of the route in map units
+    pub fn length(&self) -> f32 {
+        self.points
+            .windows(2)
+            .map(|w| map_distance(w[0], w[1]))
+            .sum()
+    }
+    pub fn travel_time_s(&self) -> f32 {
+        self.length() * VISITOR_SECONDS_PER_MAP_UNIT
+            + self.transfers as f32 * STREAM_TRANSFER_SECONDS
+    }
+    /// Position on the route after covering the given fraction of its length (0.0 to 1.0)
+    pub fn position_at(&self, progress: f32) -> (f32, f32) {
+        if progress >= 1.0 {
+            return self.points.last().copied().unwrap_or((0.0, 0.0));
+        }
+        let mut remaining = self.length() * progress.max(0.0).min(1.0);
+        for w in self.points.windows(2) {
+            let d = map_distance(w[0], w[1]);
+            if remaining <= d && d > 0.0 {
+                let t = remaining / d;
+                return (
+                    w[0].0 + (w[1].0 - w[0].0) * t,
+                    w[0].1 + (w[1].1 - w[0].1) * t,
+                );
+            }
+            remaining -= d;
+        }
+        self.points.last().copied().unwrap_or((0.0, 0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn route_on_same_stream() {
+        let stream = StreamPath::new(1.0, &[1.0, 5.5, 2.0, 7.0, 1.5, 8.5]);
+        let route = VisitorRoute::between_villages((3.0, 8.0), &stream, (2.5, 9.5), &stream);
+        assert_eq!(route.transfers, 0);
+        assert_eq!(route.points.first(), Some(&(2.5, 7.5)));
+        assert_eq!(route.points.last(), Some(&(2.0, 9.0)));
+        // Does not detour to the main river
+        assert!(route.points.iter().all(|p| p.1 > 6.0));
+    }
+
+    #[test]
+    fn stream_starts_on_main_river_once() {
+        let stream = StreamPath::new(1.0, &[1.0, 5.5, 2.0, 7.0]);
+        assert_eq!(stream.points, vec![(1.0, MAIN_RIVER_Y), (2.0, 7.0)]);
+        let empty = StreamPath::new(3.0, &[]);
+        assert_eq!(empty.points, vec![(3.0, MAIN_RIVER_Y)]);
+    }
+
+    #[test]
+    fn route_between_streams_uses_main_river() {
+        let a = StreamPath::new(1.0, &[1.0, 5.5, 2.0, 7.0]);
+        let b = StreamPath::new(9.0, &[9.0, 5.5, 8.5, 4.0]);
+        let route = VisitorRoute::between_villages((3.0, 8.0), &a, (9.0, 4.0), &b);
+        assert_eq!(route.transfers, 2);
+        assert!(route.points.contains(&(1.0, MAIN_RIVER_Y)));
+        assert!(route.points.contains(&(9.0, MAIN_RIVER_Y)));
+        let direct = map_distance(village_center((3.0, 8.0)), village_center((9.0, 4.0)));
+        assert!(route.length() > direct);
+        assert!(route.travel_time_s() > route.length() * VISITOR_SECONDS_PER_MAP_UNIT);
+        assert_eq!(route.position_at(0.0), village_center((3.0, 8.0)));
+        assert_eq!(route.position_at(1.0), village_center((9.0, 4.0)));
+    }
+}
@@ -1,7 +1,9 @@
 pub mod defence;
 pub mod town_layout;
 
-pub use defence::{IAttackingHobo, IDefendingTown};
+pub use defence::{
+    CheeringBuilding, DefendingWorker, IAttackingHobo, IDefendingTown, CHEER_CYCLE_S,
+};
 pub use town_layout::{ITownLayout, ITownLayoutMarker, TownLayout};
 
 #[cfg(test)]
@@ -4,9 +4,13 @@
 //! Based solely on this information, the computation is defined inside the traits.
 //! The frontend and the backend can therefore use his computation by implementing the traits.
 use super::town_layout::ITownLayout;
-use super::{TOWN_RESTING_X, TOWN_X};
+use super::{TownLayoutIndex, TOWN_RESTING_X, TOWN_X};
+use crate::game_mechanics::worker::{hero_defence_range, hero_defence_strength};
 use crate::shared_types::*;
 
+/// Seconds between two cheers of a building with attacks per cycle
+pub const CHEER_CYCLE_S: i64 = 5;
+
 /// Provides information about a hobo currently attacking
 pub trait IAttackingHobo {
     // TO IMPLEMENT
@@ -16,6 +20,10 @@ pub trait IAttackingHobo {
     fn start_of_fight(&self) -> Option<Timestamp>;
     fn released(&self) -> Option<Timestamp>;
     fn effects_strength(&self) -> i32;
+    /// Cheers received until `cheered_until`, as counted by an earlier evaluation
+    fn cheers(&self) -> i32;
+    /// Cheers up to this point in time are included in `cheers`. None if no cheers have been counted, yet.
+    fn cheered_until(&self) -> Option<Timestamp>;
 
     // PROVIDED
     /// Returns the duration it takes the hobo to reach the resting place, after having reached the town.
@@ -28,11 +36,37 @@ pub trait IAttackingHobo {
     }
 }
 
+/// A building that cheers up a limited number of visitors in range, once per cycle.
+/// As opposed to an aura, which affects each visitor passing by only once, the same visitor can be cheered repeatedly.
+#[derive(Clone, Debug)]
+pub struct CheeringBuilding {
+    pub tile: TownLayoutIndex,
+    pub range: f32,
+    pub strength: i32,
+    pub attacks_per_cycle: u32,
+    pub built: Timestamp,
+}
+
+/// A worker doing the defend task on a tile of the lane.
+/// Like an aura, each visitor passing by (or resting) in range while the worker is defending is affected once.
+#[derive(Clone, Debug)]
+pub struct DefendingWorker {
+    pub tile: TownLayoutIndex,
+    pub range: f32,
+    pub strength: i32,
+    /// Start of the defend task
+    pub since: Timestamp,
+    /// End of the defend task, None while it is ongoing
+    pub until: Option<Timestamp>,
+}
+
 /// Trait for town information required to perform hp computations
-pub trait IDefendingTown: ITownLayout {
+pub trait IDefendingTown: ITownLayout<Index = TownLayoutIndex> {
     // TO IMPLEMENT
     type AuraId: Ord + PartialEq;
     fn auras_in_range(&self, index: &Self::Index, time: Timestamp) -> Vec<(Self::AuraId, i32)>;
+    fn cheering_buildings(&self) -> Vec<CheeringBuilding>;
+    fn defenders(&self) -> Vec<(Self::AuraId, DefendingWorker)>;
 
     // PROVIDED
     fn hp_left<HOBO: IAttackingHobo>(&self, attacker: &HOBO, now: Timestamp) -> u32 {
@@ -41,7 +75,9 @@ pub trait IDefendingTown: ITownLayout {
             .saturating_sub(self.total_damage(attacker, now) as u32)
     }
     fn total_damage<HOBO: IAttackingHobo>(&self, attacker: &HOBO, now: Timestamp) -> i32 {
-        self.aura_damage(attacker, now) + attacker.effects_strength()
+        self.aura_damage(attacker, now)
+            + self.defender_damage(attacker, now)
+            + attacker.effects_strength()
     }
 
     fn hobo_left_town<HOBO: IAttackingHobo>(&self, attacker: &HOBO, now: Timestamp) -> bool {
@@ -69,6 +105,9 @@ pub trait IDefendingTown: ITownLayout {
         let dmg = Self::damage(&auras);
         dmg
     }
+    fn defender_damage<HOBO: IAttackingHobo>(&self, attacker: &HOBO, now: Timestamp) -> i32 {
+        Self::damage(&self.touched_defenders(attacker, now))
+    }
     fn damage(auras: &[(Self::AuraId, i32)]) -> i32 {
         auras.iter().fold(0, |acc, aura| acc + aura.1)
     }
@@ -127,6 +166,172 @@ pub trait IDefendingTown: ITownLayout {
         out.dedup();
         out
     }
+    /// Defenders which have met the visitor while defending, until `now`
+    fn touched_defenders<HOBO: IAttackingHobo>(
+        &self,
+        attacker: &HOBO,
+        now: Timestamp,
+    ) -> Vec<(Self::AuraId, i32)> {
+        let defenders = self.defenders();
+        if defenders.is_empty() {
+            return vec![];
+        }
+        let visited = self.visited_tiles(attacker, now);
+        defenders
+            .into_iter()
+            .filter(|(_, d)| {
+                visited
+                    .iter()
+                    .any(|(from, to, tile)| d.in_range(*tile) && d.active_during(*from, *to))
+            })
+            .map(|(id, d)| (id, d.strength))
+            .collect()
+    }
+    /// The tiles a visitor has been on until `now`, each with the time it entered and left the tile.
+    /// A resting visitor stays on the last tile of the path to the resting place until it leaves (or `now`).
+    fn visited_tiles<HOBO: IAttackingHobo>(
+        &self,
+        attacker: &HOBO,
+        now: Timestamp,
+    ) -> Vec<(Timestamp, Timestamp, TownLayoutIndex)> {
+        let mut out = vec![];
+        let start = match attacker.start_of_fight() {
+            Some(t) => t,
+            None => return out,
+        };
+        let t_per_tile = Timestamp::from_float_seconds(1.0 / attacker.speed());
+        let walk = |path: &[TownLayoutIndex], mut t: Timestamp, out: &mut Vec<_>| {
+            for tile in path {
+                if t > now {
+                    break;
+                }
+                out.push((t, t + t_per_tile, *tile));
+                t = t + t_per_tile;
+            }
+            t
+        };
+        if attacker.hurried() {
+            walk(self.path_straight_through(), start, &mut out);
+        } else {
+            let path = self.path_to_rest_place();
+            let arrived = walk(path, start, &mut out);
+            let left = self.left_rest_place(attacker);
+            let rest_end = left.unwrap_or(now);
+            if let Some(rest_tile) = path.last() {
+                if arrived <= now && arrived < rest_end {
+                    out.push((arrived, rest_end, *rest_tile));
+                }
+            }
+            if let Some(left) = left {
+                walk(self.path_from_rest_place(), left, &mut out);
+            }
+        }
+        out
+    }
+    /// Cheers each visitor received from buildings with attacks per cycle, until `now`.
+    ///
+    /// Visitors compete for the attention of these buildings, hence all visitors in the town have to be considered together,
+    /// including those which have been satisfied or have left already.
+    /// In every cycle, each building cheers the visitors in range which are closest to being satisfied.
+    /// Ties are broken by the order of `visitors`, which must therefore be the same in frontend and backend (sorted by hobo id).
+    ///
+    /// Cheers counted by an earlier evaluation are taken as they are, only the cycles after `cheered_until` add to them.
+    /// The counted cheers are only known from `cheered_until` on, though. To rank visitors in earlier cycles,
+    /// the cheers they had received at that point are replayed from the first arrival in town.
+    fn cheer_damage<HOBO: IAttackingHobo>(&self, visitors: &[HOBO], now: Timestamp) -> Vec<i32> {
+        let mut cheers: Vec<i32> = visitors.iter().map(|v| v.cheers()).collect();
+        let mut replayed: Vec<i32> = vec![0; visitors.len()];
+        let counted = |v: &HOBO, t: Timestamp| v.cheered_until().map_or(false, |c| t <= c);
+        let replay_from = match visitors
+            .iter()
+            .filter_map(|v| v.start_of_fight())
+            .min_by_key(|t| t.micros())
+        {
+            Some(t) => t,
+            None => return cheers,
+        };
+        let buildings = self.cheering_buildings();
+        let mut ticks: Vec<(Timestamp, usize)> = buildings
+            .iter()
+            .enumerate()
+            .flat_map(|(i, b)| b.cheer_times(replay_from, now).map(move |t| (t, i)))
+            .collect();
+        ticks.sort_by_key(|(t, i)| (t.micros(), *i));
+        for (t, i) in ticks {
+            let building = &buildings[i];
+            let mut candidates: Vec<(i32, usize)> = visitors
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| {
+                    self.visitor_tile(*v, t)
+                        .map(|tile| building.in_range(tile))
+                        .unwrap_or(false)
+                })
+                .map(|(j, v)| {
+                    let known = if counted(v, t) {
+                        replayed[j]
+                    } else {
+                        cheers[j]
+                    };
+                    (v.max_hp() as i32 - self.total_damage(v, t) - known, j)
+                })
+                .filter(|(hp, _)| *hp > 0)
+                .collect();
+            candidates.sort();
+            for (_, j) in candidates
+                .into_iter()
+                .take(building.attacks_per_cycle as usize)
+            {
+                if counted(&visitors[j], t) {
+                    replayed[j] += building.strength;
+                } else {
+                    cheers[j] += building.strength;
+                }
+            }
+        }
+        cheers
+    }
+    /// Like `hp_left` but also including the cheers computed by `cheer_damage`, for all visitors in the town.
+    fn hp_left_in_town<HOBO: IAttackingHobo>(
+        &self,
+        visitors: &[HOBO],
+        cheers: &[i32],
+        now: Timestamp,
+    ) -> Vec<u32> {
+        visitors
+            .iter()
+            .zip(cheers)
+            .map(|(v, cheers)| {
+                v.max_hp()
+                    .saturating_sub((self.total_damage(v, now) + cheers).max(0) as u32)
+            })
+            .collect()
+    }
+    /// The tile a visitor is in at the given time. None before entering or after leaving the town.
+    fn visitor_tile<HOBO: IAttackingHobo>(
+        &self,
+        attacker: &HOBO,
+        t: Timestamp,
+    ) -> Option<TownLayoutIndex> {
+        let start = attacker.start_of_fight()?;
+        if t < start {
+            return None;
+        }
+        let tile_on_path = |path: &[TownLayoutIndex], since: Timestamp| {
+            let i = ((t - since).seconds_float() * attacker.speed()) as usize;
+            path.get(i).copied()
+        };
+        if attacker.hurried() {
+            return tile_on_path(self.path_straight_through(), start);
+        }
+        if let Some(left) = self.left_rest_place(attacker) {
+            if t >= left {
+                return tile_on_path(self.path_from_rest_place(), left);
+            }
+        }
+        let path = self.path_to_rest_place();
+        tile_on_path(path, start).or_else(|| path.last().copied())
+    }
     /// The timestamp when the resting place was left by a non-hurried hobo. May differ from hobo.released
     fn left_rest_place<HOBO: IAttackingHobo>(&self, attacker: &HOBO) -> Option<Timestamp> {
         if attacker.start_of_fight().is_none() {
@@ -144,3 +349,47 @@ pub trait IDefendingTown: ITownLayout {
         })
     }
 }
+
+fn in_range(a: TownLayoutIndex, b: TownLayoutIndex, range: f32) -> bool {
+    let dx = a.0 as f32 - b.0 as f32;
+    let dy = a.1 as f32 - b.1 as f32;
+    dx * dx + dy * dy <= range * range
+}
+
+impl CheeringBuilding {
+    pub fn in_range(&self, tile: TownLayoutIndex) -> bool {
+        in_range(self.tile, tile, self.range)
+    }
+    /// Points in time within [from, to] at which the building cheers.
+    /// The first cheer happens when the building is built, then once every cycle.
+    pub fn cheer_times(&self, from: Timestamp, to: Timestamp) -> impl Iterator<Item = Timestamp> {
+        let cycle = Timestamp::from_seconds(CHEER_CYCLE_S).micros();
+        let built = self.built.micros();
+        let first = if from.micros() > built {
+            (from.micros() - built + cycle - 1) / cycle
+        } else {
+            0
+        };
+        let last = (to.micros() - built).div_euclid(cycle);
+        (first..=last).map(move |k| Timestamp::from_us(built + k * cycle))
+    }
+}
+
+impl DefendingWorker {
+    pub fn hero(tile: TownLayoutIndex, since: Timestamp, until: Option<Timestamp>) -> Self {
+        DefendingWorker {
+            tile,
+            range: hero_defence_range(),
+            strength: hero_defence_strength(),
+            since,
+            until,
+        }
+    }
+    pub fn in_range(&self, tile: TownLayoutIndex) -> bool {
+        in_range(self.tile, tile, self.range)
+    }
+    /// Whether the worker has been defending at any point in time within [from, to]
+    pub fn active_during(&self, from: Timestamp, to: Timestamp) -> bool {
+        self.since <= to && self.until.map(|until| from < until).unwrap_or(true)
+    }
+}
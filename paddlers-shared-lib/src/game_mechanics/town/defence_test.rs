@@ -11,9 +11,13 @@ struct TestHobo {
     arrival: Timestamp,
     released: Option<Timestamp>,
     effects_strength: i32,
+    cheers: i32,
+    cheered_until: Option<Timestamp>,
 }
 struct TestTown {
     building_auras: HashMap<TownLayoutIndex, Vec<TestAura>>,
+    cheering_buildings: Vec<CheeringBuilding>,
+    defenders: Vec<(usize, DefendingWorker)>,
 }
 #[derive(Copy, Clone, Debug)]
 struct TestAura {
@@ -125,6 +129,173 @@ fn unhurried_hobo_released() {
     assert_eq!(hobo_hp_left, 97);
 }
 
+#[test]
+fn resting_hobo_cheered_every_cycle() {
+    let mut hobo = TestHobo::new();
+    hobo.hurried = false;
+    let mut town = TestTown::new();
+    // Only reaches the resting place, after 8s
+    town.add_choir((TOWN_RESTING_X, Y - 1), 1.0, 1);
+
+    let now = Timestamp::from_seconds(20);
+    let cheers = town.cheer_damage(&[hobo], now);
+    // Cheers at 10s, 15s and 20s
+    assert_eq!(cheers, vec![6]);
+}
+
+#[test]
+fn hurried_hobo_cheered_while_in_range() {
+    let hobo = TestHobo::new();
+    let mut town = TestTown::new();
+    // In range while on (6, Y), which is between 4s and 6s
+    town.add_choir((6, Y - 1), 1.0, 1);
+
+    let now = Timestamp::from_seconds(100);
+    assert!(town.hobo_left_town(&hobo, now));
+    assert_eq!(town.cheer_damage(&[hobo], now), vec![2]);
+}
+
+#[test]
+fn cheers_go_to_hobo_closest_to_satisfaction() {
+    let mut strong = TestHobo::new();
+    strong.hurried = false;
+    let mut weak = TestHobo::new();
+    weak.hurried = false;
+    weak.max_hp = 3;
+    let mut town = TestTown::new();
+    town.add_choir((TOWN_RESTING_X, Y - 1), 1.0, 1);
+
+    // At 10s and 15s the weak one is cheered, then the strong one at 20s
+    let now = Timestamp::from_seconds(20);
+    let visitors = [strong, weak];
+    let cheers = town.cheer_damage(&visitors, now);
+    assert_eq!(cheers, vec![2, 4]);
+    assert_eq!(town.hp_left_in_town(&visitors, &cheers, now), vec![98, 0]);
+}
+
+#[test]
+fn counted_cheers_are_not_replayed() {
+    let mut hobo = TestHobo::new();
+    hobo.hurried = false;
+    let mut town = TestTown::new();
+    town.add_choir((TOWN_RESTING_X, Y - 1), 1.0, 1);
+
+    // Cheers at 10s and 15s have been counted, only the one at 20s is added
+    hobo.cheers = 4;
+    hobo.cheered_until = Some(Timestamp::from_seconds(15));
+    let now = Timestamp::from_seconds(20);
+    assert_eq!(town.cheer_damage(&[hobo], now), vec![6]);
+}
+
+#[test]
+fn counted_cheers_still_compete_for_attention() {
+    let mut counted = TestHobo::new();
+    counted.hurried = false;
+    counted.max_hp = 5;
+    counted.cheers = 4;
+    counted.cheered_until = Some(Timestamp::from_seconds(15));
+    let mut new = TestHobo::new();
+    new.hurried = false;
+    let mut town = TestTown::new();
+    town.add_choir((TOWN_RESTING_X, Y - 1), 1.0, 1);
+
+    // The counted visitor is closer to satisfaction at 10s, 15s and 20s, the new one never gets a cheer
+    let now = Timestamp::from_seconds(20);
+    assert_eq!(town.cheer_damage(&[counted, new], now), vec![6, 0]);
+}
+
+#[test]
+fn counted_cheers_are_unknown_before_they_were_counted() {
+    let mut counted = TestHobo::new();
+    counted.hurried = false;
+    counted.max_hp = 10;
+    counted.cheers = 6;
+    counted.cheered_until = Some(Timestamp::from_seconds(15));
+    let mut new = TestHobo::new();
+    new.hurried = false;
+    new.max_hp = 8;
+    let mut town = TestTown::new();
+    town.add_choir((TOWN_RESTING_X, Y - 1), 1.0, 1);
+
+    // At 10s and 15s, the counted visitor has no cheers known, yet, so the new one is closer to satisfaction.
+    // At 20s, both have 4 hp left and the tie goes to the counted visitor.
+    let now = Timestamp::from_seconds(20);
+    assert_eq!(town.cheer_damage(&[counted, new], now), vec![8, 4]);
+}
+
+#[test]
+fn cheers_split_among_hobos() {
+    let mut a = TestHobo::new();
+    a.hurried = false;
+    let mut b = TestHobo::new();
+    b.hurried = false;
+    let mut town = TestTown::new();
+    town.add_choir((TOWN_RESTING_X, Y - 1), 1.0, 2);
+
+    let now = Timestamp::from_seconds(10);
+    assert_eq!(town.cheer_damage(&[a, b], now), vec![2, 2]);
+}
+
+#[test]
+fn cheer_times_follow_cycle() {
+    let building = CheeringBuilding {
+        tile: (0, 0),
+        range: 1.0,
+        strength: 1,
+        attacks_per_cycle: 1,
+        built: Timestamp::from_seconds(3),
+    };
+    let times: Vec<i64> = building
+        .cheer_times(Timestamp::from_seconds(0), Timestamp::from_seconds(14))
+        .map(|t| t.micros() / 1_000_000)
+        .collect();
+    assert_eq!(times, vec![3, 8, 13]);
+    let times: Vec<i64> = building
+        .cheer_times(Timestamp::from_seconds(9), Timestamp::from_seconds(13))
+        .map(|t| t.micros() / 1_000_000)
+        .collect();
+    assert_eq!(times, vec![13]);
+}
+
+#[test]
+fn defender_cheers_passing_hobo_once() {
+    let hobo = TestHobo::new();
+    let mut town = TestTown::new();
+    // Tiles (7, Y) to (5, Y) are in range, passed between 2s and 8s
+    town.add_defender((6, Y), 0, None);
+
+    let now = Timestamp::from_seconds(100);
+    assert_eq!(town.defender_damage(&hobo, now), 3);
+    assert_eq!(town.hp_left(&hobo, now), 97);
+}
+
+#[test]
+fn defender_only_affects_hobos_met_while_defending() {
+    let hobo = TestHobo::new();
+    let mut town = TestTown::new();
+    // Started defending after the hobo has passed
+    town.add_defender((6, Y), 9, None);
+    // Stopped defending before the hobo arrived
+    town.add_defender((6, Y), 0, Some(1));
+    // Defending at the right time, but out of range
+    town.add_defender((6, Y - 2), 0, None);
+
+    let now = Timestamp::from_seconds(100);
+    assert_eq!(town.defender_damage(&hobo, now), 0);
+}
+
+#[test]
+fn defender_reaches_resting_hobo() {
+    let mut hobo = TestHobo::new();
+    hobo.hurried = false;
+    let mut town = TestTown::new();
+    // Hobo rests at (TOWN_RESTING_X, Y) from 10s
+    town.add_defender((TOWN_RESTING_X, Y), 50, None);
+
+    assert_eq!(town.defender_damage(&hobo, Timestamp::from_seconds(40)), 0);
+    assert_eq!(town.defender_damage(&hobo, Timestamp::from_seconds(60)), 3);
+}
+
 impl TestHobo {
     fn new() -> Self {
         TestHobo {
@@ -134,6 +305,8 @@ impl TestHobo {
             arrival: Timestamp::from_seconds(0),
             released: None,
             effects_strength: 0,
+            cheers: 0,
+            cheered_until: None,
         }
     }
 }
@@ -157,6 +330,12 @@ impl IAttackingHobo for TestHobo {
     fn effects_strength(&self) -> i32 {
         self.effects_strength
     }
+    fn cheers(&self) -> i32 {
+        self.cheers
+    }
+    fn cheered_until(&self) -> Option<Timestamp> {
+        self.cheered_until
+    }
 }
 impl ITownLayoutMarker for TestTown {
     const LAYOUT: TownLayout = TownLayout::Basic;
@@ -170,6 +349,12 @@ impl IDefendingTown for TestTown {
             Vec::new()
         }
     }
+    fn cheering_buildings(&self) -> Vec<CheeringBuilding> {
+        self.cheering_buildings.clone()
+    }
+    fn defenders(&self) -> Vec<(Self::AuraId, DefendingWorker)> {
+        self.defenders.clone()
+    }
 }
 impl TestAura {
     pub fn new(strength: i32) -> Self {
@@ -185,6 +370,8 @@ impl TestTown {
     pub fn new() -> Self {
         TestTown {
             building_auras: HashMap::new(),
+            cheering_buildings: Vec::new(),
+            defenders: Vec::new(),
         }
     }
     fn add_aura(&mut self, aura: TestAura, idx: &[TownLayoutIndex]) {
@@ -195,4 +382,26 @@ impl TestTown {
                 .push(aura);
         }
     }
+    fn add_choir(&mut self, tile: TownLayoutIndex, range: f32, attacks_per_cycle: u32) {
+        self.cheering_buildings.push(CheeringBuilding {
+            tile,
+            range,
+            strength: 2,
+            attacks_per_cycle,
+            built: Timestamp::from_seconds(0),
+        });
+    }
+    fn add_defender(&mut self, tile: TownLayoutIndex, since_s: i64, until_s: Option<i64>) {
+        let id = self.defenders.len();
+        self.defenders.push((
+            id,
+            DefendingWorker {
+                tile,
+                range: 1.0,
+                strength: 3,
+                since: Timestamp::from_seconds(since_s),
+                until: until_s.map(Timestamp::from_seconds),
+            },
+        ));
+    }
 }
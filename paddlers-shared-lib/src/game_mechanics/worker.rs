@@ -20,6 +20,16 @@ pub fn hero_resource_collection_per_hour(task: TaskType) -> Option<(ResourceType
     }
 }
 
+/// Happiness given to each visitor passing by a defending hero
+pub const fn hero_defence_strength() -> i32 {
+    3
+}
+
+/// Distance in tiles within which a defending hero affects visitors
+pub const fn hero_defence_range() -> f32 {
+    1.0
+}
+
 pub const fn hero_level_exp(now: i32) -> i32 {
     now * 100
 }
@@ -1,5 +1,6 @@
 //! This module has been auto-generate using specification loader.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+use serde::{Deserialize, Serialize};
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum QuestName {
     HelloWorld,
     CreateForest,
@@ -5,8 +5,6 @@ use chrono::NaiveDateTime;
 
 // Reexport
 #[cfg(feature = "sql_db")]
-pub use crate::story::story_state::Story_state_type;
-#[cfg(feature = "sql_db")]
 pub use resources::dsl;
 
 #[cfg(feature = "sql_db")]
@@ -16,12 +14,17 @@ use ::diesel_derive_enum::DbEnum;
 
 #[cfg(feature = "sql_db")]
 use super::schema::{
-    abilities, attacks, attacks_to_hobos, buildings, effects, hobos, players,
-    quest_building_conditions, quest_res_conditions, quest_res_rewards, quest_to_player,
-    quest_worker_conditions, quests, resources, rewards, streams, tasks, villages, visit_reports,
-    worker_flags, workers,
+    abilities, attacks, attacks_to_hobos, buildings, effects, hobos, idempotency_keys, players,
+    quest_building_conditions, quest_building_rewards, quest_perk_rewards, quest_progress,
+    quest_res_conditions, quest_res_rewards, quest_stat_conditions, quest_to_player,
+    quest_worker_conditions, quest_worker_rewards, quests, resources, rewards, scheduled_events,
+    streams, tasks, unlocked_buildings, villages, visit_reports, worker_flags, workers,
 };
 
+// Reexport for the schema, the perk enum itself is defined in `civilization`
+#[cfg(feature = "sql_db")]
+pub use crate::civilization::Civ_perk_type;
+
 #[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 #[cfg_attr(feature = "enum_utils", derive(EnumIter, Display))]
@@ -53,6 +56,7 @@ pub struct Player {
     pub display_name: String,
     pub story_state: StoryState,
     pub civ_perks: i64,
+    pub utc_offset_minutes: i32,
 }
 
 #[cfg(feature = "sql_db")]
@@ -62,10 +66,11 @@ pub struct NewPlayer {
     pub uuid: uuid::Uuid,
     pub karma: i64,
     pub display_name: String,
+    pub utc_offset_minutes: i32,
 }
 
 #[cfg(feature = "sql_db")]
-#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+#[derive(Debug, Clone, Queryable, Identifiable, AsChangeset)]
 pub struct Worker {
     pub id: i64,
     pub home: i64,
@@ -105,6 +110,8 @@ pub struct Hobo {
     /// If in a hurry, hobos will not stop in a town they are visiting but swim through directly
     pub hurried: bool,
     pub nest: Option<i64>,
+    /// Level the hobo has been created with, determines the rewards for satisfying it
+    pub level: i32,
 }
 
 #[cfg(feature = "sql_db")]
@@ -117,10 +124,11 @@ pub struct NewHobo {
     pub speed: f32,
     pub hurried: bool,
     pub nest: Option<i64>,
+    pub level: i32,
 }
 
 #[cfg(feature = "sql_db")]
-#[derive(Debug, Queryable, Identifiable)]
+#[derive(Debug, Clone, Queryable, Identifiable)]
 pub struct Attack {
     pub id: i64,
     pub departure: NaiveDateTime,
@@ -143,7 +151,7 @@ pub struct NewAttack {
 }
 
 #[cfg(feature = "sql_db")]
-#[derive(Debug, Queryable, Insertable)]
+#[derive(Debug, Clone, Queryable, Insertable)]
 #[table_name = "attacks_to_hobos"]
 pub struct AttackToHobo {
     pub attack_id: i64,
@@ -152,6 +160,10 @@ pub struct AttackToHobo {
     pub satisfied: Option<bool>,
     /// if None (NULL), the unit is waiting in town unless it is hurried
     pub released: Option<NaiveDateTime>,
+    /// Cheers received from buildings until `cheered_until`
+    pub cheers: i32,
+    /// None (NULL) = no cheers counted yet
+    pub cheered_until: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, Hash)]
@@ -171,10 +183,11 @@ pub enum BuildingType {
     SingleNest,
     TripleNest,
     Watergate,
+    Choir,
 }
 
 #[cfg(feature = "sql_db")]
-#[derive(Queryable, Debug)]
+#[derive(Queryable, Debug, Clone)]
 pub struct Building {
     pub id: i64,
     pub x: i32,
@@ -369,7 +382,7 @@ pub struct WorkerFlag {
     pub last_update: NaiveDateTime,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 #[cfg_attr(feature = "enum_utils", derive(EnumIter, Display))]
 #[cfg_attr(feature = "graphql", derive(juniper::GraphQLEnum))]
@@ -379,6 +392,45 @@ pub enum WorkerFlagType {
     Work,
 }
 
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[cfg_attr(feature = "enum_utils", derive(EnumIter, Display))]
+#[cfg_attr(
+    feature = "sql_db",
+    derive(DbEnum),
+    DieselType = "Scheduled_event_type"
+)]
+pub enum ScheduledEventType {
+    WorkerTask,
+    CheckRestingVisitors,
+    CheckVisitorHp,
+    PayTaxes,
+}
+
+#[derive(Debug, Clone, Copy, Queryable, Identifiable)]
+#[cfg(feature = "sql_db")]
+/// Persisted entry of the game-master event queue.
+/// Which of the optional references is set depends on the event type.
+pub struct ScheduledEvent {
+    pub id: i64,
+    pub event_type: ScheduledEventType,
+    pub due: NaiveDateTime,
+    pub task_id: Option<i64>,
+    pub village_id: Option<i64>,
+    pub hobo_id: Option<i64>,
+}
+
+#[derive(Insertable, Debug)]
+#[cfg(feature = "sql_db")]
+#[table_name = "scheduled_events"]
+pub struct NewScheduledEvent {
+    pub event_type: ScheduledEventType,
+    pub due: NaiveDateTime,
+    pub task_id: Option<i64>,
+    pub village_id: Option<i64>,
+    pub hobo_id: Option<i64>,
+}
+
 #[derive(Debug, Clone, Copy, Queryable, Identifiable)]
 #[cfg(feature = "sql_db")]
 /// After a visitor group (an attack) has left, it may send a thank you letter afterwards with rewards
@@ -508,6 +560,108 @@ pub struct NewQuestWorkerCondition {
     pub amount: i64,
 }
 
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[cfg_attr(feature = "enum_utils", derive(EnumIter, Display))]
+#[cfg_attr(feature = "graphql", derive(juniper::GraphQLEnum))]
+#[cfg_attr(feature = "sql_db", derive(DbEnum), DieselType = "Quest_stat_type")]
+/// Quest conditions on the state of a player and its village, beyond buildings, resources and workers
+pub enum QuestStatType {
+    /// Visitors that left the village satisfied
+    WelcomedVisitors,
+    /// Level of the hero worker
+    WorkerLevel,
+    /// Hobos living in a nest of the village
+    SettledHobos,
+    /// Invitations sent to hobos in foreign nests
+    SentInvitations,
+    /// Buildings bought in the shop and placed in the village
+    PlacedBuildings,
+    /// Resources brought home by workers or collected from visitor reports
+    GatheredSticks,
+    GatheredLogs,
+    GatheredFeathers,
+    /// Worker tasks that have been finished
+    CompletedTasks,
+}
+
+impl QuestStatType {
+    /// Tracked statistics count events since the quest has been assigned, see `QuestProgress`.
+    /// All others are looked up when the quest is checked.
+    pub fn is_tracked(self) -> bool {
+        match self {
+            Self::WorkerLevel | Self::SettledHobos => false,
+            Self::WelcomedVisitors
+            | Self::SentInvitations
+            | Self::PlacedBuildings
+            | Self::GatheredSticks
+            | Self::GatheredLogs
+            | Self::GatheredFeathers
+            | Self::CompletedTasks => true,
+        }
+    }
+    pub fn gathered(res: ResourceType) -> Self {
+        match res {
+            ResourceType::Sticks => Self::GatheredSticks,
+            ResourceType::Logs => Self::GatheredLogs,
+            ResourceType::Feathers => Self::GatheredFeathers,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Queryable)]
+#[cfg(feature = "sql_db")]
+pub struct QuestStatCondition {
+    pub id: i64,
+    pub quest_id: i64,
+    pub stat_type: QuestStatType,
+    pub amount: i64,
+}
+#[derive(Debug, Clone, Insertable)]
+#[cfg(feature = "sql_db")]
+#[table_name = "quest_stat_conditions"]
+pub struct NewQuestStatCondition {
+    pub quest_id: i64,
+    pub stat_type: QuestStatType,
+    pub amount: i64,
+}
+
+#[derive(Debug, Clone, Copy, Queryable, Insertable)]
+#[cfg(feature = "sql_db")]
+#[table_name = "quest_worker_rewards"]
+/// Mana and experience for the hero worker
+pub struct QuestWorkerReward {
+    pub quest_id: i64,
+    pub mana: i64,
+    pub experience: i64,
+}
+
+#[derive(Debug, Clone, Copy, Queryable, Insertable)]
+#[cfg(feature = "sql_db")]
+#[table_name = "quest_perk_rewards"]
+pub struct QuestPerkReward {
+    pub quest_id: i64,
+    pub perk: crate::civilization::CivilizationPerk,
+}
+
+#[derive(Debug, Clone, Copy, Queryable, Insertable)]
+#[cfg(feature = "sql_db")]
+#[table_name = "quest_building_rewards"]
+/// Unlocks a building for the player, see `UnlockedBuilding`
+pub struct QuestBuildingReward {
+    pub quest_id: i64,
+    pub building_type: BuildingType,
+}
+
+#[derive(Debug, Clone, Copy, Queryable, Insertable)]
+#[cfg(feature = "sql_db")]
+#[table_name = "unlocked_buildings"]
+/// A building the player can build regardless of the restrictions in `BuildingType::player_can_build`
+pub struct UnlockedBuilding {
+    pub player_id: i64,
+    pub building_type: BuildingType,
+}
+
 #[cfg(feature = "sql_db")]
 #[derive(Debug, Queryable, Insertable)]
 #[table_name = "quest_to_player"]
@@ -515,3 +669,32 @@ pub struct QuestToPlayer {
     pub quest_id: i64,
     pub player_id: i64,
 }
+
+#[cfg(feature = "sql_db")]
+#[derive(Debug, Clone, Copy, Queryable, Insertable)]
+#[table_name = "quest_progress"]
+/// Counter of a tracked statistic for a quest assigned to a player
+pub struct QuestProgress {
+    pub quest_id: i64,
+    pub player_id: i64,
+    pub stat_type: QuestStatType,
+    pub amount: i64,
+}
+
+#[cfg(feature = "sql_db")]
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "idempotency_keys"]
+/// Idempotency key of a player action and the response recorded for it
+pub struct IdempotencyKey {
+    pub player_uuid: uuid::Uuid,
+    pub idempotency_key: String,
+    pub method: String,
+    pub path: String,
+    /// SHA-256 of the request body
+    pub body_hash: Vec<u8>,
+    /// None (NULL) while the first request is being processed
+    pub status: Option<i16>,
+    pub content_type: Option<String>,
+    pub response_body: Option<Vec<u8>>,
+    pub expires: NaiveDateTime,
+}
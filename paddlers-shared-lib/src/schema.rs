@@ -32,6 +32,8 @@ table! {
         hobo_id -> Int8,
         satisfied -> Nullable<Bool>,
         released -> Nullable<Timestamp>,
+        cheers -> Int4,
+        cheered_until -> Nullable<Timestamp>,
     }
 }
 
@@ -78,6 +80,24 @@ table! {
         hp -> Int8,
         hurried -> Bool,
         nest -> Nullable<Int8>,
+        level -> Int4,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::*;
+
+    idempotency_keys (player_uuid, idempotency_key) {
+        player_uuid -> Uuid,
+        idempotency_key -> Varchar,
+        method -> Varchar,
+        path -> Text,
+        body_hash -> Bytea,
+        status -> Nullable<Int2>,
+        content_type -> Nullable<Text>,
+        response_body -> Nullable<Bytea>,
+        expires -> Timestamp,
     }
 }
 
@@ -90,8 +110,9 @@ table! {
         uuid -> Uuid,
         karma -> Int8,
         display_name -> Varchar,
-        story_state -> Story_state_type,
+        story_state -> Varchar,
         civ_perks -> Int8,
+        utc_offset_minutes -> Int4,
     }
 }
 
@@ -107,6 +128,38 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use crate::models::*;
+
+    quest_building_rewards (quest_id, building_type) {
+        quest_id -> Int8,
+        building_type -> Building_type,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::*;
+
+    quest_perk_rewards (quest_id, perk) {
+        quest_id -> Int8,
+        perk -> Civ_perk_type,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::*;
+
+    quest_progress (quest_id, player_id, stat_type) {
+        quest_id -> Int8,
+        player_id -> Int8,
+        stat_type -> Quest_stat_type,
+        amount -> Int8,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::models::*;
@@ -131,6 +184,18 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use crate::models::*;
+
+    quest_stat_conditions (id) {
+        id -> Int8,
+        quest_id -> Int8,
+        stat_type -> Quest_stat_type,
+        amount -> Int8,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::models::*;
@@ -153,6 +218,17 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use crate::models::*;
+
+    quest_worker_rewards (quest_id) {
+        quest_id -> Int8,
+        mana -> Int8,
+        experience -> Int8,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::models::*;
@@ -189,6 +265,20 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use crate::models::*;
+
+    scheduled_events (id) {
+        id -> Int8,
+        event_type -> Scheduled_event_type,
+        due -> Timestamp,
+        task_id -> Nullable<Int8>,
+        village_id -> Nullable<Int8>,
+        hobo_id -> Nullable<Int8>,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::models::*;
@@ -215,6 +305,16 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use crate::models::*;
+
+    unlocked_buildings (player_id, building_type) {
+        player_id -> Int8,
+        building_type -> Building_type,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::models::*;
@@ -279,15 +379,23 @@ joinable!(effects -> hobos (hobo_id));
 joinable!(hobos -> buildings (nest));
 joinable!(hobos -> villages (home));
 joinable!(quest_building_conditions -> quests (quest_id));
+joinable!(quest_building_rewards -> quests (quest_id));
+joinable!(quest_perk_rewards -> quests (quest_id));
 joinable!(quest_res_conditions -> quests (quest_id));
 joinable!(quest_res_rewards -> quests (quest_id));
+joinable!(quest_stat_conditions -> quests (quest_id));
 joinable!(quest_to_player -> players (player_id));
 joinable!(quest_to_player -> quests (quest_id));
 joinable!(quest_worker_conditions -> quests (quest_id));
+joinable!(quest_worker_rewards -> quests (quest_id));
 joinable!(resources -> villages (village_id));
 joinable!(rewards -> visit_reports (visit_report_id));
+joinable!(scheduled_events -> hobos (hobo_id));
+joinable!(scheduled_events -> tasks (task_id));
+joinable!(scheduled_events -> villages (village_id));
 joinable!(tasks -> hobos (target_hobo_id));
 joinable!(tasks -> workers (worker_id));
+joinable!(unlocked_buildings -> players (player_id));
 joinable!(villages -> players (player_id));
 joinable!(villages -> streams (stream_id));
 joinable!(visit_reports -> hobos (sender));
@@ -302,17 +410,25 @@ allow_tables_to_appear_in_same_query!(
     buildings,
     effects,
     hobos,
+    idempotency_keys,
     players,
     quest_building_conditions,
+    quest_building_rewards,
+    quest_perk_rewards,
+    quest_progress,
     quest_res_conditions,
     quest_res_rewards,
+    quest_stat_conditions,
     quest_to_player,
     quest_worker_conditions,
+    quest_worker_rewards,
     quests,
     resources,
     rewards,
+    scheduled_events,
     streams,
     tasks,
+    unlocked_buildings,
     villages,
     visit_reports,
     worker_flags,
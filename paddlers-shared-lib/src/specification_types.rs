@@ -3,20 +3,14 @@ mod hobos;
 mod sprites;
 mod text_keys;
 mod ui_specification;
-mod visitor_groups;
+mod visit_director;
+mod visit_rewards;
 
 pub use hobos::*;
 pub use text_keys::*;
-pub use visitor_groups::*;
+pub use visit_director::*;
+pub use visit_rewards::*;
 
 pub use dialogue::*;
 pub use sprites::*;
 pub use ui_specification::*;
-
-use crate::generated::QuestName;
-// Pseudo const-trait
-impl QuestName {
-    pub const fn const_eq(self, other: Self) -> bool {
-        self as usize == other as usize
-    }
-}
@@ -6,7 +6,7 @@ use super::*;
 use crate::story::{story_state::StoryState, story_trigger::StoryChoice};
 use serde::Deserialize;
 #[cfg(feature = "enum_utils")]
-use strum_macros::{AsRefStr, EnumVariantNames};
+use strum_macros::{AsRefStr, EnumIter, EnumVariantNames};
 /// A Scene consists of a set of slides and can be loaded in the Dialogue view.
 /// It starts at a specific slide and the player can click through the, as defined on the slides.
 /// Slides are referenced (within a scene) by their index.
@@ -59,6 +59,9 @@ impl Default for NextView {
 pub type SlideIndex = usize;
 
 impl Scene {
+    pub fn slide_count(&self) -> usize {
+        self.slides.len()
+    }
     pub fn slide_text_key(&self, i: SlideIndex) -> &OwnedTextKey {
         &self.slides[i].text_key
     }
@@ -89,7 +92,7 @@ impl Scene {
     }
 }
 
-#[cfg_attr(feature = "enum_utils", derive(EnumVariantNames, AsRefStr))]
+#[cfg_attr(feature = "enum_utils", derive(EnumVariantNames, AsRefStr, EnumIter))]
 #[derive(Debug, Clone, Copy, PartialEq, Deserialize, Hash, Eq)]
 /// Reference to a scene defined in an external RON file
 pub enum SceneIndex {
@@ -139,7 +142,7 @@ impl SlideButtonAction {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub enum DialogueAction {
     OpenScene(SceneIndex, SlideIndex),
     StoryProgress(StoryState, Option<StoryChoice>),
@@ -1,6 +1,10 @@
-#[derive(Copy, Clone, Debug)]
+use crate::models::UnitColor;
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct HoboLevel(usize);
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum HoboType {
     Yellow,
     Camo,
@@ -10,10 +14,13 @@ pub enum HoboType {
     DefaultRandom,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct VisitorDefinition {
     pub typ: HoboType,
+    #[serde(default)]
     pub level: HoboLevel,
+    /// Overrides the HP derived from the level
+    #[serde(default)]
     pub hp: Option<u16>,
     pub hurried: bool,
 }
@@ -37,10 +44,42 @@ impl VisitorDefinition {
     }
 }
 
+impl HoboType {
+    /// The type of an existing hobo. Hobos without color are reported as `DefaultRandom`.
+    pub fn of_color(color: Option<UnitColor>) -> Self {
+        match color {
+            Some(UnitColor::Yellow) => HoboType::Yellow,
+            Some(UnitColor::Camo) => HoboType::Camo,
+            Some(UnitColor::White) => HoboType::White,
+            Some(UnitColor::Prophet) => HoboType::Prophet,
+            None => HoboType::DefaultRandom,
+        }
+    }
+}
+
 impl HoboLevel {
     pub const fn zero() -> Self {
         HoboLevel(0)
     }
+    pub const fn new(level: usize) -> Self {
+        HoboLevel(level)
+    }
+    pub fn num(&self) -> usize {
+        self.0
+    }
+    /// The lowest level at which an anarchist can have the given HP
+    pub fn of_anarchist_hp(hp: i64, hurried: bool) -> Self {
+        (0..10)
+            .map(HoboLevel)
+            .find(|level| {
+                if hurried {
+                    hp < level.hurried_anarchist_hp_range().1
+                } else {
+                    hp <= level.unhurried_anarchist_hp()
+                }
+            })
+            .unwrap_or(HoboLevel(10))
+    }
     pub fn anarchist(player_karma: i64) -> Self {
         match player_karma {
             0..=9 => HoboLevel(0),
@@ -0,0 +1,156 @@
+//! Data-driven tuning of the anarchist visitors the game-master sends to players.
+//! The definition lives in `specification/visit_director.ron`.
+
+use crate::specification_types::hobos::HoboType;
+use crate::story::story_state::StoryState;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VisitDirectorDefinition {
+    /// Seconds between two rounds in which all player villages are considered for a visit
+    pub cycle_s: u32,
+    /// Story states in which players receive anarchist visitors
+    pub active_story_states: Vec<StoryState>,
+    /// Pressure depending on the player's karma. The band with the highest `min_karma` not above the player's karma applies.
+    pub karma_bands: Vec<KarmaBand>,
+    /// Local time of the player during which no visitors are sent
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KarmaBand {
+    pub min_karma: i64,
+    /// Probability per cycle that a village without any visitors on the way receives a new group
+    pub spawn_chance: f32,
+    /// Each visitor group already on the way reduces the chance to `spawn_chance / (1 + crowding * n * n)`
+    pub crowding: f32,
+    /// Inclusive range for the number of visitors in a group
+    pub group_size: (u32, u32),
+    /// Fraction of each group that is hurried, rounded down. At least one visitor is always unhurried.
+    pub hurried_ratio: f32,
+    /// Visitor types to pick from at random
+    pub hobo_types: Vec<HoboType>,
+}
+
+/// Range of local hours, `from_hour` inclusive and `to_hour` exclusive. May wrap around midnight.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub from_hour: u32,
+    pub to_hour: u32,
+}
+
+impl VisitDirectorDefinition {
+    pub fn karma_band(&self, karma: i64) -> Option<&KarmaBand> {
+        self.karma_bands
+            .iter()
+            .filter(|band| band.min_karma <= karma)
+            .max_by_key(|band| band.min_karma)
+    }
+    pub fn is_active(&self, story_state: &StoryState) -> bool {
+        self.active_story_states.contains(story_state)
+    }
+    /// Checks for obvious mistakes in the definition
+    pub fn validate(&self) -> Result<(), String> {
+        if self.cycle_s == 0 {
+            return Err("cycle_s must be positive".to_owned());
+        }
+        for band in &self.karma_bands {
+            if !(0.0..=1.0).contains(&band.spawn_chance) {
+                return Err(format!("Invalid spawn chance in band {}", band.min_karma));
+            }
+            if !(0.0..=1.0).contains(&band.hurried_ratio) {
+                return Err(format!("Invalid hurried ratio in band {}", band.min_karma));
+            }
+            if band.group_size.0 == 0 || band.group_size.0 > band.group_size.1 {
+                return Err(format!("Invalid group size in band {}", band.min_karma));
+            }
+            if band.hobo_types.is_empty() {
+                return Err(format!("No hobo types in band {}", band.min_karma));
+            }
+        }
+        if let Some(quiet) = &self.quiet_hours {
+            if quiet.from_hour >= 24 || quiet.to_hour >= 24 {
+                return Err("Quiet hours must be between 0 and 23".to_owned());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl KarmaBand {
+    pub fn spawn_chance(&self, ongoing_visits: usize) -> f32 {
+        let n = ongoing_visits as f32;
+        self.spawn_chance / (1.0 + self.crowding * n * n)
+    }
+    /// Number of hurried visitors in a group of the given size
+    pub fn hurried_visitors(&self, group_size: u32) -> u32 {
+        ((group_size as f32 * self.hurried_ratio) as u32).min(group_size.saturating_sub(1))
+    }
+}
+
+impl QuietHours {
+    /// `utc_minute_of_day` is the current time in UTC, `utc_offset_minutes` the offset of the player's local time
+    pub fn contains(&self, utc_minute_of_day: i32, utc_offset_minutes: i32) -> bool {
+        let local_hour = (utc_minute_of_day + utc_offset_minutes).rem_euclid(24 * 60) as u32 / 60;
+        if self.from_hour <= self.to_hour {
+            self.from_hour <= local_hour && local_hour < self.to_hour
+        } else {
+            self.from_hour <= local_hour || local_hour < self.to_hour
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn band(min_karma: i64) -> KarmaBand {
+        KarmaBand {
+            min_karma,
+            spawn_chance: 0.5,
+            crowding: 1.0,
+            group_size: (1, 4),
+            hurried_ratio: 0.75,
+            hobo_types: vec![HoboType::DefaultRandom],
+        }
+    }
+
+    #[test]
+    fn karma_band_selection() {
+        let def = VisitDirectorDefinition {
+            cycle_s: 40,
+            active_story_states: vec![StoryState::ALL_DONE],
+            karma_bands: vec![band(100), band(0), band(1000)],
+            quiet_hours: None,
+        };
+        assert!(def.validate().is_ok());
+        assert_eq!(def.karma_band(-1).map(|b| b.min_karma), None);
+        assert_eq!(def.karma_band(0).map(|b| b.min_karma), Some(0));
+        assert_eq!(def.karma_band(999).map(|b| b.min_karma), Some(100));
+        assert_eq!(def.karma_band(5000).map(|b| b.min_karma), Some(1000));
+        assert_eq!(band(0).spawn_chance(1), 0.25);
+        assert_eq!(band(0).hurried_visitors(4), 3);
+        assert_eq!(band(0).hurried_visitors(1), 0);
+    }
+
+    #[test]
+    fn quiet_hours_in_local_time() {
+        let night = QuietHours {
+            from_hour: 22,
+            to_hour: 7,
+        };
+        // 23:00 UTC
+        assert!(night.contains(23 * 60, 0));
+        // 23:00 UTC is 08:00 in UTC+9
+        assert!(!night.contains(23 * 60, 9 * 60));
+        // 12:00 UTC is 02:00 in UTC-10
+        assert!(night.contains(12 * 60, -10 * 60));
+        let lunch = QuietHours {
+            from_hour: 12,
+            to_hour: 13,
+        };
+        assert!(lunch.contains(12 * 60 + 30, 0));
+        assert!(!lunch.contains(13 * 60, 0));
+    }
+}
@@ -0,0 +1,255 @@
+//! Data-driven rewards for visitors leaving a town.
+//! The table lives in `specification/visit_rewards.ron` and is used by the game-master to generate visit reports.
+//! The frontend loads the same table to preview the rewards of a visitor group before it is let in.
+
+use crate::models::ResourceType;
+use crate::specification_types::hobos::{HoboLevel, HoboType};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VisitRewardTable {
+    /// For each visitor, the first matching rule applies. Visitors without a matching rule give nothing.
+    pub rules: Vec<RewardRule>,
+}
+
+/// Rewards for visitors with certain properties. Conditions that are not set match any visitor.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RewardRule {
+    /// Empty list matches all types
+    #[serde(default)]
+    pub hobo_types: Vec<HoboType>,
+    /// Inclusive range of levels
+    #[serde(default)]
+    pub levels: Option<(usize, usize)>,
+    #[serde(default)]
+    pub hurried: Option<bool>,
+    #[serde(default)]
+    pub satisfied: Option<bool>,
+    #[serde(default)]
+    pub karma: i64,
+    #[serde(default)]
+    pub rewards: Vec<RewardRoll>,
+}
+
+/// A single random draw for a resource reward
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RewardRoll {
+    pub resource: ResourceType,
+    /// Inclusive range, uniformly distributed
+    pub amount: (i64, i64),
+    /// Probability that the reward is given at all
+    #[serde(default = "always")]
+    pub chance: f32,
+}
+
+/// The properties of a visitor that are relevant for its rewards
+#[derive(Clone, Copy, Debug)]
+pub struct RewardedVisitor {
+    pub typ: HoboType,
+    pub level: HoboLevel,
+    pub hurried: bool,
+    pub satisfied: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VisitRewards {
+    pub karma: i64,
+    pub resources: Vec<(ResourceType, i64)>,
+}
+
+/// Small deterministic RNG (SplitMix64), such that rewards for a visit are reproducible from its seed on all platforms.
+pub struct RewardRng(u64);
+
+fn always() -> f32 {
+    1.0
+}
+
+impl VisitRewardTable {
+    pub fn rule(&self, visitor: &RewardedVisitor) -> Option<&RewardRule> {
+        self.rules.iter().find(|rule| rule.matches(visitor))
+    }
+    /// Rolls the rewards for a group of visitors. The visitors must be given in a stable order to get reproducible results.
+    pub fn roll(&self, visitors: &[RewardedVisitor], rng: &mut RewardRng) -> VisitRewards {
+        let mut out = VisitRewards::default();
+        for visitor in visitors {
+            if let Some(rule) = self.rule(visitor) {
+                out.karma += rule.karma;
+                for roll in &rule.rewards {
+                    // Always draw both numbers to keep the sequence independent of previous outcomes
+                    let hit = rng.next_f32() < roll.chance;
+                    let amount = rng.range_inclusive(roll.amount.0, roll.amount.1);
+                    if hit {
+                        out.add(roll.resource, amount);
+                    }
+                }
+            }
+        }
+        out
+    }
+    /// Expected karma and resources for a group of visitors, assuming all of them will be satisfied
+    pub fn expected_rewards(
+        &self,
+        visitors: &[RewardedVisitor],
+    ) -> (f32, Vec<(ResourceType, f32)>) {
+        let mut karma = 0.0;
+        let mut resources: Vec<(ResourceType, f32)> = vec![];
+        for visitor in visitors {
+            if let Some(rule) = self.rule(visitor) {
+                karma += rule.karma as f32;
+                for roll in &rule.rewards {
+                    let expected = roll.chance * (roll.amount.0 + roll.amount.1) as f32 / 2.0;
+                    match resources.iter_mut().find(|(rt, _)| *rt == roll.resource) {
+                        Some((_, n)) => *n += expected,
+                        None => resources.push((roll.resource, expected)),
+                    }
+                }
+            }
+        }
+        (karma, resources)
+    }
+    /// Checks for obvious mistakes in the table
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, rule) in self.rules.iter().enumerate() {
+            if let Some((min, max)) = rule.levels {
+                if min > max {
+                    return Err(format!("Invalid level range in rule {}", i));
+                }
+            }
+            for roll in &rule.rewards {
+                if !(0.0..=1.0).contains(&roll.chance) {
+                    return Err(format!("Invalid chance in rule {}", i));
+                }
+                if roll.amount.0 < 0 || roll.amount.0 > roll.amount.1 {
+                    return Err(format!("Invalid amount in rule {}", i));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RewardRule {
+    fn matches(&self, visitor: &RewardedVisitor) -> bool {
+        (self.hobo_types.is_empty() || self.hobo_types.contains(&visitor.typ))
+            && self.levels.map_or(true, |(min, max)| {
+                (min..=max).contains(&visitor.level.num())
+            })
+            && self.hurried.map_or(true, |h| h == visitor.hurried)
+            && self.satisfied.map_or(true, |s| s == visitor.satisfied)
+    }
+}
+
+impl VisitRewards {
+    fn add(&mut self, resource: ResourceType, amount: i64) {
+        match self.resources.iter_mut().find(|(rt, _)| *rt == resource) {
+            Some((_, n)) => *n += amount,
+            None => self.resources.push((resource, amount)),
+        }
+    }
+    pub fn amount(&self, resource: ResourceType) -> i64 {
+        self.resources
+            .iter()
+            .filter(|(rt, _)| *rt == resource)
+            .map(|(_, n)| n)
+            .sum()
+    }
+}
+
+impl RewardRng {
+    pub fn new(seed: u64) -> Self {
+        RewardRng(seed)
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// Uniform in [0,1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+    fn range_inclusive(&mut self, min: i64, max: i64) -> i64 {
+        if max <= min {
+            return min;
+        }
+        min + (self.next_u64() % (max - min + 1) as u64) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visitor(karma: i64, hurried: bool, satisfied: bool) -> RewardedVisitor {
+        RewardedVisitor {
+            typ: HoboType::Yellow,
+            level: HoboLevel::anarchist(karma),
+            hurried,
+            satisfied,
+        }
+    }
+
+    fn table() -> VisitRewardTable {
+        VisitRewardTable {
+            rules: vec![
+                RewardRule {
+                    hobo_types: vec![],
+                    levels: None,
+                    hurried: None,
+                    satisfied: Some(false),
+                    karma: 0,
+                    rewards: vec![],
+                },
+                RewardRule {
+                    hobo_types: vec![HoboType::Yellow],
+                    levels: Some((0, 3)),
+                    hurried: None,
+                    satisfied: None,
+                    karma: 1,
+                    rewards: vec![
+                        RewardRoll {
+                            resource: ResourceType::Feathers,
+                            amount: (1, 3),
+                            chance: 1.0,
+                        },
+                        RewardRoll {
+                            resource: ResourceType::Sticks,
+                            amount: (5, 5),
+                            chance: 0.5,
+                        },
+                    ],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn rewards_are_reproducible() {
+        let table = table();
+        assert!(table.validate().is_ok());
+        let visitors: Vec<_> = (0..20)
+            .map(|i| visitor(i * 10, i % 3 == 0, i % 4 != 0))
+            .collect();
+        let a = table.roll(&visitors, &mut RewardRng::new(42));
+        let b = table.roll(&visitors, &mut RewardRng::new(42));
+        assert_eq!(a, b);
+        assert!(a.amount(ResourceType::Feathers) > 0);
+    }
+
+    #[test]
+    fn first_matching_rule_applies() {
+        let table = table();
+        let visitors = [visitor(0, true, false), visitor(500, false, true)];
+        let rewards = table.roll(&visitors, &mut RewardRng::new(1));
+        assert_eq!(rewards, VisitRewards::default());
+
+        let (karma, resources) = table.expected_rewards(&[visitor(0, false, true)]);
+        assert_eq!(karma, 1.0);
+        assert_eq!(
+            resources,
+            vec![(ResourceType::Feathers, 2.0), (ResourceType::Sticks, 2.5)]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Change notifications from the game-master to other services, sent through Postgres LISTEN/NOTIFY.
+//!
+//! The game-master publishes a `ChangeEvent` whenever it modifies state that clients display.
+//! Notifications sent inside a transaction are only delivered after the transaction has been committed.
+//! Delivery is best-effort, listeners that have been disconnected miss all events in between.
+
+use crate::api::keys::{PlayerKey, VillageKey};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+
+/// Postgres channel on which all change events are published
+pub const CHANGE_CHANNEL: &str = "padl_changes";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChangeEvent {
+    /// An attack on the village has been added, removed or has entered the village
+    Attacks(VillageKey),
+    VisitReports(VillageKey),
+    Resources(VillageKey),
+    /// Quests have been assigned to or removed from the player, or their progress has changed
+    Quests(PlayerKey),
+    /// Karma, story state, civilization perks or unlocked buildings of the player
+    Player(PlayerKey),
+}
+
+impl ChangeEvent {
+    /// Publishes the event on the change channel, errors are only logged
+    pub fn notify(&self, conn: &PgConnection) {
+        let result = diesel::sql_query("SELECT pg_notify($1, $2)")
+            .bind::<Text, _>(CHANGE_CHANNEL)
+            .bind::<Text, _>(self.payload())
+            .execute(conn);
+        if let Err(e) = result {
+            eprintln!("Failed to publish {:?}: {}", self, e);
+        }
+    }
+    /// Notification payload, for example `resources 12`
+    pub fn payload(&self) -> String {
+        let (table, id) = match self {
+            ChangeEvent::Attacks(v) => ("attacks", v.num()),
+            ChangeEvent::VisitReports(v) => ("visit_reports", v.num()),
+            ChangeEvent::Resources(v) => ("resources", v.num()),
+            ChangeEvent::Quests(p) => ("quests", p.num()),
+            ChangeEvent::Player(p) => ("players", p.num()),
+        };
+        format!("{} {}", table, id)
+    }
+    /// Inverse of `payload()`, None for unknown payloads
+    pub fn from_payload(payload: &str) -> Option<Self> {
+        let mut parts = payload.split(' ');
+        let table = parts.next()?;
+        let id = parts.next()?.parse().ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        match table {
+            "attacks" => Some(ChangeEvent::Attacks(VillageKey(id))),
+            "visit_reports" => Some(ChangeEvent::VisitReports(VillageKey(id))),
+            "resources" => Some(ChangeEvent::Resources(VillageKey(id))),
+            "quests" => Some(ChangeEvent::Quests(PlayerKey(id))),
+            "players" => Some(ChangeEvent::Player(PlayerKey(id))),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_round_trip() {
+        let events = [
+            ChangeEvent::Attacks(VillageKey(1)),
+            ChangeEvent::VisitReports(VillageKey(2)),
+            ChangeEvent::Resources(VillageKey(3)),
+            ChangeEvent::Quests(PlayerKey(4)),
+            ChangeEvent::Player(PlayerKey(5)),
+        ];
+        for event in &events {
+            assert_eq!(ChangeEvent::from_payload(&event.payload()), Some(*event));
+        }
+        assert_eq!(ChangeEvent::from_payload("buildings 1"), None);
+        assert_eq!(ChangeEvent::from_payload("attacks x"), None);
+        assert_eq!(ChangeEvent::from_payload("attacks 1 2"), None);
+    }
+}
@@ -2,8 +2,26 @@ use crate::schema::*;
 use crate::{generated::QuestName, prelude::*};
 use diesel::prelude::*;
 
+// Upper bounds for lists read through the GraphQL interface, longer lists are cut off.
+// The GraphQL interface charges the cost of queries based on these bounds.
+pub const MAX_ATTACKS_PER_VILLAGE: usize = 20;
+/// A player can send all hobos of a town, which has at most one per tile
+pub const MAX_UNITS_PER_ATTACK: usize = 64;
+pub const MAX_REPORTS_PER_VILLAGE: usize = 50;
+pub const MAX_QUESTS_PER_PLAYER: usize = 50;
+pub const MAX_VILLAGES_PER_PLAYER: usize = 10;
+pub const MAX_WORKERS_PER_VILLAGE: usize = 10;
+pub const MAX_TASKS_PER_WORKER: usize = 50;
+pub const MAX_ABILITIES_PER_WORKER: usize = 10;
+pub const MAX_EFFECTS_PER_HOBO: usize = 10;
+
 pub trait GameDB {
     fn dbconn(&self) -> &PgConnection;
+    /// The current time as seen by the game logic.
+    /// Time-dependent queries compare against this instead of the database clock, which allows implementors to simulate time.
+    fn now(&self) -> chrono::NaiveDateTime {
+        chrono::Utc::now().naive_utc()
+    }
 
     fn player(&self, player_id: PlayerKey) -> Option<Player> {
         players::table
@@ -92,7 +110,7 @@ pub trait GameDB {
             .inner_join(tasks::table)
             .filter(workers::home.eq(village.num()))
             .filter(tasks::task_type.eq_any(jobs))
-            .filter(tasks::start_time.lt(diesel::dsl::now.at_time_zone("UTC")))
+            .filter(tasks::start_time.lt(self.now()))
             .select(workers::all_columns)
             .distinct()
             .load::<Worker>(self.dbconn())
@@ -135,13 +153,45 @@ pub trait GameDB {
             .expect("Error loading data");
         results
     }
+    /// Attacks on the village, sorted by arrival
+    fn attacks_page(
+        &self,
+        village: VillageKey,
+        page: &Page<(chrono::NaiveDateTime, AttackKey)>,
+    ) -> Vec<Attack> {
+        let mut query = attacks::table
+            .filter(attacks::destination_village_id.eq(village.num()))
+            .into_boxed();
+        if let Some((arrival, id)) = page.cursor {
+            query = if page.backward {
+                query.filter(
+                    attacks::arrival
+                        .lt(arrival)
+                        .or(attacks::arrival.eq(arrival).and(attacks::id.lt(id.num()))),
+                )
+            } else {
+                query.filter(
+                    attacks::arrival
+                        .gt(arrival)
+                        .or(attacks::arrival.eq(arrival).and(attacks::id.gt(id.num()))),
+                )
+            };
+        }
+        query = if page.backward {
+            query.order_by((attacks::arrival.desc(), attacks::id.desc()))
+        } else {
+            query.order_by((attacks::arrival.asc(), attacks::id.asc()))
+        };
+        query
+            .limit(page.size + 1)
+            .load(self.dbconn())
+            .expect("Error loading data")
+    }
     fn attacks_that_entered(&self, village: VillageKey, min_id: Option<i64>) -> Vec<Attack> {
         let results = attacks::table
             .filter(attacks::destination_village_id.eq(village.num()))
             .filter(attacks::id.ge(min_id.unwrap_or(0)))
-            .filter(
-                attacks::entered_destination.le(diesel::dsl::now.at_time_zone("UTC").nullable()),
-            )
+            .filter(attacks::entered_destination.le(self.now()))
             .order_by(attacks::entered_destination)
             .limit(500)
             .load::<Attack>(self.dbconn())
@@ -221,9 +271,7 @@ pub trait GameDB {
             // condition for "resting"
             .filter(hobos::hurried.eq(false))
             .filter(attacks_to_hobos::satisfied.is_null())
-            .filter(
-                attacks::entered_destination.le(diesel::dsl::now.at_time_zone("UTC").nullable()),
-            )
+            .filter(attacks::entered_destination.le(self.now()))
             //
             .order_by(attacks::entered_destination.asc())
             .select((hobos::all_columns, attacks::id))
@@ -305,7 +353,7 @@ pub trait GameDB {
     fn past_worker_tasks(&self, worker_id: WorkerKey) -> Vec<Task> {
         let results = tasks::table
             .filter(tasks::worker_id.eq(worker_id.num()))
-            .filter(tasks::start_time.lt(diesel::dsl::now.at_time_zone("UTC")))
+            .filter(tasks::start_time.lt(self.now()))
             .order(tasks::start_time.asc())
             .limit(500)
             .load::<Task>(self.dbconn())
@@ -315,7 +363,7 @@ pub trait GameDB {
     fn earliest_future_task(&self, worker_id: WorkerKey) -> Option<Task> {
         tasks::table
             .filter(tasks::worker_id.eq(worker_id.num()))
-            .filter(tasks::start_time.ge(diesel::dsl::now.at_time_zone("UTC")))
+            .filter(tasks::start_time.ge(self.now()))
             .order(tasks::start_time.asc())
             .first(self.dbconn())
             .optional()
@@ -339,7 +387,7 @@ pub trait GameDB {
     fn current_task(&self, worker_id: WorkerKey) -> Option<Task> {
         tasks::table
             .filter(tasks::worker_id.eq(worker_id.num()))
-            .filter(tasks::start_time.le(diesel::dsl::now.at_time_zone("UTC")))
+            .filter(tasks::start_time.le(self.now()))
             .order(tasks::start_time.asc())
             .first(self.dbconn())
             .optional()
@@ -407,6 +455,16 @@ pub trait GameDB {
             .expect("Error loading data");
         results
     }
+    /// Villages without an owner in the given x range
+    fn anarchist_villages(&self, low_x: f32, high_x: f32) -> Vec<Village> {
+        let results = villages::table
+            .filter(villages::player_id.is_null())
+            .filter(villages::x.ge(low_x))
+            .filter(villages::x.le(high_x))
+            .load::<Village>(self.dbconn())
+            .expect("Error loading data");
+        results
+    }
     fn all_player_villages(&self) -> Vec<Village> {
         let results = villages::table
             .filter(villages::player_id.is_not_null())
@@ -427,7 +485,7 @@ pub trait GameDB {
         let results = quest_to_player::table
             .inner_join(quests::table)
             .filter(quest_to_player::player_id.eq(player_id.num()))
-            .limit(50)
+            .limit(MAX_QUESTS_PER_PLAYER as i64)
             .select(quests::all_columns)
             .load::<Quest>(self.dbconn())
             .expect("Error loading data");
@@ -523,6 +581,34 @@ pub trait GameDB {
             .expect("Error loading data");
         results
     }
+    /// Players with the most karma first, players with equal karma are sorted by id
+    fn players_by_karma_page(&self, page: &Page<(i64, PlayerKey)>) -> Vec<Player> {
+        let mut query = players::table.into_boxed();
+        if let Some((karma, id)) = page.cursor {
+            query = if page.backward {
+                query.filter(
+                    players::karma
+                        .gt(karma)
+                        .or(players::karma.eq(karma).and(players::id.lt(id.num()))),
+                )
+            } else {
+                query.filter(
+                    players::karma
+                        .lt(karma)
+                        .or(players::karma.eq(karma).and(players::id.gt(id.num()))),
+                )
+            };
+        }
+        query = if page.backward {
+            query.order_by((players::karma.asc(), players::id.desc()))
+        } else {
+            query.order_by((players::karma.desc(), players::id.asc()))
+        };
+        query
+            .limit(page.size + 1)
+            .load(self.dbconn())
+            .expect("Error loading data")
+    }
     fn players_count(&self) -> i64 {
         let results = players::table
             .select(diesel::dsl::count(players::id))
@@ -542,11 +628,40 @@ pub trait GameDB {
             .filter(visit_reports::village_id.eq(v.num()))
             .filter(visit_reports::id.ge(min_id.unwrap_or(0)))
             .order_by(visit_reports::reported.desc())
-            .limit(50)
+            .limit(MAX_REPORTS_PER_VILLAGE as i64)
             .load::<VisitReport>(self.dbconn())
             .expect("Error loading visit reports");
         results
     }
+    /// Visit reports of the village, newest first
+    fn reports_page(&self, v: VillageKey, page: &Page<VisitReportKey>) -> Vec<VisitReport> {
+        let mut query = visit_reports::table
+            .filter(visit_reports::village_id.eq(v.num()))
+            .into_boxed();
+        if let Some(id) = page.cursor {
+            query = if page.backward {
+                query.filter(visit_reports::id.gt(id.num()))
+            } else {
+                query.filter(visit_reports::id.lt(id.num()))
+            };
+        }
+        query = if page.backward {
+            query.order_by(visit_reports::id.asc())
+        } else {
+            query.order_by(visit_reports::id.desc())
+        };
+        query
+            .limit(page.size + 1)
+            .load(self.dbconn())
+            .expect("Error loading visit reports")
+    }
+    fn reports_count(&self, v: VillageKey) -> i64 {
+        visit_reports::table
+            .filter(visit_reports::village_id.eq(v.num()))
+            .select(diesel::dsl::count(visit_reports::id))
+            .first(self.dbconn())
+            .expect("Error counting visit reports")
+    }
     fn rewards(&self, vr: VisitReportKey) -> Vec<(ResourceType, i64)> {
         visit_reports::table
             .inner_join(rewards::table)
@@ -598,4 +713,280 @@ pub trait GameDB {
             .load::<QuestWorkerCondition>(self.dbconn())
             .expect("Error loading quest conditions")
     }
+    fn quest_stat_conditions(&self, q: QuestKey) -> Vec<QuestStatCondition> {
+        quest_stat_conditions::table
+            .filter(quest_stat_conditions::quest_id.eq(q.num()))
+            .load::<QuestStatCondition>(self.dbconn())
+            .expect("Error loading quest conditions")
+    }
+    fn quest_worker_reward(&self, q: QuestKey) -> Option<QuestWorkerReward> {
+        quest_worker_rewards::table
+            .find(q.num())
+            .first::<QuestWorkerReward>(self.dbconn())
+            .optional()
+            .expect("Error loading quest worker rewards")
+    }
+    fn quest_perk_rewards(&self, q: QuestKey) -> Vec<QuestPerkReward> {
+        quest_perk_rewards::table
+            .filter(quest_perk_rewards::quest_id.eq(q.num()))
+            .load::<QuestPerkReward>(self.dbconn())
+            .expect("Error loading quest perk rewards")
+    }
+    fn quest_building_rewards(&self, q: QuestKey) -> Vec<QuestBuildingReward> {
+        quest_building_rewards::table
+            .filter(quest_building_rewards::quest_id.eq(q.num()))
+            .load::<QuestBuildingReward>(self.dbconn())
+            .expect("Error loading quest building rewards")
+    }
+    /// Current value of a statistic that is looked up when a quest is checked.
+    /// Tracked statistics are counted in `quest_progress` instead, for them this returns None.
+    fn village_stat(&self, stat: QuestStatType, village: VillageKey) -> Option<i64> {
+        match stat {
+            QuestStatType::WorkerLevel => {
+                Some(self.hero(village).map(|w| w.level as i64).unwrap_or(0))
+            }
+            QuestStatType::SettledHobos => Some(self.settled_hobo_count(village)),
+            QuestStatType::WelcomedVisitors
+            | QuestStatType::SentInvitations
+            | QuestStatType::PlacedBuildings
+            | QuestStatType::GatheredSticks
+            | QuestStatType::GatheredLogs
+            | QuestStatType::GatheredFeathers
+            | QuestStatType::CompletedTasks => None,
+        }
+    }
+    /// Events counted for a tracked statistic since the quest has been assigned to the player
+    fn quest_progress(&self, q: QuestKey, p: PlayerKey, stat: QuestStatType) -> i64 {
+        quest_progress::table
+            .find((q.num(), p.num(), stat))
+            .select(quest_progress::amount)
+            .first(self.dbconn())
+            .optional()
+            .expect("Error loading quest progress")
+            .unwrap_or(0)
+    }
+    fn unlocked_buildings(&self, p: PlayerKey) -> Vec<BuildingType> {
+        unlocked_buildings::table
+            .filter(unlocked_buildings::player_id.eq(p.num()))
+            .select(unlocked_buildings::building_type)
+            .load::<BuildingType>(self.dbconn())
+            .expect("Error loading unlocked buildings")
+    }
+
+    /*
+     * Batched lookups for many keys at once.
+     * They allow resolving nested lists (e.g. in GraphQL) with one query per list instead of one per object.
+     */
+    fn players_by_keys(&self, keys: &[PlayerKey]) -> Vec<Player> {
+        players::table
+            .filter(players::id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading data")
+    }
+    fn villages_by_keys(&self, keys: &[VillageKey]) -> Vec<Village> {
+        villages::table
+            .filter(villages::id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading data")
+    }
+    fn villages_of_players(&self, keys: &[PlayerKey]) -> Vec<Village> {
+        let villages: Vec<Village> = villages::table
+            .filter(villages::player_id.eq_any(key_nums(keys)))
+            .order_by(villages::id)
+            .load(self.dbconn())
+            .expect("Error loading data");
+        limit_per_key(
+            villages,
+            |v| v.player_id.unwrap_or_default(),
+            MAX_VILLAGES_PER_PLAYER,
+        )
+    }
+    fn streams_by_keys(&self, keys: &[StreamKey]) -> Vec<Stream> {
+        streams::table
+            .filter(streams::id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading data")
+    }
+    fn buildings_by_keys(&self, keys: &[BuildingKey]) -> Vec<Building> {
+        buildings::table
+            .filter(buildings::id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading data")
+    }
+    fn hobos_by_keys(&self, keys: &[HoboKey]) -> Vec<Hobo> {
+        hobos::table
+            .filter(hobos::id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading data")
+    }
+    fn workers_of_villages(&self, keys: &[VillageKey]) -> Vec<Worker> {
+        let workers: Vec<Worker> = workers::table
+            .filter(workers::home.eq_any(key_nums(keys)))
+            .order_by(workers::id)
+            .load(self.dbconn())
+            .expect("Error loading data");
+        limit_per_key(workers, |w| w.home, MAX_WORKERS_PER_VILLAGE)
+    }
+    /// Tasks of the workers, sorted by start time
+    fn tasks_of_workers(&self, keys: &[WorkerKey]) -> Vec<Task> {
+        let tasks: Vec<Task> = tasks::table
+            .filter(tasks::worker_id.eq_any(key_nums(keys)))
+            .order_by(tasks::start_time)
+            .load(self.dbconn())
+            .expect("Error loading data");
+        limit_per_key(tasks, |t| t.worker_id, MAX_TASKS_PER_WORKER)
+    }
+    fn abilities_of_workers(&self, keys: &[WorkerKey]) -> Vec<Ability> {
+        let abilities: Vec<Ability> = abilities::table
+            .filter(abilities::worker_id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading data");
+        limit_per_key(abilities, |a| a.worker_id, MAX_ABILITIES_PER_WORKER)
+    }
+    /// Attacks on the villages with an id of at least the given minimum, sorted by arrival.
+    /// At most `MAX_ATTACKS_PER_VILLAGE` attacks per village are returned.
+    fn attacks_on_villages(&self, keys: &[(VillageKey, i64)]) -> Vec<Attack> {
+        let mut query = attacks::table.into_boxed();
+        for (village, min_id) in keys {
+            query = query.or_filter(
+                attacks::destination_village_id
+                    .eq(village.num())
+                    .and(attacks::id.ge(*min_id)),
+            );
+        }
+        let attacks: Vec<Attack> = query
+            .order_by(attacks::arrival)
+            .load(self.dbconn())
+            .expect("Error loading data");
+        limit_per_key(
+            attacks,
+            |a| a.destination_village_id,
+            MAX_ATTACKS_PER_VILLAGE,
+        )
+    }
+    /// At most `MAX_UNITS_PER_ATTACK` hobos per attack are returned.
+    fn hobos_of_attacks_with_attack_info(&self, keys: &[AttackKey]) -> Vec<(Hobo, AttackToHobo)> {
+        let hobos: Vec<(Hobo, AttackToHobo)> = attacks_to_hobos::table
+            .inner_join(hobos::table)
+            .filter(attacks_to_hobos::attack_id.eq_any(key_nums(keys)))
+            .select((hobos::all_columns, attacks_to_hobos::all_columns))
+            .load(self.dbconn())
+            .expect("Error loading data");
+        limit_per_key(hobos, |(_, info)| info.attack_id, MAX_UNITS_PER_ATTACK)
+    }
+    /// Attack participations of the hobos, hobos that are not attacking have none
+    fn attack_info_of_hobos(&self, keys: &[HoboKey]) -> Vec<AttackToHobo> {
+        attacks_to_hobos::table
+            .filter(attacks_to_hobos::hobo_id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading data")
+    }
+    fn effects_on_hobos(&self, keys: &[HoboKey]) -> Vec<Effect> {
+        let effects: Vec<Effect> = effects::table
+            .filter(effects::hobo_id.eq_any(key_nums(keys)))
+            .order_by(effects::start_time)
+            .load(self.dbconn())
+            .expect("Error loading data");
+        limit_per_key(effects, |e| e.hobo_id, MAX_EFFECTS_PER_HOBO)
+    }
+    fn rewards_of_reports(&self, keys: &[VisitReportKey]) -> Vec<Reward> {
+        rewards::table
+            .filter(rewards::visit_report_id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading rewards")
+    }
+    fn quests_res_rewards(&self, keys: &[QuestKey]) -> Vec<QuestResReward> {
+        quest_res_rewards::table
+            .filter(quest_res_rewards::quest_id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading quest resource rewards")
+    }
+    fn quests_res_conditions(&self, keys: &[QuestKey]) -> Vec<QuestResCondition> {
+        quest_res_conditions::table
+            .filter(quest_res_conditions::quest_id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading quest conditions")
+    }
+    fn quests_building_conditions(&self, keys: &[QuestKey]) -> Vec<QuestBuildingCondition> {
+        quest_building_conditions::table
+            .filter(quest_building_conditions::quest_id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading quest conditions")
+    }
+    fn quests_worker_conditions(&self, keys: &[QuestKey]) -> Vec<QuestWorkerCondition> {
+        quest_worker_conditions::table
+            .filter(quest_worker_conditions::quest_id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading quest conditions")
+    }
+    fn quests_stat_conditions(&self, keys: &[QuestKey]) -> Vec<QuestStatCondition> {
+        quest_stat_conditions::table
+            .filter(quest_stat_conditions::quest_id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading quest conditions")
+    }
+    fn players_quest_progress(&self, keys: &[PlayerKey]) -> Vec<QuestProgress> {
+        quest_progress::table
+            .filter(quest_progress::player_id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading quest progress")
+    }
+    fn quests_worker_rewards(&self, keys: &[QuestKey]) -> Vec<QuestWorkerReward> {
+        quest_worker_rewards::table
+            .filter(quest_worker_rewards::quest_id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading quest worker rewards")
+    }
+    fn quests_perk_rewards(&self, keys: &[QuestKey]) -> Vec<QuestPerkReward> {
+        quest_perk_rewards::table
+            .filter(quest_perk_rewards::quest_id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading quest perk rewards")
+    }
+    fn quests_building_rewards(&self, keys: &[QuestKey]) -> Vec<QuestBuildingReward> {
+        quest_building_rewards::table
+            .filter(quest_building_rewards::quest_id.eq_any(key_nums(keys)))
+            .load(self.dbconn())
+            .expect("Error loading quest building rewards")
+    }
+}
+
+/// Selects a page of rows for keyset pagination.
+/// Queries return the rows in paging direction, a backward page starts with the row closest to the cursor.
+/// One row more than the page size is loaded, which tells whether there are more rows beyond the page.
+#[derive(Clone, Debug)]
+pub struct Page<K> {
+    /// Sort key of the row after which (or before which, for backward pages) the page starts
+    pub cursor: Option<K>,
+    pub backward: bool,
+    pub size: i64,
+}
+
+fn key_nums<K: Copy + Into<i64>>(keys: &[K]) -> Vec<i64> {
+    keys.iter().map(|k| (*k).into()).collect()
+}
+
+/// Keeps the first `limit` rows of each key, in their original order.
+/// Batched lookups use this instead of a global SQL limit, which would let one key crowd out the others.
+fn limit_per_key<T>(rows: Vec<T>, key: impl Fn(&T) -> i64, limit: usize) -> Vec<T> {
+    let mut counts = std::collections::HashMap::new();
+    rows.into_iter()
+        .filter(|row| {
+            let count = counts.entry(key(row)).or_insert(0);
+            *count += 1;
+            *count <= limit
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_applies_to_each_key() {
+        let rows = vec![(1, 'a'), (2, 'b'), (1, 'c'), (1, 'd'), (2, 'e')];
+        let limited = limit_per_key(rows, |(k, _)| *k, 2);
+        assert_eq!(limited, vec![(1, 'a'), (2, 'b'), (1, 'c'), (2, 'e')]);
+    }
 }
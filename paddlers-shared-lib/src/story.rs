@@ -1,5 +1,5 @@
 pub mod dialogue_entry;
 pub mod story_action;
+pub mod story_graph;
 pub mod story_state;
-pub mod story_transitions;
 pub mod story_trigger;
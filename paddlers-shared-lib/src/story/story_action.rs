@@ -1,12 +1,10 @@
 use crate::{
-    civilization::CivilizationPerk, const_list::ConstList, generated::QuestName,
-    specification_types::VisitorGroupDefinition,
+    civilization::CivilizationPerk, generated::QuestName, specification_types::VisitorDefinition,
 };
-
-pub type StoryActionList = ConstList<StoryAction>;
+use serde::{Deserialize, Serialize};
 
 /// An action to be performed on specific story state transitions
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum StoryAction {
     AddMana(i16),
     SendHobo(StoryVisitDefinition),
@@ -14,23 +12,9 @@ pub enum StoryAction {
     UnlockPerk(CivilizationPerk),
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StoryVisitDefinition {
+    #[serde(default)]
     pub fixed_travel_time_s: Option<i32>,
-    pub visitors: VisitorGroupDefinition,
-}
-
-impl StoryVisitDefinition {
-    pub const fn new(visitors: VisitorGroupDefinition) -> Self {
-        Self {
-            visitors,
-            fixed_travel_time_s: None,
-        }
-    }
-    pub const fn delayed(visitors: VisitorGroupDefinition, delay: i32) -> Self {
-        Self {
-            visitors,
-            fixed_travel_time_s: Some(delay),
-        }
-    }
+    pub visitors: Vec<VisitorDefinition>,
 }
@@ -0,0 +1,353 @@
+//! Each player is in one StoryState, depending on the tutorial/story progression.
+//!
+//! The StoryState values are stored in the database per player and provided as PlayerInfo to the frontend.
+//! Transitions are performed in the game-master when a StoryTrigger happens, following the FSM defined in `specification/story.ron`.
+//! In each transition, a set of StoryActions is also performed in the game-master and/or frontend.
+//! The frontend loads the same graph to show the dialogue scenes that belong to a state.
+
+use super::{story_action::StoryAction, story_state::StoryState, story_trigger::StoryTrigger};
+use crate::prelude::BuildingType;
+use crate::specification_types::{SceneIndex, SlideIndex};
+use serde::Deserialize;
+use std::collections::HashSet;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StoryGraph {
+    pub states: Vec<StoryStateDefinition>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StoryStateDefinition {
+    pub name: StoryState,
+    /// Scene that opens right away when the player is in this state
+    #[serde(default)]
+    pub scene: Option<(SceneIndex, SlideIndex)>,
+    /// Scene that opens when the player selects a certain entity in town
+    #[serde(default)]
+    pub entity_trigger: Option<StoryEntityTrigger>,
+    /// Legal transitions from this state
+    #[serde(default)]
+    pub transitions: Vec<StoryTransition>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StoryEntityTrigger {
+    pub entity: StoryEntity,
+    /// Without a scene, selecting the entity only removes the trigger
+    #[serde(default)]
+    pub scene: Option<(SceneIndex, SlideIndex)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum StoryEntity {
+    Hero,
+    /// The first visitor in town
+    Visitor,
+    Building(BuildingType),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StoryTransition {
+    pub trigger: StoryTrigger,
+    pub next_state: StoryState,
+    #[serde(default)]
+    pub actions: Vec<StoryAction>,
+}
+
+impl StoryGraph {
+    pub fn state(&self, name: &StoryState) -> Option<&StoryStateDefinition> {
+        self.states.iter().find(|s| s.name == *name)
+    }
+    pub fn transition(
+        &self,
+        from: &StoryState,
+        trigger: &StoryTrigger,
+    ) -> Option<&StoryTransition> {
+        self.state(from)?
+            .transitions
+            .iter()
+            .find(|t| t.trigger == *trigger)
+    }
+    /// Checks that all states are defined exactly once, that transitions are unambiguous,
+    /// and that the story can be completed from every state.
+    pub fn validate(&self) -> Result<(), StoryGraphError> {
+        match self.errors().into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+    /// All problems found in the graph, in the order the states are defined
+    pub fn errors(&self) -> Vec<StoryGraphError> {
+        let mut errors = vec![];
+        let mut names = HashSet::new();
+        for state in &self.states {
+            if !names.insert(&state.name) {
+                errors.push(StoryGraphError::DuplicateState(state.name.clone()));
+            }
+        }
+        for well_known in StoryState::WELL_KNOWN.iter() {
+            if !names.contains(well_known) {
+                errors.push(StoryGraphError::MissingState(well_known.clone()));
+            }
+        }
+        for state in &self.states {
+            let mut triggers = vec![];
+            for t in &state.transitions {
+                if !names.contains(&t.next_state) {
+                    errors.push(StoryGraphError::UndefinedTarget {
+                        state: state.name.clone(),
+                        target: t.next_state.clone(),
+                    });
+                }
+                if triggers.contains(&t.trigger) {
+                    errors.push(StoryGraphError::AmbiguousTrigger {
+                        state: state.name.clone(),
+                        trigger: t.trigger,
+                    });
+                }
+                triggers.push(t.trigger);
+            }
+        }
+        if !errors.is_empty() {
+            return errors;
+        }
+        let reachable = self.reachable_from(&StoryState::INITIALIZED);
+        let finishing = self.reaching(&StoryState::ALL_DONE);
+        for state in &self.states {
+            if !reachable.contains(&state.name) {
+                errors.push(StoryGraphError::Unreachable(state.name.clone()));
+            }
+            if !finishing.contains(&state.name) {
+                errors.push(StoryGraphError::DeadEnd(state.name.clone()));
+            }
+        }
+        errors
+    }
+    fn reachable_from(&self, start: &StoryState) -> HashSet<&StoryState> {
+        let mut visited = HashSet::new();
+        let mut stack: Vec<&StoryState> = self.state(start).map(|s| &s.name).into_iter().collect();
+        while let Some(name) = stack.pop() {
+            if visited.insert(name) {
+                if let Some(state) = self.state(name) {
+                    stack.extend(state.transitions.iter().map(|t| &t.next_state));
+                }
+            }
+        }
+        visited
+    }
+    fn reaching(&self, goal: &StoryState) -> HashSet<&StoryState> {
+        let mut visited = HashSet::new();
+        let mut stack: Vec<&StoryState> = self.state(goal).map(|s| &s.name).into_iter().collect();
+        while let Some(name) = stack.pop() {
+            if visited.insert(name) {
+                stack.extend(
+                    self.states
+                        .iter()
+                        .filter(|s| s.transitions.iter().any(|t| t.next_state == *name))
+                        .map(|s| &s.name),
+                );
+            }
+        }
+        visited
+    }
+}
+
+/// A problem in the definition of a story graph
+#[derive(Clone, Debug, PartialEq)]
+pub enum StoryGraphError {
+    DuplicateState(StoryState),
+    MissingState(StoryState),
+    UndefinedTarget {
+        state: StoryState,
+        target: StoryState,
+    },
+    AmbiguousTrigger {
+        state: StoryState,
+        trigger: StoryTrigger,
+    },
+    /// The state cannot be reached from the initial state
+    Unreachable(StoryState),
+    /// The final state cannot be reached from this state
+    DeadEnd(StoryState),
+}
+
+impl StoryGraphError {
+    /// The state in which the problem has been found
+    pub fn state(&self) -> &StoryState {
+        match self {
+            Self::DuplicateState(s)
+            | Self::MissingState(s)
+            | Self::Unreachable(s)
+            | Self::DeadEnd(s)
+            | Self::UndefinedTarget { state: s, .. }
+            | Self::AmbiguousTrigger { state: s, .. } => s,
+        }
+    }
+}
+
+impl std::fmt::Display for StoryGraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::DuplicateState(s) => write!(f, "State {} is defined twice", s),
+            Self::MissingState(s) => write!(f, "State {} is missing", s),
+            Self::UndefinedTarget { state, target } => write!(
+                f,
+                "Transition from {} leads to undefined state {}",
+                state, target
+            ),
+            Self::AmbiguousTrigger { state, trigger } => write!(
+                f,
+                "State {} has several transitions on {:?}",
+                state, trigger
+            ),
+            Self::Unreachable(s) => write!(
+                f,
+                "State {} cannot be reached from {}",
+                s,
+                StoryState::INITIALIZED
+            ),
+            Self::DeadEnd(s) => write!(f, "State {} has no path to {}", s, StoryState::ALL_DONE),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::story::story_trigger::StoryChoice;
+
+    fn state(name: StoryState, transitions: Vec<StoryTransition>) -> StoryStateDefinition {
+        StoryStateDefinition {
+            name,
+            scene: None,
+            entity_trigger: None,
+            transitions,
+        }
+    }
+    fn transition(trigger: StoryTrigger, next_state: StoryState) -> StoryTransition {
+        StoryTransition {
+            trigger,
+            next_state,
+            actions: vec![],
+        }
+    }
+    fn graph() -> StoryGraph {
+        let queued = StoryState::new("visitor_queued");
+        StoryGraph {
+            states: vec![
+                state(
+                    StoryState::INITIALIZED,
+                    vec![
+                        transition(StoryTrigger::DialogueStoryTrigger, queued.clone()),
+                        transition(
+                            StoryTrigger::DialogueChoice(StoryChoice::new(7)),
+                            StoryState::SERVANT_ACCEPTED,
+                        ),
+                    ],
+                ),
+                state(
+                    queued,
+                    vec![transition(
+                        StoryTrigger::LetVisitorIn,
+                        StoryState::SERVANT_ACCEPTED,
+                    )],
+                ),
+                state(
+                    StoryState::SERVANT_ACCEPTED,
+                    vec![transition(
+                        StoryTrigger::DialogueStoryTrigger,
+                        StoryState::BUILDING_WATERGATE,
+                    )],
+                ),
+                state(
+                    StoryState::BUILDING_WATERGATE,
+                    vec![transition(
+                        StoryTrigger::DialogueStoryTrigger,
+                        StoryState::FIRST_VISITOR_WELCOMED,
+                    )],
+                ),
+                state(
+                    StoryState::FIRST_VISITOR_WELCOMED,
+                    vec![transition(
+                        StoryTrigger::DialogueStoryTrigger,
+                        StoryState::ALL_DONE,
+                    )],
+                ),
+                state(StoryState::ALL_DONE, vec![]),
+            ],
+        }
+    }
+
+    #[test]
+    fn find_transition() {
+        let graph = graph();
+        assert!(graph.validate().is_ok());
+        let initialized = StoryState::INITIALIZED;
+
+        let d = graph.transition(&initialized, &StoryTrigger::DialogueStoryTrigger);
+        assert_eq!(d.unwrap().next_state, StoryState::new("visitor_queued"));
+
+        let c = graph.transition(
+            &initialized,
+            &StoryTrigger::DialogueChoice(StoryChoice::new(7)),
+        );
+        assert_eq!(c.unwrap().next_state, StoryState::SERVANT_ACCEPTED);
+
+        let c2 = graph.transition(
+            &initialized,
+            &StoryTrigger::DialogueChoice(StoryChoice::new(8)),
+        );
+        assert!(c2.is_none());
+
+        let queued = StoryState::new("visitor_queued");
+        assert!(graph
+            .transition(&queued, &StoryTrigger::LetVisitorIn)
+            .is_some());
+    }
+
+    #[test]
+    fn invalid_graphs() {
+        let mut undefined_target = graph();
+        undefined_target.states[2].transitions.push(transition(
+            StoryTrigger::LetVisitorIn,
+            StoryState::new("nowhere"),
+        ));
+        assert!(undefined_target.validate().is_err());
+
+        let mut ambiguous = graph();
+        ambiguous.states[1]
+            .transitions
+            .push(transition(StoryTrigger::LetVisitorIn, StoryState::ALL_DONE));
+        assert!(ambiguous.validate().is_err());
+
+        let mut missing_final_state = graph();
+        missing_final_state.states.pop();
+        assert!(missing_final_state.validate().is_err());
+    }
+
+    #[test]
+    fn unfinished_graphs() {
+        let orphan = StoryState::new("orphan");
+        let mut unreachable = graph();
+        unreachable.states.push(state(
+            orphan.clone(),
+            vec![transition(
+                StoryTrigger::DialogueStoryTrigger,
+                StoryState::ALL_DONE,
+            )],
+        ));
+        assert_eq!(
+            unreachable.errors(),
+            vec![StoryGraphError::Unreachable(orphan)]
+        );
+
+        let stuck = StoryState::new("stuck");
+        let mut dead_end = graph();
+        dead_end.states[0].transitions.push(transition(
+            StoryTrigger::DialogueChoice(StoryChoice::new(8)),
+            stuck.clone(),
+        ));
+        dead_end.states.push(state(stuck.clone(), vec![]));
+        assert_eq!(dead_end.errors(), vec![StoryGraphError::DeadEnd(stuck)]);
+    }
+}
@@ -1,31 +1,75 @@
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Name of a state in the story graph defined in `specification/story.ron`.
+///
+/// The set of states is part of the specification, the code only refers to a few well-known states by name.
+/// The name is stored in the database per player.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+#[cfg_attr(
+    feature = "sql_db",
+    derive(AsExpression, FromSqlRow),
+    sql_type = "diesel::sql_types::Text"
+)]
+pub struct StoryState(Cow<'static, str>);
+
+impl StoryState {
+    /// State of new players, also the default in the database
+    pub const INITIALIZED: StoryState = StoryState(Cow::Borrowed("initialized"));
+    pub const SERVANT_ACCEPTED: StoryState = StoryState(Cow::Borrowed("servant_accepted"));
+    pub const BUILDING_WATERGATE: StoryState = StoryState(Cow::Borrowed("building_watergate"));
+    pub const FIRST_VISITOR_WELCOMED: StoryState =
+        StoryState(Cow::Borrowed("first_visitor_welcomed"));
+    /// The tutorial is over, the game is fully open
+    pub const ALL_DONE: StoryState = StoryState(Cow::Borrowed("all_done"));
+
+    /// States that the code refers to, they must be part of every story graph
+    pub const WELL_KNOWN: [StoryState; 5] = [
+        Self::INITIALIZED,
+        Self::SERVANT_ACCEPTED,
+        Self::BUILDING_WATERGATE,
+        Self::FIRST_VISITOR_WELCOMED,
+        Self::ALL_DONE,
+    ];
+
+    pub fn new(name: impl Into<String>) -> Self {
+        StoryState(Cow::Owned(name.into()))
+    }
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for StoryState {
+    fn from(name: String) -> Self {
+        StoryState::new(name)
+    }
+}
+
+impl std::fmt::Display for StoryState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
 
 #[cfg(feature = "sql_db")]
-use ::diesel_derive_enum::DbEnum;
-
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
-#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
-#[cfg_attr(feature = "enum_utils", derive(EnumIter, Display))]
-#[cfg_attr(feature = "graphql", derive(juniper::GraphQLEnum))]
-#[cfg_attr(feature = "sql_db", derive(DbEnum), DieselType = "Story_state_type")]
-pub enum StoryState {
-    Initialized,
-    ServantAccepted,
-    TempleBuilt,
-    BuildingWatergate,
-    WatergateBuilt,
-    VisitorQueued,
-    VisitorArrived,
-    WelcomeVisitorQuestStarted,
-    FirstVisitorWelcomed,
-    PickingPrimaryCivBonus,
-    SolvingPrimaryCivQuestPartA,
-    SolvingPrimaryCivQuestPartB,
-    UnlockingInvitationPathA,
-    UnlockingInvitationPathB,
-    DialogueBalanceA,
-    DialogueBalanceB,
-    SolvingSecondaryQuestA,
-    SolvingSecondaryQuestB,
-    AllDone,
+mod sql {
+    use super::StoryState;
+    use diesel::deserialize::{self, FromSql};
+    use diesel::pg::Pg;
+    use diesel::serialize::{self, Output, ToSql};
+    use diesel::sql_types::Text;
+    use std::io::Write;
+
+    impl ToSql<Text, Pg> for StoryState {
+        fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+            <str as ToSql<Text, Pg>>::to_sql(self.name(), out)
+        }
+    }
+    impl FromSql<Text, Pg> for StoryState {
+        fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+            <String as FromSql<Text, Pg>>::from_sql(bytes).map(StoryState::new)
+        }
+    }
 }
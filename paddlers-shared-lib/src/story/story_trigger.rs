@@ -1,11 +1,9 @@
+use crate::generated::QuestName;
 use crate::prelude::BuildingType;
-use crate::{const_list::ConstList, generated::QuestName};
 use serde::{Deserialize, Serialize};
 
-pub type StoryTriggerList = ConstList<StoryTrigger>;
-
 /// Event that can trigger a story transition
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum StoryTrigger {
     /// Client acknowledges that player went through the blocking UI states of the current story
     DialogueStoryTrigger,
@@ -17,8 +15,6 @@ pub enum StoryTrigger {
     FinishedQuest(QuestName),
     /// Letting (the first) visitor into town
     LetVisitorIn,
-    // Note on adding new values: Make sure to add them to `is_trigger` in paddlers-shared-lib/src/story/story_transitions.rs
-    // (That's a custom partial_eq implementation)
 }
 
 #[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Hash, Eq)]
@@ -32,10 +28,3 @@ impl StoryChoice {
         Self { chosen_option }
     }
 }
-
-// Pseudo const-trait
-impl StoryChoice {
-    pub const fn const_eq(self, other: Self) -> bool {
-        self.chosen_option == other.chosen_option
-    }
-}
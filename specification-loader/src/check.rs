@@ -1,9 +1,13 @@
 use paddlers_shared_lib::{
-    specification_types::{Scene, SceneIndex},
+    specification_types::{Scene, SceneIndex, VisitDirectorDefinition, VisitRewardTable},
+    story::story_graph::StoryGraph,
     strum::VariantNames,
 };
 use std::path::Path;
 
+mod story;
+pub use story::check_story;
+
 pub enum DialogueCheckError {
     Io(std::io::Error),
     InvalidPath(String),
@@ -36,6 +40,36 @@ pub fn check_dialogue_scenes(path: &Path) -> Result<(), DialogueCheckError> {
     Ok(())
 }
 
+/// Checks that the visit director definition can be parsed and has sensible values
+pub fn check_visit_director(path: &Path) -> Result<(), String> {
+    let reader = super::open_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let definition: VisitDirectorDefinition = ron::de::from_reader(reader)
+        .map_err(|e| format!("Invalid definition in {}: {}", path.display(), e))?;
+    definition
+        .validate()
+        .map_err(|e| format!("Invalid definition in {}: {}", path.display(), e))
+}
+
+/// Checks that the visit reward table can be parsed and has sensible values
+pub fn check_visit_rewards(path: &Path) -> Result<(), String> {
+    let reader = super::open_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let table: VisitRewardTable = ron::de::from_reader(reader)
+        .map_err(|e| format!("Invalid reward table in {}: {}", path.display(), e))?;
+    table
+        .validate()
+        .map_err(|e| format!("Invalid reward table in {}: {}", path.display(), e))
+}
+
+pub fn load_story_graph(path: &Path) -> Result<StoryGraph, String> {
+    let reader = super::open_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let graph: StoryGraph = ron::de::from_reader(reader)
+        .map_err(|e| format!("Invalid story graph in {}: {}", path.display(), e))?;
+    graph
+        .validate()
+        .map_err(|e| format!("Invalid story graph in {}: {}", path.display(), e))?;
+    Ok(graph)
+}
+
 impl From<std::io::Error> for DialogueCheckError {
     fn from(err: std::io::Error) -> Self {
         Self::Io(err)
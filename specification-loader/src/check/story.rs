@@ -0,0 +1,394 @@
+//! Consistency checks of the story graph against the other specification files.
+
+use paddlers_shared_lib::{
+    specification_types::{DialogueAction, Scene, SceneIndex},
+    story::{
+        story_action::StoryAction,
+        story_graph::{StoryGraph, StoryGraphError, StoryStateDefinition},
+        story_trigger::StoryTrigger,
+    },
+    strum::IntoEnumIterator,
+};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// A problem found in a specification file, with the line where it has been found (if known)
+pub struct SpecificationError {
+    file: PathBuf,
+    line: Option<usize>,
+    message: String,
+}
+
+/// Checks the story graph and everything it refers to
+///
+/// * Every state can be reached from the initial state and the final state can be reached from every state
+/// * Quests started and finished in the story are defined in the quests RON files
+/// * Story progress in dialogue scenes matches a transition in the story graph
+/// * All text keys used in dialogue scenes are translated in every locale
+pub fn check_story(spec_dir: &Path, texts_dir: &Path) -> Result<(), Vec<SpecificationError>> {
+    let story = SourceFile::read(&spec_dir.join("story.ron")).map_err(|e| vec![e])?;
+    let graph: StoryGraph = story.parse().map_err(|e| vec![e])?;
+
+    let mut errors = vec![];
+    errors.extend(check_graph(&story, &graph));
+    match quest_keys(spec_dir) {
+        Ok(quests) => errors.extend(check_quests(&story, &graph, &quests)),
+        Err(e) => errors.push(e),
+    }
+    let mut locales = vec![];
+    for locale in &["en.po", "de.po"] {
+        match SourceFile::read(&texts_dir.join(locale)) {
+            Ok(po) => locales.push(po),
+            Err(e) => errors.push(e),
+        }
+    }
+    for index in SceneIndex::iter() {
+        let path = spec_dir
+            .join("dialogue")
+            .join(index.as_ref())
+            .with_extension("ron");
+        match SourceFile::read(&path).and_then(|f| f.parse::<Scene>().map(|s| (f, s))) {
+            Ok((file, scene)) => {
+                errors.extend(check_scene(&file, index, &scene, &graph));
+                for po in &locales {
+                    errors.extend(check_text_keys(&file, &scene, po));
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn check_graph(story: &SourceFile, graph: &StoryGraph) -> Vec<SpecificationError> {
+    graph
+        .errors()
+        .into_iter()
+        .map(|e| {
+            let state_line = story.state_line(e.state());
+            let line = match &e {
+                StoryGraphError::UndefinedTarget { target, .. } => {
+                    story.line_after(&format!("next_state: \"{}\"", target), state_line)
+                }
+                _ => state_line,
+            };
+            story.error(line, e.to_string())
+        })
+        .collect()
+}
+
+fn check_quests(
+    story: &SourceFile,
+    graph: &StoryGraph,
+    quests: &HashSet<String>,
+) -> Vec<SpecificationError> {
+    let mut errors = vec![];
+    for state in &graph.states {
+        let state_line = story.state_line(&state.name);
+        for t in &state.transitions {
+            let mut referenced = vec![];
+            if let StoryTrigger::FinishedQuest(q) = t.trigger {
+                referenced.push((format!("FinishedQuest({:?})", q), q));
+            }
+            for action in &t.actions {
+                if let StoryAction::StartQuest(q) = action {
+                    referenced.push((format!("StartQuest({:?})", q), *q));
+                }
+            }
+            for (needle, quest) in referenced {
+                if !quests.contains(quest.unique_string()) {
+                    errors.push(story.error(
+                        story.line_after(&needle, state_line),
+                        format!(
+                            "Quest {} used in state {} is not defined",
+                            quest.unique_string(),
+                            state.name
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Story progress in a scene must match a transition out of a state in which the scene can be open
+fn check_scene(
+    file: &SourceFile,
+    index: SceneIndex,
+    scene: &Scene,
+    graph: &StoryGraph,
+) -> Vec<SpecificationError> {
+    let mut errors = vec![];
+    let openers: Vec<&StoryStateDefinition> = graph
+        .states
+        .iter()
+        .filter(|s| opens_scene(s, index))
+        .collect();
+    for slide in 0..scene.slide_count() {
+        for button in scene.slide_buttons(slide) {
+            for action in &button.action.actions {
+                if let DialogueAction::StoryProgress(next_state, choice) = action {
+                    let trigger = match choice {
+                        Some(choice) => StoryTrigger::DialogueChoice(*choice),
+                        None => StoryTrigger::DialogueStoryTrigger,
+                    };
+                    let guarded = openers.iter().any(|s| {
+                        s.transitions
+                            .iter()
+                            .any(|t| t.trigger == trigger && t.next_state == *next_state)
+                    });
+                    if !guarded {
+                        errors.push(file.error(
+                            file.line_after(&format!("StoryProgress(\"{}\"", next_state), None),
+                            format!(
+                                "No transition on {:?} from a state opening {:?} leads to state {}",
+                                trigger, index, next_state
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    errors
+}
+
+fn opens_scene(state: &StoryStateDefinition, index: SceneIndex) -> bool {
+    let entity_scene = state.entity_trigger.as_ref().and_then(|t| t.scene);
+    state
+        .scene
+        .iter()
+        .chain(entity_scene.iter())
+        .any(|(s, _)| *s == index)
+}
+
+fn check_text_keys(file: &SourceFile, scene: &Scene, po: &SourceFile) -> Vec<SpecificationError> {
+    let mut keys = vec![];
+    for slide in 0..scene.slide_count() {
+        keys.push(scene.slide_text_key(slide).key());
+        keys.extend(scene.slide_buttons(slide).iter().map(|b| b.text_key.key()));
+    }
+    keys.into_iter()
+        .filter(|key| {
+            po.line_starting_with(&format!("msgid \"{}\"", key))
+                .is_none()
+        })
+        .map(|key| {
+            file.error(
+                file.line_after(&format!("text_key: \"{}\"", key), None),
+                format!("Text key {} is missing in {}", key, po.path.display()),
+            )
+        })
+        .collect()
+}
+
+/// Collects the keys of all quests defined in `quests.*.ron` files
+fn quest_keys(spec_dir: &Path) -> Result<HashSet<String>, SpecificationError> {
+    let mut keys = HashSet::new();
+    let entries = std::fs::read_dir(spec_dir).map_err(|e| SpecificationError {
+        file: spec_dir.to_path_buf(),
+        line: None,
+        message: e.to_string(),
+    })?;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with("quests.") && name.ends_with(".ron") {
+            let file = SourceFile::read(&entry.path())?;
+            let quests: Vec<crate::quest::QuestDefinition> = file.parse()?;
+            keys.extend(quests.into_iter().map(|q| q.quest_key));
+        }
+    }
+    Ok(keys)
+}
+
+/// A specification file kept in memory to look up line numbers
+struct SourceFile {
+    path: PathBuf,
+    content: String,
+}
+
+impl SourceFile {
+    fn read(path: &Path) -> Result<Self, SpecificationError> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(Self {
+                path: path.to_path_buf(),
+                content,
+            }),
+            Err(e) => Err(SpecificationError {
+                file: path.to_path_buf(),
+                line: None,
+                message: e.to_string(),
+            }),
+        }
+    }
+    fn parse<T: serde::de::DeserializeOwned>(&self) -> Result<T, SpecificationError> {
+        ron::de::from_str(&self.content).map_err(|e| {
+            self.error(
+                Some(e.position.line).filter(|line| *line > 0),
+                e.code.to_string(),
+            )
+        })
+    }
+    /// First line (1-based) containing `needle`, starting the search at line `after`
+    ///
+    /// Commented out lines are skipped.
+    fn line_after(&self, needle: &str, after: Option<usize>) -> Option<usize> {
+        let skip = after.map(|line| line - 1).unwrap_or(0);
+        self.content
+            .lines()
+            .enumerate()
+            .skip(skip)
+            .find(|(_, line)| !line.trim_start().starts_with("//") && line.contains(needle))
+            .map(|(i, _)| i + 1)
+    }
+    /// First line (1-based) starting with `prefix`, which ignores obsolete `#~` entries in PO files
+    fn line_starting_with(&self, prefix: &str) -> Option<usize> {
+        self.content
+            .lines()
+            .position(|line| line.starts_with(prefix))
+            .map(|i| i + 1)
+    }
+    fn state_line(&self, state: &impl std::fmt::Display) -> Option<usize> {
+        self.line_after(&format!("name: \"{}\"", state), None)
+    }
+    fn error(&self, line: Option<usize>, message: String) -> SpecificationError {
+        SpecificationError {
+            file: self.path.clone(),
+            line,
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for SpecificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file.display(), line, self.message),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORY: &str = r##"#![enable(implicit_some)]
+(
+    states: [
+        (
+            name: "initialized",
+            entity_trigger: (entity: Hero, scene: (Entrance, 0)),
+            transitions: [
+                (trigger: DialogueStoryTrigger, next_state: "servant_accepted"),
+            ],
+        ),
+        (
+            name: "servant_accepted",
+            scene: (BuildWatergate, 0),
+            transitions: [
+                (
+                    trigger: FinishedQuest(HelloWorld),
+                    next_state: "temple_built",
+                    actions: [StartQuest(CreateForest)],
+                ),
+            ],
+        ),
+        (
+            name: "temple_built",
+        ),
+    ],
+)"##;
+
+    const SCENE: &str = r##"#![enable(implicit_some)]
+(slides: [
+    Slide(
+        text_key: "welcome",
+        buttons: [
+            SlideButton(
+                text_key: "accept",
+                action: SlideButtonAction(
+                    // actions: [StoryProgress("temple_built", None)],
+                    actions: [StoryProgress("servant_accepted", None)],
+                ),
+            ),
+            SlideButton(
+                text_key: "skip",
+                action: SlideButtonAction(
+                    actions: [StoryProgress("temple_built", None)],
+                ),
+            ),
+        ],
+        sprite: Simple(RogerLargeSad),
+        back_button: false,
+        next_button: false,
+    ),
+])"##;
+
+    const PO: &str = r#"msgid "welcome"
+msgstr "Welcome"
+
+#~ msgid "accept"
+#~ msgstr "Accept"
+
+msgid "skip"
+msgstr "Skip"
+"#;
+
+    fn source(path: &str, content: &str) -> SourceFile {
+        SourceFile {
+            path: PathBuf::from(path),
+            content: content.to_owned(),
+        }
+    }
+
+    fn messages(errors: Vec<SpecificationError>) -> Vec<String> {
+        errors.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn undefined_quests_are_reported_with_line() {
+        let story = source("story.ron", STORY);
+        let graph: StoryGraph = story.parse().unwrap_or_else(|e| panic!("{}", e));
+        let quests = vec!["hello-world".to_owned()].into_iter().collect();
+        assert_eq!(
+            messages(check_quests(&story, &graph, &quests)),
+            vec!["story.ron:18: Quest create-forest used in state servant_accepted is not defined"]
+        );
+    }
+
+    #[test]
+    fn story_progress_is_checked_against_states_opening_the_scene() {
+        let story = source("story.ron", STORY);
+        let graph: StoryGraph = story.parse().unwrap_or_else(|e| panic!("{}", e));
+        let file = source("scene.ron", SCENE);
+        let scene: Scene = file.parse().unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(
+            messages(check_scene(&file, SceneIndex::Entrance, &scene, &graph)),
+            vec!["scene.ron:16: No transition on DialogueStoryTrigger from a state opening Entrance leads to state temple_built"]
+        );
+        assert_eq!(
+            messages(check_scene(&file, SceneIndex::BuildWatergate, &scene, &graph)),
+            vec![
+                "scene.ron:10: No transition on DialogueStoryTrigger from a state opening BuildWatergate leads to state servant_accepted",
+                "scene.ron:16: No transition on DialogueStoryTrigger from a state opening BuildWatergate leads to state temple_built",
+            ]
+        );
+    }
+
+    #[test]
+    fn obsolete_translations_do_not_count() {
+        let file = source("scene.ron", SCENE);
+        let scene: Scene = file.parse().unwrap_or_else(|e| panic!("{}", e));
+        let po = source("en.po", PO);
+        assert_eq!(
+            messages(check_text_keys(&file, &scene, &po)),
+            vec!["scene.ron:7: Text key accept is missing in en.po"]
+        );
+    }
+}
@@ -17,7 +17,11 @@ fn generate_type(
     out: &mut impl std::io::Write,
     parsed_quests: &[QuestDefinition],
 ) -> std::io::Result<()> {
-    writeln!(out, "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]")?;
+    writeln!(out, "use serde::{{Deserialize, Serialize}};")?;
+    writeln!(
+        out,
+        "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]"
+    )?;
     writeln!(out, "pub enum QuestName {{")?;
     let indent = "    ";
     for quest in parsed_quests {
@@ -1,34 +1,42 @@
-use paddlers_shared_lib::story::story_state::StoryState;
-use paddlers_shared_lib::strum::IntoEnumIterator;
+use paddlers_shared_lib::story::story_graph::{StoryGraph, StoryStateDefinition};
 
 /// Generate a flow chart in dot format from the defined FSM
-pub fn generate_dot_story_diagram(out: &mut impl std::io::Write) -> std::io::Result<()> {
+pub fn generate_dot_story_diagram(
+    graph: &StoryGraph,
+    out: &mut impl std::io::Write,
+) -> std::io::Result<()> {
     writeln!(out, "digraph MyGraph {{")?;
     writeln!(out, "graph [outputorder=edgesfirst];")?;
-    for s in StoryState::iter() {
+    for s in &graph.states {
         state_definition(out, s)?;
     }
-    for s in StoryState::iter() {
+    for s in &graph.states {
         state_transitions(out, s)?;
     }
     writeln!(out, "}}")?;
     Ok(())
 }
-fn state_definition(out: &mut impl std::io::Write, s: StoryState) -> std::io::Result<()> {
-    writeln!(out, "{0:?} [label=\"{0}\"]", s)
+fn state_definition(
+    out: &mut impl std::io::Write,
+    s: &StoryStateDefinition,
+) -> std::io::Result<()> {
+    writeln!(out, "\"{0}\" [label=\"{0}\"]", s.name)
 }
-fn state_transitions(out: &mut impl std::io::Write, s: StoryState) -> std::io::Result<()> {
-    for transition in s.guards().into_iter() {
+fn state_transitions(
+    out: &mut impl std::io::Write,
+    s: &StoryStateDefinition,
+) -> std::io::Result<()> {
+    for transition in &s.transitions {
         let mut col = "black";
-        if s == transition.next_state {
+        if s.name == transition.next_state {
             col = "invis";
         }
         write!(
             out,
-            "{:?} -> {:?} [decorate=true, color={}, label=< <B>{:?}</B> ",
-            s, transition.next_state, col, transition.trigger
+            "\"{}\" -> \"{}\" [decorate=true, color={}, label=< <B>{:?}</B> ",
+            s.name, transition.next_state, col, transition.trigger
         )?;
-        for action in transition.actions.into_iter() {
+        for action in &transition.actions {
             write!(out, "<br/>{:?} ", action)?;
         }
         writeln!(out, ">]")?;
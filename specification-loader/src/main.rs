@@ -1,6 +1,6 @@
 use clap::{App, Arg, SubCommand};
 use diesel::PgConnection;
-use std::io::{BufRead, Read, Write};
+use std::io::{BufRead, Write};
 
 mod check;
 mod gen;
@@ -10,7 +10,25 @@ fn main() {
     let matches = App::new("Paddlers Specification Loader")
         .subcommand(
             SubCommand::with_name("upload-quests")
-                .arg(Arg::with_name("INPUT_FILE").required(true).index(1)),
+                .before_help(
+                    "Brings the quests in the database up to date with the given quest definitions.",
+                )
+                .arg(
+                    Arg::with_name("INPUT_FILE")
+                        .required(true)
+                        .multiple(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .long("dry-run")
+                        .help("Only prints the changes, without applying them."),
+                )
+                .arg(
+                    Arg::with_name("prune")
+                        .long("prune")
+                        .help("Deletes quests that are not in the input files, unless a player holds them."),
+                ),
         )
         .subcommand(
             SubCommand::with_name("generate")
@@ -41,17 +59,41 @@ fn main() {
 
     if let Some(matches) = matches.subcommand_matches("upload-quests") {
         let db = paddlers_shared_lib::establish_connection();
-        let file = matches.value_of("INPUT_FILE").unwrap();
-        upload_quests(&db, open_file(file).unwrap());
+        let mut quests = vec![];
+        for file in matches.values_of("INPUT_FILE").unwrap() {
+            match ron::de::from_reader::<_, Vec<quest::QuestDefinition>>(open_file(file).unwrap()) {
+                Ok(mut parsed) => quests.append(&mut parsed),
+                Err(e) => {
+                    eprintln!("Quest parsing failed. {}", e);
+                    return;
+                }
+            }
+        }
+        upload_quests(
+            &db,
+            &quests,
+            matches.is_present("dry-run"),
+            matches.is_present("prune"),
+        );
     }
     if let Some(matches) = matches.subcommand_matches("check") {
-        let dir = matches
-            .value_of("SPECIFICATION_DIRECTORY")
-            .unwrap()
-            .to_string()
-            + "/dialogue/";
+        let spec_dir = matches.value_of("SPECIFICATION_DIRECTORY").unwrap();
+        let dir = spec_dir.to_string() + "/dialogue/";
+        let director = spec_dir.to_string() + "/visit_director.ron";
+        let rewards = spec_dir.to_string() + "/visit_rewards.ron";
+        let texts = spec_dir.to_string() + "/../texts";
         if let Err(e) = check::check_dialogue_scenes(std::path::Path::new(&dir)) {
             println!("\x1b[031mFAILED\x1b[0m: {}", e)
+        } else if let Err(e) = check::check_visit_director(std::path::Path::new(&director)) {
+            println!("\x1b[031mFAILED\x1b[0m: {}", e)
+        } else if let Err(e) = check::check_visit_rewards(std::path::Path::new(&rewards)) {
+            println!("\x1b[031mFAILED\x1b[0m: {}", e)
+        } else if let Err(errors) =
+            check::check_story(std::path::Path::new(spec_dir), std::path::Path::new(&texts))
+        {
+            for e in errors {
+                println!("\x1b[031mFAILED\x1b[0m: {}", e)
+            }
         } else {
             println!("OK");
         }
@@ -69,8 +111,10 @@ fn main() {
             }
             "chart" => {
                 let path = matches.value_of("OUTPUT_DIR").unwrap();
+                let story = path.to_owned() + "/story.ron";
+                let graph = check::load_story_graph(std::path::Path::new(&story)).unwrap();
                 let mut story_dot = write_file(&(path.to_owned() + "/story.dot")).unwrap();
-                gen::generate_dot_story_diagram(&mut story_dot).unwrap();
+                gen::generate_dot_story_diagram(&graph, &mut story_dot).unwrap();
             }
             target => {
                 eprintln!("Unknown generation target: {}", target)
@@ -79,20 +123,21 @@ fn main() {
     }
 }
 
-fn upload_quests(db: &PgConnection, input: impl Read) {
-    match ron::de::from_reader::<_, Vec<quest::QuestDefinition>>(input) {
-        Ok(quests) => {
-            let n = quests.len();
-            let mut okay = n;
-            for quest in quests {
-                if let Err(e) = quest.upload(db) {
-                    eprintln!("{}", e);
-                    okay -= 1;
-                }
-            }
-            println!("Uploaded {} quests, {} failed.", okay, n - okay)
+fn upload_quests(db: &PgConnection, quests: &[quest::QuestDefinition], dry_run: bool, prune: bool) {
+    let migration = match quest::QuestMigration::plan(db, quests, prune) {
+        Ok(migration) => migration,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
         }
-        Err(e) => eprintln!("Quest parsing failed. {}", e),
+    };
+    migration.print(&mut std::io::stdout()).unwrap();
+    if dry_run || migration.is_empty() {
+        return;
+    }
+    match migration.apply(db) {
+        Ok(()) => println!("Quests updated."),
+        Err(e) => eprintln!("Updating quests failed, nothing has been changed. {}", e),
     }
 }
 
@@ -1,17 +1,23 @@
 use diesel::{PgConnection, QueryResult, RunQueryDsl};
 use paddlers_shared_lib::{
+    civilization::CivilizationPerk,
     prelude::{
         BuildingType, NewQuest, NewQuestBuildingCondition, NewQuestResCondition, NewQuestResReward,
-        NewQuestWorkerCondition, Quest, ResourceType, TaskType,
+        NewQuestStatCondition, NewQuestWorkerCondition, Quest, QuestBuildingReward,
+        QuestPerkReward, QuestStatType, QuestWorkerReward, ResourceType, TaskType,
     },
     schema::{
-        quest_building_conditions, quest_res_conditions, quest_res_rewards,
-        quest_worker_conditions, quests,
+        quest_building_conditions, quest_building_rewards, quest_perk_rewards,
+        quest_res_conditions, quest_res_rewards, quest_stat_conditions, quest_worker_conditions,
+        quest_worker_rewards, quests,
     },
 };
 use serde::Deserialize;
 use std::collections::HashMap;
 
+mod migration;
+pub use migration::*;
+
 #[derive(Deserialize)]
 pub struct QuestDefinition {
     pub quest_key: String,
@@ -27,28 +33,39 @@ pub struct QuestConditions {
     pub buildings: Option<HashMap<BuildingType, i64>>,
     pub resources: Option<HashMap<ResourceType, i64>>,
     pub workers: Option<HashMap<TaskType, i64>>,
+    pub stats: Option<HashMap<QuestStatType, i64>>,
 }
 
 #[derive(Deserialize)]
 pub struct QuestRewards {
     pub resources: Option<HashMap<ResourceType, i64>>,
+    /// Mana for the hero
+    pub mana: Option<i64>,
+    /// Experience for the hero
+    pub experience: Option<i64>,
+    pub perks: Option<Vec<CivilizationPerk>>,
+    /// Buildings the player can build afterwards, regardless of other restrictions
+    pub buildings: Option<Vec<BuildingType>>,
 }
 
 impl QuestDefinition {
-    pub fn upload(self, db: &PgConnection) -> QueryResult<()> {
+    pub fn upload(&self, db: &PgConnection) -> QueryResult<()> {
         let quest = NewQuest {
-            quest_key: self.quest_key,
-            follow_up_quest: self.follow_up_quest,
+            quest_key: self.quest_key.clone(),
+            follow_up_quest: self.follow_up_quest.clone(),
             karma_condition: self.karma_condition,
             pop_condition: self.pop_condition,
         };
         let quest = diesel::insert_into(quests::dsl::quests)
             .values(&quest)
             .get_result::<Quest>(db)?;
-        let quest_id = quest.id;
+        self.upload_details(quest.id, db)
+    }
 
+    /// Inserts conditions and rewards for an existing quest
+    fn upload_details(&self, quest_id: i64, db: &PgConnection) -> QueryResult<()> {
         // Conditions
-        for (building_type, amount) in self.condition.buildings.into_iter().flatten() {
+        for (&building_type, &amount) in self.condition.buildings.iter().flatten() {
             let new = NewQuestBuildingCondition {
                 quest_id,
                 building_type,
@@ -58,7 +75,7 @@ impl QuestDefinition {
                 .values(new)
                 .execute(db)?;
         }
-        for (resource_type, amount) in self.condition.resources.into_iter().flatten() {
+        for (&resource_type, &amount) in self.condition.resources.iter().flatten() {
             let new = NewQuestResCondition {
                 quest_id,
                 resource_type,
@@ -68,7 +85,7 @@ impl QuestDefinition {
                 .values(new)
                 .execute(db)?;
         }
-        for (task_type, amount) in self.condition.workers.into_iter().flatten() {
+        for (&task_type, &amount) in self.condition.workers.iter().flatten() {
             let new = NewQuestWorkerCondition {
                 quest_id,
                 task_type,
@@ -78,9 +95,19 @@ impl QuestDefinition {
                 .values(new)
                 .execute(db)?;
         }
+        for (&stat_type, &amount) in self.condition.stats.iter().flatten() {
+            let new = NewQuestStatCondition {
+                quest_id,
+                stat_type,
+                amount,
+            };
+            diesel::insert_into(quest_stat_conditions::dsl::quest_stat_conditions)
+                .values(new)
+                .execute(db)?;
+        }
 
         // Rewards
-        for (resource_type, amount) in self.reward.resources.into_iter().flatten() {
+        for (&resource_type, &amount) in self.reward.resources.iter().flatten() {
             let new = NewQuestResReward {
                 quest_id,
                 resource_type,
@@ -90,6 +117,31 @@ impl QuestDefinition {
                 .values(new)
                 .execute(db)?;
         }
+        if self.reward.mana.is_some() || self.reward.experience.is_some() {
+            let new = QuestWorkerReward {
+                quest_id,
+                mana: self.reward.mana.unwrap_or(0),
+                experience: self.reward.experience.unwrap_or(0),
+            };
+            diesel::insert_into(quest_worker_rewards::dsl::quest_worker_rewards)
+                .values(new)
+                .execute(db)?;
+        }
+        for &perk in self.reward.perks.iter().flatten() {
+            let new = QuestPerkReward { quest_id, perk };
+            diesel::insert_into(quest_perk_rewards::dsl::quest_perk_rewards)
+                .values(new)
+                .execute(db)?;
+        }
+        for &building_type in self.reward.buildings.iter().flatten() {
+            let new = QuestBuildingReward {
+                quest_id,
+                building_type,
+            };
+            diesel::insert_into(quest_building_rewards::dsl::quest_building_rewards)
+                .values(new)
+                .execute(db)?;
+        }
 
         Ok(())
     }
@@ -0,0 +1,684 @@
+//! Brings the quests in the database up to date with the quest definitions.
+//!
+//! Quests are matched by their key.
+//! Changed quests are updated in place, which keeps them assigned to the players that already hold them.
+//! Only the progress counters of these players are adjusted to the new conditions, see `QuestProgress`.
+
+use super::QuestDefinition;
+use diesel::prelude::*;
+use paddlers_shared_lib::{
+    civilization::CivilizationPerk,
+    prelude::{BuildingType, Quest, QuestProgress, QuestStatType, ResourceType, TaskType},
+    schema::{
+        quest_building_conditions, quest_building_rewards, quest_perk_rewards, quest_progress,
+        quest_res_conditions, quest_res_rewards, quest_stat_conditions, quest_to_player,
+        quest_worker_conditions, quest_worker_rewards, quests,
+    },
+    strum::IntoEnumIterator,
+};
+use std::collections::{HashMap, HashSet};
+
+pub struct QuestMigration<'a> {
+    changes: Vec<QuestChange<'a>>,
+    unchanged: usize,
+}
+
+enum QuestChange<'a> {
+    Create(&'a QuestDefinition),
+    Update {
+        quest: Quest,
+        definition: &'a QuestDefinition,
+        fields: Vec<&'static str>,
+        players: usize,
+    },
+    /// Quest is not defined anymore and nobody holds it
+    Delete(Quest),
+    /// Quest is not defined anymore but it cannot be deleted without taking it away from players
+    Keep {
+        quest: Quest,
+        players: usize,
+    },
+}
+
+/// Reasons why a migration cannot be planned
+#[derive(Debug)]
+pub enum PlanError {
+    /// Quest keys that are defined more than once, possibly in different files
+    DuplicateKeys(Vec<String>),
+    /// Quest keys that are stored more than once in the database, the stored quests cannot be matched to definitions
+    DuplicateStoredKeys(Vec<String>),
+    Db(diesel::result::Error),
+}
+
+/// Everything that defines a quest, in a form that can be compared between definition and database
+#[derive(Debug, PartialEq)]
+struct QuestContent {
+    karma_condition: Option<i64>,
+    pop_condition: Option<i64>,
+    follow_up_quest: Option<String>,
+    buildings: HashMap<BuildingType, i64>,
+    resources: HashMap<ResourceType, i64>,
+    workers: HashMap<TaskType, i64>,
+    stats: HashMap<QuestStatType, i64>,
+    resource_rewards: HashMap<ResourceType, i64>,
+    /// (mana, experience)
+    worker_reward: Option<(i64, i64)>,
+    perk_rewards: HashSet<CivilizationPerk>,
+    building_rewards: HashSet<BuildingType>,
+}
+
+impl<'a> QuestMigration<'a> {
+    /// Compares the definitions with the database.
+    /// With `prune`, quests missing in the definitions are deleted unless a player holds them.
+    pub fn plan(
+        db: &PgConnection,
+        definitions: &'a [QuestDefinition],
+        prune: bool,
+    ) -> Result<Self, PlanError> {
+        let duplicates = duplicate_keys(definitions.iter().map(|d| &d.quest_key));
+        if !duplicates.is_empty() {
+            return Err(PlanError::DuplicateKeys(duplicates));
+        }
+        let stored = quests::table.load::<Quest>(db)?;
+        // The unique index on the key prevents this, unless the schema has been altered
+        let duplicates = duplicate_keys(stored.iter().map(|q| &q.quest_key));
+        if !duplicates.is_empty() {
+            return Err(PlanError::DuplicateStoredKeys(duplicates));
+        }
+        let mut stored: HashMap<String, Quest> = stored
+            .into_iter()
+            .map(|q| (q.quest_key.clone(), q))
+            .collect();
+        let mut changes = vec![];
+        let mut unchanged = 0;
+        for definition in definitions {
+            if let Some(quest) = stored.remove(&definition.quest_key) {
+                let fields = QuestContent::load(db, &quest)?.diff(&definition.into());
+                if fields.is_empty() {
+                    unchanged += 1;
+                } else {
+                    let players = count_players(db, &quest)?;
+                    changes.push(QuestChange::Update {
+                        quest,
+                        definition,
+                        fields,
+                        players,
+                    });
+                }
+            } else {
+                changes.push(QuestChange::Create(definition));
+            }
+        }
+        if prune {
+            let mut removed: Vec<Quest> = stored.into_iter().map(|(_, q)| q).collect();
+            removed.sort_by_key(|q| q.id);
+            for quest in removed {
+                let players = count_players(db, &quest)?;
+                if players == 0 {
+                    changes.push(QuestChange::Delete(quest));
+                } else {
+                    changes.push(QuestChange::Keep { quest, players });
+                }
+            }
+        }
+        Ok(QuestMigration { changes, unchanged })
+    }
+
+    /// True if applying the migration would not change anything
+    pub fn is_empty(&self) -> bool {
+        self.changes
+            .iter()
+            .all(|c| matches!(c, QuestChange::Keep { .. }))
+    }
+
+    pub fn print(&self, out: &mut impl std::io::Write) -> std::io::Result<()> {
+        for change in &self.changes {
+            match change {
+                QuestChange::Create(definition) => writeln!(out, "+ {}", definition.quest_key)?,
+                QuestChange::Update {
+                    quest,
+                    fields,
+                    players,
+                    ..
+                } => writeln!(
+                    out,
+                    "~ {} ({}), held by {} players",
+                    quest.quest_key,
+                    fields.join(", "),
+                    players
+                )?,
+                QuestChange::Delete(quest) => writeln!(out, "- {}", quest.quest_key)?,
+                QuestChange::Keep { quest, players } => writeln!(
+                    out,
+                    "! {} is not defined anymore but held by {} players, it is kept",
+                    quest.quest_key, players
+                )?,
+            }
+        }
+        writeln!(out, "{} quests unchanged", self.unchanged)?;
+        Ok(())
+    }
+
+    /// Applies all changes in a single transaction
+    pub fn apply(&self, db: &PgConnection) -> QueryResult<()> {
+        db.transaction(|| {
+            for change in &self.changes {
+                match change {
+                    QuestChange::Create(definition) => definition.upload(db)?,
+                    QuestChange::Update {
+                        quest, definition, ..
+                    } => update_quest(db, quest, definition)?,
+                    QuestChange::Delete(quest) => {
+                        // Conditions and rewards are deleted by cascade
+                        diesel::delete(quests::table.find(quest.id)).execute(db)?;
+                    }
+                    QuestChange::Keep { .. } => {}
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Keys that appear more than once, sorted
+fn duplicate_keys<'k>(keys: impl Iterator<Item = &'k String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut duplicates: Vec<String> = keys.filter(|key| !seen.insert(*key)).cloned().collect();
+    duplicates.sort();
+    duplicates.dedup();
+    duplicates
+}
+
+fn count_players(db: &PgConnection, quest: &Quest) -> QueryResult<usize> {
+    quest_to_player::table
+        .filter(quest_to_player::quest_id.eq(quest.id))
+        .count()
+        .get_result::<i64>(db)
+        .map(|n| n as usize)
+}
+
+fn update_quest(db: &PgConnection, quest: &Quest, definition: &QuestDefinition) -> QueryResult<()> {
+    let quest_id = quest.id;
+    diesel::update(quests::table.find(quest_id))
+        .set((
+            quests::karma_condition.eq(definition.karma_condition),
+            quests::pop_condition.eq(definition.pop_condition),
+            quests::follow_up_quest.eq(&definition.follow_up_quest),
+        ))
+        .execute(db)?;
+
+    // Replace all conditions and rewards, the assignments to players remain untouched
+    diesel::delete(
+        quest_building_conditions::table.filter(quest_building_conditions::quest_id.eq(quest_id)),
+    )
+    .execute(db)?;
+    diesel::delete(quest_res_conditions::table.filter(quest_res_conditions::quest_id.eq(quest_id)))
+        .execute(db)?;
+    diesel::delete(
+        quest_worker_conditions::table.filter(quest_worker_conditions::quest_id.eq(quest_id)),
+    )
+    .execute(db)?;
+    diesel::delete(
+        quest_stat_conditions::table.filter(quest_stat_conditions::quest_id.eq(quest_id)),
+    )
+    .execute(db)?;
+    diesel::delete(quest_res_rewards::table.filter(quest_res_rewards::quest_id.eq(quest_id)))
+        .execute(db)?;
+    diesel::delete(quest_worker_rewards::table.find(quest_id)).execute(db)?;
+    diesel::delete(quest_perk_rewards::table.filter(quest_perk_rewards::quest_id.eq(quest_id)))
+        .execute(db)?;
+    diesel::delete(
+        quest_building_rewards::table.filter(quest_building_rewards::quest_id.eq(quest_id)),
+    )
+    .execute(db)?;
+    definition.upload_details(quest_id, db)?;
+
+    migrate_progress(db, quest_id, definition)
+}
+
+/// Players holding the quest keep the progress on conditions that still exist.
+/// New tracked conditions start counting now.
+fn migrate_progress(
+    db: &PgConnection,
+    quest_id: i64,
+    definition: &QuestDefinition,
+) -> QueryResult<()> {
+    let tracked: HashSet<QuestStatType> = definition
+        .condition
+        .stats
+        .iter()
+        .flatten()
+        .map(|(&stat, _)| stat)
+        .filter(|stat| stat.is_tracked())
+        .collect();
+    for stat in QuestStatType::iter().filter(|stat| !tracked.contains(stat)) {
+        diesel::delete(
+            quest_progress::table
+                .filter(quest_progress::quest_id.eq(quest_id))
+                .filter(quest_progress::stat_type.eq(stat)),
+        )
+        .execute(db)?;
+    }
+    let players: Vec<i64> = quest_to_player::table
+        .filter(quest_to_player::quest_id.eq(quest_id))
+        .select(quest_to_player::player_id)
+        .load(db)?;
+    let progress: Vec<QuestProgress> = players
+        .iter()
+        .flat_map(|&player_id| {
+            tracked.iter().map(move |&stat_type| QuestProgress {
+                quest_id,
+                player_id,
+                stat_type,
+                amount: 0,
+            })
+        })
+        .collect();
+    if !progress.is_empty() {
+        diesel::insert_into(quest_progress::table)
+            .values(&progress)
+            .on_conflict_do_nothing()
+            .execute(db)?;
+    }
+    Ok(())
+}
+
+impl From<diesel::result::Error> for PlanError {
+    fn from(e: diesel::result::Error) -> Self {
+        PlanError::Db(e)
+    }
+}
+
+impl std::fmt::Display for PlanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlanError::DuplicateKeys(keys) => {
+                write!(f, "Quests defined more than once: {}", keys.join(", "))
+            }
+            PlanError::DuplicateStoredKeys(keys) => write!(
+                f,
+                "Quests stored more than once in the database: {}",
+                keys.join(", ")
+            ),
+            PlanError::Db(e) => write!(f, "Comparing quests with the database failed. {}", e),
+        }
+    }
+}
+
+impl QuestContent {
+    fn load(db: &PgConnection, quest: &Quest) -> QueryResult<Self> {
+        let id = quest.id;
+        Ok(QuestContent {
+            karma_condition: quest.karma_condition,
+            pop_condition: quest.pop_condition,
+            follow_up_quest: quest.follow_up_quest.clone(),
+            buildings: quest_building_conditions::table
+                .filter(quest_building_conditions::quest_id.eq(id))
+                .select((
+                    quest_building_conditions::building_type,
+                    quest_building_conditions::amount,
+                ))
+                .load(db)?
+                .into_iter()
+                .collect(),
+            resources: quest_res_conditions::table
+                .filter(quest_res_conditions::quest_id.eq(id))
+                .select((
+                    quest_res_conditions::resource_type,
+                    quest_res_conditions::amount,
+                ))
+                .load(db)?
+                .into_iter()
+                .collect(),
+            workers: quest_worker_conditions::table
+                .filter(quest_worker_conditions::quest_id.eq(id))
+                .select((
+                    quest_worker_conditions::task_type,
+                    quest_worker_conditions::amount,
+                ))
+                .load(db)?
+                .into_iter()
+                .collect(),
+            stats: quest_stat_conditions::table
+                .filter(quest_stat_conditions::quest_id.eq(id))
+                .select((
+                    quest_stat_conditions::stat_type,
+                    quest_stat_conditions::amount,
+                ))
+                .load(db)?
+                .into_iter()
+                .collect(),
+            resource_rewards: quest_res_rewards::table
+                .filter(quest_res_rewards::quest_id.eq(id))
+                .select((quest_res_rewards::resource_type, quest_res_rewards::amount))
+                .load(db)?
+                .into_iter()
+                .collect(),
+            worker_reward: quest_worker_rewards::table
+                .find(id)
+                .select((quest_worker_rewards::mana, quest_worker_rewards::experience))
+                .first(db)
+                .optional()?,
+            perk_rewards: quest_perk_rewards::table
+                .filter(quest_perk_rewards::quest_id.eq(id))
+                .select(quest_perk_rewards::perk)
+                .load(db)?
+                .into_iter()
+                .collect(),
+            building_rewards: quest_building_rewards::table
+                .filter(quest_building_rewards::quest_id.eq(id))
+                .select(quest_building_rewards::building_type)
+                .load(db)?
+                .into_iter()
+                .collect(),
+        })
+    }
+
+    /// Names of the fields that differ, as they appear in the quest definitions
+    fn diff(&self, other: &Self) -> Vec<&'static str> {
+        let mut fields = vec![];
+        if self.karma_condition != other.karma_condition {
+            fields.push("karma_condition");
+        }
+        if self.pop_condition != other.pop_condition {
+            fields.push("pop_condition");
+        }
+        if self.follow_up_quest != other.follow_up_quest {
+            fields.push("follow_up_quest");
+        }
+        if self.buildings != other.buildings {
+            fields.push("condition.buildings");
+        }
+        if self.resources != other.resources {
+            fields.push("condition.resources");
+        }
+        if self.workers != other.workers {
+            fields.push("condition.workers");
+        }
+        if self.stats != other.stats {
+            fields.push("condition.stats");
+        }
+        if self.resource_rewards != other.resource_rewards {
+            fields.push("reward.resources");
+        }
+        if self.worker_reward != other.worker_reward {
+            fields.push("reward.mana/experience");
+        }
+        if self.perk_rewards != other.perk_rewards {
+            fields.push("reward.perks");
+        }
+        if self.building_rewards != other.building_rewards {
+            fields.push("reward.buildings");
+        }
+        fields
+    }
+}
+
+impl From<&QuestDefinition> for QuestContent {
+    fn from(definition: &QuestDefinition) -> Self {
+        let reward = &definition.reward;
+        let worker_reward = if reward.mana.is_some() || reward.experience.is_some() {
+            Some((reward.mana.unwrap_or(0), reward.experience.unwrap_or(0)))
+        } else {
+            None
+        };
+        QuestContent {
+            karma_condition: definition.karma_condition,
+            pop_condition: definition.pop_condition,
+            follow_up_quest: definition.follow_up_quest.clone(),
+            buildings: definition.condition.buildings.clone().unwrap_or_default(),
+            resources: definition.condition.resources.clone().unwrap_or_default(),
+            workers: definition.condition.workers.clone().unwrap_or_default(),
+            stats: definition.condition.stats.clone().unwrap_or_default(),
+            resource_rewards: reward.resources.clone().unwrap_or_default(),
+            worker_reward,
+            perk_rewards: reward.perks.iter().flatten().copied().collect(),
+            building_rewards: reward.buildings.iter().flatten().copied().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use paddlers_shared_lib::{prelude::NewPlayer, schema::players};
+
+    /// Quests as they are stored before the migration
+    const STORED: &str = r#"#![enable(implicit_some)]
+[
+    (
+        quest_key: "test-migration-update",
+        karma_condition: 5,
+        condition: (
+            buildings: { TREE: 2 },
+            stats: { WELCOMED_VISITORS: 3, COMPLETED_TASKS: 2 },
+        ),
+        reward: (
+            resources: { FEATHERS: 20 },
+            mana: 10,
+            perks: [Invitation],
+        ),
+    ),
+    (quest_key: "test-migration-held", condition: (), reward: ()),
+    (quest_key: "test-migration-free", condition: (), reward: ()),
+]"#;
+
+    /// New definitions, with changed stats and rewards on the updated quest
+    const DEFINED: &str = r#"#![enable(implicit_some)]
+[
+    (
+        quest_key: "test-migration-update",
+        karma_condition: 5,
+        condition: (
+            buildings: { TREE: 2 },
+            stats: { WELCOMED_VISITORS: 3, GATHERED_STICKS: 10 },
+        ),
+        reward: (
+            resources: { FEATHERS: 20 },
+            mana: 10,
+            experience: 5,
+            perks: [Invitation],
+        ),
+    ),
+    (quest_key: "test-migration-create", condition: (), reward: ()),
+]"#;
+
+    fn definitions(ron: &str) -> Vec<QuestDefinition> {
+        ron::de::from_str(ron).unwrap()
+    }
+
+    #[test]
+    fn content_of_definition() {
+        let stored = definitions(STORED);
+        let content = QuestContent::from(&stored[0]);
+        assert_eq!(content.karma_condition, Some(5));
+        assert_eq!(content.pop_condition, None);
+        assert_eq!(
+            content.buildings,
+            vec![(BuildingType::Tree, 2)].into_iter().collect()
+        );
+        assert_eq!(
+            content.stats,
+            vec![
+                (QuestStatType::WelcomedVisitors, 3),
+                (QuestStatType::CompletedTasks, 2)
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(
+            content.resource_rewards,
+            vec![(ResourceType::Feathers, 20)].into_iter().collect()
+        );
+        // Experience defaults to zero if only mana is rewarded
+        assert_eq!(content.worker_reward, Some((10, 0)));
+        assert_eq!(
+            content.perk_rewards,
+            vec![CivilizationPerk::Invitation].into_iter().collect()
+        );
+        assert!(content.building_rewards.is_empty());
+
+        let empty = QuestContent::from(&stored[1]);
+        assert_eq!(empty.worker_reward, None);
+        assert!(empty.stats.is_empty());
+        assert!(empty.perk_rewards.is_empty());
+    }
+
+    #[test]
+    fn diff_names_changed_fields() {
+        let stored = definitions(STORED);
+        let defined = definitions(DEFINED);
+        let content = QuestContent::from(&stored[0]);
+        assert!(content.diff(&QuestContent::from(&stored[0])).is_empty());
+        assert_eq!(
+            content.diff(&QuestContent::from(&defined[0])),
+            vec!["condition.stats", "reward.mana/experience"]
+        );
+        assert_eq!(
+            content.diff(&QuestContent::from(&stored[1])),
+            vec![
+                "karma_condition",
+                "condition.buildings",
+                "condition.stats",
+                "reward.resources",
+                "reward.mana/experience",
+                "reward.perks",
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_keys_across_files() {
+        let mut all = definitions(STORED);
+        all.extend(definitions(DEFINED));
+        let keys = |definitions: &[QuestDefinition]| {
+            duplicate_keys(definitions.iter().map(|d| &d.quest_key))
+        };
+        assert_eq!(keys(&all), vec!["test-migration-update"]);
+        assert!(keys(&definitions(STORED)).is_empty());
+    }
+
+    #[test]
+    fn duplicate_stored_keys() {
+        let quest = |id, quest_key: &str| Quest {
+            id,
+            quest_key: quest_key.to_owned(),
+            karma_condition: None,
+            pop_condition: None,
+            follow_up_quest: None,
+        };
+        let stored = vec![
+            quest(1, "test-stored-a"),
+            quest(2, "test-stored-b"),
+            quest(3, "test-stored-a"),
+        ];
+        assert_eq!(
+            duplicate_keys(stored.iter().map(|q| &q.quest_key)),
+            vec!["test-stored-a"]
+        );
+    }
+
+    /// Creates, updates and prunes quests, one of them held by a player, and migrates the progress of the updated quest.
+    /// Requires a database, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn migration_creates_updates_and_prunes() {
+        let db = paddlers_shared_lib::establish_connection();
+        // Nothing is committed, the test data disappears with the connection
+        db.begin_test_transaction().unwrap();
+        for definition in &definitions(STORED) {
+            definition.upload(&db).unwrap();
+        }
+        let player: i64 = diesel::insert_into(players::table)
+            .values(&NewPlayer {
+                uuid: uuid::Uuid::new_v4(),
+                karma: 0,
+                display_name: "Migration Test".to_owned(),
+                utc_offset_minutes: 0,
+            })
+            .returning(players::id)
+            .get_result(&db)
+            .unwrap();
+        let updated = quest_id(&db, "test-migration-update").unwrap();
+        let held = quest_id(&db, "test-migration-held").unwrap();
+        for &quest in &[updated, held] {
+            diesel::insert_into(quest_to_player::table)
+                .values((
+                    quest_to_player::quest_id.eq(quest),
+                    quest_to_player::player_id.eq(player),
+                ))
+                .execute(&db)
+                .unwrap();
+        }
+        let progress: Vec<QuestProgress> = vec![
+            (QuestStatType::WelcomedVisitors, 2),
+            (QuestStatType::CompletedTasks, 1),
+        ]
+        .into_iter()
+        .map(|(stat_type, amount)| QuestProgress {
+            quest_id: updated,
+            player_id: player,
+            stat_type,
+            amount,
+        })
+        .collect();
+        diesel::insert_into(quest_progress::table)
+            .values(&progress)
+            .execute(&db)
+            .unwrap();
+
+        let defined = definitions(DEFINED);
+        let migration = QuestMigration::plan(&db, &defined, true).unwrap();
+        let mut out = vec![];
+        migration.print(&mut out).unwrap();
+        // Quests of other tests or a development database may show up, too
+        let printed: Vec<String> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .filter(|line| line.contains("test-migration"))
+            .map(str::to_owned)
+            .collect();
+        assert_eq!(
+            printed,
+            vec![
+                "~ test-migration-update (condition.stats, reward.mana/experience), held by 1 players",
+                "+ test-migration-create",
+                "! test-migration-held is not defined anymore but held by 1 players, it is kept",
+                "- test-migration-free",
+            ]
+        );
+
+        migration.apply(&db).unwrap();
+        assert_eq!(quest_id(&db, "test-migration-update"), Some(updated));
+        assert_eq!(quest_id(&db, "test-migration-held"), Some(held));
+        assert!(quest_id(&db, "test-migration-create").is_some());
+        assert_eq!(quest_id(&db, "test-migration-free"), None);
+        // Progress on the remaining condition is kept, the new one starts at zero
+        let progress: HashMap<QuestStatType, i64> = quest_progress::table
+            .filter(quest_progress::quest_id.eq(updated))
+            .filter(quest_progress::player_id.eq(player))
+            .select((quest_progress::stat_type, quest_progress::amount))
+            .load(&db)
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(
+            progress,
+            vec![
+                (QuestStatType::WelcomedVisitors, 2),
+                (QuestStatType::GatheredSticks, 0)
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    fn quest_id(db: &PgConnection, key: &str) -> Option<i64> {
+        quests::table
+            .filter(quests::quest_key.eq(key))
+            .select(quests::id)
+            .first(db)
+            .optional()
+            .unwrap()
+    }
+}